@@ -0,0 +1,4 @@
+// sqlx::migrate! вшивает файлы миграций при компиляции: пересобрать при их изменении.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
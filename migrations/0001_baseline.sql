@@ -0,0 +1,237 @@
+-- Схема на момент перехода на версионированные миграции. Для существующих БД
+-- недостающие колонки заранее добавляет Db::upgrade_legacy_schema, поэтому здесь
+-- везде IF NOT EXISTS. Файл не меняется: sqlx сверяет контрольные суммы применённых миграций.
+
+CREATE TABLE IF NOT EXISTS registration_requests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tg_user_id INTEGER NOT NULL,
+    tg_username TEXT,
+    tg_display_name TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    telemt_username TEXT,
+    secret TEXT,
+    created_at INTEGER NOT NULL,
+    resolved_at INTEGER,
+    note TEXT,
+    invite_token TEXT,
+    user_group TEXT,
+    suspended INTEGER NOT NULL DEFAULT 0,
+    reminded_at INTEGER,
+    escalated_at INTEGER,
+    expires_at INTEGER,
+    expiry_warned_at INTEGER,
+    UNIQUE(tg_user_id)
+);
+CREATE INDEX IF NOT EXISTS idx_requests_status ON registration_requests(status);
+CREATE INDEX IF NOT EXISTS idx_requests_tg_user ON registration_requests(tg_user_id);
+CREATE INDEX IF NOT EXISTS idx_requests_status_created
+    ON registration_requests(status, created_at, id);
+
+CREATE TABLE IF NOT EXISTS invite_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token TEXT UNIQUE NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    auto_approve INTEGER NOT NULL DEFAULT 0,
+    created_by INTEGER,
+    usage_count INTEGER NOT NULL DEFAULT 0,
+    max_usage INTEGER,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    revoked_at INTEGER,
+    for_tg_user_id INTEGER,
+    web_visits INTEGER NOT NULL DEFAULT 0,
+    plans TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_invite_tokens_token ON invite_tokens(token);
+CREATE INDEX IF NOT EXISTS idx_invite_tokens_active ON invite_tokens(is_active);
+CREATE INDEX IF NOT EXISTS idx_invite_tokens_expires_at ON invite_tokens(expires_at);
+
+CREATE TABLE IF NOT EXISTS announcements (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER,
+    created_by INTEGER,
+    cleared_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_announcements_cleared_at ON announcements(cleared_at);
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    cursor INTEGER NOT NULL DEFAULT 0,
+    processed INTEGER NOT NULL DEFAULT 0,
+    total INTEGER,
+    error TEXT,
+    created_by INTEGER,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status);
+
+CREATE TABLE IF NOT EXISTS approval_basket (
+    request_id INTEGER PRIMARY KEY,
+    staged_by INTEGER,
+    staged_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS archived_requests (
+    id INTEGER PRIMARY KEY,
+    tg_user_id INTEGER NOT NULL,
+    tg_username TEXT,
+    tg_display_name TEXT,
+    status TEXT NOT NULL,
+    telemt_username TEXT,
+    note TEXT,
+    created_at INTEGER NOT NULL,
+    resolved_at INTEGER,
+    archived_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_archived_requests_tg_user ON archived_requests(tg_user_id);
+CREATE TABLE IF NOT EXISTS archived_tokens (
+    id INTEGER PRIMARY KEY,
+    token TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    auto_approve INTEGER NOT NULL,
+    created_by INTEGER,
+    usage_count INTEGER NOT NULL,
+    max_usage INTEGER,
+    revoked_at INTEGER,
+    archived_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS link_reveals (
+    token TEXT PRIMARY KEY,
+    tg_user_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    message_id INTEGER,
+    text TEXT,
+    created_at INTEGER NOT NULL,
+    revealed_at INTEGER,
+    delete_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_link_reveals_delete_at ON link_reveals(delete_at);
+
+CREATE TABLE IF NOT EXISTS link_auto_deletes (
+    chat_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    delete_at INTEGER NOT NULL,
+    PRIMARY KEY (chat_id, message_id)
+);
+CREATE INDEX IF NOT EXISTS idx_link_auto_deletes_delete_at ON link_auto_deletes(delete_at);
+
+CREATE TABLE IF NOT EXISTS provisioned_users (
+    tg_user_id INTEGER PRIMARY KEY,
+    note TEXT,
+    created_at INTEGER NOT NULL,
+    consumed_at INTEGER,
+    user_group TEXT,
+    expires_in_days INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS onboarding_messages (
+    tg_user_id INTEGER NOT NULL,
+    step INTEGER NOT NULL,
+    due_at INTEGER NOT NULL,
+    PRIMARY KEY (tg_user_id, step)
+);
+CREATE INDEX IF NOT EXISTS idx_onboarding_messages_due_at ON onboarding_messages(due_at);
+
+CREATE TABLE IF NOT EXISTS survey_invites (
+    tg_user_id INTEGER PRIMARY KEY,
+    due_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS survey_answers (
+    tg_user_id INTEGER NOT NULL,
+    question INTEGER NOT NULL,
+    answer INTEGER NOT NULL,
+    answered_at INTEGER NOT NULL,
+    PRIMARY KEY (tg_user_id, question)
+);
+
+CREATE TABLE IF NOT EXISTS group_schedules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_name TEXT NOT NULL,
+    action TEXT NOT NULL,
+    run_at INTEGER NOT NULL,
+    created_by INTEGER,
+    created_at INTEGER NOT NULL,
+    done_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_group_schedules_run_at ON group_schedules(done_at, run_at);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    at INTEGER NOT NULL,
+    actor_id INTEGER,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    details TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX IF NOT EXISTS idx_audit_log_at ON audit_log(at);
+
+CREATE TABLE IF NOT EXISTS support_messages (
+    admin_chat_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    tg_user_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (admin_chat_id, message_id)
+);
+
+CREATE TABLE IF NOT EXISTS support_conversations (
+    tg_user_id INTEGER PRIMARY KEY,
+    assigned_to INTEGER,
+    assigned_name TEXT,
+    assigned_at INTEGER,
+    status TEXT NOT NULL DEFAULT 'open',
+    opened_at INTEGER,
+    last_activity_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS token_views (
+    token TEXT NOT NULL,
+    tg_user_id INTEGER NOT NULL,
+    viewed_at INTEGER NOT NULL,
+    PRIMARY KEY (token, tg_user_id)
+);
+
+CREATE TABLE IF NOT EXISTS short_links (
+    slug TEXT PRIMARY KEY,
+    tg_user_id INTEGER NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sent_reports (
+    period TEXT PRIMARY KEY,
+    sent_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS blocked_users (
+    tg_user_id INTEGER PRIMARY KEY,
+    added_at INTEGER NOT NULL
+);
+
+-- Полнотекстовый индекс (FTS5, trigram) по username, имени и заметке, синхронизируется триггерами.
+CREATE VIRTUAL TABLE IF NOT EXISTS users_fts USING fts5(
+    tg_username, tg_display_name, note,
+    content = 'registration_requests', content_rowid = 'id',
+    tokenize = 'trigram'
+);
+CREATE TRIGGER IF NOT EXISTS users_fts_ai AFTER INSERT ON registration_requests BEGIN
+    INSERT INTO users_fts(rowid, tg_username, tg_display_name, note)
+    VALUES (new.id, new.tg_username, new.tg_display_name, new.note);
+END;
+CREATE TRIGGER IF NOT EXISTS users_fts_ad AFTER DELETE ON registration_requests BEGIN
+    INSERT INTO users_fts(users_fts, rowid, tg_username, tg_display_name, note)
+    VALUES ('delete', old.id, old.tg_username, old.tg_display_name, old.note);
+END;
+CREATE TRIGGER IF NOT EXISTS users_fts_au
+AFTER UPDATE OF tg_username, tg_display_name, note ON registration_requests BEGIN
+    INSERT INTO users_fts(users_fts, rowid, tg_username, tg_display_name, note)
+    VALUES ('delete', old.id, old.tg_username, old.tg_display_name, old.note);
+    INSERT INTO users_fts(rowid, tg_username, tg_display_name, note)
+    VALUES (new.id, new.tg_username, new.tg_display_name, new.note);
+END;
+INSERT INTO users_fts(users_fts) VALUES ('rebuild');
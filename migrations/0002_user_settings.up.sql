@@ -0,0 +1,5 @@
+-- Настройки пользователя, не привязанные к заявке: язык выбирается и до регистрации.
+CREATE TABLE IF NOT EXISTS user_settings (
+    tg_user_id INTEGER PRIMARY KEY,
+    language TEXT NOT NULL
+);
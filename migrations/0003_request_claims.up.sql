@@ -0,0 +1,7 @@
+-- Заявки, взятые админом в работу из списка ожидающих, чтобы двое не разбирали одну.
+CREATE TABLE IF NOT EXISTS request_claims (
+    request_id INTEGER PRIMARY KEY,
+    admin_id INTEGER NOT NULL,
+    admin_name TEXT,
+    claimed_at INTEGER NOT NULL
+);
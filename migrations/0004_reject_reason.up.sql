@@ -0,0 +1,2 @@
+-- Причина отклонения заявки, которую админ сообщил пользователю.
+ALTER TABLE registration_requests ADD COLUMN reject_reason TEXT;
@@ -0,0 +1,22 @@
+-- Рассылки админов: черновик с выбором аудитории, расписание и отчёт о доставке.
+CREATE TABLE IF NOT EXISTS broadcasts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    audience TEXT NOT NULL DEFAULT 'approved',
+    group_name TEXT,
+    status TEXT NOT NULL DEFAULT 'draft',
+    scheduled_at INTEGER,
+    created_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    started_at INTEGER,
+    finished_at INTEGER
+);
+
+-- Получатели фиксируются при старте рассылки; после рестарта бота отправка
+-- продолжается с оставшихся в статусе `queued`.
+CREATE TABLE IF NOT EXISTS broadcast_deliveries (
+    broadcast_id INTEGER NOT NULL,
+    tg_user_id INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    PRIMARY KEY (broadcast_id, tg_user_id)
+);
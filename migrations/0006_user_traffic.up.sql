@@ -0,0 +1,10 @@
+-- Накопленный трафик пользователей по метрикам telemt. `last_raw_*` — последние
+-- значения счётчиков telemt: они обнуляются при его рестарте, а накопленные — нет.
+CREATE TABLE IF NOT EXISTS user_traffic (
+    tg_user_id INTEGER PRIMARY KEY,
+    bytes_from_client INTEGER NOT NULL DEFAULT 0,
+    bytes_to_client INTEGER NOT NULL DEFAULT 0,
+    last_raw_from_client INTEGER NOT NULL DEFAULT 0,
+    last_raw_to_client INTEGER NOT NULL DEFAULT 0,
+    updated_at INTEGER NOT NULL
+);
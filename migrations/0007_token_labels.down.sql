@@ -0,0 +1,2 @@
+ALTER TABLE archived_tokens DROP COLUMN label;
+ALTER TABLE invite_tokens DROP COLUMN label;
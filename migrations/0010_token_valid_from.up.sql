@@ -0,0 +1,2 @@
+-- Начало действия токена (`/token create --from`): раньше него токен не применяется.
+ALTER TABLE invite_tokens ADD COLUMN valid_from INTEGER;
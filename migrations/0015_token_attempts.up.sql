@@ -0,0 +1,7 @@
+-- Неверные invite-токены подряд по пользователю: защита от подбора.
+CREATE TABLE IF NOT EXISTS token_attempts (
+    tg_user_id INTEGER PRIMARY KEY,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at INTEGER NOT NULL,
+    locked_until INTEGER
+);
@@ -0,0 +1,214 @@
+-- Схема Postgres на момент перехода на версионированные миграции; повторяет
+-- migrations/0001_baseline.sql без FTS5 (поиск в Postgres идёт через ILIKE).
+-- Файл не меняется: sqlx сверяет контрольные суммы применённых миграций.
+
+CREATE TABLE IF NOT EXISTS registration_requests (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    tg_user_id BIGINT NOT NULL,
+    tg_username TEXT,
+    tg_display_name TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    telemt_username TEXT,
+    secret TEXT,
+    created_at BIGINT NOT NULL,
+    resolved_at BIGINT,
+    note TEXT,
+    invite_token TEXT,
+    user_group TEXT,
+    suspended BIGINT NOT NULL DEFAULT 0,
+    reminded_at BIGINT,
+    escalated_at BIGINT,
+    expires_at BIGINT,
+    expiry_warned_at BIGINT,
+    UNIQUE(tg_user_id)
+);
+CREATE INDEX IF NOT EXISTS idx_requests_status ON registration_requests(status);
+CREATE INDEX IF NOT EXISTS idx_requests_tg_user ON registration_requests(tg_user_id);
+CREATE INDEX IF NOT EXISTS idx_requests_status_created
+    ON registration_requests(status, created_at, id);
+
+CREATE TABLE IF NOT EXISTS invite_tokens (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    token TEXT UNIQUE NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    auto_approve BIGINT NOT NULL DEFAULT 0,
+    created_by BIGINT,
+    usage_count BIGINT NOT NULL DEFAULT 0,
+    max_usage BIGINT,
+    is_active BIGINT NOT NULL DEFAULT 1,
+    revoked_at BIGINT,
+    for_tg_user_id BIGINT,
+    web_visits BIGINT NOT NULL DEFAULT 0,
+    plans TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_invite_tokens_token ON invite_tokens(token);
+CREATE INDEX IF NOT EXISTS idx_invite_tokens_active ON invite_tokens(is_active);
+CREATE INDEX IF NOT EXISTS idx_invite_tokens_expires_at ON invite_tokens(expires_at);
+
+CREATE TABLE IF NOT EXISTS announcements (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    text TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT,
+    created_by BIGINT,
+    cleared_at BIGINT
+);
+CREATE INDEX IF NOT EXISTS idx_announcements_cleared_at ON announcements(cleared_at);
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    cursor BIGINT NOT NULL DEFAULT 0,
+    processed BIGINT NOT NULL DEFAULT 0,
+    total BIGINT,
+    error TEXT,
+    created_by BIGINT,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status);
+
+CREATE TABLE IF NOT EXISTS approval_basket (
+    request_id BIGINT PRIMARY KEY,
+    staged_by BIGINT,
+    staged_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS archived_requests (
+    id BIGINT PRIMARY KEY,
+    tg_user_id BIGINT NOT NULL,
+    tg_username TEXT,
+    tg_display_name TEXT,
+    status TEXT NOT NULL,
+    telemt_username TEXT,
+    note TEXT,
+    created_at BIGINT NOT NULL,
+    resolved_at BIGINT,
+    archived_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_archived_requests_tg_user ON archived_requests(tg_user_id);
+CREATE TABLE IF NOT EXISTS archived_tokens (
+    id BIGINT PRIMARY KEY,
+    token TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    auto_approve BIGINT NOT NULL,
+    created_by BIGINT,
+    usage_count BIGINT NOT NULL,
+    max_usage BIGINT,
+    revoked_at BIGINT,
+    archived_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS link_reveals (
+    token TEXT PRIMARY KEY,
+    tg_user_id BIGINT NOT NULL,
+    chat_id BIGINT NOT NULL,
+    message_id BIGINT,
+    text TEXT,
+    created_at BIGINT NOT NULL,
+    revealed_at BIGINT,
+    delete_at BIGINT
+);
+CREATE INDEX IF NOT EXISTS idx_link_reveals_delete_at ON link_reveals(delete_at);
+
+CREATE TABLE IF NOT EXISTS link_auto_deletes (
+    chat_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    delete_at BIGINT NOT NULL,
+    PRIMARY KEY (chat_id, message_id)
+);
+CREATE INDEX IF NOT EXISTS idx_link_auto_deletes_delete_at ON link_auto_deletes(delete_at);
+
+CREATE TABLE IF NOT EXISTS provisioned_users (
+    tg_user_id BIGINT PRIMARY KEY,
+    note TEXT,
+    created_at BIGINT NOT NULL,
+    consumed_at BIGINT,
+    user_group TEXT,
+    expires_in_days BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS onboarding_messages (
+    tg_user_id BIGINT NOT NULL,
+    step BIGINT NOT NULL,
+    due_at BIGINT NOT NULL,
+    PRIMARY KEY (tg_user_id, step)
+);
+CREATE INDEX IF NOT EXISTS idx_onboarding_messages_due_at ON onboarding_messages(due_at);
+
+CREATE TABLE IF NOT EXISTS survey_invites (
+    tg_user_id BIGINT PRIMARY KEY,
+    due_at BIGINT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS survey_answers (
+    tg_user_id BIGINT NOT NULL,
+    question BIGINT NOT NULL,
+    answer BIGINT NOT NULL,
+    answered_at BIGINT NOT NULL,
+    PRIMARY KEY (tg_user_id, question)
+);
+
+CREATE TABLE IF NOT EXISTS group_schedules (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    group_name TEXT NOT NULL,
+    action TEXT NOT NULL,
+    run_at BIGINT NOT NULL,
+    created_by BIGINT,
+    created_at BIGINT NOT NULL,
+    done_at BIGINT
+);
+CREATE INDEX IF NOT EXISTS idx_group_schedules_run_at ON group_schedules(done_at, run_at);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    at BIGINT NOT NULL,
+    actor_id BIGINT,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    details TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX IF NOT EXISTS idx_audit_log_at ON audit_log(at);
+
+CREATE TABLE IF NOT EXISTS support_messages (
+    admin_chat_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    tg_user_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (admin_chat_id, message_id)
+);
+
+CREATE TABLE IF NOT EXISTS support_conversations (
+    tg_user_id BIGINT PRIMARY KEY,
+    assigned_to BIGINT,
+    assigned_name TEXT,
+    assigned_at BIGINT,
+    status TEXT NOT NULL DEFAULT 'open',
+    opened_at BIGINT,
+    last_activity_at BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS token_views (
+    token TEXT NOT NULL,
+    tg_user_id BIGINT NOT NULL,
+    viewed_at BIGINT NOT NULL,
+    PRIMARY KEY (token, tg_user_id)
+);
+
+CREATE TABLE IF NOT EXISTS short_links (
+    slug TEXT PRIMARY KEY,
+    tg_user_id BIGINT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sent_reports (
+    period TEXT PRIMARY KEY,
+    sent_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS blocked_users (
+    tg_user_id BIGINT PRIMARY KEY,
+    added_at BIGINT NOT NULL
+);
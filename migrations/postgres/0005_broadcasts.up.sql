@@ -0,0 +1,22 @@
+-- Рассылки админов: черновик с выбором аудитории, расписание и отчёт о доставке.
+CREATE TABLE IF NOT EXISTS broadcasts (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    text TEXT NOT NULL,
+    audience TEXT NOT NULL DEFAULT 'approved',
+    group_name TEXT,
+    status TEXT NOT NULL DEFAULT 'draft',
+    scheduled_at BIGINT,
+    created_by BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    started_at BIGINT,
+    finished_at BIGINT
+);
+
+-- Получатели фиксируются при старте рассылки; после рестарта бота отправка
+-- продолжается с оставшихся в статусе `queued`.
+CREATE TABLE IF NOT EXISTS broadcast_deliveries (
+    broadcast_id BIGINT NOT NULL,
+    tg_user_id BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    PRIMARY KEY (broadcast_id, tg_user_id)
+);
@@ -0,0 +1,3 @@
+-- Подпись токена: для чего он выписан (`/token create --label`).
+ALTER TABLE invite_tokens ADD COLUMN label TEXT;
+ALTER TABLE archived_tokens ADD COLUMN label TEXT;
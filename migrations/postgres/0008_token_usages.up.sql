@@ -0,0 +1,10 @@
+-- История применений invite-токенов: кто и когда прошёл по токену (`/token info`).
+-- Записи не удаляются при архивации токена, чтобы можно было разобрать утечку позже.
+CREATE TABLE IF NOT EXISTS token_usages (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    token TEXT NOT NULL,
+    tg_user_id BIGINT NOT NULL,
+    used_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_token_usages_token ON token_usages(token, used_at);
+CREATE INDEX IF NOT EXISTS idx_token_usages_tg_user ON token_usages(tg_user_id);
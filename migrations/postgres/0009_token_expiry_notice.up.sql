@@ -0,0 +1,2 @@
+-- Когда создателю токена напомнили о скором истечении; сбрасывается при продлении.
+ALTER TABLE invite_tokens ADD COLUMN expiry_notified_at BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE invite_tokens DROP COLUMN valid_from;
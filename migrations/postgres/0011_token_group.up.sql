@@ -0,0 +1,2 @@
+-- Группа, в которую попадают пришедшие по токену (`/token create --group`).
+ALTER TABLE invite_tokens ADD COLUMN group_name TEXT;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_invite_tokens_referrer;
+ALTER TABLE invite_tokens DROP COLUMN referrer_tg_user_id;
@@ -0,0 +1,3 @@
+-- Реферальные токены: выписаны одобренным пользователем кнопкой «Пригласить друга».
+ALTER TABLE invite_tokens ADD COLUMN referrer_tg_user_id BIGINT;
+CREATE INDEX IF NOT EXISTS idx_invite_tokens_referrer ON invite_tokens(referrer_tg_user_id);
@@ -0,0 +1,8 @@
+-- Ручные блокировки админов (`/ban`): в отличие от `blocked_users`, не заменяются
+-- синхронизацией внешнего списка и снимаются только `/unban`.
+CREATE TABLE IF NOT EXISTS banned_users (
+    tg_user_id BIGINT PRIMARY KEY,
+    banned_by BIGINT,
+    reason TEXT,
+    banned_at BIGINT NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Белый список для `registration_mode = "whitelist"`: заявку могут подать только эти ID.
+CREATE TABLE IF NOT EXISTS allowed_users (
+    tg_user_id BIGINT PRIMARY KEY,
+    added_by BIGINT,
+    added_at BIGINT NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Неверные invite-токены подряд по пользователю: защита от подбора.
+CREATE TABLE IF NOT EXISTS token_attempts (
+    tg_user_id BIGINT PRIMARY KEY,
+    failures BIGINT NOT NULL DEFAULT 0,
+    last_failure_at BIGINT NOT NULL,
+    locked_until BIGINT
+);
@@ -0,0 +1,34 @@
+//! Очередь уведомлений администраторам из слоёв без доступа к `Bot`
+//! (systemd, писатель конфига). Сообщения доставляет задача из
+//! [`crate::bot::handlers::spawn_admin_alerts`].
+
+use crate::config::AdminTopic;
+use std::sync::Arc;
+use tokio::sync::{Mutex, mpsc};
+
+/// Handle для отправки уведомления всем администраторам.
+#[derive(Clone)]
+pub struct AdminAlerts {
+    tx: mpsc::UnboundedSender<(AdminTopic, String)>,
+}
+
+/// Приёмная сторона очереди; переживает пересоздание бота при смене токена.
+pub type AdminAlertsReceiver = Arc<Mutex<mpsc::UnboundedReceiver<(AdminTopic, String)>>>;
+
+pub fn channel() -> (AdminAlerts, AdminAlertsReceiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (AdminAlerts { tx }, Arc::new(Mutex::new(rx)))
+}
+
+impl AdminAlerts {
+    pub fn send(&self, text: impl Into<String>) {
+        self.send_to(AdminTopic::Alerts, text);
+    }
+
+    /// Уведомление в заданную тему группы админов (без группы — всем админам в личку).
+    pub fn send_to(&self, topic: AdminTopic, text: impl Into<String>) {
+        if self.tx.send((topic, text.into())).is_err() {
+            tracing::warn!("Admin alerts receiver dropped, alert lost");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Журнал аудита действий администраторов: кто, когда и что сделал с
+//! пользователями, токенами и сервисом. Записи хранятся в таблице `audit_log`,
+//! дублируются в лог с `target: "audit"` и при настроенной секции `[audit]`
+//! пересылаются в syslog (UDP) и/или HTTP-коллектор в формате JSON или CEF.
+//! При заданном `admin_chat_id` каждое событие также публикуется в группе админов.
+
+use crate::alerts::AdminAlerts;
+use crate::config::{AdminTopic, AuditConfig, AuditFormat};
+use crate::db::{AuditEntry, Db};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Facility `authpriv` (10) и severity `notice` (5).
+const SYSLOG_PRIORITY: u8 = 10 * 8 + 5;
+const APP_NAME: &str = "telemt-admin";
+
+#[derive(Clone)]
+pub struct AuditLog {
+    db: Arc<Db>,
+    forwarder: Option<Arc<Forwarder>>,
+    admin_chat: Option<AdminAlerts>,
+}
+
+struct Forwarder {
+    config: AuditConfig,
+    client: reqwest::Client,
+}
+
+impl AuditLog {
+    pub fn new(db: Arc<Db>, config: AuditConfig) -> Self {
+        let forwarder = (config.syslog_addr.is_some() || config.http_url.is_some()).then(|| {
+            tracing::info!(
+                syslog = config.syslog_addr.as_deref().unwrap_or("-"),
+                http = config.http_url.is_some(),
+                format = %config.format,
+                "Audit forwarding enabled"
+            );
+            Arc::new(Forwarder {
+                config,
+                client: reqwest::Client::new(),
+            })
+        });
+        Self {
+            db,
+            forwarder,
+            admin_chat: None,
+        }
+    }
+
+    /// Публиковать события в теме аудита группы админов.
+    pub fn with_admin_chat(mut self, alerts: AdminAlerts) -> Self {
+        self.admin_chat = Some(alerts);
+        self
+    }
+
+    /// Записывает действие. Ошибка записи не отменяет само действие: она только логируется.
+    pub async fn record(&self, actor_id: i64, action: &str, target: &str, details: &str) {
+        self.record_entry(Some(actor_id), action, target, details)
+            .await;
+    }
+
+    /// Событие самого бота, без админа-исполнителя (в журнале актор «—»).
+    pub async fn record_system(&self, action: &str, target: &str, details: &str) {
+        self.record_entry(None, action, target, details).await;
+    }
+
+    async fn record_entry(&self, actor_id: Option<i64>, action: &str, target: &str, details: &str) {
+        tracing::info!(
+            target: "audit",
+            actor_id = ?actor_id,
+            action = action,
+            object = target,
+            details = details,
+            "Admin action"
+        );
+        match self
+            .db
+            .add_audit_entry(actor_id, action, target, details)
+            .await
+        {
+            Ok(entry) => {
+                if let Some(alerts) = &self.admin_chat {
+                    alerts.send_to(AdminTopic::Audit, render_chat_entry(&entry));
+                }
+                if let Some(forwarder) = &self.forwarder {
+                    // Пересылка не задерживает ответ админу.
+                    let forwarder = forwarder.clone();
+                    tokio::spawn(async move { forwarder.forward(&entry).await });
+                }
+            }
+            Err(error) => tracing::warn!(
+                action = action,
+                error = %error,
+                "Не удалось записать событие в журнал аудита"
+            ),
+        }
+    }
+}
+
+impl Forwarder {
+    async fn forward(&self, entry: &AuditEntry) {
+        let payload = match self.config.format {
+            AuditFormat::Json => serde_json::to_string(entry).unwrap_or_default(),
+            AuditFormat::Cef => to_cef(entry),
+        };
+        if let Some(addr) = &self.config.syslog_addr
+            && let Err(error) = send_syslog(addr, entry, &payload).await
+        {
+            tracing::warn!(addr = %addr, error = %error, "Не удалось отправить событие аудита в syslog");
+        }
+        if let Some(url) = &self.config.http_url
+            && let Err(error) = self.send_http(url, &payload).await
+        {
+            tracing::warn!(url = %url, error = %error, "Не удалось отправить событие аудита в коллектор");
+        }
+    }
+
+    async fn send_http(&self, url: &str, payload: &str) -> Result<(), String> {
+        let content_type = match self.config.format {
+            AuditFormat::Json => "application/json",
+            AuditFormat::Cef => "text/plain",
+        };
+        let mut request = self
+            .client
+            .post(url)
+            .header("Content-Type", content_type)
+            .timeout(std::time::Duration::from_secs(10))
+            .body(payload.to_string());
+        if let Some(authorization) = &self.config.http_authorization {
+            request = request.header("Authorization", authorization);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("{} вернул {}", url, status));
+        }
+        Ok(())
+    }
+}
+
+/// Событие для группы админов в том же виде, что и строка `/audit`.
+fn render_chat_entry(entry: &AuditEntry) -> String {
+    let mut text = format!(
+        "🛡 {} {} — {}",
+        entry.action,
+        entry.target,
+        entry
+            .actor_id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "—".to_string())
+    );
+    if !entry.details.is_empty() {
+        let _ = write!(text, "\n{}", entry.details);
+    }
+    text
+}
+
+/// Одно событие — одна датаграмма RFC 5424.
+async fn send_syslog(addr: &str, entry: &AuditEntry, payload: &str) -> std::io::Result<()> {
+    let target = tokio::net::lookup_host(addr)
+        .await?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "адрес не найден"))?;
+    let bind = if target.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = tokio::net::UdpSocket::bind(bind).await?;
+    let message = format!(
+        "<{}>1 {} - {} {} audit - {}",
+        SYSLOG_PRIORITY,
+        rfc3339(entry.at),
+        APP_NAME,
+        std::process::id(),
+        payload
+    );
+    socket.send_to(message.as_bytes(), target).await?;
+    Ok(())
+}
+
+fn rfc3339(ts: i64) -> String {
+    chrono::DateTime::<chrono::Utc>::from_timestamp(ts, 0)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+/// `CEF:0|Vendor|Product|Version|SignatureID|Name|Severity|Extension`.
+fn to_cef(entry: &AuditEntry) -> String {
+    let mut extension = format!("rt={} act={}", entry.at * 1000, cef_value(&entry.action));
+    if let Some(actor_id) = entry.actor_id {
+        let _ = write!(extension, " suid={}", actor_id);
+    }
+    let _ = write!(
+        extension,
+        " cs1Label=target cs1={} msg={} externalId={}",
+        cef_value(&entry.target),
+        cef_value(&entry.details),
+        entry.id
+    );
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|3|{}",
+        APP_NAME,
+        APP_NAME,
+        env!("CARGO_PKG_VERSION"),
+        cef_header(&entry.action),
+        cef_header(&entry.action),
+        extension
+    )
+}
+
+fn cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn cef_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+/// CSV с заголовком; поля с запятыми, кавычками и переводами строк экранируются.
+pub fn to_csv(entries: &[AuditEntry]) -> String {
+    let mut out = String::from("id,at,actor_id,action,target,details\n");
+    for entry in entries {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            entry.id,
+            rfc3339(entry.at),
+            entry.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            csv_field(&entry.action),
+            csv_field(&entry.target),
+            csv_field(&entry.details)
+        );
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,66 @@
+//! HTTP-клиент бота: собственный Bot API сервер и исходящий прокси.
+//!
+//! Прокси передаётся в reqwest как есть; `socks5://` заменяется на `socks5h://`,
+//! чтобы имя хоста резолвилось на стороне прокси.
+
+use crate::bot::Bot;
+use crate::config::TelegramConfig;
+use anyhow::Context;
+use teloxide::adaptors::throttle::Limits;
+use teloxide::requests::RequesterExt;
+
+/// Переменная окружения teloxide с адресом прокси.
+const TELOXIDE_PROXY_ENV: &str = "TELOXIDE_PROXY";
+
+/// Создаёт HTTP-клиент для Bot API с учётом `[telegram]`.
+pub fn build_http_client(config: &TelegramConfig) -> Result<reqwest::Client, anyhow::Error> {
+    let mut builder = teloxide::net::default_reqwest_settings();
+    let proxy = config
+        .proxy
+        .clone()
+        .or_else(|| std::env::var(TELOXIDE_PROXY_ENV).ok());
+    if let Some(proxy) = proxy {
+        let mut url = reqwest::Url::parse(&proxy).context("Некорректный адрес telegram.proxy")?;
+        match url.scheme() {
+            "http" | "https" | "socks5h" => {}
+            "socks5" => {
+                url.set_scheme("socks5h")
+                    .map_err(|()| anyhow::anyhow!("Некорректный адрес SOCKS5-прокси"))?;
+            }
+            scheme => anyhow::bail!("Неподдерживаемая схема прокси: {}", scheme),
+        }
+        builder =
+            builder.proxy(reqwest::Proxy::all(url.as_str()).context("Ошибка настройки прокси")?);
+        tracing::info!(
+            scheme = url.scheme(),
+            host = url.host_str().unwrap_or(""),
+            port = url.port_or_known_default(),
+            "Outbound proxy configured for Bot API"
+        );
+    }
+    builder
+        .build()
+        .context("Не удалось создать HTTP-клиент бота")
+}
+
+/// Создаёт `Bot` с общим HTTP-клиентом, ограничением частоты из `[telegram.throttle]`
+/// и, если задан, собственным Bot API URL.
+pub fn build_bot(
+    config: &TelegramConfig,
+    client: reqwest::Client,
+    token: String,
+) -> Result<Bot, anyhow::Error> {
+    let mut bot = teloxide::Bot::with_client(token, client);
+    if let Some(api_url) = &config.api_url {
+        let url = reqwest::Url::parse(api_url).context("Некорректный telegram.api_url")?;
+        tracing::info!(api_url = %url, "Using custom Bot API server");
+        bot = bot.set_api_url(url);
+    }
+    let limits = Limits {
+        messages_per_sec_chat: config.throttle.messages_per_sec_chat.max(1),
+        messages_per_min_chat: config.throttle.messages_per_min_chat.max(1),
+        messages_per_sec_overall: config.throttle.messages_per_sec_overall.max(1),
+        ..Limits::default()
+    };
+    Ok(bot.throttle(limits))
+}
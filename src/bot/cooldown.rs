@@ -0,0 +1,64 @@
+//! Кулдауны дорогих команд для обычных пользователей.
+//!
+//! В отличие от очереди [`crate::bot::Bot`], которая ограничивает исходящие
+//! сообщения, здесь ограничивается частота входящих команд одного пользователя:
+//! повтор `/start` или `/link` раньше срока не доходит до БД и конфига telemt.
+
+use crate::config::CooldownConfig;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// При таком числе записей устаревшие удаляются.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Команды с кулдауном.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CooldownCommand {
+    Start,
+    Link,
+}
+
+pub struct Cooldowns {
+    config: CooldownConfig,
+    last_used: Mutex<HashMap<(i64, CooldownCommand), Instant>>,
+}
+
+impl Cooldowns {
+    pub fn new(config: CooldownConfig) -> Self {
+        Self {
+            config,
+            last_used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn duration(&self, command: CooldownCommand) -> Duration {
+        Duration::from_secs(match command {
+            CooldownCommand::Start => self.config.start_secs,
+            CooldownCommand::Link => self.config.link_secs,
+        })
+    }
+
+    /// Отмечает использование команды. `Err` — сколько секунд осталось ждать.
+    pub async fn check(&self, user_id: i64, command: CooldownCommand) -> Result<(), u64> {
+        let cooldown = self.duration(command);
+        if !self.config.enabled || cooldown.is_zero() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut last_used = self.last_used.lock().await;
+        if let Some(last) = last_used.get(&(user_id, command)) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < cooldown {
+                return Err((cooldown - elapsed).as_secs().max(1));
+            }
+        }
+        if last_used.len() >= PRUNE_THRESHOLD {
+            last_used
+                .retain(|(_, command), last| now.duration_since(*last) < self.duration(*command));
+        }
+        last_used.insert((user_id, command), now);
+        Ok(())
+    }
+}
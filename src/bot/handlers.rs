@@ -1,34 +1,171 @@
 //! Обработчики команд пользователя и админа.
 
+#[path = "handlers/allowlist.rs"]
+mod allowlist;
+#[path = "handlers/audit.rs"]
+mod audit;
+#[path = "handlers/bans.rs"]
+mod bans;
+#[path = "handlers/basket.rs"]
+mod basket;
+#[path = "handlers/blocklist.rs"]
+mod blocklist;
+#[path = "handlers/broadcast.rs"]
+mod broadcast;
 #[path = "handlers/callbacks/mod.rs"]
 mod callbacks;
+#[path = "handlers/channel_gate.rs"]
+mod channel_gate;
+#[path = "handlers/cleanup.rs"]
+mod cleanup;
 #[path = "handlers/commands/mod.rs"]
 mod commands;
+#[path = "handlers/config_export.rs"]
+mod config_export;
+#[path = "handlers/config_preview.rs"]
+mod config_preview;
+#[path = "handlers/ephemeral.rs"]
+mod ephemeral;
+#[path = "handlers/expiry.rs"]
+mod expiry;
 #[path = "handlers/format.rs"]
 mod format;
+#[path = "handlers/groups.rs"]
+mod groups;
+#[path = "handlers/import.rs"]
+mod import;
+#[path = "handlers/inline.rs"]
+mod inline;
+#[path = "handlers/jobs.rs"]
+mod jobs;
+#[path = "handlers/logs.rs"]
+mod logs;
 #[path = "handlers/menu.rs"]
 mod menu;
+#[path = "handlers/monitor.rs"]
+mod monitor;
+#[path = "handlers/onboarding.rs"]
+mod onboarding;
+#[path = "handlers/pending.rs"]
+mod pending;
+#[path = "handlers/purge.rs"]
+mod purge;
+#[path = "handlers/referral.rs"]
+mod referral;
+#[path = "handlers/reminders.rs"]
+mod reminders;
+#[path = "handlers/report.rs"]
+mod report;
+#[path = "handlers/restart.rs"]
+mod restart;
 #[path = "handlers/shared.rs"]
 mod shared;
 #[path = "handlers/state.rs"]
 mod state;
+#[path = "handlers/support.rs"]
+mod support;
+#[path = "handlers/survey.rs"]
+mod survey;
+#[path = "handlers/sync.rs"]
+mod sync;
+#[path = "handlers/token_guard.rs"]
+mod token_guard;
+#[path = "handlers/viewas.rs"]
+mod viewas;
+#[path = "handlers/webapp.rs"]
+mod webapp;
 
+pub use blocklist::spawn_blocklist_sync;
+pub use broadcast::spawn_broadcast_worker;
+pub use ephemeral::spawn_ephemeral_sweeper;
+pub use expiry::spawn_expiry_worker;
+pub use groups::spawn_group_scheduler;
+pub use import::import_on_first_run;
+pub use jobs::spawn_job_worker;
+pub use monitor::spawn_proxy_monitor;
+pub use onboarding::spawn_onboarding_drip;
+pub use reminders::spawn_pending_reminders;
+pub use report::spawn_weekly_report;
 pub use state::BotState;
+pub use support::spawn_ticket_closer;
+pub use survey::spawn_survey_worker;
+pub use sync::{check_config_drift_on_startup, detect_drift, render_drift};
+pub use webapp::{ADMIN_APP_PAGE, AdminApp, ApiResponse, setup_admin_app_menu_button};
 
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::error::AppError;
+use std::sync::Arc;
 use teloxide::dispatching::DpHandlerDescription;
 use teloxide::dptree;
+use teloxide::error_handlers::ErrorHandler;
 use teloxide::prelude::*;
 
-pub fn schema() -> dptree::Handler<
-    'static,
-    Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>,
-    DpHandlerDescription,
-> {
+pub fn schema() -> dptree::Handler<'static, Result<(), AppError>, DpHandlerDescription> {
     let message_handler = Update::filter_message()
         .branch(commands::handler())
-        .endpoint(menu::handle_menu_buttons);
+        .endpoint(shared::reply_on_error(menu::handle_menu_buttons));
 
     dptree::entry()
         .branch(message_handler)
         .branch(callbacks::handler())
+        .branch(Update::filter_inline_query().endpoint(inline::handle_inline_query))
+}
+
+/// Доставляет администраторам уведомления из [`crate::alerts`]. События аудита
+/// публикуются только в группе админов и никогда не рассылаются в личку.
+pub fn spawn_admin_alerts(
+    bot: Bot,
+    state: BotState,
+    receiver: crate::alerts::AdminAlertsReceiver,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut receiver = receiver.lock().await;
+        while let Some((topic, text)) = receiver.recv().await {
+            if topic == AdminTopic::Audit && state.config.admin_chat_id.is_none() {
+                continue;
+            }
+            for destination in shared::admin_destinations(&state, topic) {
+                if let Err(error) = destination.send_message(&bot, text.clone()).await {
+                    tracing::warn!(
+                        chat_id = destination.chat_id.0,
+                        error = %error,
+                        "Не удалось отправить админу уведомление"
+                    );
+                }
+            }
+        }
+    })
+}
+
+/// Общий обработчик ошибок диспетчера: логирует все ошибки и сообщает
+/// администраторам о неожиданных (БД, конфиг telemt, systemd, внутренние).
+pub fn admin_error_reporter(
+    bot: Bot,
+    state: BotState,
+) -> Arc<dyn ErrorHandler<AppError> + Send + Sync> {
+    Arc::new(move |error: AppError| {
+        let bot = bot.clone();
+        let destinations = shared::admin_destinations(&state, AdminTopic::Alerts);
+        async move {
+            tracing::error!(class = error.class(), error = %error, "Unhandled handler error");
+            if !error.is_unexpected() {
+                return;
+            }
+            let text = format!(
+                "⚠️ Ошибка при обработке запроса\nКласс: {}\n{}",
+                error.class(),
+                error
+            );
+            for destination in destinations {
+                if let Err(send_error) = destination.send_message(&bot, text.clone()).await {
+                    tracing::warn!(
+                        chat_id = destination.chat_id.0,
+                        error = %send_error,
+                        "Не удалось отправить админу отчёт об ошибке"
+                    );
+                }
+            }
+        }
+    })
 }
@@ -0,0 +1,150 @@
+//! Белый список (`registration_mode = "whitelist"`): заявку подают только ID,
+//! заранее добавленные через `/allow`; остальные на /start и ввод токена молча
+//! получают текст `blocked_text`. Уже одобренные пользователи список не проходят.
+
+use super::format::format_timestamp;
+use super::shared::HandlerResult;
+use super::state::{BotState, is_admin_message, sender_user_id};
+use crate::bot::Bot;
+use crate::config::RegistrationMode;
+use crate::error::AppError;
+use teloxide::prelude::*;
+
+const ALLOWED_LIST_LIMIT: i64 = 50;
+const ALLOW_USAGE: &str = "Использование:
+/allow — белый список
+/allow <tg_user_id> [tg_user_id …] — добавить (можно вставить список через пробел, запятую или с новой строки)
+/allow remove <tg_user_id> — убрать";
+
+/// Может ли пользователь подать заявку при текущем `registration_mode`.
+pub async fn registration_allowed(state: &BotState, tg_user_id: i64) -> Result<bool, AppError> {
+    Ok(match state.config.registration_mode {
+        RegistrationMode::Open => true,
+        RegistrationMode::Whitelist => state.db.is_user_allowed(tg_user_id).await?,
+    })
+}
+
+pub async fn cmd_allow(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let Some(admin_id) = sender_user_id(&msg) else {
+        return Ok(());
+    };
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|value| !value.is_empty())
+        .skip(1)
+        .collect();
+
+    match args.as_slice() {
+        [] => show_allowed_list(&bot, msg.chat.id, &state).await,
+        ["remove", tg_user_id] => {
+            let Ok(tg_user_id) = tg_user_id.parse::<i64>() else {
+                bot.send_message(msg.chat.id, ALLOW_USAGE).await?;
+                return Ok(());
+            };
+            let text = if state.db.disallow_user(tg_user_id).await? {
+                state
+                    .audit
+                    .record(admin_id, "disallow", &format!("tg_user:{}", tg_user_id), "")
+                    .await;
+                format!(
+                    "Пользователь {} убран из белого списка. Выданный доступ не отзывается.",
+                    tg_user_id
+                )
+            } else {
+                format!("Пользователя {} нет в белом списке.", tg_user_id)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+            Ok(())
+        }
+        ids => {
+            let parsed: Result<Vec<i64>, &str> = ids
+                .iter()
+                .map(|value| {
+                    value
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|tg_user_id| *tg_user_id > 0)
+                        .ok_or(*value)
+                })
+                .collect();
+            let tg_user_ids = match parsed {
+                Ok(tg_user_ids) => tg_user_ids,
+                Err(invalid) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        format!("«{}» — не tg_user_id.\n\n{}", invalid, ALLOW_USAGE),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            };
+            let added = state.db.allow_users(&tg_user_ids, admin_id).await?;
+            let target = match tg_user_ids.as_slice() {
+                [tg_user_id] => format!("tg_user:{}", tg_user_id),
+                _ => "allowlist".to_string(),
+            };
+            state
+                .audit
+                .record(
+                    admin_id,
+                    "allow",
+                    &target,
+                    &format!("добавлено: {} из {}", added, tg_user_ids.len()),
+                )
+                .await;
+            tracing::info!(
+                admin_id = admin_id,
+                added = added,
+                total = tg_user_ids.len(),
+                "Users added to allowlist"
+            );
+            let mut text = format!(
+                "✅ В белый список добавлено: {} (уже были: {}).",
+                added,
+                tg_user_ids.len() as u64 - added
+            );
+            if state.config.registration_mode != RegistrationMode::Whitelist {
+                text.push_str(
+                    "\nРежим registration_mode сейчас open: список начнёт действовать после \
+                     переключения на whitelist.",
+                );
+            }
+            bot.send_message(msg.chat.id, text).await?;
+            Ok(())
+        }
+    }
+}
+
+async fn show_allowed_list(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
+    let (allowed, total) = state.db.list_allowed_users(ALLOWED_LIST_LIMIT).await?;
+    let mut text = format!(
+        "📋 Белый список ({}), режим регистрации: {}",
+        total, state.config.registration_mode
+    );
+    if allowed.is_empty() {
+        text.push_str("\nСписок пуст.");
+    }
+    for user in &allowed {
+        let status = user
+            .status
+            .map(|status| status.to_string())
+            .unwrap_or_else(|| "ещё не писал боту".to_string());
+        text.push_str(&format!(
+            "\n{} — добавлен {}, {}",
+            user.tg_user_id,
+            format_timestamp(user.added_at),
+            status
+        ));
+    }
+    if total > allowed.len() as i64 {
+        text.push_str(&format!("\n…показаны последние {}", allowed.len()));
+    }
+    text.push_str(&format!("\n\n{}", ALLOW_USAGE));
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,190 @@
+//! `/audit`: последние записи журнала аудита с листанием кнопками и выгрузка
+//! за период в CSV/JSON.
+
+use super::format::format_timestamp;
+use super::shared::{HandlerResult, callback_message_target, require_admin_callback};
+use super::state::{BotState, is_admin_message};
+use crate::bot::Bot;
+use crate::error::AppError;
+use chrono::{Days, Local, NaiveDate};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardMarkup, InputFile};
+
+const RECENT_LIMIT: i64 = 20;
+/// Больше записей на странице не помещается в одно сообщение Telegram.
+const MAX_PAGE_LIMIT: i64 = 50;
+const DEFAULT_EXPORT_DAYS: u64 = 30;
+const AUDIT_USAGE: &str = "Использование:
+/audit [N] — последние N записей журнала аудита (по умолчанию 20, не больше 50), листание кнопками
+/audit export [с ГГГГ-ММ-ДД] [по ГГГГ-ММ-ДД] [--json] — выгрузка за период (по умолчанию 30 дней, CSV)";
+
+fn local_day_start(date: NaiveDate) -> Option<i64> {
+    date.and_hms_opt(0, 0, 0)?
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|dt| dt.timestamp())
+}
+
+async fn render_audit_page(
+    state: &BotState,
+    limit: i64,
+    offset: i64,
+) -> Result<(String, InlineKeyboardMarkup), AppError> {
+    let total = state.db.count_audit_entries().await?;
+    let entries = state.db.recent_audit_entries(limit, offset).await?;
+    let mut text = if entries.is_empty() {
+        String::from("🛡 Журнал аудита:\nзаписей нет")
+    } else {
+        format!(
+            "🛡 Журнал аудита, записи {}–{} из {}:",
+            offset + 1,
+            offset + entries.len() as i64,
+            total
+        )
+    };
+    for entry in &entries {
+        text.push_str(&format!(
+            "\n{} | {} | {} {}",
+            format_timestamp(entry.at),
+            entry
+                .actor_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "—".to_string()),
+            entry.action,
+            entry.target
+        ));
+        if !entry.details.is_empty() {
+            text.push_str(&format!(" ({})", entry.details));
+        }
+    }
+    Ok((
+        text,
+        crate::bot::keyboards::audit_page_keyboard(limit, offset, total),
+    ))
+}
+
+pub async fn cmd_audit(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .skip(1)
+        .collect();
+
+    let page_limit = match args.as_slice() {
+        [] => Some(RECENT_LIMIT),
+        [value] => value.parse::<i64>().ok().filter(|limit| *limit > 0),
+        _ => None,
+    };
+    if let Some(limit) = page_limit {
+        let (text, markup) = render_audit_page(&state, limit.min(MAX_PAGE_LIMIT), 0).await?;
+        bot.send_message(msg.chat.id, text)
+            .reply_markup(markup)
+            .await?;
+        return Ok(());
+    }
+
+    match args.split_first() {
+        Some((&"export", rest)) => {
+            let json = rest.contains(&"--json");
+            let dates: Vec<&str> = rest
+                .iter()
+                .copied()
+                .filter(|arg| *arg != "--json")
+                .collect();
+            let parsed: Option<Vec<NaiveDate>> = dates
+                .iter()
+                .map(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+                .collect();
+            let today = Local::now().date_naive();
+            let (from_day, to_day) = match parsed.as_deref() {
+                Some([]) => (
+                    today
+                        .checked_sub_days(Days::new(DEFAULT_EXPORT_DAYS))
+                        .unwrap_or(today),
+                    today,
+                ),
+                Some([from]) => (*from, today),
+                Some([from, to]) if from <= to => (*from, *to),
+                _ => {
+                    bot.send_message(msg.chat.id, AUDIT_USAGE).await?;
+                    return Ok(());
+                }
+            };
+            // Конец периода включительно: до начала следующего дня.
+            let (Some(from), Some(to)) = (
+                local_day_start(from_day),
+                to_day.succ_opt().and_then(local_day_start),
+            ) else {
+                bot.send_message(msg.chat.id, AUDIT_USAGE).await?;
+                return Ok(());
+            };
+
+            let entries = state.db.list_audit_entries(from, to).await?;
+            let (body, extension) = if json {
+                (
+                    serde_json::to_string_pretty(&entries)
+                        .map_err(|e| anyhow::anyhow!("Не удалось сериализовать журнал: {}", e))?,
+                    "json",
+                )
+            } else {
+                (crate::audit::to_csv(&entries), "csv")
+            };
+            let file_name = format!("audit-{}-{}.{}", from_day, to_day, extension);
+            tracing::info!(
+                from = %from_day,
+                to = %to_day,
+                entries = entries.len(),
+                format = extension,
+                "Admin command /audit export"
+            );
+            bot.send_document(
+                msg.chat.id,
+                InputFile::memory(body.into_bytes()).file_name(file_name),
+            )
+            .caption(format!(
+                "🛡 Журнал аудита с {} по {}: {} записей",
+                from_day,
+                to_day,
+                entries.len()
+            ))
+            .await?;
+        }
+        _ => {
+            bot.send_message(msg.chat.id, AUDIT_USAGE).await?;
+        }
+    }
+    Ok(())
+}
+
+/// `audit_page:<limit>:<offset>`: страница журнала в том же сообщении.
+pub async fn callback_audit_page(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    if require_admin_callback(&bot, &q, &state).await?.is_none() {
+        return Ok(());
+    }
+    let mut parts = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("audit_page:"))
+        .unwrap_or("")
+        .split(':')
+        .map(|value| value.parse::<i64>().ok());
+    let (Some(Some(limit)), Some(Some(offset))) = (parts.next(), parts.next()) else {
+        bot.answer_callback_query(q.id.clone())
+            .text("Некорректная страница")
+            .await?;
+        return Ok(());
+    };
+    let limit = limit.clamp(1, MAX_PAGE_LIMIT);
+    bot.answer_callback_query(q.id.clone()).await?;
+    let (text, markup) = render_audit_page(&state, limit, offset.max(0)).await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(markup)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Ручные блокировки (`/ban`, `/unban`): заблокированный теряет доступ, его заявка
+//! отклоняется, а повторная регистрация невозможна даже с действующим токеном —
+//! /start и ввод токена получают текст `blocked_text`. В отличие от внешнего
+//! `[blocklist]`, запись живёт в `banned_users` до явного `/unban`.
+
+use super::format::format_timestamp;
+use super::shared::{HandlerResult, perform_hard_ban};
+use super::state::{BotState, is_admin_message, sender_user_id};
+use crate::bot::Bot;
+use teloxide::prelude::*;
+
+const BANNED_LIST_LIMIT: i64 = 50;
+const BAN_USAGE: &str = "Использование:
+/ban — список заблокированных
+/ban <tg_user_id> [причина] — заблокировать
+/unban <tg_user_id> — снять блокировку";
+
+pub async fn cmd_ban(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let Some(admin_id) = sender_user_id(&msg) else {
+        return Ok(());
+    };
+    let args = msg
+        .text()
+        .unwrap_or("")
+        .split_once(char::is_whitespace)
+        .map(|(_, rest)| rest.trim())
+        .unwrap_or("");
+    if args.is_empty() {
+        return show_banned_list(&bot, msg.chat.id, &state).await;
+    }
+    let (target, reason) = match args.split_once(char::is_whitespace) {
+        Some((target, reason)) => (target, Some(reason.trim())),
+        None => (args, None),
+    };
+    let Ok(tg_user_id) = target.parse::<i64>() else {
+        bot.send_message(msg.chat.id, BAN_USAGE).await?;
+        return Ok(());
+    };
+    if state.config.is_admin(tg_user_id) {
+        bot.send_message(msg.chat.id, "Нельзя заблокировать администратора.")
+            .await?;
+        return Ok(());
+    }
+    let reason = reason.filter(|reason| !reason.is_empty());
+
+    if !state.db.ban_user(tg_user_id, admin_id, reason).await? {
+        bot.send_message(
+            msg.chat.id,
+            format!("Пользователь {} уже заблокирован.", tg_user_id),
+        )
+        .await?;
+        return Ok(());
+    }
+    let mut lines = vec![format!("⛔ Пользователь {} заблокирован.", tg_user_id)];
+    if state.db.get_approved(tg_user_id).await?.is_some() {
+        lines.push(perform_hard_ban(&state, tg_user_id).await?);
+    }
+    if let Some(request) = state.db.get_pending_by_tg_user(tg_user_id).await?
+        && state.db.reject(request.id, None).await?.is_some()
+    {
+        crate::metrics::record_rejection();
+        lines.push(format!("Заявка #{} отклонена.", request.id));
+    }
+    state
+        .audit
+        .record(
+            admin_id,
+            "ban",
+            &format!("tg_user:{}", tg_user_id),
+            reason.unwrap_or(""),
+        )
+        .await;
+    tracing::info!(admin_id = admin_id, tg_user_id = tg_user_id, "User banned");
+    bot.send_message(msg.chat.id, lines.join("\n")).await?;
+    Ok(())
+}
+
+pub async fn cmd_unban(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let Some(admin_id) = sender_user_id(&msg) else {
+        return Ok(());
+    };
+    let Some(tg_user_id) = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .nth(1)
+        .and_then(|value| value.parse::<i64>().ok())
+    else {
+        bot.send_message(msg.chat.id, BAN_USAGE).await?;
+        return Ok(());
+    };
+
+    let text = if state.db.unban_user(tg_user_id).await? {
+        state
+            .audit
+            .record(admin_id, "unban", &format!("tg_user:{}", tg_user_id), "")
+            .await;
+        tracing::info!(
+            admin_id = admin_id,
+            tg_user_id = tg_user_id,
+            "User unbanned"
+        );
+        let mut text = format!("✅ Блокировка {} снята.", tg_user_id);
+        if state.db.is_user_blocked(tg_user_id).await? {
+            text.push_str("\nОн всё ещё есть во внешнем списке блокировки ([blocklist]).");
+        }
+        text
+    } else {
+        format!("Пользователь {} не заблокирован через /ban.", tg_user_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+async fn show_banned_list(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
+    let (banned, total) = state.db.list_banned_users(BANNED_LIST_LIMIT).await?;
+    if banned.is_empty() {
+        bot.send_message(chat_id, format!("Заблокированных нет.\n\n{}", BAN_USAGE))
+            .await?;
+        return Ok(());
+    }
+    let mut text = format!("⛔ Заблокированные ({}):", total);
+    for user in &banned {
+        text.push_str(&format!("\n{}", user.tg_user_id));
+        if let Some(username) = user.tg_username.as_deref() {
+            text.push_str(&format!(" @{}", username));
+        }
+        text.push_str(&format!(" — {}", format_timestamp(user.banned_at)));
+        if let Some(admin_id) = user.banned_by {
+            text.push_str(&format!(", админ {}", admin_id));
+        }
+        if let Some(reason) = user.reason.as_deref() {
+            text.push_str(&format!(": {}", reason));
+        }
+    }
+    if total > banned.len() as i64 {
+        text.push_str(&format!("\n…показаны последние {}", banned.len()));
+    }
+    text.push_str("\n\n/unban <tg_user_id> — снять блокировку");
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,167 @@
+//! Корзина одобрения: админ откладывает несколько заявок и применяет их разом —
+//! все секреты записываются в конфиг telemt за один проход с одним рестартом,
+//! а пользователи получают ссылки только после успешного рестарта.
+
+use super::ephemeral::send_proxy_link;
+use super::format::{format_timestamp, user_display_name};
+use super::shared::{schedule_post_approval, user_lang};
+use super::state::{BotState, telemt_username};
+use crate::bot::Bot;
+use crate::db::RegistrationRequest;
+use crate::error::AppError;
+use crate::i18n::tf;
+use crate::link::{build_proxy_link, generate_user_secret};
+use crate::telemt_cfg::UserMutation;
+use teloxide::prelude::*;
+
+/// Итог применения корзины.
+pub struct BasketOutcome {
+    pub approved: usize,
+    pub delivered: usize,
+}
+
+/// Текст со списком заявок в корзине.
+pub async fn render_basket(state: &BotState) -> Result<(String, usize), AppError> {
+    let staged = state.db.list_staged_requests().await?;
+    if staged.is_empty() {
+        return Ok(("🧺 Корзина одобрения пуста.".to_string(), 0));
+    }
+    let mut text = format!("🧺 Корзина одобрения ({}):\n", staged.len());
+    for request in &staged {
+        text.push_str(&format!(
+            "\n#{} — {}",
+            request.id,
+            user_display_name(request)
+        ));
+    }
+    text.push_str("\n\nВсе заявки будут применены одной записью конфига и одним рестартом.");
+    Ok((text, staged.len()))
+}
+
+pub fn render_basket_outcome(outcome: &BasketOutcome) -> String {
+    if outcome.approved == 0 {
+        return "Корзина пуста — нечего применять.".to_string();
+    }
+    format!(
+        "✅ Одобрено заявок: {} (один рестарт). Ссылки доставлены: {}.",
+        outcome.approved, outcome.delivered
+    )
+}
+
+/// Одобряет все ожидающие заявки разом: кладёт их в корзину и применяет её.
+pub async fn approve_all_pending(
+    bot: &Bot,
+    state: &BotState,
+    admin_id: i64,
+) -> Result<BasketOutcome, AppError> {
+    let staged = state.db.stage_all_pending(admin_id).await?;
+    tracing::info!(
+        admin_id = admin_id,
+        staged = staged,
+        "All pending requests staged"
+    );
+    apply_approval_basket(bot, state, admin_id).await
+}
+
+/// Одобряет все заявки из корзины с одним рестартом telemt и рассылает ссылки.
+pub async fn apply_approval_basket(
+    bot: &Bot,
+    state: &BotState,
+    admin_id: i64,
+) -> Result<BasketOutcome, AppError> {
+    let staged = state.db.list_staged_requests().await?;
+    // Сначала заявки одобряются в БД: решённая параллельно (другим админом или
+    // отклонением) заявка сюда не попадёт, и её секрет не окажется в конфиге.
+    let mut approved: Vec<(RegistrationRequest, String, String)> = Vec::with_capacity(staged.len());
+    for request in staged {
+        let telemt_user = telemt_username(request.tg_user_id);
+        let secret = generate_user_secret();
+        if state
+            .db
+            .approve(request.id, &telemt_user, &secret)
+            .await?
+            .is_none()
+        {
+            continue;
+        }
+        approved.push((request, telemt_user, secret));
+    }
+    if approved.is_empty() {
+        return Ok(BasketOutcome {
+            approved: 0,
+            delivered: 0,
+        });
+    }
+
+    let mutations = approved
+        .iter()
+        .map(|(_, username, secret)| UserMutation::Upsert {
+            username: username.clone(),
+            secret: secret.clone(),
+        })
+        .collect();
+    // Одна запись и один рестарт. Ссылки уходят только после рестарта, применившего
+    // секреты (в том числе отложенного); при ошибке заявки возвращаются в ожидание.
+    if let Err(error) = state.cfg_writer.apply_and_wait(mutations).await {
+        for (request, _, secret) in &approved {
+            if let Err(revert_error) = state.db.revert_approval(request.id, secret).await {
+                tracing::warn!(
+                    request_id = request.id,
+                    error = %revert_error,
+                    "Не удалось вернуть заявку из корзины в ожидание"
+                );
+            }
+        }
+        return Err(error.into());
+    }
+    tracing::info!(
+        count = approved.len(),
+        "Approval basket written to telemt config"
+    );
+
+    let params = state.telemt_cfg.read_link_params().await?;
+    let mut outcome = BasketOutcome {
+        approved: approved.len(),
+        delivered: 0,
+    };
+    let mut approved_ids = Vec::with_capacity(approved.len());
+    for (request, _, secret) in &approved {
+        approved_ids.push(request.id.to_string());
+        schedule_post_approval(state, request.tg_user_id).await;
+        let link = build_proxy_link(&params, secret)?;
+        let lang = user_lang(state, request.tg_user_id).await?;
+        let mut text = tf(lang, "link_message", &[("link", &link)]);
+        let group = state.db.get_user_group(request.tg_user_id).await?;
+        if let Some(seconds) = state.config.group_access_secs(group.as_deref()) {
+            let expires_at = chrono::Utc::now().timestamp() + seconds;
+            state
+                .db
+                .set_user_expiry(request.tg_user_id, Some(expires_at))
+                .await?;
+            text.push('\n');
+            text.push_str(&tf(
+                lang,
+                "access_until",
+                &[("date", &format_timestamp(expires_at))],
+            ));
+        }
+        match send_proxy_link(bot, state, ChatId(request.tg_user_id), text, false).await {
+            Ok(_) => outcome.delivered += 1,
+            Err(error) => tracing::warn!(
+                request_id = request.id,
+                error = %error,
+                "Не удалось отправить ссылку пользователю из корзины"
+            ),
+        }
+    }
+    state
+        .audit
+        .record(
+            admin_id,
+            "basket_apply",
+            &format!("requests:{}", approved_ids.len()),
+            &approved_ids.join(" "),
+        )
+        .await;
+    Ok(outcome)
+}
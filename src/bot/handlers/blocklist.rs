@@ -0,0 +1,144 @@
+//! Внешний список заблокированных (`[blocklist]`): периодически скачивается по URL,
+//! новые ID из него теряют доступ (одна запись конфига telemt и один рестарт на
+//! всю пачку), их заявки отклоняются, а повторный /start получает отказ.
+
+use super::shared::admin_destinations;
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::error::AppError;
+use crate::telemt_cfg::UserMutation;
+use std::time::Duration;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn spawn_blocklist_sync(bot: Bot, state: BotState) -> Option<tokio::task::JoinHandle<()>> {
+    let url = state.config.blocklist.url.clone()?;
+    let interval = Duration::from_secs(state.config.blocklist.interval_minutes.max(1) * 60);
+    Some(tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            if let Err(error) = sync_blocklist(&bot, &state, &client, &url).await {
+                tracing::warn!(url = %url, error = %error, "Не удалось обновить список блокировки");
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }))
+}
+
+async fn sync_blocklist(
+    bot: &Bot,
+    state: &BotState,
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<(), AppError> {
+    let response = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Запрос списка блокировки: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow::anyhow!("{} вернул {}", url, status).into());
+    }
+    let body = response
+        .text()
+        .await
+        .map_err(|e| anyhow::anyhow!("Чтение списка блокировки: {}", e))?;
+    // Битый список не применяется частично: прежний остаётся в силе.
+    let ids = parse_blocklist(&body)
+        .ok_or_else(|| anyhow::anyhow!("Список блокировки в неизвестном формате"))?;
+
+    let added = state.db.replace_blocklist(&ids).await?;
+    tracing::info!(total = ids.len(), added = added.len(), "Blocklist synced");
+    if added.is_empty() {
+        return Ok(());
+    }
+
+    let mut mutations = Vec::new();
+    for tg_user_id in &added {
+        if let Some((username, _)) = state.db.get_approved(*tg_user_id).await? {
+            mutations.push(UserMutation::Remove { username });
+        }
+    }
+    if !mutations.is_empty() {
+        state.cfg_writer.apply(mutations).await?;
+    }
+    let mut revoked = Vec::new();
+    let mut rejected = Vec::new();
+    for tg_user_id in &added {
+        if state.db.deactivate_user(*tg_user_id).await? {
+            crate::metrics::record_deletion("blocklist");
+            revoked.push(*tg_user_id);
+        }
+        if let Some(request) = state.db.get_pending_by_tg_user(*tg_user_id).await?
+            && state.db.reject(request.id, None).await?.is_some()
+        {
+            crate::metrics::record_rejection();
+            rejected.push(*tg_user_id);
+        }
+    }
+
+    if revoked.is_empty() && rejected.is_empty() {
+        return Ok(());
+    }
+    let text = render_summary(added.len(), &revoked, &rejected);
+    for destination in admin_destinations(state, AdminTopic::Alerts) {
+        if let Err(error) = destination.send_message(bot, text.clone()).await {
+            tracing::warn!(
+                chat_id = destination.chat_id.0,
+                error = %error,
+                "Не удалось отправить админу сводку по списку блокировки"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// ID по одному в строке (пустые строки и `#`-комментарии пропускаются) или
+/// JSON-массив чисел/строк. Любая нераспознанная запись делает список недействительным.
+fn parse_blocklist(body: &str) -> Option<Vec<i64>> {
+    let trimmed = body.trim_start();
+    if trimmed.starts_with('[') {
+        let values: Vec<serde_json::Value> = serde_json::from_str(trimmed).ok()?;
+        return values
+            .iter()
+            .map(|value| match value {
+                serde_json::Value::Number(number) => number.as_i64(),
+                serde_json::Value::String(text) => text.trim().parse().ok(),
+                _ => None,
+            })
+            .collect();
+    }
+    body.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse().ok())
+        .collect()
+}
+
+fn render_summary(added: usize, revoked: &[i64], rejected: &[i64]) -> String {
+    let join = |ids: &[i64]| {
+        ids.iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut text = format!("🚫 Список блокировки: новых записей {}", added);
+    if !revoked.is_empty() {
+        text.push_str(&format!(
+            "\nДоступ отозван ({}): {}",
+            revoked.len(),
+            join(revoked)
+        ));
+    }
+    if !rejected.is_empty() {
+        text.push_str(&format!(
+            "\nЗаявки отклонены ({}): {}",
+            rejected.len(),
+            join(rejected)
+        ));
+    }
+    text
+}
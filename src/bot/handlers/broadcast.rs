@@ -0,0 +1,504 @@
+//! `/broadcast`: рассылка произвольного текста выбранной аудитории (все одобренные,
+//! ожидающие, группа, пользователи с истекающим доступом). Админ получает черновик с
+//! предпросмотром, выбирает аудиторию кнопками, отправляет сразу или по расписанию и
+//! по завершении получает отчёт о доставке. Очередь и получатели хранятся в SQLite
+//! (`broadcasts`, `broadcast_deliveries`), поэтому рестарт бота не теряет рассылку.
+
+use super::format::format_timestamp;
+use super::groups::parse_local_datetime;
+use super::shared::{HandlerResult, callback_message_target, require_admin_callback};
+use super::state::{BotState, is_admin_message, sender_user_id};
+use crate::bot::Bot;
+use crate::db::{Broadcast, BroadcastAudience, BroadcastStatus, DeliveryStatus};
+use crate::error::AppError;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
+use teloxide::{ApiError, RequestError};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const BATCH_SIZE: i64 = 50;
+/// Аудитория «скоро истекает доступ»: срок заканчивается в ближайшие дни.
+const EXPIRING_WITHIN_DAYS: i64 = 7;
+const MAX_GROUP_BUTTONS: usize = 9;
+const RECENT_LIMIT: i64 = 10;
+const BROADCAST_USAGE: &str = "Использование:
+/broadcast — запланированные и последние рассылки
+/broadcast [ГГГГ-ММ-ДД ЧЧ:ММ] <текст> — новая рассылка: выбор аудитории, предпросмотр, отправка
+/broadcast report <id> — отчёт о доставке
+/broadcast cancel <id> — отменить запланированную или идущую рассылку";
+
+pub fn spawn_broadcast_worker(bot: Bot, state: BotState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(error) = run_due_broadcasts(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось выполнить рассылку");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    })
+}
+
+fn expiring_before() -> i64 {
+    chrono::Utc::now().timestamp() + EXPIRING_WITHIN_DAYS * 86_400
+}
+
+async fn run_due_broadcasts(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    for broadcast in state.db.list_due_broadcasts().await? {
+        if broadcast.status == BroadcastStatus::Scheduled {
+            if !state
+                .db
+                .start_broadcast(&broadcast, expiring_before())
+                .await?
+            {
+                continue;
+            }
+            tracing::info!(broadcast_id = broadcast.id, "Broadcast started");
+        }
+        if send_broadcast(bot, state, &broadcast).await? {
+            report_to_author(bot, state, &broadcast).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Отправляет оставшимся получателям; `false`, если рассылку отменили по ходу.
+async fn send_broadcast(
+    bot: &Bot,
+    state: &BotState,
+    broadcast: &Broadcast,
+) -> Result<bool, AppError> {
+    loop {
+        let cancelled = state
+            .db
+            .get_broadcast(broadcast.id)
+            .await?
+            .is_none_or(|current| current.status == BroadcastStatus::Cancelled);
+        if cancelled {
+            tracing::info!(broadcast_id = broadcast.id, "Broadcast cancelled");
+            return Ok(false);
+        }
+        let batch = state
+            .db
+            .list_queued_deliveries(broadcast.id, BATCH_SIZE)
+            .await?;
+        if batch.is_empty() {
+            break;
+        }
+        for tg_user_id in batch {
+            let status = match bot
+                .send_message(ChatId(tg_user_id), broadcast.text.clone())
+                .await
+            {
+                Ok(_) => DeliveryStatus::Sent,
+                Err(error) => {
+                    tracing::warn!(
+                        broadcast_id = broadcast.id,
+                        tg_user_id = tg_user_id,
+                        error = %error,
+                        "Не удалось отправить рассылку пользователю"
+                    );
+                    if is_blocked(&error) {
+                        DeliveryStatus::Blocked
+                    } else {
+                        DeliveryStatus::Failed
+                    }
+                }
+            };
+            // Статус сохраняется сразу: после рестарта сообщение не уйдёт повторно.
+            state
+                .db
+                .set_delivery_status(broadcast.id, tg_user_id, status)
+                .await?;
+        }
+    }
+    state.db.finish_broadcast(broadcast.id).await?;
+    tracing::info!(broadcast_id = broadcast.id, "Broadcast finished");
+    Ok(true)
+}
+
+/// Пользователь заблокировал бота, удалил аккаунт или ни разу не писал боту.
+fn is_blocked(error: &RequestError) -> bool {
+    matches!(
+        error,
+        RequestError::Api(
+            ApiError::BotBlocked | ApiError::UserDeactivated | ApiError::CantInitiateConversation
+        )
+    )
+}
+
+async fn render_report(state: &BotState, broadcast: &Broadcast) -> Result<String, AppError> {
+    let report = state.db.broadcast_report(broadcast.id).await?;
+    let status = match broadcast.status {
+        BroadcastStatus::Draft => "черновик",
+        BroadcastStatus::Scheduled => "запланирована",
+        BroadcastStatus::Sending => "отправляется",
+        BroadcastStatus::Done => "завершена",
+        BroadcastStatus::Cancelled => "отменена",
+    };
+    let mut text = format!(
+        "📣 Рассылка #{} ({}) — {}\n✅ Доставлено: {}\n⚠️ Ошибки: {}\n🚫 Заблокировали бота: {}",
+        broadcast.id,
+        audience_label(broadcast),
+        status,
+        report.sent,
+        report.failed,
+        report.blocked
+    );
+    if report.queued > 0 {
+        text.push_str(&format!("\n⏳ Не отправлено: {}", report.queued));
+    }
+    Ok(text)
+}
+
+async fn report_to_author(
+    bot: &Bot,
+    state: &BotState,
+    broadcast: &Broadcast,
+) -> Result<(), AppError> {
+    let Some(broadcast) = state.db.get_broadcast(broadcast.id).await? else {
+        return Ok(());
+    };
+    let text = render_report(state, &broadcast).await?;
+    if let Err(error) = bot.send_message(ChatId(broadcast.created_by), text).await {
+        tracing::warn!(
+            admin_id = broadcast.created_by,
+            error = %error,
+            "Не удалось отправить админу отчёт о рассылке"
+        );
+    }
+    Ok(())
+}
+
+fn audience_label(broadcast: &Broadcast) -> String {
+    match broadcast.audience {
+        BroadcastAudience::Approved => "все одобренные".to_string(),
+        BroadcastAudience::Pending => "ожидающие заявки".to_string(),
+        BroadcastAudience::Expiring => {
+            format!("доступ истекает в ближайшие {} дней", EXPIRING_WITHIN_DAYS)
+        }
+        BroadcastAudience::Group => format!(
+            "группа «{}»",
+            broadcast.group_name.as_deref().unwrap_or("—")
+        ),
+    }
+}
+
+/// Ключ аудитории в callback-данных: `approved`, `pending`, `expiring`, `g:<группа>`.
+fn audience_key(broadcast: &Broadcast) -> String {
+    match broadcast.audience {
+        BroadcastAudience::Approved => "approved".to_string(),
+        BroadcastAudience::Pending => "pending".to_string(),
+        BroadcastAudience::Expiring => "expiring".to_string(),
+        BroadcastAudience::Group => {
+            format!("g:{}", broadcast.group_name.as_deref().unwrap_or(""))
+        }
+    }
+}
+
+fn parse_audience_key(key: &str) -> Option<(BroadcastAudience, Option<&str>)> {
+    match key {
+        "approved" => Some((BroadcastAudience::Approved, None)),
+        "pending" => Some((BroadcastAudience::Pending, None)),
+        "expiring" => Some((BroadcastAudience::Expiring, None)),
+        _ => key
+            .strip_prefix("g:")
+            .filter(|group| !group.is_empty())
+            .map(|group| (BroadcastAudience::Group, Some(group))),
+    }
+}
+
+async fn count_recipients(state: &BotState, broadcast: &Broadcast) -> Result<i64, AppError> {
+    Ok(state
+        .db
+        .count_broadcast_audience(
+            broadcast.audience,
+            broadcast.group_name.as_deref(),
+            expiring_before(),
+        )
+        .await?)
+}
+
+/// Карточка черновика: аудитория, время отправки и текст в том виде, в каком его
+/// получат пользователи.
+async fn render_draft(
+    state: &BotState,
+    broadcast: &Broadcast,
+) -> Result<(String, InlineKeyboardMarkup), AppError> {
+    let recipients = count_recipients(state, broadcast).await?;
+    let when = match broadcast.scheduled_at {
+        Some(at) => format_timestamp(at),
+        None => "сразу после подтверждения".to_string(),
+    };
+    let text = format!(
+        "📣 Рассылка #{} — черновик от {}\nАудитория: {} — получателей: {}\nОтправка: {}\n\nПредпросмотр:\n\n{}",
+        broadcast.id,
+        format_timestamp(broadcast.created_at),
+        audience_label(broadcast),
+        recipients,
+        when,
+        broadcast.text
+    );
+    let groups: Vec<String> = state
+        .db
+        .list_groups()
+        .await?
+        .into_iter()
+        .map(|(group, _)| group)
+        .take(MAX_GROUP_BUTTONS)
+        .collect();
+    let keyboard = crate::bot::keyboards::broadcast_draft_keyboard(
+        broadcast.id,
+        &audience_key(broadcast),
+        &groups,
+        broadcast.scheduled_at.is_some(),
+    );
+    Ok((text, keyboard))
+}
+
+fn render_line(broadcast: &Broadcast) -> String {
+    let when = broadcast
+        .scheduled_at
+        .map(format_timestamp)
+        .unwrap_or_else(|| "—".to_string());
+    let status = match broadcast.status {
+        BroadcastStatus::Draft => "📝",
+        BroadcastStatus::Scheduled => "🗓",
+        BroadcastStatus::Sending => "🚀",
+        BroadcastStatus::Done => "✅",
+        BroadcastStatus::Cancelled => "✖️",
+    };
+    let preview: String = broadcast.text.chars().take(40).collect();
+    format!(
+        "{} #{} {} — {}: {}",
+        status,
+        broadcast.id,
+        when,
+        audience_label(broadcast),
+        preview.replace('\n', " ")
+    )
+}
+
+async fn render_overview(state: &BotState) -> Result<String, AppError> {
+    let broadcasts = state.db.list_recent_broadcasts(RECENT_LIMIT).await?;
+    let mut text = String::from("📣 Рассылки:");
+    if broadcasts.is_empty() {
+        text.push_str("\nнет");
+    }
+    for broadcast in &broadcasts {
+        text.push_str(&format!("\n{}", render_line(broadcast)));
+    }
+    text.push_str(&format!("\n\n{}", BROADCAST_USAGE));
+    Ok(text)
+}
+
+/// Время отправки в начале текста (`ГГГГ-ММ-ДД ЧЧ:ММ`) и сам текст с сохранением переносов.
+fn split_schedule(args: &str) -> (Option<i64>, &str) {
+    let parsed = args
+        .split_once(char::is_whitespace)
+        .and_then(|(date, rest)| {
+            let (time, text) = rest.trim_start().split_once(char::is_whitespace)?;
+            parse_local_datetime(date, time).map(|at| (at, text.trim()))
+        });
+    match parsed {
+        Some((at, text)) => (Some(at), text),
+        None => (None, args),
+    }
+}
+
+pub async fn cmd_broadcast(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let Some(admin_id) = sender_user_id(&msg) else {
+        return Ok(());
+    };
+    let args = msg
+        .text()
+        .unwrap_or("")
+        .split_once(char::is_whitespace)
+        .map(|(_, args)| args.trim())
+        .unwrap_or("");
+    let words: Vec<&str> = args.split_whitespace().collect();
+
+    let reply = match words.as_slice() {
+        [] => render_overview(&state).await?,
+        ["report", id] => match id.trim_start_matches('#').parse::<i64>() {
+            Ok(id) => match state.db.get_broadcast(id).await? {
+                Some(broadcast) => render_report(&state, &broadcast).await?,
+                None => "Рассылка не найдена.".to_string(),
+            },
+            Err(_) => BROADCAST_USAGE.to_string(),
+        },
+        ["cancel", id] => match id.trim_start_matches('#').parse::<i64>() {
+            Ok(id) => cancel_broadcast(&state, admin_id, id).await?,
+            Err(_) => BROADCAST_USAGE.to_string(),
+        },
+        _ => {
+            let (scheduled_at, text) = split_schedule(args);
+            if text.is_empty() {
+                BROADCAST_USAGE.to_string()
+            } else if scheduled_at.is_some_and(|at| at <= chrono::Utc::now().timestamp()) {
+                "Время отправки уже прошло.".to_string()
+            } else {
+                let id = state
+                    .db
+                    .create_broadcast(text, scheduled_at, admin_id)
+                    .await?;
+                tracing::info!(
+                    admin_id = admin_id,
+                    broadcast_id = id,
+                    "Broadcast draft created"
+                );
+                let Some(broadcast) = state.db.get_broadcast(id).await? else {
+                    return Ok(());
+                };
+                let (text, keyboard) = render_draft(&state, &broadcast).await?;
+                bot.send_message(msg.chat.id, text)
+                    .reply_markup(keyboard)
+                    .await?;
+                return Ok(());
+            }
+        }
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+async fn cancel_broadcast(state: &BotState, admin_id: i64, id: i64) -> Result<String, AppError> {
+    if !state.db.cancel_broadcast(id).await? {
+        return Ok("Рассылка не найдена или уже завершена.".to_string());
+    }
+    state
+        .audit
+        .record(
+            admin_id,
+            "broadcast_cancel",
+            &format!("broadcast:{}", id),
+            "",
+        )
+        .await;
+    tracing::info!(
+        admin_id = admin_id,
+        broadcast_id = id,
+        "Broadcast cancelled by admin"
+    );
+    Ok(format!("Рассылка #{} отменена.", id))
+}
+
+/// `bcast:aud:<id>:<аудитория>`, `bcast:send:<id>` или `bcast:cancel:<id>`.
+pub async fn callback_broadcast(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let data = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("bcast:"))
+        .unwrap_or("");
+    let mut parts = data.splitn(3, ':');
+    let (Some(action), Some(Ok(id))) = (parts.next(), parts.next().map(str::parse::<i64>)) else {
+        bot.answer_callback_query(q.id.clone()).await?;
+        return Ok(());
+    };
+    let Some(broadcast) = state.db.get_broadcast(id).await? else {
+        bot.answer_callback_query(q.id.clone())
+            .text("Рассылка не найдена")
+            .await?;
+        return Ok(());
+    };
+
+    let (text, markup) = match action {
+        "aud" => {
+            let Some((audience, group)) = parts.next().and_then(parse_audience_key) else {
+                bot.answer_callback_query(q.id.clone()).await?;
+                return Ok(());
+            };
+            if !state.db.set_broadcast_audience(id, audience, group).await? {
+                bot.answer_callback_query(q.id.clone())
+                    .text("Рассылка уже отправлена или отменена")
+                    .await?;
+                return Ok(());
+            }
+            bot.answer_callback_query(q.id.clone()).await?;
+            let Some(broadcast) = state.db.get_broadcast(id).await? else {
+                return Ok(());
+            };
+            render_draft(&state, &broadcast).await?
+        }
+        "send" => {
+            let recipients = count_recipients(&state, &broadcast).await?;
+            if recipients == 0 {
+                bot.answer_callback_query(q.id.clone())
+                    .text("В выбранной аудитории нет получателей")
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+            let now = chrono::Utc::now().timestamp();
+            let run_at = broadcast.scheduled_at.filter(|at| *at > now).unwrap_or(now);
+            if !state.db.schedule_broadcast(id, run_at).await? {
+                bot.answer_callback_query(q.id.clone())
+                    .text("Рассылка уже отправлена или отменена")
+                    .await?;
+                return Ok(());
+            }
+            bot.answer_callback_query(q.id.clone()).await?;
+            state
+                .audit
+                .record(
+                    admin_id,
+                    "broadcast_schedule",
+                    &format!("broadcast:{}", id),
+                    &format!(
+                        "{}, получателей: {}, отправка: {}",
+                        audience_label(&broadcast),
+                        recipients,
+                        format_timestamp(run_at)
+                    ),
+                )
+                .await;
+            tracing::info!(
+                admin_id = admin_id,
+                broadcast_id = id,
+                recipients = recipients,
+                run_at = run_at,
+                "Broadcast scheduled"
+            );
+            let status = if run_at > now {
+                format!(
+                    "🗓 Рассылка #{} запланирована на {}",
+                    id,
+                    format_timestamp(run_at)
+                )
+            } else {
+                format!("🚀 Рассылка #{} отправляется", id)
+            };
+            let text = format!(
+                "{}\nАудитория: {} — получателей: {}\nОтчёт о доставке придёт по завершении.\n\n{}",
+                status,
+                audience_label(&broadcast),
+                recipients,
+                broadcast.text
+            );
+            (text, crate::bot::keyboards::broadcast_cancel_keyboard(id))
+        }
+        "cancel" => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            (
+                cancel_broadcast(&state, admin_id, id).await?,
+                InlineKeyboardMarkup::default(),
+            )
+        }
+        _ => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            return Ok(());
+        }
+    };
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(markup)
+            .await?;
+    }
+    Ok(())
+}
@@ -1,75 +1,349 @@
-use super::format::render_user_card_text;
+use super::audit::callback_audit_page;
+use super::basket::{apply_approval_basket, approve_all_pending, render_basket_outcome};
+use super::broadcast::callback_broadcast;
+use super::cleanup::callback_cleanup;
+use super::config_preview::{ConfigAction, callback_config_preview, preview_from_callback};
+use super::ephemeral::{callback_resend_link, callback_reveal_link};
+use super::expiry::parse_access_duration;
+use super::format::{format_timestamp, render_user_card_text};
+use super::pending::{
+    admin_show_pending_page, callback_pending_action, callback_pending_page,
+    callback_reject_reason, prompt_reject_reason, refuse_if_claimed_by_other,
+};
+use super::purge::callback_purge;
+use super::restart::schedule_restart_with_notice;
 use super::shared::{
-    admin_show_users_page, approve_request_and_build_link, callback_message_target,
-    callback_prefix_filter, parse_callback_page, parse_callback_request_id, parse_callback_user_action,
-    perform_hard_ban, require_admin_callback, send_user_qr_to_admin, HandlerResult,
+    HandlerResult, admin_show_users_page, answer_on_error, approve_pending_request,
+    callback_message_target, callback_prefix_filter, parse_callback_page,
+    parse_callback_request_id, parse_callback_user_action, perform_hard_ban,
+    perform_secret_rotation, render_service_report, require_admin_callback,
+    require_reviewer_callback, send_user_qr_to_admin,
 };
 use super::state::BotState;
+use super::support::{callback_support_reply, callback_support_take};
+use super::survey::callback_survey_answer;
+use super::sync::callback_sync;
+use crate::bot::Bot;
+use crate::error::AppError;
+use anyhow::anyhow;
 use teloxide::dptree;
 use teloxide::prelude::*;
 
-pub fn handler() -> teloxide::dispatching::UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+pub fn handler() -> teloxide::dispatching::UpdateHandler<AppError> {
     Update::filter_callback_query()
         .branch(
-            dptree::filter_map(callback_prefix_filter("users_page:")).endpoint(callback_users_page),
+            dptree::filter_map(callback_prefix_filter("audit_page:"))
+                .endpoint(answer_on_error(callback_audit_page)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("users_page:"))
+                .endpoint(answer_on_error(callback_users_page)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("user_open:"))
+                .endpoint(answer_on_error(callback_user_open)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("user_view:"))
+                .endpoint(answer_on_error(callback_user_view)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("user_rotate:"))
+                .endpoint(answer_on_error(callback_user_rotate)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("user_ban:"))
+                .endpoint(answer_on_error(callback_user_ban)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("approve:"))
+                .endpoint(answer_on_error(callback_approve)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("reject:"))
+                .endpoint(answer_on_error(callback_reject)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("reject_reason:"))
+                .endpoint(answer_on_error(callback_reject_reason)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("delete_user:"))
+                .endpoint(answer_on_error(callback_delete_user)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("service:"))
+                .endpoint(answer_on_error(callback_service_action)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("reveal:"))
+                .endpoint(answer_on_error(callback_reveal_link)),
         )
-        .branch(dptree::filter_map(callback_prefix_filter("user_open:")).endpoint(callback_user_open))
-        .branch(dptree::filter_map(callback_prefix_filter("user_view:")).endpoint(callback_user_view))
-        .branch(dptree::filter_map(callback_prefix_filter("user_ban:")).endpoint(callback_user_ban))
-        .branch(dptree::filter_map(callback_prefix_filter("approve:")).endpoint(callback_approve))
-        .branch(dptree::filter_map(callback_prefix_filter("reject:")).endpoint(callback_reject))
         .branch(
-            dptree::filter_map(callback_prefix_filter("delete_user:")).endpoint(callback_delete_user),
+            dptree::filter_map(callback_prefix_filter("resend_link"))
+                .endpoint(answer_on_error(callback_resend_link)),
         )
         .branch(
-            dptree::filter_map(callback_prefix_filter("service:")).endpoint(callback_service_action),
+            dptree::filter_map(callback_prefix_filter("stage:"))
+                .endpoint(answer_on_error(callback_stage)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("unstage:"))
+                .endpoint(answer_on_error(callback_unstage)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("basket:"))
+                .endpoint(answer_on_error(callback_basket)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("pending:"))
+                .endpoint(answer_on_error(callback_pending)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("pending_page:"))
+                .endpoint(answer_on_error(callback_pending_page)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("pending_act:"))
+                .endpoint(answer_on_error(callback_pending_action)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("bcast:"))
+                .endpoint(answer_on_error(callback_broadcast)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("cleanup:"))
+                .endpoint(answer_on_error(callback_cleanup)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("purge:"))
+                .endpoint(answer_on_error(callback_purge)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("cfg_preview:"))
+                .endpoint(answer_on_error(callback_config_preview)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("sync:"))
+                .endpoint(answer_on_error(callback_sync)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("support_take:"))
+                .endpoint(answer_on_error(callback_support_take)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("support_reply:"))
+                .endpoint(answer_on_error(callback_support_reply)),
+        )
+        .branch(
+            dptree::filter_map(callback_prefix_filter("survey:"))
+                .endpoint(answer_on_error(callback_survey_answer)),
         )
 }
 
-async fn callback_approve(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+async fn callback_pending(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
     let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
         return Ok(());
     };
 
     let data = q.data.as_deref().unwrap_or("");
-    let request_id = parse_callback_request_id(data, "approve:")?;
+    match data.strip_prefix("pending:").unwrap_or("") {
+        "list" => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            if let Some((chat_id, _)) = callback_message_target(&q) {
+                admin_show_pending_page(&bot, chat_id, &state, admin_id, 0, None).await?;
+            }
+        }
+        "approve_all" => {
+            bot.answer_callback_query(q.id.clone())
+                .text("Одобряю все заявки…")
+                .await?;
+            let outcome = approve_all_pending(&bot, &state, admin_id).await?;
+            tracing::info!(
+                admin_id = admin_id,
+                approved = outcome.approved,
+                "All pending requests approved"
+            );
+            if let Some((chat_id, message_id)) = callback_message_target(&q) {
+                bot.edit_message_text(chat_id, message_id, render_basket_outcome(&outcome))
+                    .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
+                    .await?;
+            }
+        }
+        _ => {
+            bot.answer_callback_query(q.id.clone()).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn callback_stage(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+
+    let data = q.data.as_deref().unwrap_or("");
+    let request_id = parse_callback_request_id(data, "stage:")?;
+    if refuse_if_claimed_by_other(&bot, &q, &state, request_id, admin_id).await? {
+        return Ok(());
+    }
+    if !state.db.stage_request(request_id, admin_id).await? {
+        bot.answer_callback_query(q.id.clone())
+            .text("Заявка уже обработана или не найдена")
+            .await?;
+        return Ok(());
+    }
+    let basket_size = state.db.list_staged_requests().await?.len();
     tracing::info!(
         admin_id = admin_id,
         request_id = request_id,
-        "Approve callback received"
+        basket_size = basket_size,
+        "Request staged to approval basket"
     );
-    let message_target = callback_message_target(&q);
 
-    let (request, link) = match approve_request_and_build_link(&state, request_id).await? {
-        Some(payload) => payload,
-        None => {
+    bot.answer_callback_query(q.id.clone())
+        .text(format!("В корзине: {}", basket_size))
+        .await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(
+            chat_id,
+            message_id,
+            format!("🧺 Заявка #{} в корзине одобрения", request_id),
+        )
+        .reply_markup(crate::bot::keyboards::staged_request_buttons(
+            request_id,
+            basket_size,
+        ))
+        .await?;
+    }
+    Ok(())
+}
+
+async fn callback_unstage(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    if require_admin_callback(&bot, &q, &state).await?.is_none() {
+        return Ok(());
+    }
+
+    let data = q.data.as_deref().unwrap_or("");
+    let request_id = parse_callback_request_id(data, "unstage:")?;
+    state.db.unstage_request(request_id).await?;
+    bot.answer_callback_query(q.id.clone())
+        .text("Убрано из корзины")
+        .await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, format!("📋 Заявка #{}", request_id))
+            .reply_markup(crate::bot::keyboards::approve_reject_buttons(request_id))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn callback_basket(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+
+    let data = q.data.as_deref().unwrap_or("");
+    let text = match data.strip_prefix("basket:").unwrap_or("") {
+        "apply" => {
             bot.answer_callback_query(q.id.clone())
-                .text("Заявка уже обработана или не найдена")
+                .text("Применяю корзину…")
                 .await?;
+            let outcome = apply_approval_basket(&bot, &state, admin_id).await?;
+            tracing::info!(
+                admin_id = admin_id,
+                approved = outcome.approved,
+                "Approval basket applied"
+            );
+            render_basket_outcome(&outcome)
+        }
+        "clear" => {
+            let removed = state.db.clear_basket().await?;
+            bot.answer_callback_query(q.id.clone()).await?;
+            format!("Корзина очищена ({}).", removed)
+        }
+        _ => {
+            bot.answer_callback_query(q.id.clone()).await?;
             return Ok(());
         }
     };
 
-    bot.answer_callback_query(q.id.clone()).text("Одобрено").await?;
-
-    if let Some((chat_id, message_id)) = message_target {
-        bot.edit_message_text(chat_id, message_id, "✅ Заявка одобрена")
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
             .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
             .await?;
     }
+    Ok(())
+}
 
-    bot.send_message(
-        ChatId(request.tg_user_id),
-        format!("Ваша ссылка на прокси:\n\n{}", link),
-    )
-    .await?;
+async fn callback_approve(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_reviewer_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+
+    // `approve:<id>` — бессрочно, `approve:<id>:<срок>` — со сроком вида `30d`.
+    let payload = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("approve:"))
+        .unwrap_or("");
+    let (request_id, duration) = match payload.split_once(':') {
+        Some((request_id, duration)) => (request_id, Some(duration)),
+        None => (payload, None),
+    };
+    let request_id = request_id
+        .parse::<i64>()
+        .map_err(|_| anyhow!("Некорректный request_id"))?;
+    let duration_secs = duration
+        .map(|duration| {
+            parse_access_duration(duration).ok_or_else(|| anyhow!("Некорректный срок доступа"))
+        })
+        .transpose()?;
+    tracing::info!(
+        admin_id = admin_id,
+        request_id = request_id,
+        duration = duration.unwrap_or("forever"),
+        "Approve callback received"
+    );
+    if refuse_if_claimed_by_other(&bot, &q, &state, request_id, admin_id).await? {
+        return Ok(());
+    }
+    let action = ConfigAction::Approve {
+        request_id,
+        duration_secs,
+    };
+    if preview_from_callback(&bot, &q, &state, admin_id, action).await? {
+        return Ok(());
+    }
+    let Some(approved) =
+        approve_pending_request(&bot, &state, admin_id, request_id, duration_secs).await?
+    else {
+        bot.answer_callback_query(q.id.clone())
+            .text("Заявка уже обработана или не найдена")
+            .await?;
+        return Ok(());
+    };
 
-    tracing::info!("Admin {} approved request #{}", admin_id, request_id);
+    bot.answer_callback_query(q.id.clone())
+        .text("Одобрено")
+        .await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        let text = match approved.expires_at {
+            Some(expires_at) => format!(
+                "✅ Заявка одобрена, доступ до {}",
+                format_timestamp(expires_at)
+            ),
+            None => "✅ Заявка одобрена бессрочно".to_string(),
+        };
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
+            .await?;
+    }
     Ok(())
 }
 
 async fn callback_reject(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
-    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+    let Some(admin_id) = require_reviewer_callback(&bot, &q, &state).await? else {
         return Ok(());
     };
 
@@ -80,25 +354,11 @@ async fn callback_reject(bot: Bot, q: CallbackQuery, state: BotState) -> Handler
         request_id = request_id,
         "Reject callback received"
     );
-    let message_target = callback_message_target(&q);
-    let request = state.db.reject(request_id).await?;
-
-    bot.answer_callback_query(q.id.clone()).text("Отклонено").await?;
-
-    if let Some(request) = request {
-        if let Some((chat_id, message_id)) = message_target {
-            bot.edit_message_text(chat_id, message_id, "❌ Заявка отклонена")
-                .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
-                .await?;
-        }
-        bot.send_message(
-            ChatId(request.tg_user_id),
-            "Ваша заявка на регистрацию отклонена администратором.",
-        )
-        .await?;
+    if prompt_reject_reason(&bot, &q, &state, admin_id, request_id, true).await? {
+        bot.answer_callback_query(q.id.clone())
+            .text("Напишите причину отклонения")
+            .await?;
     }
-
-    tracing::info!("Admin {} rejected request #{}", admin_id, request_id);
     Ok(())
 }
 
@@ -133,12 +393,23 @@ async fn callback_user_open(bot: Bot, q: CallbackQuery, state: BotState) -> Hand
         return Ok(());
     };
 
+    let expires_at = state.db.get_user_expiry(user.tg_user_id).await?;
+    let traffic = state.db.get_user_traffic(user.tg_user_id).await?;
     bot.answer_callback_query(q.id.clone())
         .text("Открыта карточка")
         .await?;
     if let Some((chat_id, message_id)) = callback_message_target(&q) {
-        bot.edit_message_text(chat_id, message_id, render_user_card_text(&user))
-            .reply_markup(crate::bot::keyboards::user_card_keyboard(user.tg_user_id, page))
+        let text = render_user_card_text(
+            &user,
+            expires_at,
+            state.config.expiry.grace_secs(),
+            traffic.as_ref(),
+        );
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(crate::bot::keyboards::user_card_keyboard(
+                user.tg_user_id,
+                page,
+            ))
             .await?;
     }
     Ok(())
@@ -168,13 +439,28 @@ async fn callback_user_view(bot: Bot, q: CallbackQuery, state: BotState) -> Hand
 }
 
 async fn callback_user_ban(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
-    if require_admin_callback(&bot, &q, &state).await?.is_none() {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
         return Ok(());
-    }
+    };
 
     let data = q.data.as_deref().unwrap_or("");
     let (tg_user_id, page) = parse_callback_user_action(data, "user_ban:")?;
+    if preview_from_callback(
+        &bot,
+        &q,
+        &state,
+        admin_id,
+        ConfigAction::Delete { tg_user_id },
+    )
+    .await?
+    {
+        return Ok(());
+    }
     let status_text = perform_hard_ban(&state, tg_user_id).await?;
+    state
+        .audit
+        .record(admin_id, "delete", &format!("tg_user:{}", tg_user_id), "")
+        .await;
     bot.answer_callback_query(q.id.clone())
         .text(status_text.clone())
         .await?;
@@ -186,14 +472,62 @@ async fn callback_user_ban(bot: Bot, q: CallbackQuery, state: BotState) -> Handl
     Ok(())
 }
 
-async fn callback_delete_user(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
-    if require_admin_callback(&bot, &q, &state).await?.is_none() {
+async fn callback_user_rotate(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+
+    let data = q.data.as_deref().unwrap_or("");
+    let (tg_user_id, _) = parse_callback_user_action(data, "user_rotate:")?;
+    if preview_from_callback(
+        &bot,
+        &q,
+        &state,
+        admin_id,
+        ConfigAction::Rotate { tg_user_id },
+    )
+    .await?
+    {
         return Ok(());
     }
+    bot.answer_callback_query(q.id.clone())
+        .text("Перевыпускаю секрет…")
+        .await?;
+    let status_text = perform_secret_rotation(&bot, &state, tg_user_id).await?;
+    state
+        .audit
+        .record(admin_id, "rotate", &format!("tg_user:{}", tg_user_id), "")
+        .await;
+
+    if let Some((chat_id, _)) = callback_message_target(&q) {
+        bot.send_message(chat_id, status_text).await?;
+    }
+    Ok(())
+}
+
+async fn callback_delete_user(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
 
     let data = q.data.as_deref().unwrap_or("");
     let tg_user_id = parse_callback_request_id(data, "delete_user:")?;
+    if preview_from_callback(
+        &bot,
+        &q,
+        &state,
+        admin_id,
+        ConfigAction::Delete { tg_user_id },
+    )
+    .await?
+    {
+        return Ok(());
+    }
     let status_text = perform_hard_ban(&state, tg_user_id).await?;
+    state
+        .audit
+        .record(admin_id, "delete", &format!("tg_user:{}", tg_user_id), "")
+        .await;
 
     bot.answer_callback_query(q.id.clone())
         .text(status_text.clone())
@@ -211,18 +545,61 @@ async fn callback_delete_user(bot: Bot, q: CallbackQuery, state: BotState) -> Ha
 }
 
 async fn callback_service_action(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
-    if require_admin_callback(&bot, &q, &state).await?.is_none() {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
         return Ok(());
-    }
+    };
 
     let data = q.data.as_deref().unwrap_or("");
     let action = data.strip_prefix("service:").unwrap_or("status");
+    if action == "notice_restart" {
+        let minutes = state.config.restart.notice_minutes;
+        let text = match callback_message_target(&q) {
+            Some((chat_id, _)) => {
+                if schedule_restart_with_notice(&bot, &state, minutes, chat_id).await? {
+                    format!("Рестарт через {} мин.", minutes)
+                } else {
+                    "Перезапуск уже запланирован".to_string()
+                }
+            }
+            None => "Сообщение недоступно".to_string(),
+        };
+        bot.answer_callback_query(q.id.clone()).text(text).await?;
+        return Ok(());
+    }
     let (action_name, result) = match action {
-        "restart" => ("restart", state.service.restart()),
-        "reload" => ("reload", state.service.reload()),
-        "status" => ("status", state.service.status()),
-        _ => ("status", state.service.status()),
+        "restart" => {
+            let reason = format!("кнопка «Рестарт» от админа {}", q.from.id.0);
+            match state.service.restart(&reason, false).await {
+                Ok(result) => ("restart", result),
+                Err(denied) => {
+                    bot.answer_callback_query(q.id.clone())
+                        .text(format!(
+                            "🚦 {}\nПринудительно: /service restart --force",
+                            denied
+                        ))
+                        .show_alert(true)
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        "reload" => ("reload", state.service.reload().await),
+        "status" => ("status", state.service.status().await),
+        "enable" => ("enable", state.service.enable().await),
+        "disable" => ("disable", state.service.disable().await),
+        _ => ("status", state.service.status().await),
     };
+    if action_name != "status" {
+        state
+            .audit
+            .record(
+                admin_id,
+                "service",
+                &state.config.service_name,
+                &format!("{}: {}", action_name, result.status_label()),
+            )
+            .await;
+    }
 
     bot.answer_callback_query(q.id.clone())
         .text(format!("Выполнено: {}", action_name))
@@ -231,7 +608,7 @@ async fn callback_service_action(bot: Bot, q: CallbackQuery, state: BotState) ->
     if let Some((chat_id, message_id)) = callback_message_target(&q) {
         let text = format!(
             "⚙️ Сервис telemt\n\n{}",
-            state.service.format_result(action_name, &result)
+            render_service_report(&state, action_name, &result).await
         );
         bot.edit_message_text(chat_id, message_id, text)
             .reply_markup(crate::bot::keyboards::service_control_buttons())
@@ -0,0 +1,66 @@
+//! Обязательная подписка (`required_channel_id`): перед применением токена бот
+//! проверяет через `getChatMember`, что пользователь состоит в канале или группе,
+//! и иначе отказывает с кнопкой «Подписаться». Токен при отказе не расходуется.
+
+use super::shared::mark_user_waiting_for_invite;
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::error::AppError;
+use crate::i18n::{Lang, t};
+use teloxide::prelude::*;
+
+/// `true` — можно продолжать: канал не задан, пользователь подписан или проверка не
+/// удалась (бот не админ канала — регистрация не блокируется, ошибка пишется в лог).
+/// При `false` пользователю уже отправлен отказ.
+pub async fn check_channel_membership(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    tg_user_id: i64,
+    lang: Lang,
+) -> Result<bool, AppError> {
+    let Some(channel_id) = state.config.required_channel_id else {
+        return Ok(true);
+    };
+    let member = match bot
+        .get_chat_member(ChatId(channel_id), UserId(tg_user_id as u64))
+        .await
+    {
+        Ok(member) => member,
+        Err(error) => {
+            tracing::warn!(
+                channel_id = channel_id,
+                tg_user_id = tg_user_id,
+                error = %error,
+                "Не удалось проверить подписку на обязательный канал"
+            );
+            return Ok(true);
+        }
+    };
+    if member.is_present() {
+        return Ok(true);
+    }
+
+    tracing::info!(
+        tg_user_id = tg_user_id,
+        channel_id = channel_id,
+        "Invite token refused: user is not subscribed to the required channel"
+    );
+    // Повторно отправленный токен обработается без нового /start.
+    mark_user_waiting_for_invite(state, tg_user_id).await;
+    let request = bot.send_message(chat_id, t(lang, "channel_required"));
+    let join_url = state
+        .config
+        .required_channel_url
+        .as_deref()
+        .and_then(|url| reqwest::Url::parse(url).ok());
+    match join_url {
+        Some(url) => {
+            request
+                .reply_markup(crate::bot::keyboards::join_channel_keyboard(lang, url))
+                .await?
+        }
+        None => request.await?,
+    };
+    Ok(false)
+}
@@ -0,0 +1,229 @@
+//! Массовое удаление по фильтру (`/cleanup`): бот показывает число затронутых
+//! записей, ждёт подтверждения кнопкой и применяет удаление одной записью
+//! конфига telemt и одним рестартом. Каждое удаление пишется в журнал аудита
+//! ([`crate::audit`]) со списком затронутых `tg_user_id`.
+
+use super::groups::normalize_group_name;
+use super::shared::{HandlerResult, callback_message_target, require_admin_callback};
+use super::state::{BotState, is_admin_message};
+use crate::bot::Bot;
+use crate::error::AppError;
+use crate::telemt_cfg::UserMutation;
+use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
+
+const CLEANUP_USAGE: &str = "Использование:
+/cleanup expired — всех пользователей с истёкшим сроком доступа
+/cleanup rejected <дней> — отклонённые заявки старше N дней
+/cleanup group <группа> — всех пользователей группы";
+
+/// Фильтр массового удаления; сериализуется в payload кнопки подтверждения.
+enum CleanupFilter {
+    Expired,
+    Rejected { days: i64 },
+    Group(String),
+}
+
+impl CleanupFilter {
+    fn parse(args: &[&str]) -> Option<Self> {
+        match args {
+            ["expired"] => Some(Self::Expired),
+            ["rejected", days] => days
+                .parse::<i64>()
+                .ok()
+                .filter(|days| *days >= 0)
+                .map(|days| Self::Rejected { days }),
+            ["group", group] => normalize_group_name(group).map(Self::Group),
+            _ => None,
+        }
+    }
+
+    fn to_payload(&self) -> String {
+        match self {
+            Self::Expired => "expired".to_string(),
+            Self::Rejected { days } => format!("rejected:{}", days),
+            Self::Group(group) => format!("group:{}", group),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Expired => "пользователи с истёкшим сроком доступа".to_string(),
+            Self::Rejected { days } => format!("отклонённые заявки старше {} дн.", days),
+            Self::Group(group) => format!("пользователи группы «{}»", group),
+        }
+    }
+
+    fn rejected_before(days: i64) -> i64 {
+        chrono::Utc::now().timestamp() - days * 86_400
+    }
+
+    /// `tg_user_id`, которые затронет удаление.
+    async fn affected(&self, state: &BotState) -> Result<Vec<i64>, AppError> {
+        let ids = match self {
+            Self::Expired => expired_users(state)
+                .await?
+                .into_iter()
+                .map(|(tg_user_id, _)| tg_user_id)
+                .collect(),
+            Self::Rejected { days } => {
+                state
+                    .db
+                    .list_rejected_before(Self::rejected_before(*days))
+                    .await?
+            }
+            Self::Group(group) => state
+                .db
+                .list_group_members(group)
+                .await?
+                .into_iter()
+                .map(|(member, _)| member.tg_user_id)
+                .collect(),
+        };
+        Ok(ids)
+    }
+}
+
+pub async fn cmd_cleanup(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .skip(1)
+        .collect();
+    let Some(filter) = CleanupFilter::parse(&args) else {
+        bot.send_message(msg.chat.id, CLEANUP_USAGE).await?;
+        return Ok(());
+    };
+
+    let affected = filter.affected(&state).await?;
+    if affected.is_empty() {
+        bot.send_message(msg.chat.id, "Под фильтр не попала ни одна запись.")
+            .await?;
+        return Ok(());
+    }
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🗑 Будут удалены {}: {}.\nПодтвердите удаление.",
+            filter.describe(),
+            affected.len()
+        ),
+    )
+    .reply_markup(crate::bot::keyboards::cleanup_confirm_keyboard(
+        &filter.to_payload(),
+        affected.len(),
+    ))
+    .await?;
+    Ok(())
+}
+
+pub async fn callback_cleanup(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let payload = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("cleanup:"))
+        .unwrap_or("");
+    let args: Vec<&str> = payload.split(':').collect();
+
+    let text = match CleanupFilter::parse(&args) {
+        Some(filter) => {
+            bot.answer_callback_query(q.id.clone())
+                .text("Удаляю…")
+                .await?;
+            let deleted = apply_cleanup(&state, &filter, admin_id).await?;
+            format!("🗑 Удалены {}: {}.", filter.describe(), deleted)
+        }
+        None => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            "Удаление отменено.".to_string()
+        }
+    };
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(InlineKeyboardMarkup::default())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Применяет удаление; набор записей пересчитывается в момент подтверждения.
+async fn apply_cleanup(
+    state: &BotState,
+    filter: &CleanupFilter,
+    admin_id: i64,
+) -> Result<usize, AppError> {
+    let affected = match filter {
+        // Отклонённые заявки не попадают в конфиг telemt — рестарт не нужен.
+        CleanupFilter::Rejected { days } => {
+            state
+                .db
+                .delete_rejected_before(CleanupFilter::rejected_before(*days))
+                .await?
+        }
+        CleanupFilter::Expired => remove_users(state, expired_users(state).await?).await?,
+        CleanupFilter::Group(group) => {
+            let members = state
+                .db
+                .list_group_members(group)
+                .await?
+                .into_iter()
+                .map(|(member, _)| (member.tg_user_id, member.telemt_username))
+                .collect();
+            remove_users(state, members).await?
+        }
+    };
+
+    let ids: Vec<String> = affected.iter().map(|id| id.to_string()).collect();
+    state
+        .audit
+        .record(
+            admin_id,
+            "cleanup",
+            &filter.to_payload(),
+            &format!("удалено {}: {}", affected.len(), ids.join(" ")),
+        )
+        .await;
+    Ok(affected.len())
+}
+
+/// Одобренные пользователи с истёкшим сроком, включая льготный период `[expiry]`.
+async fn expired_users(state: &BotState) -> Result<Vec<(i64, Option<String>)>, AppError> {
+    Ok(state
+        .db
+        .list_expired_users(chrono::Utc::now().timestamp())
+        .await?
+        .into_iter()
+        .map(|user| (user.tg_user_id, user.telemt_username))
+        .collect())
+}
+
+/// Удаляет пользователей из конфига telemt одной записью и одним рестартом и
+/// помечает их удалёнными. Возвращает `tg_user_id` фактически удалённых.
+async fn remove_users(
+    state: &BotState,
+    users: Vec<(i64, Option<String>)>,
+) -> Result<Vec<i64>, AppError> {
+    let mutations: Vec<UserMutation> = users
+        .iter()
+        .filter_map(|(_, telemt_username)| telemt_username.clone())
+        .map(|username| UserMutation::Remove { username })
+        .collect();
+    if !mutations.is_empty() {
+        state.cfg_writer.apply(mutations).await?;
+    }
+    let mut ids = Vec::with_capacity(users.len());
+    for (tg_user_id, _) in users {
+        if state.db.deactivate_user(tg_user_id).await? {
+            crate::metrics::record_deletion("cleanup");
+            ids.push(tg_user_id);
+        }
+    }
+    Ok(ids)
+}
@@ -1,17 +1,51 @@
-use super::format::{format_date, format_mode, render_invite_token_line};
+use super::allowlist::{cmd_allow, registration_allowed};
+use super::audit::cmd_audit;
+use super::bans::{cmd_ban, cmd_unban};
+use super::basket::{apply_approval_basket, render_basket, render_basket_outcome};
+use super::broadcast::cmd_broadcast;
+use super::cleanup::cmd_cleanup;
+use super::config_export::cmd_config;
+use super::config_preview::{ConfigAction, apply_or_preview};
+use super::ephemeral::send_proxy_link;
+use super::expiry::parse_access_duration;
+use super::format::{
+    format_date, format_mode, format_percent, format_timestamp, render_archived_request_line,
+    render_invite_token_info, render_invite_token_line, render_job_line, render_search_hit_line,
+    render_token_usage_line,
+};
+use super::groups::{cmd_group, normalize_group_name, parse_local_datetime};
+use super::import::{cmd_bind, cmd_import};
+use super::jobs::JobKind;
+use super::logs::cmd_logs;
+use super::pending::{admin_show_pending_page, claimed_by_other};
+use super::purge::cmd_purge;
+use super::report::cmd_report;
+use super::restart::{cancel_scheduled_restart, schedule_restart_with_notice};
 use super::shared::{
-    admin_show_pending, admin_show_service_panel, admin_show_stats, admin_show_users_page,
-    approve_request_and_build_link, approve_user_direct_and_build_link, build_bot_start_link,
-    is_user_waiting_for_invite, mark_user_waiting_for_invite, parse_create_target, parse_start_token,
-    perform_hard_ban, process_invite_token, send_user_link, unmark_user_waiting_for_invite,
-    user_id_or_reply, CreateTarget, HandlerResult,
+    CreateTarget, HandlerResult, admin_show_pending_summary, admin_show_service_panel,
+    admin_show_stats, admin_show_users_page, approve_user_direct_and_build_link, blocked_text,
+    build_user_qr_png_bytes, is_user_waiting_for_invite, mark_user_waiting_for_invite,
+    parse_create_target, parse_start_token, pass_cooldown, process_invite_token,
+    reject_pending_request, render_service_report, render_user_link_message, reply_on_error,
+    send_user_link, unmark_user_waiting_for_invite, user_id_or_reply, user_lang,
+};
+use super::state::{
+    BotState, is_admin_message, sender_display_name, sender_user_id, telemt_username,
 };
-use super::state::{is_admin_message, sender_display_name, sender_user_id, telemt_username, BotState};
-use crate::db::RequestStatus;
+use super::support::cmd_tickets;
+use super::sync::cmd_sync;
+use super::viewas::{cmd_viewas, preview_start, viewed_user};
+use crate::bot::Bot;
+use crate::bot::cooldown::CooldownCommand;
+use crate::db::{NewInviteToken, RequestStatus, UsersPageRequest};
+use crate::error::AppError;
+use crate::i18n::{t, tf};
+use crate::link::{build_bot_start_link, build_start_payload, split_start_payload};
 use teloxide::dptree;
 use teloxide::prelude::*;
-use teloxide::types::ParseMode;
+use teloxide::types::{InputFile, ParseMode};
 use teloxide::utils::command::BotCommands;
+use teloxide::utils::html;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase")]
@@ -34,19 +68,94 @@ pub enum BotCommand {
     Service,
     #[command(description = "Управление invite-токенами (админ)")]
     Token,
+    #[command(description = "Объявление для пользователей (админ)")]
+    Announce,
+    #[command(description = "Рассылка выбранной аудитории (админ)")]
+    Broadcast,
+    #[command(description = "Ротация секретов (админ)")]
+    Rotate,
+    #[command(description = "Фоновые задачи (админ)")]
+    Jobs,
+    #[command(description = "Корзина одобрения заявок (админ)")]
+    Basket,
+    #[command(description = "Поиск пользователей (админ)")]
+    Find,
+    #[command(description = "Заметка о пользователе (админ)")]
+    Note,
+    #[command(description = "Поиск в архиве старых заявок (админ)")]
+    Archive,
+    #[command(description = "Сводка по ожидающим заявкам (админ)")]
+    Pending,
+    #[command(description = "Группы пользователей и их расписание (админ)")]
+    Group,
+    #[command(description = "Массовое удаление по фильтру (админ)")]
+    Cleanup,
+    #[command(description = "Отчёт за неделю (админ)")]
+    Report,
+    #[command(description = "Обращения в поддержку (админ)")]
+    Tickets,
+    #[command(description = "Журнал аудита (админ)")]
+    Audit,
+    #[command(description = "Последние строки журнала telemt (админ)")]
+    Logs,
+    #[command(description = "Перечитать токен бота без перезапуска (админ)")]
+    Reloadcfg,
+    #[command(description = "Выгрузка пользователей из конфига telemt (админ)")]
+    Config,
+    #[command(description = "Безвозвратно стереть пользователя (админ)")]
+    Purge,
+    #[command(description = "Заблокировать пользователя (админ)")]
+    Ban,
+    #[command(description = "Снять блокировку (админ)")]
+    Unban,
+    #[command(description = "Белый список регистрации (админ)")]
+    Allow,
+    #[command(description = "Смотреть на бота глазами пользователя (админ)")]
+    Viewas,
+    #[command(description = "Сверить БД с конфигом telemt (админ)")]
+    Sync,
+    #[command(description = "Импортировать пользователей из конфига telemt (админ)")]
+    Import,
+    #[command(description = "Привязать импортированного пользователя к Telegram (админ)")]
+    Bind,
 }
 
-pub fn handler() -> teloxide::dispatching::UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+pub fn handler() -> teloxide::dispatching::UpdateHandler<AppError> {
     teloxide::filter_command::<BotCommand, _>()
-        .branch(dptree::case![BotCommand::Start].endpoint(start_cmd))
-        .branch(dptree::case![BotCommand::Link].endpoint(cmd_link))
-        .branch(dptree::case![BotCommand::Help].endpoint(cmd_help))
-        .branch(dptree::case![BotCommand::Approve].endpoint(cmd_approve))
-        .branch(dptree::case![BotCommand::Reject].endpoint(cmd_reject))
-        .branch(dptree::case![BotCommand::Create].endpoint(cmd_create))
-        .branch(dptree::case![BotCommand::Delete].endpoint(cmd_delete))
-        .branch(dptree::case![BotCommand::Service].endpoint(cmd_service))
-        .branch(dptree::case![BotCommand::Token].endpoint(cmd_token))
+        .branch(dptree::case![BotCommand::Start].endpoint(reply_on_error(start_cmd)))
+        .branch(dptree::case![BotCommand::Link].endpoint(reply_on_error(cmd_link)))
+        .branch(dptree::case![BotCommand::Help].endpoint(reply_on_error(cmd_help)))
+        .branch(dptree::case![BotCommand::Approve].endpoint(reply_on_error(cmd_approve)))
+        .branch(dptree::case![BotCommand::Reject].endpoint(reply_on_error(cmd_reject)))
+        .branch(dptree::case![BotCommand::Create].endpoint(reply_on_error(cmd_create)))
+        .branch(dptree::case![BotCommand::Delete].endpoint(reply_on_error(cmd_delete)))
+        .branch(dptree::case![BotCommand::Service].endpoint(reply_on_error(cmd_service)))
+        .branch(dptree::case![BotCommand::Token].endpoint(reply_on_error(cmd_token)))
+        .branch(dptree::case![BotCommand::Announce].endpoint(reply_on_error(cmd_announce)))
+        .branch(dptree::case![BotCommand::Broadcast].endpoint(reply_on_error(cmd_broadcast)))
+        .branch(dptree::case![BotCommand::Rotate].endpoint(reply_on_error(cmd_rotate)))
+        .branch(dptree::case![BotCommand::Jobs].endpoint(reply_on_error(cmd_jobs)))
+        .branch(dptree::case![BotCommand::Basket].endpoint(reply_on_error(cmd_basket)))
+        .branch(dptree::case![BotCommand::Find].endpoint(reply_on_error(cmd_find)))
+        .branch(dptree::case![BotCommand::Note].endpoint(reply_on_error(cmd_note)))
+        .branch(dptree::case![BotCommand::Archive].endpoint(reply_on_error(cmd_archive)))
+        .branch(dptree::case![BotCommand::Pending].endpoint(reply_on_error(cmd_pending)))
+        .branch(dptree::case![BotCommand::Group].endpoint(reply_on_error(cmd_group)))
+        .branch(dptree::case![BotCommand::Cleanup].endpoint(reply_on_error(cmd_cleanup)))
+        .branch(dptree::case![BotCommand::Report].endpoint(reply_on_error(cmd_report)))
+        .branch(dptree::case![BotCommand::Tickets].endpoint(reply_on_error(cmd_tickets)))
+        .branch(dptree::case![BotCommand::Audit].endpoint(reply_on_error(cmd_audit)))
+        .branch(dptree::case![BotCommand::Logs].endpoint(reply_on_error(cmd_logs)))
+        .branch(dptree::case![BotCommand::Reloadcfg].endpoint(reply_on_error(cmd_reloadcfg)))
+        .branch(dptree::case![BotCommand::Config].endpoint(reply_on_error(cmd_config)))
+        .branch(dptree::case![BotCommand::Purge].endpoint(reply_on_error(cmd_purge)))
+        .branch(dptree::case![BotCommand::Ban].endpoint(reply_on_error(cmd_ban)))
+        .branch(dptree::case![BotCommand::Unban].endpoint(reply_on_error(cmd_unban)))
+        .branch(dptree::case![BotCommand::Allow].endpoint(reply_on_error(cmd_allow)))
+        .branch(dptree::case![BotCommand::Viewas].endpoint(reply_on_error(cmd_viewas)))
+        .branch(dptree::case![BotCommand::Sync].endpoint(reply_on_error(cmd_sync)))
+        .branch(dptree::case![BotCommand::Import].endpoint(reply_on_error(cmd_import)))
+        .branch(dptree::case![BotCommand::Bind].endpoint(reply_on_error(cmd_bind)))
 }
 
 pub async fn cmd_help(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
@@ -59,18 +168,55 @@ pub async fn cmd_help(bot: Bot, msg: Message, state: BotState) -> HandlerResult
 /link — получить ссылку на прокси (если уже одобрены)
 
 Для администраторов:
-/approve <id> — одобрить заявку
-/reject <id> — отклонить заявку
+/pending — сводка по ожидающим заявкам с кнопками «Показать список» и «Одобрить все»
+/approve <id> [30d | 12h] — одобрить заявку (со сроком — доступ отзывается автоматически)
+/reject <id> [причина] — отклонить заявку, причина уйдёт пользователю
 /create <tg_user_id | @username> — создать пользователя
 /delete <tg_user_id> — удалить пользователя
-/service <start|stop|restart|reload|status> — управление telemt.service
-/token create [days] [--auto|-a] [--max-uses N] — создать invite-токен
+/purge <tg_user_id> — безвозвратно стереть пользователя и все его данные (с подтверждением)
+/ban <tg_user_id> [причина] — заблокировать: доступ отзывается, повторная регистрация невозможна даже по токену; /ban — список, /unban <tg_user_id> — снять
+/allow <tg_user_id> [tg_user_id …] — добавить в белый список (registration_mode = "whitelist"); /allow — список, /allow remove <tg_user_id> — убрать
+/service <start|stop|restart|reload|status|enable|disable> — управление telemt.service
+/service restart --notice [мин] — рестарт с предупреждением пользователей (/service cancel — отмена)
+/service restart --force — рестарт в обход защиты от частых рестартов
+/service restart now — выполнить отложенный рестарт (окно restart.debounce_secs) сразу
+/token create [days] [--auto|-a] [--max-uses N] [--for <tg_user_id | @username>] [--plans группа1,группа2] [--label "подпись"] [--from ГГГГ-ММ-ДД [ЧЧ:ММ]] [--group группа] — создать invite-токен (--plans: варианты ссылки, назначающие группу)
 /token list — список активных invite-токенов
-/token revoke <token> — отозвать invite-токен"#;
+/token revoke <token> — отозвать invite-токен
+/token stats <token> — воронка токена: переходы, применения, одобрения, активные
+/token info <token> — параметры токена и кто им воспользовался
+/token edit <token> [--days N] [--max-uses M | none] — продлить токен (N дней от сегодня) или изменить лимит использований
+/token qr <token> [группа] — QR-код ссылки на бота с токеном для печати или показа на экране
+/announce [--days N] <текст> — объявление для одобренных пользователей
+/announce clear — снять объявление
+/broadcast [ГГГГ-ММ-ДД ЧЧ:ММ] <текст> — рассылка: выбор аудитории (все одобренные, ожидающие, группа, истекающий доступ), предпросмотр, отправка сразу или по расписанию и отчёт о доставке
+/broadcast — рассылки, /broadcast report <id> — отчёт, /broadcast cancel <id> — отменить
+/rotate <tg_user_id> — перевыпустить секрет пользователя и отправить ему новую ссылку
+/rotate all — перевыпустить секреты всех пользователей (фоновая задача)
+/jobs — фоновые задачи, /jobs cancel <id> — отменить
+/basket — корзина одобрения: /basket add <id>, /basket apply (один рестарт), /basket clear
+/find <запрос> — поиск по имени, username, заметке, tg_user_id, имени в telemt (tg_123…) или началу/концу секрета (также inline: @бот запрос)
+/note <tg_user_id> <текст> — заметка о пользователе, /note <tg_user_id> clear — удалить
+/archive <tg_user_id | запрос> — поиск в архиве заявок, удалённых политикой хранения
+/group set <tg_user_id> <группа> — добавить в группу, /group unset <tg_user_id> — убрать
+/group schedule <группа> <disable|enable> <ГГГГ-ММ-ДД ЧЧ:ММ> — отключить или включить группу по расписанию
+/group — группы и расписания, /group cancel <id> — отменить событие
+/cleanup expired | rejected <дней> | group <группа> — массовое удаление с подтверждением
+/report — отчёт за последние 7 дней
+/tickets — открытые обращения в поддержку, /tickets close <tg_user_id> — закрыть
+/audit [N] — последние действия админов с листанием, /audit export [с] [по] [--json] — выгрузка журнала (ГГГГ-ММ-ДД)
+/logs [N] [текст] — последние N строк журнала telemt (по умолчанию 50), с текстом — только совпадающие строки
+/reloadcfg — перечитать токен бота из файла и переподключиться (как SIGHUP)
+/config export-users [--full] — файл с пользователями из конфига telemt (секреты скрыты, --full — полностью)
+/config backups — резервные копии конфига telemt, /config rollback <номер> — откат к копии с рестартом
+/sync — сверить пользователей в БД и конфиге telemt, исправить расхождения кнопками
+/import — завести в БД пользователей, уже существующих в конфиге telemt
+/bind <имя в конфиге> <tg_user_id | @username> — привязать импортированного пользователя к Telegram
+/viewas <tg_user_id> — видеть /start, /link и меню так, как их видит пользователь; /viewas off — выйти"#;
     let reply_markup = if is_admin {
         crate::bot::keyboards::admin_menu()
     } else {
-        crate::bot::keyboards::user_menu()
+        crate::bot::keyboards::user_menu(user_lang(&state, user_id).await?)
     };
     bot.send_message(msg.chat.id, text)
         .reply_markup(reply_markup)
@@ -94,8 +240,14 @@ async fn start_cmd(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
         display_name = ?display_name,
         "Received /start command"
     );
+    if !pass_cooldown(&bot, msg.chat.id, &state, user_id, CooldownCommand::Start).await? {
+        return Ok(());
+    }
 
     if state.config.is_admin(user_id) {
+        if let Some(tg_user_id) = viewed_user(&state, user_id).await {
+            return preview_start(&bot, msg.chat.id, &state, tg_user_id).await;
+        }
         bot.send_message(
             msg.chat.id,
             "Добро пожаловать в панель администратора. Используйте кнопки ниже.",
@@ -105,36 +257,43 @@ async fn start_cmd(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
         return Ok(());
     }
 
+    let lang = user_lang(&state, user_id).await?;
+    if state.db.is_user_blocked(user_id).await? {
+        tracing::info!(user_id = user_id, "Blocked user tried /start");
+        bot.send_message(msg.chat.id, blocked_text(&state, lang))
+            .await?;
+        return Ok(());
+    }
+
     if let Some(existing) = state.db.get_request_by_tg_user(user_id).await? {
         match existing.status {
             RequestStatus::Approved => {
+                if state.db.is_user_suspended(user_id).await? {
+                    bot.send_message(msg.chat.id, t(lang, "suspended"))
+                        .reply_markup(crate::bot::keyboards::user_menu(lang))
+                        .await?;
+                    return Ok(());
+                }
                 if let Some(secret) = existing.secret {
-                    let params = state.telemt_cfg.read_link_params()?;
+                    let params = state.telemt_cfg.read_link_params().await?;
                     let link = crate::link::build_proxy_link(&params, &secret)?;
-                    bot.send_message(msg.chat.id, format!("Ваша ссылка на прокси:\n\n{}", link))
-                        .reply_markup(crate::bot::keyboards::user_menu())
-                        .await?;
+                    let text = render_user_link_message(&state, user_id, &link).await?;
+                    send_proxy_link(&bot, &state, msg.chat.id, text, true).await?;
                     unmark_user_waiting_for_invite(&state, user_id).await;
                     return Ok(());
                 }
             }
             RequestStatus::Pending => {
-                bot.send_message(
-                    msg.chat.id,
-                    "Ваша заявка уже на рассмотрении. Ожидайте подтверждения администратора.",
-                )
-                .reply_markup(crate::bot::keyboards::user_menu())
-                .await?;
+                bot.send_message(msg.chat.id, t(lang, "pending"))
+                    .reply_markup(crate::bot::keyboards::user_menu(lang))
+                    .await?;
                 unmark_user_waiting_for_invite(&state, user_id).await;
                 return Ok(());
             }
             RequestStatus::Rejected => {
-                bot.send_message(
-                    msg.chat.id,
-                    "Ваша заявка на регистрацию отклонена администратором.",
-                )
-                .reply_markup(crate::bot::keyboards::user_menu())
-                .await?;
+                bot.send_message(msg.chat.id, t(lang, "rejected"))
+                    .reply_markup(crate::bot::keyboards::user_menu(lang))
+                    .await?;
                 unmark_user_waiting_for_invite(&state, user_id).await;
                 return Ok(());
             }
@@ -142,8 +301,70 @@ async fn start_cmd(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
         }
     }
 
+    if let Some(provisioned) = state.db.get_unconsumed_provision(user_id).await? {
+        let link = approve_user_direct_and_build_link(
+            &state,
+            user_id,
+            username.as_deref(),
+            display_name.as_deref(),
+        )
+        .await?;
+        state.db.mark_provision_consumed(user_id).await?;
+        if let Some(note) = provisioned.note.as_deref() {
+            state.db.set_user_note(user_id, Some(note)).await?;
+        }
+        let group = provisioned.user_group.as_deref().and_then(|group| {
+            let normalized = normalize_group_name(group);
+            if normalized.is_none() {
+                tracing::warn!(
+                    user_id = user_id,
+                    group = group,
+                    "Некорректная группа в [[provision]]"
+                );
+            }
+            normalized
+        });
+        if let Some(group) = group.as_deref() {
+            state.db.set_user_group(user_id, Some(group)).await?;
+        }
+        // Явный срок из [[provision]] важнее профиля группы.
+        let expires_at = provisioned
+            .expires_in_days
+            .and_then(|days| days.checked_mul(86_400))
+            .or_else(|| state.config.group_access_secs(group.as_deref()))
+            .map(|seconds| chrono::Utc::now().timestamp() + seconds);
+        if let Some(expires_at) = expires_at {
+            state.db.set_user_expiry(user_id, Some(expires_at)).await?;
+        }
+        tracing::info!(
+            user_id = user_id,
+            group = ?group,
+            expires_at = ?expires_at,
+            "Provisioned user approved on first /start"
+        );
+        send_proxy_link(
+            &bot,
+            &state,
+            msg.chat.id,
+            tf(lang, "access_approved", &[("link", &link)]),
+            true,
+        )
+        .await?;
+        unmark_user_waiting_for_invite(&state, user_id).await;
+        return Ok(());
+    }
+
+    if !registration_allowed(&state, user_id).await? {
+        tracing::info!(user_id = user_id, "User outside the allowlist tried /start");
+        bot.send_message(msg.chat.id, blocked_text(&state, lang))
+            .await?;
+        return Ok(());
+    }
+
     let text = msg.text().unwrap_or("");
     if let Some(token) = parse_start_token(text) {
+        let (token_only, _) = split_start_payload(&token);
+        state.db.record_token_view(token_only, user_id).await?;
         process_invite_token(
             &bot,
             &msg,
@@ -158,12 +379,9 @@ async fn start_cmd(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
     }
 
     mark_user_waiting_for_invite(&state, user_id).await;
-    bot.send_message(
-        msg.chat.id,
-        "Введите пригласительный токен для подачи заявки на доступ.",
-    )
-    .reply_markup(crate::bot::keyboards::user_menu())
-    .await?;
+    bot.send_message(msg.chat.id, t(lang, "invite_prompt"))
+        .reply_markup(crate::bot::keyboards::user_menu(lang))
+        .await?;
     Ok(())
 }
 
@@ -172,8 +390,16 @@ async fn cmd_link(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
         return Ok(());
     };
     tracing::info!(user_id = user_id, "Received /link command");
+    if !pass_cooldown(&bot, msg.chat.id, &state, user_id, CooldownCommand::Link).await? {
+        return Ok(());
+    }
 
-    send_user_link(&bot, msg.chat.id, user_id, &state).await
+    let target = if state.config.is_admin(user_id) {
+        viewed_user(&state, user_id).await.unwrap_or(user_id)
+    } else {
+        user_id
+    };
+    send_user_link(&bot, msg.chat.id, target, &state).await
 }
 
 async fn cmd_approve(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
@@ -182,36 +408,36 @@ async fn cmd_approve(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
     }
 
     let text = msg.text().unwrap_or("");
-    let request_id: i64 = match text.split_whitespace().nth(1).unwrap_or("").parse() {
-        Ok(id) => id,
-        Err(_) => {
-            bot.send_message(msg.chat.id, "Использование: /approve <request_id>")
-                .await?;
-            return Ok(());
-        }
+    let mut args = text.split_whitespace().skip(1);
+    let request_id = args.next().and_then(|value| value.parse::<i64>().ok());
+    let duration = match args.next() {
+        Some(value) => parse_access_duration(value).map(Some),
+        None => Some(None),
+    };
+    let (Some(request_id), Some(duration)) = (request_id, duration) else {
+        bot.send_message(
+            msg.chat.id,
+            "Использование: /approve <request_id> [срок: 30d | 12h]",
+        )
+        .await?;
+        return Ok(());
     };
     tracing::info!(request_id = request_id, "Admin command /approve");
+    let admin_id = sender_user_id(&msg).unwrap_or_default();
+    if let Some(holder) = claimed_by_other(&state, request_id, admin_id).await? {
+        bot.send_message(
+            msg.chat.id,
+            format!("🙋 Заявку #{} разбирает {}", request_id, holder),
+        )
+        .await?;
+        return Ok(());
+    }
 
-    let (request, link) = match approve_request_and_build_link(&state, request_id).await? {
-        Some(payload) => payload,
-        None => {
-            bot.send_message(msg.chat.id, "Заявка не найдена или уже обработана")
-                .await?;
-            return Ok(());
-        }
+    let action = ConfigAction::Approve {
+        request_id,
+        duration_secs: duration,
     };
-
-    bot.send_message(
-        msg.chat.id,
-        format!("Одобрено. Ссылка отправлена пользователю.\n{}", link),
-    )
-    .await?;
-    bot.send_message(
-        ChatId(request.tg_user_id),
-        format!("Ваша ссылка на прокси:\n\n{}", link),
-    )
-    .await?;
-    Ok(())
+    apply_or_preview(&bot, msg.chat.id, &state, admin_id, action).await
 }
 
 async fn cmd_reject(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
@@ -219,29 +445,40 @@ async fn cmd_reject(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
         return Ok(());
     }
 
-    let text = msg.text().unwrap_or("");
-    let request_id: i64 = match text.split_whitespace().nth(1).unwrap_or("").parse() {
-        Ok(id) => id,
-        Err(_) => {
-            bot.send_message(msg.chat.id, "Использование: /reject <request_id>")
-                .await?;
-            return Ok(());
-        }
+    // `/reject <id> [причина]`: причина — весь остаток строки.
+    let args = msg
+        .text()
+        .unwrap_or("")
+        .split_once(char::is_whitespace)
+        .map(|(_, args)| args.trim())
+        .unwrap_or("");
+    let (request_id, reason) = match args.split_once(char::is_whitespace) {
+        Some((request_id, reason)) => (request_id, Some(reason.trim())),
+        None => (args, None),
     };
+    let Ok(request_id) = request_id.parse::<i64>() else {
+        bot.send_message(msg.chat.id, "Использование: /reject <request_id> [причина]")
+            .await?;
+        return Ok(());
+    };
+    let reason = reason.filter(|reason| !reason.is_empty());
     tracing::info!(request_id = request_id, "Admin command /reject");
-
-    let req = state.db.reject(request_id).await?;
-    if let Some(r) = req {
-        bot.send_message(msg.chat.id, "Заявка отклонена").await?;
+    let admin_id = sender_user_id(&msg).unwrap_or_default();
+    if let Some(holder) = claimed_by_other(&state, request_id, admin_id).await? {
         bot.send_message(
-            ChatId(r.tg_user_id),
-            "Ваша заявка на регистрацию отклонена администратором.",
+            msg.chat.id,
+            format!("🙋 Заявку #{} разбирает {}", request_id, holder),
         )
         .await?;
-    } else {
-        bot.send_message(msg.chat.id, "Заявка не найдена или уже обработана")
-            .await?;
+        return Ok(());
     }
+
+    let text = match reject_pending_request(&bot, &state, admin_id, request_id, reason).await? {
+        Some(_) if reason.is_some() => "Заявка отклонена, причина отправлена пользователю",
+        Some(_) => "Заявка отклонена",
+        None => "Заявка не найдена или уже обработана",
+    };
+    bot.send_message(msg.chat.id, text).await?;
     Ok(())
 }
 
@@ -284,6 +521,15 @@ async fn cmd_create(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
 
     let telemt_user = telemt_username(tg_user_id);
     let link = approve_user_direct_and_build_link(&state, tg_user_id, None, None).await?;
+    state
+        .audit
+        .record(
+            sender_user_id(&msg).unwrap_or_default(),
+            "create",
+            &format!("tg_user:{}", tg_user_id),
+            "",
+        )
+        .await;
 
     bot.send_message(
         msg.chat.id,
@@ -309,9 +555,9 @@ async fn cmd_delete(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
     };
     tracing::info!(tg_user_id = tg_user_id, "Admin command /delete");
 
-    let status_text = perform_hard_ban(&state, tg_user_id).await?;
-    bot.send_message(msg.chat.id, status_text).await?;
-    Ok(())
+    let admin_id = sender_user_id(&msg).unwrap_or_default();
+    let action = ConfigAction::Delete { tg_user_id };
+    apply_or_preview(&bot, msg.chat.id, &state, admin_id, action).await
 }
 
 async fn cmd_service(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
@@ -324,38 +570,170 @@ async fn cmd_service(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
     let action = args.get(1).copied().unwrap_or("status");
     tracing::info!(action = action, "Admin command /service");
 
+    if action == "restart" && args.get(2) == Some(&"--notice") {
+        let minutes = match args.get(3) {
+            Some(value) => match value.parse::<u64>() {
+                Ok(minutes) if minutes > 0 => minutes,
+                _ => {
+                    bot.send_message(
+                        msg.chat.id,
+                        "Использование: /service restart --notice [минуты]",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            },
+            None => state.config.restart.notice_minutes,
+        };
+        if !schedule_restart_with_notice(&bot, &state, minutes, msg.chat.id).await? {
+            bot.send_message(
+                msg.chat.id,
+                "Перезапуск уже запланирован. Отмена: /service cancel",
+            )
+            .await?;
+        }
+        return Ok(());
+    }
+    if action == "restart" && args.get(2) == Some(&"now") {
+        let text = if state.cfg_writer.restart_pending_now() {
+            state
+                .audit
+                .record(
+                    sender_user_id(&msg).unwrap_or_default(),
+                    "service",
+                    &state.config.service_name,
+                    "restart now",
+                )
+                .await;
+            "♻️ Отложенный рестарт выполняется сейчас."
+        } else {
+            "Отложенного рестарта нет: все изменения уже применены."
+        };
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+    if action == "cancel" {
+        let text = if cancel_scheduled_restart(&bot, &state).await? {
+            "Плановый перезапуск отменён, пользователи уведомлены."
+        } else {
+            "Нет запланированного перезапуска."
+        };
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+
     let (action_name, result) = match action {
-        "start" => ("start", state.service.start()),
-        "stop" => ("stop", state.service.stop()),
-        "restart" => ("restart", state.service.restart()),
-        "reload" => ("reload", state.service.reload()),
-        "status" => ("status", state.service.status()),
+        "start" => ("start", state.service.start().await),
+        "stop" => ("stop", state.service.stop().await),
+        "restart" => {
+            let force = args.get(2) == Some(&"--force");
+            let reason = format!(
+                "/service restart от админа {}",
+                sender_user_id(&msg).unwrap_or_default()
+            );
+            match state.service.restart(&reason, force).await {
+                Ok(result) => ("restart", result),
+                Err(denied) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        format!("🚦 {}\nПринудительно: /service restart --force", denied),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+        }
+        "reload" => ("reload", state.service.reload().await),
+        "status" => ("status", state.service.status().await),
+        "enable" => ("enable", state.service.enable().await),
+        "disable" => ("disable", state.service.disable().await),
         _ => {
             bot.send_message(
                 msg.chat.id,
-                "Использование: /service <start|stop|restart|reload|status>",
+                "Использование: /service <start|stop|restart|reload|status|enable|disable>\n\
+                 /service restart --force — рестарт в обход защиты от частых рестартов\n\
+                 /service restart now — выполнить отложенный рестарт сразу\n\
+                 /service restart --notice [минуты] — рестарт с предупреждением пользователей\n\
+                 /service cancel — отменить запланированный рестарт",
             )
             .await?;
             return Ok(());
         }
     };
 
-    let reply = state.service.format_result(action_name, &result);
+    if action_name != "status" {
+        state
+            .audit
+            .record(
+                sender_user_id(&msg).unwrap_or_default(),
+                "service",
+                &state.config.service_name,
+                &format!("{}: {}", action_name, result.status_label()),
+            )
+            .await;
+    }
+    let reply = render_service_report(&state, action_name, &result).await;
     bot.send_message(msg.chat.id, reply).await?;
     Ok(())
 }
 
+/// Строка о группе токена и сроке доступа из её профиля.
+fn render_token_group_line(state: &BotState, group: Option<&str>) -> String {
+    let Some(group) = group else {
+        return String::new();
+    };
+    match state.config.group_access_secs(Some(group)) {
+        Some(seconds) => format!(
+            "Группа: {} (доступ на {} дн. по профилю группы)\n",
+            group,
+            seconds / 86_400
+        ),
+        None => format!("Группа: {}\n", group),
+    }
+}
+
+/// Значение параметра из одного слова или из нескольких в кавычках (`"…"` или `«…»`).
+/// Возвращает значение и число занятых слов.
+fn parse_quoted_arg(args: &[&str]) -> Option<(String, usize)> {
+    let first = *args.first()?;
+    let Some((open, close)) = [('"', '"'), ('«', '»')]
+        .into_iter()
+        .find(|(open, _)| first.starts_with(*open))
+    else {
+        return Some((first.to_string(), 1));
+    };
+    let mut words = Vec::new();
+    for (index, word) in args.iter().enumerate() {
+        let word: &str = if index == 0 {
+            &word[open.len_utf8()..]
+        } else {
+            word
+        };
+        if let Some(last) = word.strip_suffix(close) {
+            words.push(last);
+            let value = words.join(" ");
+            return (!value.trim().is_empty()).then(|| (value.trim().to_string(), index + 1));
+        }
+        words.push(word);
+    }
+    None
+}
+
 async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
     if !is_admin_message(&msg, &state) {
         return Ok(());
     }
 
+    const TOKEN_LABEL_MAX_CHARS: usize = 64;
+    /// Столько применений помещается в одно сообщение `/token info`.
+    const TOKEN_INFO_USAGES: i64 = 30;
+    const TOKEN_CREATE_USAGE: &str = "Использование: /token create [days] [--auto|-a] [--max-uses N] [--for <tg_user_id | @username>] [--plans группа1,группа2] [--label \"подпись\"] [--from ГГГГ-ММ-ДД [ЧЧ:ММ]] [--group группа]";
     let text = msg.text().unwrap_or("");
     let args: Vec<&str> = text.split_whitespace().collect();
     let Some(subcommand) = args.get(1).copied() else {
         bot.send_message(
             msg.chat.id,
-            "Использование:\n/token create [days] [--auto|-a] [--max-uses N]\n/token list\n/token revoke <token>",
+            "Использование:\n/token create [days] [--auto|-a] [--max-uses N] [--for <tg_user_id | @username>] [--plans группа1,группа2] [--label \"подпись\"] [--from ГГГГ-ММ-ДД [ЧЧ:ММ]] [--group группа]\n/token list\n/token revoke <token>\n/token stats <token>\n/token info <token>\n/token edit <token> [--days N] [--max-uses M | none]\n/token qr <token> [группа]",
         )
         .await?;
         return Ok(());
@@ -366,6 +744,11 @@ async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
             let mut days: Option<i64> = None;
             let mut auto_approve = false;
             let mut max_uses: Option<i64> = None;
+            let mut for_tg_user_id: Option<i64> = None;
+            let mut plans: Vec<String> = Vec::new();
+            let mut label: Option<String> = None;
+            let mut valid_from: Option<i64> = None;
+            let mut group_name: Option<String> = None;
             let mut index = 2;
 
             while index < args.len() {
@@ -376,11 +759,7 @@ async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
                     }
                     "--max-uses" => {
                         let Some(value) = args.get(index + 1) else {
-                            bot.send_message(
-                                msg.chat.id,
-                                "Использование: /token create [days] [--auto|-a] [--max-uses N]",
-                            )
-                            .await?;
+                            bot.send_message(msg.chat.id, TOKEN_CREATE_USAGE).await?;
                             return Ok(());
                         };
                         let parsed = match value.parse::<i64>() {
@@ -397,25 +776,130 @@ async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
                         max_uses = Some(parsed);
                         index += 2;
                     }
+                    "--for" => {
+                        let target = args
+                            .get(index + 1)
+                            .and_then(|value| parse_create_target(value));
+                        let tg_user_id = match target {
+                            Some(CreateTarget::UserId(id)) => id,
+                            Some(CreateTarget::Username(username)) => {
+                                match state.db.find_tg_user_id_by_username(&username).await? {
+                                    Some(user_id) => user_id,
+                                    None => {
+                                        bot.send_message(
+                                            msg.chat.id,
+                                            format!(
+                                                "Пользователь @{} не найден в базе.\n\
+                                                 Он должен хотя бы раз отправить боту /start, \
+                                                 либо укажите его tg_user_id.",
+                                                username
+                                            ),
+                                        )
+                                        .await?;
+                                        return Ok(());
+                                    }
+                                }
+                            }
+                            None => {
+                                bot.send_message(msg.chat.id, TOKEN_CREATE_USAGE).await?;
+                                return Ok(());
+                            }
+                        };
+                        for_tg_user_id = Some(tg_user_id);
+                        index += 2;
+                    }
+                    "--plans" => {
+                        let parsed: Option<Vec<String>> = args
+                            .get(index + 1)
+                            .and_then(|value| value.split(',').map(normalize_group_name).collect());
+                        let Some(parsed) = parsed else {
+                            bot.send_message(
+                                msg.chat.id,
+                                "Параметр --plans: имена групп через запятую (латиница, цифры, - и _).",
+                            )
+                            .await?;
+                            return Ok(());
+                        };
+                        for plan in parsed {
+                            if !plans.contains(&plan) {
+                                plans.push(plan);
+                            }
+                        }
+                        index += 2;
+                    }
+                    "--group" => {
+                        let Some(group) = args
+                            .get(index + 1)
+                            .and_then(|value| normalize_group_name(value))
+                        else {
+                            bot.send_message(
+                                msg.chat.id,
+                                "Параметр --group: имя группы (латиница, цифры, - и _).",
+                            )
+                            .await?;
+                            return Ok(());
+                        };
+                        group_name = Some(group);
+                        index += 2;
+                    }
+                    "--from" => {
+                        // Время необязательно: без него токен начинает действовать с начала дня.
+                        let time = args
+                            .get(index + 2)
+                            .copied()
+                            .filter(|value| value.contains(':'));
+                        let parsed = args
+                            .get(index + 1)
+                            .and_then(|date| parse_local_datetime(date, time.unwrap_or("00:00")));
+                        let Some(parsed) = parsed else {
+                            bot.send_message(
+                                msg.chat.id,
+                                "Параметр --from: дата ГГГГ-ММ-ДД и необязательное время ЧЧ:ММ (локальное время сервера).",
+                            )
+                            .await?;
+                            return Ok(());
+                        };
+                        if parsed <= chrono::Utc::now().timestamp() {
+                            bot.send_message(
+                                msg.chat.id,
+                                "Начало действия токена должно быть в будущем.",
+                            )
+                            .await?;
+                            return Ok(());
+                        }
+                        valid_from = Some(parsed);
+                        index += if time.is_some() { 3 } else { 2 };
+                    }
+                    "--label" => {
+                        let Some((value, consumed)) = parse_quoted_arg(&args[index + 1..]) else {
+                            bot.send_message(msg.chat.id, TOKEN_CREATE_USAGE).await?;
+                            return Ok(());
+                        };
+                        if value.chars().count() > TOKEN_LABEL_MAX_CHARS {
+                            bot.send_message(
+                                msg.chat.id,
+                                format!(
+                                    "Подпись токена — не длиннее {} символов.",
+                                    TOKEN_LABEL_MAX_CHARS
+                                ),
+                            )
+                            .await?;
+                            return Ok(());
+                        }
+                        label = Some(value);
+                        index += 1 + consumed;
+                    }
                     value => {
                         if let Ok(parsed_days) = value.parse::<i64>() {
                             if days.is_some() {
-                                bot.send_message(
-                                    msg.chat.id,
-                                    "Использование: /token create [days] [--auto|-a] [--max-uses N]",
-                                )
-                                .await?;
+                                bot.send_message(msg.chat.id, TOKEN_CREATE_USAGE).await?;
                                 return Ok(());
                             }
                             days = Some(parsed_days);
                             index += 1;
                             continue;
                         }
-                        bot.send_message(
-                            msg.chat.id,
-                            "Использование: /token create [days] [--auto|-a] [--max-uses N]",
-                        )
-                        .await?;
+                        bot.send_message(msg.chat.id, TOKEN_CREATE_USAGE).await?;
                         return Ok(());
                     }
                 }
@@ -448,39 +932,115 @@ async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
                 return Ok(());
             }
 
+            // Персональный токен по умолчанию одноразовый: второе применение тем же
+            // аккаунтом ничего не даёт, а открытый лимит лишь продлевает жизнь коду.
+            if for_tg_user_id.is_some() && max_uses.is_none() {
+                max_uses = Some(1);
+            }
+
             let created_by = sender_user_id(&msg);
             let token = state
                 .db
-                .create_invite_token(days, auto_approve, max_uses, created_by)
+                .create_invite_token(NewInviteToken {
+                    days,
+                    auto_approve,
+                    max_usage: max_uses,
+                    created_by,
+                    for_tg_user_id,
+                    plans: &plans,
+                    label: label.as_deref(),
+                    valid_from,
+                    group_name: group_name.as_deref(),
+                    referrer_tg_user_id: None,
+                })
                 .await?;
+            let mut details = format!(
+                "days={} auto={} plans={}",
+                days,
+                auto_approve,
+                plans.join(",")
+            );
+            if let Some(label) = &label {
+                details.push_str(&format!(" label=«{}»", label));
+            }
+            if let Some(valid_from) = valid_from {
+                details.push_str(&format!(" from={}", format_timestamp(valid_from)));
+            }
+            if let Some(group) = &group_name {
+                details.push_str(&format!(" group={}", group));
+            }
+            state
+                .audit
+                .record(
+                    created_by.unwrap_or_default(),
+                    "token_create",
+                    &format!("token:{}", token.token),
+                    &details,
+                )
+                .await;
 
             let link_line = state
                 .bot_username
                 .as_deref()
                 .map(|bot_username| {
                     let invite_link = build_bot_start_link(bot_username, &token.token);
-                    format!("Ссылка: {}\n", invite_link)
+                    let mut lines = format!("Ссылка: {}\n", invite_link);
+                    for plan in &plans {
+                        let plan_link = build_bot_start_link(
+                            bot_username,
+                            &build_start_payload(&token.token, plan),
+                        );
+                        lines.push_str(&format!("Ссылка ({}): {}\n", plan, plan_link));
+                    }
+                    lines
                 })
                 .unwrap_or_else(|| {
                     "Ссылка: недоступна (у бота не задан username в Telegram).\n".to_string()
                 });
+            let page_line = crate::web::invite_page_url(&state.config.web, &token.token)
+                .map(|url| format!("Веб-страница: {}\n", url))
+                .unwrap_or_default();
 
+            let label_line = token
+                .label
+                .as_deref()
+                .map(|label| format!("Подпись: {}\n", html::escape(label)))
+                .unwrap_or_default();
+            let mut details_lines = token
+                .valid_from
+                .map(|valid_from| format!("Начнёт действовать: {}\n", format_timestamp(valid_from)))
+                .unwrap_or_default();
+            details_lines.push_str(&render_token_group_line(
+                &state,
+                token.group_name.as_deref(),
+            ));
             let response = format!(
                 "✅ Токен создан:\n\
                  Код: <code>{}</code>\n\
                  {}\
+                 {}\
+                 {}\
                  Режим: {}\n\
+                 {}\
                  Действует до: {}\n\
                  Лимит использований: {}\n\
+                 Выписан для: {}\n\
                  Используйте команду <code>/token revoke {}</code> для отзыва.",
                 token.token,
+                label_line,
                 link_line,
+                page_line,
                 format_mode(token.auto_approve),
+                details_lines,
                 format_date(token.expires_at),
                 token
                     .max_usage
                     .map(|value| value.to_string())
                     .unwrap_or_else(|| "без лимита".to_string()),
+                token
+                    .for_tg_user_id
+                    .map(|value| value.to_string())
+                    .unwrap_or_else(|| "любого пользователя".to_string()),
                 token.token
             );
             bot.send_message(msg.chat.id, response)
@@ -508,8 +1068,24 @@ async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
                     .await?;
                 return Ok(());
             };
+            let label = state
+                .db
+                .get_invite_token(token_value)
+                .await?
+                .and_then(|token| token.label);
             let revoked = state.db.revoke_invite_token(token_value).await?;
             if revoked {
+                state
+                    .audit
+                    .record(
+                        sender_user_id(&msg).unwrap_or_default(),
+                        "token_revoke",
+                        &format!("token:{}", token_value),
+                        &label
+                            .map(|label| format!("label=«{}»", label))
+                            .unwrap_or_default(),
+                    )
+                    .await;
                 bot.send_message(msg.chat.id, format!("Токен {} отозван.", token_value))
                     .await?;
             } else {
@@ -517,10 +1093,241 @@ async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
                     .await?;
             }
         }
+        "stats" => {
+            let Some(token_value) = args.get(2).copied() else {
+                bot.send_message(msg.chat.id, "Использование: /token stats <token>")
+                    .await?;
+                return Ok(());
+            };
+            let Some(funnel) = state.db.token_funnel(token_value).await? else {
+                bot.send_message(msg.chat.id, "Токен не найден.").await?;
+                return Ok(());
+            };
+            let text = format!(
+                "📊 Воронка токена {}:\n\
+                 Посещения веб-страницы: {}\n\
+                 Переходы по ссылке: {}\n\
+                 Применили токен: {} ({})\n\
+                 Получили доступ: {} ({})\n\
+                 Активны сейчас: {} ({})",
+                token_value,
+                funnel.web_visits,
+                funnel.views,
+                funnel.consumed,
+                format_percent(funnel.consumed, funnel.views),
+                funnel.approved,
+                format_percent(funnel.approved, funnel.consumed),
+                funnel.active,
+                format_percent(funnel.active, funnel.approved),
+            );
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        "edit" => {
+            const TOKEN_EDIT_USAGE: &str =
+                "Использование: /token edit <token> [--days N] [--max-uses M | none]";
+            let Some(token_value) = args.get(2).copied() else {
+                bot.send_message(msg.chat.id, TOKEN_EDIT_USAGE).await?;
+                return Ok(());
+            };
+            let mut days: Option<i64> = None;
+            let mut max_usage: Option<Option<i64>> = None;
+            let mut index = 3;
+            while index < args.len() {
+                let value = args.get(index + 1).copied();
+                match (args[index], value) {
+                    ("--days", Some(value)) => match value.parse::<i64>() {
+                        Ok(parsed) => days = Some(parsed),
+                        Err(_) => {
+                            bot.send_message(msg.chat.id, TOKEN_EDIT_USAGE).await?;
+                            return Ok(());
+                        }
+                    },
+                    ("--max-uses", Some("none")) => max_usage = Some(None),
+                    ("--max-uses", Some(value)) => match value.parse::<i64>() {
+                        Ok(parsed) if parsed >= 1 => max_usage = Some(Some(parsed)),
+                        _ => {
+                            bot.send_message(
+                                msg.chat.id,
+                                "Параметр --max-uses должен быть целым числом >= 1 или none.",
+                            )
+                            .await?;
+                            return Ok(());
+                        }
+                    },
+                    _ => {
+                        bot.send_message(msg.chat.id, TOKEN_EDIT_USAGE).await?;
+                        return Ok(());
+                    }
+                }
+                index += 2;
+            }
+            if days.is_none() && max_usage.is_none() {
+                bot.send_message(msg.chat.id, TOKEN_EDIT_USAGE).await?;
+                return Ok(());
+            }
+            let max_token_days = state.config.security.max_token_days;
+            if days.is_some_and(|days| !(1..=max_token_days).contains(&days)) {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("Срок действия токена — от 1 до {} дней.", max_token_days),
+                )
+                .await?;
+                return Ok(());
+            }
+
+            let Some(before) = state.db.get_invite_token(token_value).await? else {
+                bot.send_message(msg.chat.id, "Токен не найден.").await?;
+                return Ok(());
+            };
+            // Для токена, который ещё не начал действовать, срок отсчитывается от начала действия.
+            let starts_at = before
+                .valid_from
+                .unwrap_or_default()
+                .max(chrono::Utc::now().timestamp());
+            let expires_at = days.map(|days| starts_at + days * 86_400);
+            let Some(after) = state
+                .db
+                .update_invite_token(token_value, expires_at, max_usage)
+                .await?
+            else {
+                bot.send_message(msg.chat.id, "Токен отозван — изменить его нельзя.")
+                    .await?;
+                return Ok(());
+            };
+
+            let format_limit = |max_usage: Option<i64>| {
+                max_usage
+                    .map(|value| value.to_string())
+                    .unwrap_or_else(|| "∞".to_string())
+            };
+            let mut changes = Vec::new();
+            if days.is_some() {
+                changes.push(format!(
+                    "срок: {} → {}",
+                    format_date(before.expires_at),
+                    format_date(after.expires_at)
+                ));
+            }
+            if max_usage.is_some() {
+                changes.push(format!(
+                    "лимит: {} → {}",
+                    format_limit(before.max_usage),
+                    format_limit(after.max_usage)
+                ));
+            }
+            let changes = changes.join(", ");
+            state
+                .audit
+                .record(
+                    sender_user_id(&msg).unwrap_or_default(),
+                    "token_edit",
+                    &format!("token:{}", token_value),
+                    &changes,
+                )
+                .await;
+            tracing::info!(token = %token_value, changes = %changes, "Invite token edited");
+            let mut text = format!("✏️ Токен {} изменён: {}.", token_value, changes);
+            if after.max_usage.is_some_and(|max| after.usage_count >= max) {
+                text.push_str(&format!(
+                    "\nИспользований уже {} — новых применений не будет.",
+                    after.usage_count
+                ));
+            }
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        "qr" => {
+            let Some(token_value) = args.get(2).copied() else {
+                bot.send_message(msg.chat.id, "Использование: /token qr <token> [группа]")
+                    .await?;
+                return Ok(());
+            };
+            let Some(token) = state.db.get_invite_token(token_value).await? else {
+                bot.send_message(msg.chat.id, "Токен не найден.").await?;
+                return Ok(());
+            };
+            let Some(bot_username) = state.bot_username.as_deref() else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Ссылка недоступна: у бота не задан username в Telegram.",
+                )
+                .await?;
+                return Ok(());
+            };
+            let payload = match args.get(3).copied() {
+                Some(plan) => match normalize_group_name(plan) {
+                    Some(plan) if token.plan_names().contains(&plan) => {
+                        build_start_payload(&token.token, &plan)
+                    }
+                    _ => {
+                        bot.send_message(
+                            msg.chat.id,
+                            format!("У токена нет варианта ссылки «{}».", plan),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                },
+                None => token.token.clone(),
+            };
+            let link = build_bot_start_link(bot_username, &payload);
+            let qr_png = build_user_qr_png_bytes(&link)?;
+            let mut caption = link;
+            if let Some(label) = token.label.as_deref() {
+                caption.push_str(&format!("\n«{}»", label));
+            }
+            caption.push_str(&format!("\nДействует до {}", format_date(token.expires_at)));
+            let usable = token.is_active
+                && token.expires_at > chrono::Utc::now().timestamp()
+                && token.max_usage.is_none_or(|max| token.usage_count < max);
+            if !usable {
+                caption.push_str("\n⚠️ Токен уже нельзя применить — проверьте /token info.");
+            }
+            bot.send_photo(
+                msg.chat.id,
+                InputFile::memory(qr_png).file_name(format!("token-{}.png", token.token)),
+            )
+            .caption(caption)
+            .await?;
+        }
+        "info" => {
+            let Some(token_value) = args.get(2).copied() else {
+                bot.send_message(msg.chat.id, "Использование: /token info <token>")
+                    .await?;
+                return Ok(());
+            };
+            let token = state.db.get_invite_token(token_value).await?;
+            let (usages, total) = state
+                .db
+                .token_usages(token_value, TOKEN_INFO_USAGES)
+                .await?;
+            if token.is_none() && total == 0 {
+                bot.send_message(msg.chat.id, "Токен не найден.").await?;
+                return Ok(());
+            }
+            let mut text = match &token {
+                Some(token) => render_invite_token_info(token),
+                None => format!(
+                    "🎟 Токен {}\nТокена уже нет в списке (перенесён в архив), история применений сохранена.",
+                    token_value
+                ),
+            };
+            if usages.is_empty() {
+                text.push_str("\n\nТокеном ещё никто не воспользовался.");
+            } else {
+                text.push_str(&format!("\n\nПрименили: {}", total));
+                if total > usages.len() as i64 {
+                    text.push_str(&format!(" (показаны последние {})", usages.len()));
+                }
+                text.push_str(":\n");
+                let lines: Vec<String> = usages.iter().map(render_token_usage_line).collect();
+                text.push_str(&lines.join("\n"));
+            }
+            bot.send_message(msg.chat.id, text).await?;
+        }
         _ => {
             bot.send_message(
                 msg.chat.id,
-                "Использование:\n/token create [days] [--auto|-a] [--max-uses N]\n/token list\n/token revoke <token>",
+                "Использование:\n/token create [days] [--auto|-a] [--max-uses N] [--for <tg_user_id | @username>] [--plans группа1,группа2] [--label \"подпись\"] [--from ГГГГ-ММ-ДД [ЧЧ:ММ]] [--group группа]\n/token list\n/token revoke <token>\n/token stats <token>\n/token info <token>\n/token edit <token> [--days N] [--max-uses M | none]\n/token qr <token> [группа]",
             )
             .await?;
         }
@@ -529,12 +1336,430 @@ async fn cmd_token(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
     Ok(())
 }
 
-pub async fn admin_show_pending_cmd(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
-    admin_show_pending(bot, chat_id, state).await
+async fn cmd_announce(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+
+    const USAGE: &str = "Использование:\n/announce — показать текущее объявление\n/announce [--days N] <текст> — опубликовать\n/announce clear — снять";
+    let text = msg.text().unwrap_or("");
+    let mut rest = text
+        .split_once(char::is_whitespace)
+        .map(|(_, rest)| rest.trim())
+        .unwrap_or("");
+
+    if rest.is_empty() {
+        let reply = match state.db.get_active_announcement().await? {
+            Some(announcement) => format!(
+                "📢 Текущее объявление:\n\n{}\n\nОпубликовано: {} (admin {})\nДействует до: {}",
+                announcement.text,
+                format_timestamp(announcement.created_at),
+                announcement
+                    .created_by
+                    .map(|value| value.to_string())
+                    .unwrap_or_else(|| "—".to_string()),
+                announcement
+                    .expires_at
+                    .map(format_timestamp)
+                    .unwrap_or_else(|| "снятия вручную".to_string())
+            ),
+            None => format!("Объявление не задано.\n\n{}", USAGE),
+        };
+        bot.send_message(msg.chat.id, reply).await?;
+        return Ok(());
+    }
+
+    if rest == "clear" {
+        let cleared = state.db.clear_announcement().await?;
+        tracing::info!(cleared = cleared, "Admin command /announce clear");
+        let reply = if cleared {
+            "Объявление снято."
+        } else {
+            "Действующего объявления нет."
+        };
+        bot.send_message(msg.chat.id, reply).await?;
+        return Ok(());
+    }
+
+    let mut expires_at: Option<i64> = None;
+    if let Some(after_flag) = rest.strip_prefix("--days") {
+        let after_flag = after_flag.trim_start();
+        let (value, tail) = after_flag
+            .split_once(char::is_whitespace)
+            .unwrap_or((after_flag, ""));
+        let days = match value.parse::<i64>() {
+            Ok(days) if days >= 1 => days,
+            _ => {
+                bot.send_message(
+                    msg.chat.id,
+                    "Параметр --days должен быть целым числом >= 1.",
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+        let expires = chrono::Utc::now()
+            .timestamp()
+            .checked_add(days.saturating_mul(86_400));
+        let Some(expires) = expires else {
+            bot.send_message(msg.chat.id, "Срок действия объявления слишком большой.")
+                .await?;
+            return Ok(());
+        };
+        expires_at = Some(expires);
+        rest = tail.trim();
+    }
+
+    if rest.is_empty() {
+        bot.send_message(msg.chat.id, USAGE).await?;
+        return Ok(());
+    }
+
+    let announcement = state
+        .db
+        .set_announcement(rest, expires_at, sender_user_id(&msg))
+        .await?;
+    tracing::info!(
+        announcement_id = announcement.id,
+        expires_at = ?announcement.expires_at,
+        "Admin command /announce: announcement published"
+    );
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "📢 Объявление опубликовано и будет показываться в ответах /start и /link.\nДействует до: {}",
+            announcement
+                .expires_at
+                .map(format_timestamp)
+                .unwrap_or_else(|| "снятия вручную".to_string())
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn cmd_rotate(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+
+    let text = msg.text().unwrap_or("");
+    let arg = text.split_whitespace().nth(1);
+    if let Some(tg_user_id) = arg.and_then(|value| value.parse::<i64>().ok()) {
+        tracing::info!(tg_user_id = tg_user_id, "Admin command /rotate");
+        let admin_id = sender_user_id(&msg).unwrap_or_default();
+        let action = ConfigAction::Rotate { tg_user_id };
+        return apply_or_preview(&bot, msg.chat.id, &state, admin_id, action).await;
+    }
+    if arg != Some("all") {
+        bot.send_message(
+            msg.chat.id,
+            "Использование: /rotate <tg_user_id> — перевыпустить секрет пользователя\n\
+             /rotate all — перевыпустить секреты всех пользователей",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let job = state
+        .db
+        .enqueue_job(JobKind::RotateSecrets.as_str(), sender_user_id(&msg))
+        .await?;
+    state.job_notify.notify_one();
+    tracing::info!(job_id = job.id, "Admin command /rotate all: job enqueued");
+    state
+        .audit
+        .record(
+            sender_user_id(&msg).unwrap_or_default(),
+            "rotate_all",
+            &format!("job:{}", job.id),
+            "",
+        )
+        .await;
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🧰 Задача #{} поставлена в очередь: {}.\n\
+             Пользователи получат новые ссылки по мере обработки. Прогресс: /jobs",
+            job.id,
+            JobKind::RotateSecrets.title()
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn cmd_reloadcfg(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+
+    if !state.config.bot_token_from_file() {
+        bot.send_message(
+            msg.chat.id,
+            "Токен бота задан не файлом (bot_token / провайдер секретов / TELOXIDE_TOKEN) — \
+             перечитывать нечего. Укажите bot_token_file, чтобы менять токен без перезапуска.",
+        )
+        .await?;
+        return Ok(());
+    }
+    tracing::info!("Admin command /reloadcfg");
+    state
+        .audit
+        .record(
+            sender_user_id(&msg).unwrap_or_default(),
+            "token_reload",
+            "bot",
+            "",
+        )
+        .await;
+    state.token_reload.notify_one();
+    bot.send_message(
+        msg.chat.id,
+        "🔑 Перечитываю токен бота. Если он изменился, бот проверит его и переподключится; \
+         итог придёт отдельным сообщением.",
+    )
+    .await?;
+    Ok(())
+}
+
+async fn cmd_jobs(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+
+    let text = msg.text().unwrap_or("");
+    let args: Vec<&str> = text.split_whitespace().collect();
+    match args.get(1).copied() {
+        None | Some("list") => {
+            let jobs = state.db.list_recent_jobs(20).await?;
+            if jobs.is_empty() {
+                bot.send_message(msg.chat.id, "Фоновых задач нет.").await?;
+                return Ok(());
+            }
+            let lines: Vec<String> = jobs.iter().map(render_job_line).collect();
+            bot.send_message(msg.chat.id, format!("🧰 Задачи:\n\n{}", lines.join("\n")))
+                .await?;
+        }
+        Some("cancel") => {
+            let Some(job_id) = args.get(2).and_then(|value| value.parse::<i64>().ok()) else {
+                bot.send_message(msg.chat.id, "Использование: /jobs cancel <id>")
+                    .await?;
+                return Ok(());
+            };
+            let cancelled = state.db.cancel_job(job_id).await?;
+            tracing::info!(
+                job_id = job_id,
+                cancelled = cancelled,
+                "Admin command /jobs cancel"
+            );
+            let reply = if cancelled {
+                format!("Задача #{} отменена.", job_id)
+            } else {
+                "Задача не найдена или уже завершена.".to_string()
+            };
+            bot.send_message(msg.chat.id, reply).await?;
+        }
+        Some(_) => {
+            bot.send_message(msg.chat.id, "Использование:\n/jobs\n/jobs cancel <id>")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_basket(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    let Some(admin_id) = sender_user_id(&msg).filter(|id| state.config.is_admin(*id)) else {
+        return Ok(());
+    };
+
+    let text = msg.text().unwrap_or("");
+    let args: Vec<&str> = text.split_whitespace().collect();
+    match args.get(1).copied() {
+        None | Some("list") => {
+            let (text, count) = render_basket(&state).await?;
+            let mut request = bot.send_message(msg.chat.id, text);
+            if count > 0 {
+                request = request.reply_markup(crate::bot::keyboards::basket_keyboard(count));
+            }
+            request.await?;
+        }
+        Some("add") => {
+            let Some(request_id) = args.get(2).and_then(|value| value.parse::<i64>().ok()) else {
+                bot.send_message(msg.chat.id, "Использование: /basket add <id>")
+                    .await?;
+                return Ok(());
+            };
+            let reply = if state.db.stage_request(request_id, admin_id).await? {
+                format!("🧺 Заявка #{} в корзине.", request_id)
+            } else {
+                "Заявка уже обработана или не найдена.".to_string()
+            };
+            bot.send_message(msg.chat.id, reply).await?;
+        }
+        Some("apply") => {
+            let outcome = apply_approval_basket(&bot, &state, admin_id).await?;
+            tracing::info!(
+                admin_id = admin_id,
+                approved = outcome.approved,
+                "Admin command /basket apply"
+            );
+            bot.send_message(msg.chat.id, render_basket_outcome(&outcome))
+                .await?;
+        }
+        Some("clear") => {
+            let removed = state.db.clear_basket().await?;
+            bot.send_message(msg.chat.id, format!("Корзина очищена ({}).", removed))
+                .await?;
+        }
+        Some(_) => {
+            bot.send_message(
+                msg.chat.id,
+                "Использование:\n/basket\n/basket add <id>\n/basket apply\n/basket clear",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_find(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+
+    let text = msg.text().unwrap_or("");
+    let query = text
+        .split_once(char::is_whitespace)
+        .map(|(_, rest)| rest.trim())
+        .unwrap_or("");
+    if query.is_empty() {
+        bot.send_message(msg.chat.id, "Использование: /find <имя | @username | заметка | tg_user_id | tg_123… | часть секрета>")
+            .await?;
+        return Ok(());
+    }
+
+    let hits = state
+        .db
+        .search_users(query, state.config.search_results_limit)
+        .await?;
+    tracing::info!(query = %query, hits = hits.len(), "Admin command /find");
+    if hits.is_empty() {
+        bot.send_message(msg.chat.id, "Ничего не найдено.").await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = hits.iter().map(render_search_hit_line).collect();
+    bot.send_message(
+        msg.chat.id,
+        format!("🔎 Найдено: {}\n\n{}", hits.len(), lines.join("\n")),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn cmd_note(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+
+    let text = msg.text().unwrap_or("");
+    let mut parts = text.splitn(3, char::is_whitespace);
+    parts.next();
+    let tg_user_id = parts.next().and_then(|value| value.parse::<i64>().ok());
+    let note = parts
+        .next()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    let (Some(tg_user_id), Some(note)) = (tg_user_id, note) else {
+        bot.send_message(
+            msg.chat.id,
+            "Использование:\n/note <tg_user_id> <текст>\n/note <tg_user_id> clear",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let note = if note == "clear" { None } else { Some(note) };
+    let updated = state.db.set_user_note(tg_user_id, note).await?;
+    let reply = match (updated, note) {
+        (false, _) => "Пользователь не найден.".to_string(),
+        (true, Some(_)) => format!("📝 Заметка для {} сохранена.", tg_user_id),
+        (true, None) => format!("Заметка для {} удалена.", tg_user_id),
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+async fn cmd_archive(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+
+    let text = msg.text().unwrap_or("");
+    let query = text
+        .split_once(char::is_whitespace)
+        .map(|(_, rest)| rest.trim())
+        .unwrap_or("");
+    if query.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "Использование: /archive <tg_user_id | @username | имя>",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let limit = state.config.search_results_limit;
+    let archived = state.db.search_archive(query, limit).await?;
+    let active = state.db.search_users(query, limit).await?;
+    tracing::info!(
+        query = %query,
+        archived = archived.len(),
+        active = active.len(),
+        "Admin command /archive"
+    );
+
+    let mut reply = if archived.is_empty() {
+        "🗄 В архиве ничего не найдено.".to_string()
+    } else {
+        let lines: Vec<String> = archived.iter().map(render_archived_request_line).collect();
+        format!(
+            "🗄 Найдено в архиве: {}\n\n{}",
+            archived.len(),
+            lines.join("\n")
+        )
+    };
+    if !active.is_empty() {
+        let lines: Vec<String> = active.iter().map(render_search_hit_line).collect();
+        reply.push_str(&format!(
+            "\n\nВ текущих записях ({}):\n{}",
+            active.len(),
+            lines.join("\n")
+        ));
+    }
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+async fn cmd_pending(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    tracing::info!("Admin command /pending");
+    admin_show_pending_summary(&bot, msg.chat.id, &state).await
+}
+
+pub async fn admin_show_pending_cmd(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    admin_id: i64,
+) -> HandlerResult {
+    admin_show_pending_page(bot, chat_id, state, admin_id, 0, None).await
 }
 
 pub async fn admin_show_users_cmd(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
-    admin_show_users_page(bot, chat_id, state, 1, None).await
+    admin_show_users_page(bot, chat_id, state, UsersPageRequest::First, None).await
 }
 
 pub async fn admin_show_service_cmd(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
@@ -550,7 +1775,7 @@ pub async fn try_process_waiting_invite(
     msg: &Message,
     state: &BotState,
     user_id: i64,
-) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<bool, AppError> {
     if !state.config.is_admin(user_id)
         && !msg.text().unwrap_or("").starts_with('/')
         && is_user_waiting_for_invite(state, user_id).await
@@ -0,0 +1,234 @@
+//! `/config export-users`: текущее содержимое `[access.users]` из telemt.toml файлом,
+//! чтобы сверить реальное состояние прокси без SSH. Секреты по умолчанию скрыты.
+//! `/config backups` и `/config rollback`: резервные копии конфига и откат к ним.
+
+use super::format::format_bytes;
+use super::shared::HandlerResult;
+use super::state::{BotState, is_admin_message, sender_user_id};
+use crate::bot::Bot;
+use crate::error::AppError;
+use crate::telemt_cfg::retain_users;
+use std::collections::HashSet;
+use teloxide::prelude::*;
+use teloxide::types::InputFile;
+
+const CONFIG_USAGE: &str = "Использование:
+/config export-users — секция [access.users] из конфига telemt (секреты скрыты)
+/config export-users --full — то же с полными секретами
+/config backups — резервные копии конфига telemt
+/config rollback <номер | имя> — вернуть конфиг из копии (без удалённых и заблокированных пользователей) и перезапустить telemt";
+
+/// Сколько символов секрета оставлять видимыми с каждого края.
+const VISIBLE_SECRET_CHARS: usize = 4;
+
+pub async fn cmd_config(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .skip(1)
+        .collect();
+    let full = match args.as_slice() {
+        ["export-users"] => false,
+        ["export-users", "--full"] => true,
+        ["backups"] => return show_backups(&bot, &msg, &state).await,
+        ["rollback", backup] => return rollback(&bot, &msg, &state, backup).await,
+        _ => {
+            bot.send_message(msg.chat.id, CONFIG_USAGE).await?;
+            return Ok(());
+        }
+    };
+
+    let users = state.telemt_cfg.read_users().await?;
+    let mut body = format!(
+        "# [access.users] из конфига telemt, {} ({})\n[access.users]\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        if full {
+            "секреты полностью"
+        } else {
+            "секреты скрыты"
+        }
+    );
+    for (name, secret) in &users {
+        let secret = if full {
+            secret.clone()
+        } else {
+            mask_secret(secret)
+        };
+        body.push_str(&format!("{} = {:?}\n", name, secret));
+    }
+
+    if full && let Some(admin_id) = sender_user_id(&msg) {
+        state
+            .audit
+            .record(
+                admin_id,
+                "config_export",
+                "telemt:access.users",
+                &format!("с секретами, пользователей: {}", users.len()),
+            )
+            .await;
+    }
+    tracing::info!(
+        users = users.len(),
+        full = full,
+        "Admin command /config export-users"
+    );
+
+    let file_name = format!(
+        "telemt-users-{}.toml",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let mut caption = format!("⚙️ Пользователи в конфиге telemt: {}", users.len());
+    if full {
+        caption.push_str("\n⚠️ Файл содержит секреты — удалите его из чата после просмотра.");
+    }
+    bot.send_document(
+        msg.chat.id,
+        InputFile::memory(body.into_bytes()).file_name(file_name),
+    )
+    .caption(caption)
+    .await?;
+    Ok(())
+}
+
+async fn show_backups(bot: &Bot, msg: &Message, state: &BotState) -> HandlerResult {
+    let backups = state.telemt_cfg.list_backups().await?;
+    if backups.is_empty() {
+        bot.send_message(msg.chat.id, "Резервных копий конфига telemt нет.")
+            .await?;
+        return Ok(());
+    }
+    let mut text = String::from("🗂 Резервные копии конфига telemt (сначала новые):");
+    for (index, backup) in backups.iter().enumerate() {
+        text.push_str(&format!(
+            "\n{}. {} — {}",
+            index + 1,
+            backup.name,
+            format_bytes(backup.size as i64)
+        ));
+    }
+    text.push_str("\n\nОткат: /config rollback <номер>");
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+async fn rollback(bot: &Bot, msg: &Message, state: &BotState, backup: &str) -> HandlerResult {
+    let Some(admin_id) = sender_user_id(msg) else {
+        return Ok(());
+    };
+    let backups = state.telemt_cfg.list_backups().await?;
+    // Номер из /config backups (1 — самая свежая копия) или имя файла.
+    let name = match backup.parse::<usize>() {
+        Ok(index) => index
+            .checked_sub(1)
+            .and_then(|index| backups.get(index))
+            .map(|backup| backup.name.clone()),
+        Err(_) => backups
+            .iter()
+            .find(|candidate| candidate.name == backup)
+            .map(|backup| backup.name.clone()),
+    };
+    let Some(name) = name else {
+        bot.send_message(
+            msg.chat.id,
+            "Резервная копия не найдена. Список: /config backups",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let content = state.telemt_cfg.read_backup(&name).await?;
+    // Копия могла сохраниться до удаления, бана, истечения срока или стирания
+    // пользователя: такие записи из неё выбрасываются, чтобы откат не вернул доступ.
+    let allowed = users_allowed_in_config(state).await?;
+    let known: HashSet<String> = state
+        .db
+        .list_known_telemt_usernames()
+        .await?
+        .into_iter()
+        .collect();
+    let (content, dropped) = retain_users(&content, |name| {
+        allowed.contains(name) || !(known.contains(name) || is_bot_username(name))
+    })?;
+    let reason = format!("откат конфига к {} от админа {}", name, admin_id);
+    state.cfg_writer.replace(content, reason).await?;
+    tracing::info!(backup = %name, dropped = dropped.len(), "telemt config rolled back by admin");
+
+    let restart_status = if state.cfg_writer.restart_deferred() {
+        "рестарт отложен"
+    } else {
+        "рестарт выполнен"
+    };
+    let mut details = format!("{}, {}", name, restart_status);
+    if !dropped.is_empty() {
+        details.push_str(&format!(", без пользователей: {}", dropped.join(" ")));
+    }
+    state
+        .audit
+        .record(admin_id, "config_rollback", "telemt:config", &details)
+        .await;
+
+    let mut text = format!("⏪ Конфиг telemt восстановлен из {}.", name);
+    if state.cfg_writer.restart_deferred() {
+        text.push_str(
+            "\n🕒 Рестарт отложен (окно объединения изменений или защита от частых \
+             рестартов) и выполнится автоматически. Сейчас: /service restart now",
+        );
+    } else {
+        text.push_str("\n✅ telemt перезапущен.");
+    }
+    if !dropped.is_empty() {
+        text.push_str(&format!(
+            "\n\nНе восстановлены (удалены, заблокированы или с истёкшим доступом): {}",
+            dropped.join(", ")
+        ));
+    }
+    text.push_str("\n\nСекреты в копии могли устареть — проверьте /sync.");
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Имена пользователей, которым сейчас положен доступ: одобренные, не приостановленные,
+/// без истёкшего срока и не заблокированные.
+async fn users_allowed_in_config(state: &BotState) -> Result<HashSet<String>, AppError> {
+    let expired_before = chrono::Utc::now().timestamp() - state.config.expiry.grace_secs();
+    let expired: HashSet<i64> = state
+        .db
+        .list_expired_users(expired_before)
+        .await?
+        .into_iter()
+        .map(|user| user.tg_user_id)
+        .collect();
+    let mut allowed = HashSet::new();
+    for user in state.db.list_users_expected_in_config().await? {
+        if expired.contains(&user.tg_user_id) || state.db.is_user_blocked(user.tg_user_id).await? {
+            continue;
+        }
+        if let Some(name) = user.telemt_username {
+            allowed.insert(name);
+        }
+    }
+    Ok(allowed)
+}
+
+/// Имя вида `tg_<id>` — так бот называет своих пользователей; без записи в БД
+/// (например, после `/purge`) такое имя в копии не восстанавливается.
+fn is_bot_username(name: &str) -> bool {
+    name.strip_prefix("tg_")
+        .is_some_and(|id| !id.is_empty() && id.parse::<i64>().is_ok())
+}
+
+/// `0123…cdef`: по краям секрета видно, что он сменился после ротации.
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= VISIBLE_SECRET_CHARS * 2 {
+        return "…".to_string();
+    }
+    let head: String = chars[..VISIBLE_SECRET_CHARS].iter().collect();
+    let tail: String = chars[chars.len() - VISIBLE_SECRET_CHARS..].iter().collect();
+    format!("{}…{}", head, tail)
+}
@@ -0,0 +1,384 @@
+//! Предпросмотр изменений конфига telemt (`preview_config_changes`): одобрение, удаление
+//! и перевыпуск секрета — командами `/approve`, `/delete`, `/rotate <id>`, кнопками в
+//! чате и из Mini App — сначала показывают diff `[access.users]` со скрытыми секретами,
+//! а запись и рестарт выполняются только по кнопке «Применить». Действие целиком
+//! закодировано в callback-данных, поэтому подтверждение переживает перезапуск бота.
+
+use super::config_export::mask_secret;
+use super::format::format_timestamp;
+use super::pending::claimed_by_other;
+use super::shared::{
+    HandlerResult, approve_pending_request, callback_message_target, perform_hard_ban,
+    perform_secret_rotation, require_admin_callback,
+};
+use super::state::{BotState, telemt_username};
+use crate::bot::Bot;
+use crate::error::AppError;
+use crate::telemt_cfg::UserMutation;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardMarkup, ParseMode};
+use teloxide::utils::html;
+
+/// Подставляется вместо секрета, который сгенерируется только при применении.
+const NEW_SECRET: &str = "<новый секрет>";
+/// Строк контекста вокруг изменений.
+const DIFF_CONTEXT: usize = 2;
+/// Больше в одно сообщение с `<pre>` не помещается.
+const MAX_DIFF_CHARS: usize = 3500;
+
+/// Изменение конфига, которое можно показать заранее.
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigAction {
+    Approve {
+        request_id: i64,
+        duration_secs: Option<i64>,
+    },
+    Delete {
+        tg_user_id: i64,
+    },
+    Rotate {
+        tg_user_id: i64,
+    },
+}
+
+impl ConfigAction {
+    fn callback_data(&self) -> String {
+        match self {
+            Self::Approve {
+                request_id,
+                duration_secs,
+            } => format!("approve:{}:{}", request_id, duration_secs.unwrap_or(0)),
+            Self::Delete { tg_user_id } => format!("delete:{}", tg_user_id),
+            Self::Rotate { tg_user_id } => format!("rotate:{}", tg_user_id),
+        }
+    }
+
+    fn parse(data: &str) -> Option<Self> {
+        let mut parts = data.split(':');
+        let action = match (parts.next()?, parts.next()?.parse::<i64>().ok()?) {
+            ("approve", request_id) => {
+                let seconds = parts.next()?.parse::<i64>().ok()?;
+                Self::Approve {
+                    request_id,
+                    duration_secs: (seconds > 0).then_some(seconds),
+                }
+            }
+            ("delete", tg_user_id) => Self::Delete { tg_user_id },
+            ("rotate", tg_user_id) => Self::Rotate { tg_user_id },
+            _ => return None,
+        };
+        parts.next().is_none().then_some(action)
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Self::Approve { request_id, .. } => format!("одобрение заявки #{}", request_id),
+            Self::Delete { tg_user_id } => format!("удаление пользователя {}", tg_user_id),
+            Self::Rotate { tg_user_id } => format!("новый секрет пользователя {}", tg_user_id),
+        }
+    }
+}
+
+/// Выполняет действие сразу или, с `preview_config_changes`, показывает diff конфига
+/// с кнопками подтверждения.
+pub async fn apply_or_preview(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    admin_id: i64,
+    action: ConfigAction,
+) -> HandlerResult {
+    if !state.config.preview_config_changes {
+        let text = execute_config_action(bot, state, admin_id, action).await?;
+        bot.send_message(chat_id, text).await?;
+        return Ok(());
+    }
+
+    let Some(mutations) = planned_mutations(state, action).await? else {
+        let text = match action {
+            ConfigAction::Approve { .. } => "Заявка не найдена или уже обработана".to_string(),
+            ConfigAction::Delete { tg_user_id } | ConfigAction::Rotate { tg_user_id } => format!(
+                "Пользователь {} не найден среди активных",
+                telemt_username(tg_user_id)
+            ),
+        };
+        bot.send_message(chat_id, text).await?;
+        return Ok(());
+    };
+    let (before, after) = state
+        .telemt_cfg
+        .preview_mutations(&mutations, |secret| {
+            if secret == NEW_SECRET {
+                secret.to_string()
+            } else {
+                mask_secret(secret)
+            }
+        })
+        .await?;
+    let diff = unified_diff(&before, &after);
+    let body = if diff.is_empty() {
+        "Конфиг telemt не изменится.".to_string()
+    } else {
+        format!(
+            "<pre>{}</pre>",
+            html::escape(&truncate_chars(&diff, MAX_DIFF_CHARS))
+        )
+    };
+    tracing::info!(admin_id = admin_id, action = ?action, "Config change preview shown");
+    bot.send_message(
+        chat_id,
+        format!(
+            "🔍 Предпросмотр: {}\n{}\nПрименить и перезапустить telemt?",
+            html::escape(&action.title()),
+            body
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .reply_markup(crate::bot::keyboards::config_preview_keyboard(
+        &action.callback_data(),
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Для путей, которые применяют действие сами (кнопки карточек, Mini App): с
+/// `preview_config_changes` показывает предпросмотр в `chat_id` и возвращает true,
+/// без него — false.
+pub async fn preview_if_enabled(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    admin_id: i64,
+    action: ConfigAction,
+) -> Result<bool, AppError> {
+    if !state.config.preview_config_changes {
+        return Ok(false);
+    }
+    apply_or_preview(bot, chat_id, state, admin_id, action).await?;
+    Ok(true)
+}
+
+/// Как [`preview_if_enabled`] для inline-кнопки: предпросмотр уходит в чат кнопки
+/// (или в личный чат админа), callback закрывается.
+pub async fn preview_from_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &BotState,
+    admin_id: i64,
+    action: ConfigAction,
+) -> Result<bool, AppError> {
+    if !state.config.preview_config_changes {
+        return Ok(false);
+    }
+    bot.answer_callback_query(q.id.clone())
+        .text("Показываю предпросмотр")
+        .await?;
+    let chat_id = callback_message_target(q)
+        .map(|(chat_id, _)| chat_id)
+        .unwrap_or(ChatId(admin_id));
+    preview_if_enabled(bot, chat_id, state, admin_id, action).await
+}
+
+pub async fn callback_config_preview(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let action = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("cfg_preview:"))
+        .and_then(ConfigAction::parse);
+
+    let text = match action {
+        Some(action) => {
+            if let ConfigAction::Approve { request_id, .. } = action
+                && let Some(holder) = claimed_by_other(&state, request_id, admin_id).await?
+            {
+                bot.answer_callback_query(q.id.clone())
+                    .text(format!("🙋 Заявку #{} разбирает {}", request_id, holder))
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+            bot.answer_callback_query(q.id.clone())
+                .text("Применяю…")
+                .await?;
+            execute_config_action(&bot, &state, admin_id, action).await?
+        }
+        None => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            "Изменение отменено.".to_string()
+        }
+    };
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(InlineKeyboardMarkup::default())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Выполняет действие с записью в журнал аудита и возвращает ответ для админа.
+pub async fn execute_config_action(
+    bot: &Bot,
+    state: &BotState,
+    admin_id: i64,
+    action: ConfigAction,
+) -> Result<String, AppError> {
+    match action {
+        ConfigAction::Approve {
+            request_id,
+            duration_secs,
+        } => {
+            let Some(approved) =
+                approve_pending_request(bot, state, admin_id, request_id, duration_secs).await?
+            else {
+                return Ok("Заявка не найдена или уже обработана".to_string());
+            };
+            let until = approved
+                .expires_at
+                .map(|expires_at| format!("\nДоступ до {}.", format_timestamp(expires_at)))
+                .unwrap_or_default();
+            Ok(format!(
+                "Одобрено. Ссылка отправлена пользователю.{}\n{}",
+                until, approved.link
+            ))
+        }
+        ConfigAction::Delete { tg_user_id } => {
+            let status_text = perform_hard_ban(state, tg_user_id).await?;
+            state
+                .audit
+                .record(admin_id, "delete", &format!("tg_user:{}", tg_user_id), "")
+                .await;
+            Ok(status_text)
+        }
+        ConfigAction::Rotate { tg_user_id } => {
+            let status_text = perform_secret_rotation(bot, state, tg_user_id).await?;
+            state
+                .audit
+                .record(admin_id, "rotate", &format!("tg_user:{}", tg_user_id), "")
+                .await;
+            Ok(status_text)
+        }
+    }
+}
+
+/// Мутации, которые выполнит действие; `None` — объект действия не найден.
+async fn planned_mutations(
+    state: &BotState,
+    action: ConfigAction,
+) -> Result<Option<Vec<UserMutation>>, AppError> {
+    let mutation = match action {
+        ConfigAction::Approve { request_id, .. } => {
+            let Some(request) = state.db.get_pending_by_id(request_id).await? else {
+                return Ok(None);
+            };
+            UserMutation::Upsert {
+                username: telemt_username(request.tg_user_id),
+                secret: NEW_SECRET.to_string(),
+            }
+        }
+        ConfigAction::Delete { tg_user_id } => {
+            // Как в `perform_hard_ban`: импортированные живут под прежним именем.
+            let username = match state.db.get_approved(tg_user_id).await? {
+                Some((telemt_user, _)) => telemt_user,
+                None => telemt_username(tg_user_id),
+            };
+            UserMutation::Remove { username }
+        }
+        ConfigAction::Rotate { tg_user_id } => {
+            if state
+                .db
+                .get_active_user_by_tg_user(tg_user_id)
+                .await?
+                .is_none()
+            {
+                return Ok(None);
+            }
+            UserMutation::Upsert {
+                username: telemt_username(tg_user_id),
+                secret: NEW_SECRET.to_string(),
+            }
+        }
+    };
+    Ok(Some(vec![mutation]))
+}
+
+/// Один hunk unified diff: общие начало и конец отбрасываются (изменения пользователей
+/// всегда в одной секции), середина сравнивается по наибольшей общей подпоследовательности.
+/// Пустая строка — текст не изменился.
+fn unified_diff(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let prefix = old
+        .iter()
+        .zip(&new)
+        .take_while(|(left, right)| left == right)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(left, right)| left == right)
+        .count();
+    if prefix == old.len() && prefix == new.len() {
+        return String::new();
+    }
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j] — длина общей подпоследовательности old_mid[i..] и new_mid[j..].
+    let mut lcs = vec![vec![0_usize; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let context_start = prefix.saturating_sub(DIFF_CONTEXT);
+    let context_after = suffix.min(DIFF_CONTEXT);
+    let mut lines = Vec::new();
+    for line in &old[context_start..prefix] {
+        lines.push(format!(" {}", line));
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            lines.push(format!(" {}", old_mid[i]));
+            i += 1;
+            j += 1;
+        } else if i < old_mid.len() && (j == new_mid.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(format!("-{}", old_mid[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", new_mid[j]));
+            j += 1;
+        }
+    }
+    let old_end = old.len() - suffix;
+    for line in &old[old_end..old_end + context_after] {
+        lines.push(format!(" {}", line));
+    }
+
+    let old_count = prefix - context_start + old_mid.len() + context_after;
+    let new_count = prefix - context_start + new_mid.len() + context_after;
+    format!(
+        "@@ -{},{} +{},{} @@\n{}",
+        context_start + 1,
+        old_count,
+        context_start + 1,
+        new_count,
+        lines.join("\n")
+    )
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}\n…", &text[..index]),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,255 @@
+//! Одноразовые ссылки: при `[links] ephemeral = true` ссылка на прокси приходит
+//! скрытой, показывается по кнопке ровно один раз и через `ephemeral_ttl_secs`
+//! удаляется из чата. Сроки удаления хранятся в БД и переживают перезапуск бота.
+//! При `[links] protect_content = true` сообщения со ссылкой нельзя переслать или
+//! сохранить; админы получают их без защиты. При `auto_delete_minutes` открыто
+//! отправленная ссылка удаляется из чата, а пользователь получает кнопку «Отправить снова».
+//! При `[links] qr = true` на `/link` и «🔗 Моя ссылка» ссылка приходит фото с QR-кодом.
+
+use super::shared::{
+    HandlerResult, build_user_qr_png_bytes, pass_cooldown, send_user_link, user_lang,
+};
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::bot::cooldown::CooldownCommand;
+use crate::error::AppError;
+use crate::i18n::{t, tf};
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardMarkup, InputFile, MessageId};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Лимит Telegram на длину подписи к фото.
+const MAX_CAPTION_CHARS: usize = 1024;
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Отправляет пользователю сообщение со ссылкой — открыто или скрытым под кнопкой.
+pub async fn send_proxy_link(
+    bot: &Bot,
+    state: &BotState,
+    chat_id: ChatId,
+    text: String,
+    with_menu: bool,
+) -> Result<(), AppError> {
+    let links = &state.config.links;
+    let protect = links.protect_content && !state.config.is_admin(chat_id.0);
+    let lang = user_lang(state, chat_id.0).await?;
+    if !links.ephemeral {
+        let request = bot.send_message(chat_id, text).protect_content(protect);
+        let sent = if with_menu {
+            request
+                .reply_markup(crate::bot::keyboards::user_menu(lang))
+                .await?
+        } else {
+            request.await?
+        };
+        return schedule_auto_delete(state, chat_id, sent.id).await;
+    }
+
+    let delete_at = unix_now() + (links.ephemeral_unrevealed_hours * 60 * 60) as i64;
+    let token = state
+        .db
+        .create_link_reveal(chat_id.0, chat_id.0, &text, delete_at)
+        .await?;
+    let sent = bot
+        .send_message(
+            chat_id,
+            tf(lang, "link_hidden", &[("secs", &links.ephemeral_ttl_secs)]),
+        )
+        .protect_content(protect)
+        .reply_markup(crate::bot::keyboards::reveal_link_button(&token, lang))
+        .await?;
+    state.db.set_link_reveal_message(&token, sent.id.0).await?;
+    Ok(())
+}
+
+/// Ссылка по запросу пользователя (`/link`, «🔗 Моя ссылка»): при `[links] qr = true`
+/// приходит фото с QR-кодом и текстом ссылки в подписи. Скрытые ссылки (`ephemeral`)
+/// и слишком длинный для подписи текст отправляются как обычно, без QR.
+pub async fn send_proxy_link_with_qr(
+    bot: &Bot,
+    state: &BotState,
+    chat_id: ChatId,
+    text: String,
+    link: &str,
+) -> Result<(), AppError> {
+    let links = &state.config.links;
+    if !links.qr || links.ephemeral || text.chars().count() > MAX_CAPTION_CHARS {
+        return send_proxy_link(bot, state, chat_id, text, true).await;
+    }
+    let protect = links.protect_content && !state.config.is_admin(chat_id.0);
+    let lang = user_lang(state, chat_id.0).await?;
+    let qr_png = build_user_qr_png_bytes(link)?;
+    let sent = bot
+        .send_photo(
+            chat_id,
+            InputFile::memory(qr_png).file_name("telemt-proxy.png"),
+        )
+        .caption(text)
+        .protect_content(protect)
+        .reply_markup(crate::bot::keyboards::user_menu(lang))
+        .await?;
+    schedule_auto_delete(state, chat_id, sent.id).await
+}
+
+/// Ставит открыто отправленную ссылку в очередь автоудаления (`auto_delete_minutes`).
+async fn schedule_auto_delete(
+    state: &BotState,
+    chat_id: ChatId,
+    message_id: MessageId,
+) -> Result<(), AppError> {
+    if let Some(minutes) = state.config.links.auto_delete_minutes
+        && !state.config.is_admin(chat_id.0)
+    {
+        let delete_at = unix_now() + (minutes * 60) as i64;
+        state
+            .db
+            .schedule_link_auto_delete(chat_id.0, message_id.0, delete_at)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn callback_reveal_link(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let token = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("reveal:"))
+        .unwrap_or("");
+    let ttl = state.config.links.ephemeral_ttl_secs;
+    let revealed = state
+        .db
+        .take_link_reveal(token, q.from.id.0 as i64, unix_now() + ttl as i64)
+        .await?;
+    let target = q.message.as_ref().map(|msg| (msg.chat().id, msg.id()));
+    let lang = user_lang(&state, q.from.id.0 as i64).await?;
+
+    let Some(text) = revealed else {
+        bot.answer_callback_query(q.id.clone())
+            .text(t(lang, "link_already_revealed"))
+            .show_alert(true)
+            .await?;
+        if let Some((chat_id, message_id)) = target {
+            delete_link_message(&bot, chat_id, message_id).await;
+        }
+        return Ok(());
+    };
+
+    bot.answer_callback_query(q.id.clone()).await?;
+    tracing::info!(tg_user_id = q.from.id.0, "One-view proxy link revealed");
+    if let Some((chat_id, message_id)) = target {
+        bot.edit_message_text(
+            chat_id,
+            message_id,
+            format!(
+                "{}\n\n{}",
+                text,
+                tf(lang, "link_delete_in", &[("secs", &ttl)])
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::default())
+        .await?;
+    }
+    Ok(())
+}
+
+/// «Отправить снова» после автоудаления: заглушка удаляется, ссылка приходит заново.
+pub async fn callback_resend_link(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+    let target = q.message.as_ref().map(|msg| (msg.chat().id, msg.id()));
+    let Some((chat_id, message_id)) = target else {
+        return Ok(());
+    };
+    let user_id = q.from.id.0 as i64;
+    if !pass_cooldown(&bot, chat_id, &state, user_id, CooldownCommand::Link).await? {
+        return Ok(());
+    }
+    delete_link_message(&bot, chat_id, message_id).await;
+    send_user_link(&bot, chat_id, user_id, &state).await
+}
+
+/// Удаляет из чатов просмотренные ссылки по истечении срока и непросмотренные — через `ephemeral_unrevealed_hours`,
+/// а также открытые ссылки по `auto_delete_minutes`.
+pub fn spawn_ephemeral_sweeper(bot: Bot, state: BotState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match state.db.take_due_link_auto_deletes().await {
+                Ok(due) => {
+                    for message in due {
+                        if let Some(message_id) = message.message_id {
+                            auto_delete_link_message(
+                                &bot,
+                                &state,
+                                ChatId(message.chat_id),
+                                MessageId(message_id),
+                            )
+                            .await;
+                        }
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!(error = %error, "Не удалось выбрать ссылки для автоудаления")
+                }
+            }
+            match state.db.take_expired_link_messages().await {
+                Ok(expired) => {
+                    for message in expired {
+                        if let Some(message_id) = message.message_id {
+                            delete_link_message(
+                                &bot,
+                                ChatId(message.chat_id),
+                                MessageId(message_id),
+                            )
+                            .await;
+                        }
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!(error = %error, "Не удалось выбрать истёкшие одноразовые ссылки")
+                }
+            }
+            tokio::time::sleep(SWEEP_INTERVAL).await;
+        }
+    })
+}
+
+async fn auto_delete_link_message(
+    bot: &Bot,
+    state: &BotState,
+    chat_id: ChatId,
+    message_id: MessageId,
+) {
+    delete_link_message(bot, chat_id, message_id).await;
+    let lang = user_lang(state, chat_id.0)
+        .await
+        .unwrap_or(state.config.default_language);
+    if let Err(error) = bot
+        .send_message(chat_id, t(lang, "link_auto_deleted"))
+        .reply_markup(crate::bot::keyboards::resend_link_button(lang))
+        .await
+    {
+        tracing::debug!(
+            chat_id = chat_id.0,
+            error = %error,
+            "Не удалось отправить кнопку повторной отправки ссылки"
+        );
+    }
+}
+
+async fn delete_link_message(bot: &Bot, chat_id: ChatId, message_id: MessageId) {
+    if let Err(error) = bot.delete_message(chat_id, message_id).await {
+        // Пользователь мог удалить сообщение сам — это штатно.
+        tracing::debug!(
+            chat_id = chat_id.0,
+            error = %error,
+            "Не удалось удалить сообщение с одноразовой ссылкой"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Срок доступа пользователя (`/approve <id> 30d`): по его истечении пользователь
+//! удаляется из конфига telemt (одна запись и один рестарт на всех истёкших),
+//! помечается удалённым и получает уведомление. С `[expiry] grace_days` доступ
+//! отзывается не сразу: в льготный период пользователь раз в сутки получает
+//! предупреждение, а в списке админа отмечен как истекающий.
+
+use super::format::format_timestamp;
+use super::shared::{admin_destinations, user_lang};
+use super::state::{BotState, telemt_username};
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::error::AppError;
+use crate::i18n::{t, tf};
+use crate::telemt_cfg::UserMutation;
+use std::time::Duration;
+use teloxide::prelude::*;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Как часто пользователь в льготном периоде получает предупреждение.
+const GRACE_WARNING_INTERVAL_SECS: i64 = 86_400;
+
+pub fn spawn_expiry_worker(bot: Bot, state: BotState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(error) = revoke_expired(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось отозвать доступ с истёкшим сроком");
+            }
+            if let Err(error) = warn_grace_users(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось предупредить о льготном периоде");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    })
+}
+
+/// Срок вида `30d` (дни) или `12h` (часы) в секундах.
+pub fn parse_access_duration(value: &str) -> Option<i64> {
+    let (amount, unit_secs) = if let Some(days) = value.strip_suffix('d') {
+        (days, 86_400)
+    } else if let Some(hours) = value.strip_suffix('h') {
+        (hours, 3_600)
+    } else {
+        return None;
+    };
+    let amount: i64 = amount.parse().ok().filter(|amount| *amount > 0)?;
+    amount.checked_mul(unit_secs)
+}
+
+/// Предупреждает пользователей в льготном периоде не чаще раза в сутки.
+async fn warn_grace_users(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    let grace_secs = state.config.expiry.grace_secs();
+    if grace_secs == 0 {
+        return Ok(());
+    }
+    let now = chrono::Utc::now().timestamp();
+    for user in state.db.list_grace_users(now).await? {
+        let warned_recently = user
+            .expiry_warned_at
+            .is_some_and(|warned_at| now - warned_at < GRACE_WARNING_INTERVAL_SECS);
+        if warned_recently {
+            continue;
+        }
+        let text = tf(
+            user_lang(state, user.tg_user_id).await?,
+            "access_grace",
+            &[("date", &format_timestamp(user.expires_at + grace_secs))],
+        );
+        if let Err(error) = bot.send_message(ChatId(user.tg_user_id), text).await {
+            tracing::warn!(
+                tg_user_id = user.tg_user_id,
+                error = %error,
+                "Не удалось предупредить пользователя о льготном периоде"
+            );
+        }
+        // Отметка ставится и при ошибке доставки: заблокировавший бота пользователь
+        // иначе получал бы попытку каждую минуту.
+        state.db.mark_expiry_warned(user.tg_user_id, now).await?;
+    }
+    Ok(())
+}
+
+async fn revoke_expired(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    let grace_secs = state.config.expiry.grace_secs();
+    let expired = state
+        .db
+        .list_expired_users(chrono::Utc::now().timestamp() - grace_secs)
+        .await?;
+    if expired.is_empty() {
+        return Ok(());
+    }
+
+    let mutations = expired
+        .iter()
+        .filter_map(|user| user.telemt_username.clone())
+        .map(|username| UserMutation::Remove { username })
+        .collect::<Vec<_>>();
+    if !mutations.is_empty() {
+        state.cfg_writer.apply(mutations).await?;
+    }
+
+    let mut revoked = Vec::new();
+    for user in &expired {
+        if !state.db.deactivate_user(user.tg_user_id).await? {
+            continue;
+        }
+        crate::metrics::record_deletion("expiry");
+        revoked.push(user.tg_user_id);
+        state
+            .audit
+            .record_system(
+                "expire",
+                &format!("tg_user:{}", user.tg_user_id),
+                "срок доступа истёк",
+            )
+            .await;
+        tracing::info!(tg_user_id = user.tg_user_id, "User access expired");
+        let lang = user_lang(state, user.tg_user_id).await?;
+        if let Err(error) = bot
+            .send_message(ChatId(user.tg_user_id), t(lang, "access_expired"))
+            .await
+        {
+            tracing::warn!(
+                tg_user_id = user.tg_user_id,
+                error = %error,
+                "Не удалось уведомить пользователя об истечении доступа"
+            );
+        }
+    }
+
+    if revoked.is_empty() {
+        return Ok(());
+    }
+    let text = format!(
+        "⌛️ Истёк срок доступа ({}): {}",
+        revoked.len(),
+        revoked
+            .iter()
+            .map(|tg_user_id| telemt_username(*tg_user_id))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    for destination in admin_destinations(state, AdminTopic::Alerts) {
+        if let Err(error) = destination.send_message(bot, text.clone()).await {
+            tracing::warn!(
+                chat_id = destination.chat_id.0,
+                error = %error,
+                "Не удалось отправить админу сводку по истёкшему доступу"
+            );
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use crate::db::{InviteToken, RegistrationRequest};
+use crate::db::{
+    ArchivedRequest, InviteToken, Job, RegistrationRequest, TokenUsage, UserSearchHit, UserTraffic,
+};
 use chrono::{DateTime, Local, Utc};
 
 pub fn format_date(ts: i64) -> String {
@@ -7,6 +9,14 @@ pub fn format_date(ts: i64) -> String {
         .unwrap_or_else(|| "—".to_string())
 }
 
+/// Доля `part` от `total` в процентах; «—», если делить не на что.
+pub fn format_percent(part: i64, total: i64) -> String {
+    if total <= 0 {
+        return "—".to_string();
+    }
+    format!("{}%", part * 100 / total)
+}
+
 pub fn format_mode(auto_approve: bool) -> &'static str {
     if auto_approve {
         "АВТОПОДТВЕРЖДЕНИЕ 🚀"
@@ -25,6 +35,61 @@ pub fn format_timestamp(ts: i64) -> String {
         .unwrap_or_else(|| format!("Некорректный timestamp: {}", ts))
 }
 
+/// Объём в байтах для людей: «512 Б», «1.5 МБ», «12.3 ГБ».
+pub fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["Б", "КБ", "МБ", "ГБ", "ТБ"];
+    let mut value = bytes.max(0) as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes.max(0), UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Трафик пользователя: всего, к клиенту (↓) и от клиента (↑).
+pub fn format_traffic(traffic: &UserTraffic) -> String {
+    format!(
+        "{} (↓ {}, ↑ {})",
+        format_bytes(traffic.total()),
+        format_bytes(traffic.bytes_to_client),
+        format_bytes(traffic.bytes_from_client)
+    )
+}
+
+/// Русская форма существительного для числа: `plural_ru(2, "день", "дня", "дней")`.
+pub fn plural_ru<'a>(n: i64, one: &'a str, few: &'a str, many: &'a str) -> &'a str {
+    let n = n.abs();
+    match (n % 10, n % 100) {
+        (1, rem) if rem != 11 => one,
+        (2..=4, rem) if !(12..=14).contains(&rem) => few,
+        _ => many,
+    }
+}
+
+/// Длительность ожидания для людей: «3 дня», «5 часов», «10 минут».
+pub fn format_wait(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    let days = seconds / 86_400;
+    if days > 0 {
+        return format!("{} {}", days, plural_ru(days, "день", "дня", "дней"));
+    }
+    let hours = seconds / 3_600;
+    if hours > 0 {
+        return format!("{} {}", hours, plural_ru(hours, "час", "часа", "часов"));
+    }
+    let minutes = (seconds / 60).max(1);
+    format!(
+        "{} {}",
+        minutes,
+        plural_ru(minutes, "минуту", "минуты", "минут")
+    )
+}
+
 pub fn user_display_name(user: &RegistrationRequest) -> String {
     user.tg_display_name
         .clone()
@@ -47,18 +112,141 @@ pub fn render_invite_token_line(token: &InviteToken) -> String {
         .created_by
         .map(|v| v.to_string())
         .unwrap_or_else(|| "—".to_string());
-    format!(
-        "• {} | {} | до {} | usage {} | creator {} | создан {}",
+    let mut line = format!(
+        "• {}{} | {} | до {} | usage {} | creator {} | создан {}",
         token.token,
+        token
+            .label
+            .as_deref()
+            .map(|label| format!(" «{}»", label))
+            .unwrap_or_default(),
         mode,
         format_date(token.expires_at),
         usage,
         created_by,
         format_date(token.created_at)
+    );
+    if let Some(for_tg_user_id) = token.for_tg_user_id {
+        line.push_str(&format!(" | только для {}", for_tg_user_id));
+    }
+    if let Some(referrer) = token.referrer_tg_user_id {
+        line.push_str(&format!(" | реферал от {}", referrer));
+    }
+    if let Some(group) = token.group_name.as_deref() {
+        line.push_str(&format!(" | группа {}", group));
+    }
+    if let Some(valid_from) = token
+        .valid_from
+        .filter(|valid_from| *valid_from > Utc::now().timestamp())
+    {
+        line.push_str(&format!(" | с {}", format_date(valid_from)));
+    }
+    let plans = token.plan_names();
+    if !plans.is_empty() {
+        line.push_str(&format!(" | варианты {}", plans.join(", ")));
+    }
+    line
+}
+
+/// Карточка токена для `/token info`.
+pub fn render_invite_token_info(token: &InviteToken) -> String {
+    let status = if !token.is_active {
+        "отозван"
+    } else if token.expires_at <= Utc::now().timestamp() {
+        "истёк"
+    } else if token.max_usage.is_some_and(|max| token.usage_count >= max) {
+        "лимит исчерпан"
+    } else if token
+        .valid_from
+        .is_some_and(|valid_from| valid_from > Utc::now().timestamp())
+    {
+        "ещё не действует"
+    } else {
+        "активен"
+    };
+    let mut text = format!("🎟 Токен {}", token.token);
+    if let Some(label) = token.label.as_deref() {
+        text.push_str(&format!(" «{}»", label));
+    }
+    text.push_str(&format!(
+        "\nСтатус: {}\nРежим: {}\nСоздан: {} (админ {})\nДействует до: {}\nИспользований: {}/{}",
+        status,
+        format_mode(token.auto_approve),
+        format_date(token.created_at),
+        token
+            .created_by
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "—".to_string()),
+        format_date(token.expires_at),
+        token.usage_count,
+        token
+            .max_usage
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "∞".to_string())
+    ));
+    if let Some(valid_from) = token.valid_from {
+        text.push_str(&format!(
+            "\nНачало действия: {}",
+            format_timestamp(valid_from)
+        ));
+    }
+    if let Some(for_tg_user_id) = token.for_tg_user_id {
+        text.push_str(&format!("\nВыписан для: {}", for_tg_user_id));
+    }
+    if let Some(referrer) = token.referrer_tg_user_id {
+        text.push_str(&format!("\nПригласил: {}", referrer));
+    }
+    if let Some(group) = token.group_name.as_deref() {
+        text.push_str(&format!("\nГруппа: {}", group));
+    }
+    let plans = token.plan_names();
+    if !plans.is_empty() {
+        text.push_str(&format!("\nВарианты: {}", plans.join(", ")));
+    }
+    text
+}
+
+pub fn render_token_usage_line(usage: &TokenUsage) -> String {
+    format!(
+        "• {} | @{} | {} | {} | {}",
+        usage.tg_user_id,
+        usage.tg_username.as_deref().unwrap_or("—"),
+        usage.tg_display_name.as_deref().unwrap_or("—"),
+        usage.status.as_deref().unwrap_or("нет заявки"),
+        format_timestamp(usage.used_at)
     )
 }
 
-pub fn render_user_card_text(user: &RegistrationRequest) -> String {
+pub fn render_job_line(job: &Job) -> String {
+    let kind = super::jobs::JobKind::parse(&job.kind)
+        .map(super::jobs::JobKind::title)
+        .unwrap_or(job.kind.as_str());
+    let progress = job
+        .total
+        .map(|total| format!("{}/{}", job.processed, total))
+        .unwrap_or_else(|| job.processed.to_string());
+    let mut line = format!(
+        "• #{} | {} | {} | {} | создана {} | обновлена {}",
+        job.id,
+        kind,
+        job.status,
+        progress,
+        format_timestamp(job.created_at),
+        format_timestamp(job.updated_at)
+    );
+    if let Some(error) = job.error.as_deref() {
+        line.push_str(&format!(" | ошибка: {}", error));
+    }
+    line
+}
+
+/// Карточка пользователя; `grace_secs` — льготный период `[expiry] grace_days`.
+pub fn render_user_card_text(
+    user: &RegistrationRequest,
+    expires_at: Option<i64>,
+    grace_secs: i64,
+    traffic: Option<&UserTraffic>,
+) -> String {
     let username = user
         .tg_username
         .as_deref()
@@ -66,7 +254,7 @@ pub fn render_user_card_text(user: &RegistrationRequest) -> String {
         .unwrap_or_else(|| "—".to_string());
     let telemt = user.telemt_username.as_deref().unwrap_or("—");
 
-    format!(
+    let mut text = format!(
         "👤 {}\n\n\
          🆔 {}\n\
          📱 {}\n\
@@ -79,7 +267,24 @@ pub fn render_user_card_text(user: &RegistrationRequest) -> String {
         user.status,
         telemt,
         format_timestamp(user.created_at),
-    )
+    );
+    match expires_at {
+        Some(expires_at) if expires_at <= Utc::now().timestamp() => text.push_str(&format!(
+            "\n⏳ истекает: срок закончился {}, доступ будет отозван {}",
+            format_timestamp(expires_at),
+            format_timestamp(expires_at + grace_secs)
+        )),
+        Some(expires_at) => text.push_str(&format!("\n⌛️ до {}", format_timestamp(expires_at))),
+        None => {}
+    }
+    if let Some(traffic) = traffic {
+        text.push_str(&format!(
+            "\n📶 {} на {}",
+            format_traffic(traffic),
+            format_timestamp(traffic.updated_at)
+        ));
+    }
+    text
 }
 
 pub fn render_user_proxy_for_forward(user: &RegistrationRequest, link: &str) -> String {
@@ -91,12 +296,54 @@ pub fn render_user_proxy_for_forward(user: &RegistrationRequest, link: &str) ->
     )
 }
 
-pub fn usage_guide_text() -> &'static str {
-    r#"Как подключиться к прокси:
+pub fn search_hit_title(hit: &UserSearchHit) -> String {
+    hit.tg_display_name
+        .clone()
+        .or_else(|| {
+            hit.tg_username
+                .as_ref()
+                .map(|username| format!("@{}", username))
+        })
+        .unwrap_or_else(|| format!("tg_{}", hit.tg_user_id))
+}
 
-1) Нажмите «🔗 Моя ссылка» — бот отправит вам ссылку.
-2) Нажмите на ссылку — Telegram автоматически предложит добавить прокси.
-3) Подтвердите добавление.
+pub fn render_search_hit_line(hit: &UserSearchHit) -> String {
+    let mut line = format!(
+        "• {} | @{} | id {} | {}",
+        search_hit_title(hit),
+        hit.tg_username.as_deref().unwrap_or("—"),
+        hit.tg_user_id,
+        hit.status
+    );
+    if let Some(note) = hit.note.as_deref() {
+        line.push_str(&format!(" | 📝 {}", note));
+    }
+    line
+}
 
-Если не получается, обратитесь к администратору."#
+pub fn render_archived_request_line(record: &ArchivedRequest) -> String {
+    let name = record
+        .tg_display_name
+        .clone()
+        .unwrap_or_else(|| format!("tg_{}", record.tg_user_id));
+    let mut line = format!(
+        "• #{} {} | @{} | id {} | {}\n  заявка: {}",
+        record.id,
+        name,
+        record.tg_username.as_deref().unwrap_or("—"),
+        record.tg_user_id,
+        record.status,
+        format_date(record.created_at),
+    );
+    if let Some(resolved_at) = record.resolved_at {
+        line.push_str(&format!(", решение: {}", format_date(resolved_at)));
+    }
+    line.push_str(&format!(", в архиве с {}", format_date(record.archived_at)));
+    if let Some(telemt_username) = record.telemt_username.as_deref() {
+        line.push_str(&format!("\n  telemt: {}", telemt_username));
+    }
+    if let Some(note) = record.note.as_deref() {
+        line.push_str(&format!("\n  📝 {}", note));
+    }
+    line
 }
@@ -0,0 +1,345 @@
+//! Группы пользователей (`trial`, `guests`, …) и расписание их отключения и
+//! включения. Все наступившие события применяются одной записью конфига telemt
+//! и одним рестартом; затронутые пользователи получают уведомление.
+
+use super::format::format_timestamp;
+use super::shared::{HandlerResult, user_lang};
+use super::state::{BotState, is_admin_message};
+use crate::bot::Bot;
+use crate::db::{GroupAction, GroupSchedule};
+use crate::error::AppError;
+use crate::i18n::t;
+use crate::telemt_cfg::UserMutation;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use std::time::Duration;
+use teloxide::prelude::*;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_GROUP_NAME_LEN: usize = 32;
+const GROUP_USAGE: &str = "Использование:
+/group — список групп и расписаний
+/group set <tg_user_id> <группа>
+/group unset <tg_user_id>
+/group schedule <группа> <disable|enable> <ГГГГ-ММ-ДД ЧЧ:ММ>
+/group cancel <id>";
+
+pub fn spawn_group_scheduler(bot: Bot, state: BotState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(error) = run_due_schedules(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось применить расписание групп");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    })
+}
+
+/// Изменение доступа одного пользователя по расписанию группы.
+struct SuspendChange {
+    tg_user_id: i64,
+    suspend: bool,
+}
+
+async fn run_due_schedules(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    let due = state.db.list_group_schedules(true).await?;
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    // События применяются по порядку: если для группы наступили и отключение,
+    // и включение, побеждает более позднее.
+    let mut changes: Vec<SuspendChange> = Vec::new();
+    let mut mutations = Vec::new();
+    for schedule in &due {
+        for (member, suspended) in state.db.list_group_members(&schedule.group_name).await? {
+            let currently_suspended = changes
+                .iter()
+                .rev()
+                .find(|change| change.tg_user_id == member.tg_user_id)
+                .map(|change| change.suspend)
+                .unwrap_or(suspended);
+            let (Some(username), Some(secret)) = (member.telemt_username, member.secret) else {
+                continue;
+            };
+            match schedule.action {
+                GroupAction::Disable if !currently_suspended => {
+                    mutations.push(UserMutation::Remove { username });
+                    changes.push(SuspendChange {
+                        tg_user_id: member.tg_user_id,
+                        suspend: true,
+                    });
+                }
+                GroupAction::Enable if currently_suspended => {
+                    mutations.push(UserMutation::Upsert { username, secret });
+                    changes.push(SuspendChange {
+                        tg_user_id: member.tg_user_id,
+                        suspend: false,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !mutations.is_empty() {
+        // Одна запись и один рестарт на все наступившие события.
+        state.cfg_writer.apply(mutations).await?;
+    }
+    for schedule in &due {
+        state.db.mark_group_schedule_done(schedule.id).await?;
+        tracing::info!(
+            schedule_id = schedule.id,
+            group = %schedule.group_name,
+            action = %schedule.action,
+            "Group schedule applied"
+        );
+    }
+
+    // Пользователю важно только итоговое состояние.
+    let mut notified: Vec<i64> = Vec::new();
+    for change in changes.iter().rev() {
+        if notified.contains(&change.tg_user_id) {
+            continue;
+        }
+        notified.push(change.tg_user_id);
+        state
+            .db
+            .set_user_suspended(change.tg_user_id, change.suspend)
+            .await?;
+        let lang = user_lang(state, change.tg_user_id).await?;
+        let text = if change.suspend {
+            t(lang, "group_suspended")
+        } else {
+            t(lang, "group_resumed")
+        };
+        if let Err(error) = bot.send_message(ChatId(change.tg_user_id), text).await {
+            tracing::warn!(
+                tg_user_id = change.tg_user_id,
+                error = %error,
+                "Не удалось уведомить пользователя об изменении доступа группы"
+            );
+        }
+    }
+
+    let summary = due
+        .iter()
+        .map(|schedule| format!("{} {}", schedule.action, schedule.group_name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let text = format!(
+        "🗓 Расписание групп выполнено: {}. Затронуто пользователей: {}.",
+        summary,
+        notified.len()
+    );
+    let mut admins: Vec<i64> = due
+        .iter()
+        .filter_map(|schedule| schedule.created_by)
+        .collect();
+    admins.sort_unstable();
+    admins.dedup();
+    for admin_id in admins {
+        if let Err(error) = bot.send_message(ChatId(admin_id), text.clone()).await {
+            tracing::warn!(
+                admin_id = admin_id,
+                error = %error,
+                "Не удалось отправить админу итог расписания групп"
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn normalize_group_name(value: &str) -> Option<String> {
+    let name = value.trim().to_lowercase();
+    let valid = !name.is_empty()
+        && name.len() <= MAX_GROUP_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(name)
+}
+
+pub fn parse_local_datetime(date: &str, time: &str) -> Option<i64> {
+    let naive =
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.timestamp())
+}
+
+fn render_schedule_line(schedule: &GroupSchedule) -> String {
+    let action = match schedule.action {
+        GroupAction::Disable => "⏸ отключить",
+        GroupAction::Enable => "▶️ включить",
+    };
+    format!(
+        "#{} {} «{}» — {}",
+        schedule.id,
+        action,
+        schedule.group_name,
+        format_timestamp(schedule.run_at)
+    )
+}
+
+pub async fn cmd_group(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let admin_id = msg.from.as_ref().map(|user| user.id.0 as i64);
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .skip(1)
+        .collect();
+
+    let reply = match args.as_slice() {
+        [] => render_groups_overview(&state).await?,
+        ["set", tg_user_id, group] => {
+            let (Ok(tg_user_id), Some(group)) =
+                (tg_user_id.parse::<i64>(), normalize_group_name(group))
+            else {
+                bot.send_message(msg.chat.id, GROUP_USAGE).await?;
+                return Ok(());
+            };
+            if state.db.set_user_group(tg_user_id, Some(&group)).await? {
+                tracing::info!(tg_user_id = tg_user_id, group = %group, "User group set");
+                state
+                    .audit
+                    .record(
+                        admin_id.unwrap_or_default(),
+                        "group_set",
+                        &format!("tg_user:{}", tg_user_id),
+                        &group,
+                    )
+                    .await;
+                format!(
+                    "🏷 Пользователь {} добавлен в группу «{}».",
+                    tg_user_id, group
+                )
+            } else {
+                "Пользователь не найден.".to_string()
+            }
+        }
+        ["unset", tg_user_id] => {
+            let Ok(tg_user_id) = tg_user_id.parse::<i64>() else {
+                bot.send_message(msg.chat.id, GROUP_USAGE).await?;
+                return Ok(());
+            };
+            if state.db.set_user_group(tg_user_id, None).await? {
+                resume_if_suspended(&state, tg_user_id).await?;
+                state
+                    .audit
+                    .record(
+                        admin_id.unwrap_or_default(),
+                        "group_unset",
+                        &format!("tg_user:{}", tg_user_id),
+                        "",
+                    )
+                    .await;
+                format!("Пользователь {} убран из группы.", tg_user_id)
+            } else {
+                "Пользователь не найден.".to_string()
+            }
+        }
+        ["schedule", group, action, date, time] => {
+            let action = match *action {
+                "disable" => Some(GroupAction::Disable),
+                "enable" => Some(GroupAction::Enable),
+                _ => None,
+            };
+            let (Some(group), Some(action), Some(run_at)) = (
+                normalize_group_name(group),
+                action,
+                parse_local_datetime(date, time),
+            ) else {
+                bot.send_message(msg.chat.id, GROUP_USAGE).await?;
+                return Ok(());
+            };
+            let id = state
+                .db
+                .add_group_schedule(&group, action, run_at, admin_id)
+                .await?;
+            tracing::info!(
+                schedule_id = id,
+                group = %group,
+                action = %action,
+                run_at = run_at,
+                "Group schedule added"
+            );
+            state
+                .audit
+                .record(
+                    admin_id.unwrap_or_default(),
+                    "group_schedule",
+                    &format!("group:{}", group),
+                    &format!("#{} {} {}", id, action, format_timestamp(run_at)),
+                )
+                .await;
+            let schedule = GroupSchedule {
+                id,
+                group_name: group,
+                action,
+                run_at,
+                created_by: admin_id,
+            };
+            format!("🗓 Запланировано: {}", render_schedule_line(&schedule))
+        }
+        ["cancel", id] => {
+            let Ok(id) = id.trim_start_matches('#').parse::<i64>() else {
+                bot.send_message(msg.chat.id, GROUP_USAGE).await?;
+                return Ok(());
+            };
+            if state.db.cancel_group_schedule(id).await? {
+                state
+                    .audit
+                    .record(
+                        admin_id.unwrap_or_default(),
+                        "group_schedule_cancel",
+                        &format!("schedule:{}", id),
+                        "",
+                    )
+                    .await;
+                format!("Расписание #{} отменено.", id)
+            } else {
+                "Расписание не найдено или уже выполнено.".to_string()
+            }
+        }
+        _ => GROUP_USAGE.to_string(),
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+/// Пользователь, убранный из отключённой группы, сразу получает доступ обратно.
+async fn resume_if_suspended(state: &BotState, tg_user_id: i64) -> Result<(), AppError> {
+    if !state.db.is_user_suspended(tg_user_id).await? {
+        return Ok(());
+    }
+    if let Some((username, secret)) = state.db.get_approved(tg_user_id).await? {
+        state.cfg_writer.upsert_user(&username, &secret).await?;
+    }
+    state.db.set_user_suspended(tg_user_id, false).await?;
+    Ok(())
+}
+
+async fn render_groups_overview(state: &BotState) -> Result<String, AppError> {
+    let groups = state.db.list_groups().await?;
+    let schedules = state.db.list_group_schedules(false).await?;
+    let mut text = String::from("🏷 Группы:");
+    if groups.is_empty() {
+        text.push_str("\nнет");
+    }
+    for (group, count) in &groups {
+        text.push_str(&format!("\n{} — {}", group, count));
+    }
+    text.push_str("\n\n🗓 Расписание:");
+    if schedules.is_empty() {
+        text.push_str("\nнет");
+    }
+    for schedule in &schedules {
+        text.push_str(&format!("\n{}", render_schedule_line(schedule)));
+    }
+    Ok(text)
+}
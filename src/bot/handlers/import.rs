@@ -0,0 +1,286 @@
+//! `/import` и `/bind`: перенос пользователей, заведённых в конфиге telemt до бота.
+//! Записи `tg_<id>` сразу получают настоящий tg_user_id, остальные — отрицательный
+//! id-заглушку; `/bind` привязывает такую запись к аккаунту Telegram и переименовывает
+//! её в конфиге в `tg_<id>` с тем же секретом, так что ссылка пользователя не меняется.
+//! При первом запуске (пустая БД) импорт выполняется автоматически.
+
+use super::shared::{HandlerResult, admin_destinations, send_user_link};
+use super::state::{BotState, is_admin_message, sender_user_id, telemt_username};
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::db::RequestStatus;
+use crate::error::AppError;
+use crate::telemt_cfg::UserMutation;
+use teloxide::prelude::*;
+
+const BIND_USAGE: &str = "Использование: /bind <имя в конфиге> <tg_user_id | @username> — \
+привязать импортированного пользователя к аккаунту Telegram";
+
+/// Сколько непривязанных записей показывать в отчёте.
+const MAX_LISTED: usize = 20;
+
+struct ImportSummary {
+    /// Имя в конфиге и выданный tg_user_id (отрицательный — заглушка).
+    imported: Vec<(String, i64)>,
+    /// Записи `tg_<id>`, у аккаунта которых уже есть заявка в БД: их разбирает `/sync`.
+    skipped: Vec<String>,
+}
+
+async fn import_config_users(state: &BotState) -> Result<ImportSummary, AppError> {
+    let mut summary = ImportSummary {
+        imported: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for (username, secret) in state.telemt_cfg.read_users().await? {
+        if state
+            .db
+            .find_request_by_telemt_username(&username)
+            .await?
+            .is_some()
+        {
+            continue;
+        }
+        let known_id = username
+            .strip_prefix("tg_")
+            .and_then(|id| id.parse::<i64>().ok())
+            .filter(|id| *id > 0);
+        if let Some(tg_user_id) = known_id
+            && state.db.get_request_by_tg_user(tg_user_id).await?.is_some()
+        {
+            summary.skipped.push(username);
+            continue;
+        }
+        let tg_user_id = state
+            .db
+            .import_config_user(known_id, &username, &secret)
+            .await?;
+        tracing::info!(
+            telemt_username = %username,
+            tg_user_id = tg_user_id,
+            "User imported from telemt config"
+        );
+        summary.imported.push((username, tg_user_id));
+    }
+    Ok(summary)
+}
+
+async fn render_import(state: &BotState, summary: &ImportSummary) -> Result<String, AppError> {
+    let mut text = if summary.imported.is_empty() {
+        "📥 Новых пользователей в конфиге telemt нет.".to_string()
+    } else {
+        format!(
+            "📥 Импортировано из конфига telemt: {}.",
+            summary.imported.len()
+        )
+    };
+    if !summary.skipped.is_empty() {
+        text.push_str(&format!(
+            "\n\nПропущено — у аккаунта уже есть заявка, см. /sync ({}):\n{}",
+            summary.skipped.len(),
+            summary.skipped.join(" ")
+        ));
+    }
+    let unbound = state.db.list_unbound_imports().await?;
+    if !unbound.is_empty() {
+        text.push_str(&format!("\n\nНе привязаны к Telegram ({}):", unbound.len()));
+        for user in unbound.iter().take(MAX_LISTED) {
+            if let Some(username) = &user.telemt_username {
+                text.push_str(&format!("\n/bind {} <tg_user_id | @username>", username));
+            }
+        }
+        if unbound.len() > MAX_LISTED {
+            text.push_str("\n…");
+        }
+    }
+    Ok(text)
+}
+
+pub async fn cmd_import(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let admin_id = sender_user_id(&msg).unwrap_or_default();
+    tracing::info!(admin_id = admin_id, "Admin command /import");
+    let summary = import_config_users(&state).await?;
+    if !summary.imported.is_empty() {
+        state
+            .audit
+            .record(
+                admin_id,
+                "import",
+                "telemt:access.users",
+                &format!("записей: {}", summary.imported.len()),
+            )
+            .await;
+    }
+    bot.send_message(msg.chat.id, render_import(&state, &summary).await?)
+        .await?;
+    Ok(())
+}
+
+/// Импорт при первом запуске: если в БД ещё нет ни одной заявки, существующие
+/// пользователи telemt заводятся автоматически, а админы получают список для привязки.
+pub async fn import_on_first_run(bot: &Bot, state: &BotState) {
+    let summary = match state.db.has_any_requests().await {
+        Ok(true) => return,
+        Ok(false) => import_config_users(state).await,
+        Err(error) => Err(error.into()),
+    };
+    let text = match summary {
+        Ok(summary) if summary.imported.is_empty() => return,
+        Ok(summary) => {
+            state
+                .audit
+                .record_system(
+                    "import",
+                    "telemt:access.users",
+                    &format!("при первом запуске, записей: {}", summary.imported.len()),
+                )
+                .await;
+            match render_import(state, &summary).await {
+                Ok(text) => format!("{}\n\n(первый запуск бота)", text),
+                Err(error) => {
+                    tracing::warn!(error = %error, "Не удалось составить отчёт об импорте");
+                    return;
+                }
+            }
+        }
+        Err(error) => {
+            tracing::warn!(error = %error, "Не удалось импортировать пользователей из конфига telemt");
+            return;
+        }
+    };
+    for destination in admin_destinations(state, AdminTopic::Alerts) {
+        if let Err(error) = destination.send_message(bot, text.clone()).await {
+            tracing::warn!(
+                chat_id = destination.chat_id.0,
+                error = %error,
+                "Не удалось отправить админу отчёт об импорте"
+            );
+        }
+    }
+}
+
+pub async fn cmd_bind(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let Some(admin_id) = sender_user_id(&msg) else {
+        return Ok(());
+    };
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .skip(1)
+        .collect();
+    let [name, target] = args.as_slice() else {
+        bot.send_message(msg.chat.id, BIND_USAGE).await?;
+        return Ok(());
+    };
+
+    let placeholder = state
+        .db
+        .find_request_by_telemt_username(name)
+        .await?
+        .filter(|user| user.tg_user_id < 0 && user.status == RequestStatus::Approved);
+    let Some((placeholder_id, secret)) =
+        placeholder.and_then(|user| user.secret.map(|secret| (user.tg_user_id, secret)))
+    else {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "{} не найден среди непривязанных импортированных пользователей.",
+                name
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+    let tg_user_id = match target.parse::<i64>() {
+        Ok(tg_user_id) if tg_user_id > 0 => Some(tg_user_id),
+        Ok(_) => None,
+        Err(_) => state.db.find_tg_user_id_by_username(target).await?,
+    };
+    let Some(tg_user_id) = tg_user_id else {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Аккаунт {} не найден. Укажите tg_user_id или @username пользователя, \
+                 который уже писал боту.",
+                target
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+    if state
+        .db
+        .get_active_user_by_tg_user(tg_user_id)
+        .await?
+        .is_some()
+    {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "У {} уже есть активный доступ — сначала удалите его через /delete.",
+                telemt_username(tg_user_id)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Переименование в конфиге одной записью: секрет, а значит и ссылка, не меняются.
+    let new_username = telemt_username(tg_user_id);
+    state
+        .cfg_writer
+        .apply(vec![
+            UserMutation::Remove {
+                username: name.to_string(),
+            },
+            UserMutation::Upsert {
+                username: new_username.clone(),
+                secret,
+            },
+        ])
+        .await?;
+    if !state
+        .db
+        .bind_imported_user(placeholder_id, tg_user_id, &new_username)
+        .await?
+    {
+        bot.send_message(
+            msg.chat.id,
+            "Конфиг обновлён, но привязать запись в БД не удалось — проверьте /sync.",
+        )
+        .await?;
+        return Ok(());
+    }
+    state
+        .audit
+        .record(
+            admin_id,
+            "bind",
+            &format!("tg_user:{}", tg_user_id),
+            &format!("импортированный {}", name),
+        )
+        .await;
+    tracing::info!(
+        admin_id = admin_id,
+        tg_user_id = tg_user_id,
+        "Imported user bound to Telegram account"
+    );
+
+    let mut text = format!("🔗 {} привязан к {}.", name, new_username);
+    if let Err(error) = send_user_link(&bot, ChatId(tg_user_id), tg_user_id, &state).await {
+        tracing::warn!(
+            tg_user_id = tg_user_id,
+            error = %error,
+            "Не удалось отправить ссылку привязанному пользователю"
+        );
+        text.push_str(" Отправить ссылку пользователю не удалось: он ещё не писал боту.");
+    }
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Inline-режим: администратор ищет пользователей прямо из поля ввода
+//! (`@бот запрос`). Для остальных запросы игнорируются.
+
+use super::format::{render_search_hit_line, search_hit_title};
+use super::shared::HandlerResult;
+use super::state::BotState;
+use crate::bot::Bot;
+use teloxide::prelude::*;
+use teloxide::types::{
+    InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText,
+};
+
+pub async fn handle_inline_query(bot: Bot, query: InlineQuery, state: BotState) -> HandlerResult {
+    if !state.config.is_admin(query.from.id.0 as i64) {
+        bot.answer_inline_query(query.id, Vec::<InlineQueryResult>::new())
+            .cache_time(0)
+            .is_personal(true)
+            .await?;
+        return Ok(());
+    }
+
+    let text = query.query.trim();
+    let hits = if text.is_empty() {
+        Vec::new()
+    } else {
+        state
+            .db
+            .search_users(text, state.config.search_results_limit)
+            .await?
+    };
+    tracing::debug!(query = %text, hits = hits.len(), "Inline user search");
+
+    let results: Vec<InlineQueryResult> = hits
+        .iter()
+        .map(|hit| {
+            let description = format!(
+                "@{} · {} · {}",
+                hit.tg_username.as_deref().unwrap_or("—"),
+                hit.status,
+                hit.tg_user_id
+            );
+            InlineQueryResultArticle::new(
+                hit.id.to_string(),
+                search_hit_title(hit),
+                InputMessageContent::Text(InputMessageContentText::new(render_search_hit_line(
+                    hit,
+                ))),
+            )
+            .description(description)
+            .into()
+        })
+        .collect();
+
+    bot.answer_inline_query(query.id, results)
+        .cache_time(0)
+        .is_personal(true)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,233 @@
+//! Фоновый исполнитель персистентной очереди задач (`jobs`).
+//!
+//! Задача обрабатывает данные пакетами и после каждого пакета сохраняет
+//! контрольную точку, поэтому после падения или деплоя продолжает работу
+//! с места остановки, а не начинает заново.
+
+use super::ephemeral::send_proxy_link;
+use super::shared::{render_user_link_message, user_lang};
+use super::state::{BotState, telemt_username};
+use crate::bot::Bot;
+use crate::db::{Job, JobStatus};
+use crate::i18n::t;
+use crate::link::{build_proxy_link, generate_user_secret};
+use crate::telemt_cfg::UserMutation;
+use std::time::Duration;
+use teloxide::prelude::*;
+
+/// Виды фоновых задач.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    /// Массовая ротация секретов всех активных пользователей.
+    RotateSecrets,
+}
+
+impl JobKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RotateSecrets => "rotate_secrets",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "rotate_secrets" => Some(Self::RotateSecrets),
+            _ => None,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::RotateSecrets => "Ротация секретов",
+        }
+    }
+}
+
+enum JobOutcome {
+    Done,
+    Cancelled,
+}
+
+/// Запускает фоновый цикл обработки очереди задач.
+///
+/// Задачу можно прервать через `abort()`: незавершённая задача останется в
+/// статусе `running` и продолжится с последней контрольной точки.
+pub fn spawn_job_worker(bot: Bot, state: BotState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let poll_interval = Duration::from_secs(state.config.jobs.poll_interval_secs.max(1));
+        tracing::info!(
+            poll_interval_secs = poll_interval.as_secs(),
+            "Job worker started"
+        );
+        loop {
+            match state.db.claim_next_job().await {
+                Ok(Some(job)) => run_job(&bot, &state, job).await,
+                Ok(None) => {
+                    tokio::select! {
+                        _ = state.job_notify.notified() => {}
+                        _ = tokio::time::sleep(poll_interval) => {}
+                    }
+                }
+                Err(error) => {
+                    tracing::error!(error = %error, "Не удалось получить задачу из очереди");
+                    tokio::time::sleep(poll_interval).await;
+                }
+            }
+        }
+    })
+}
+
+async fn run_job(bot: &Bot, state: &BotState, job: Job) {
+    tracing::info!(
+        job_id = job.id,
+        kind = %job.kind,
+        cursor = job.cursor,
+        processed = job.processed,
+        "Running job"
+    );
+
+    let result = match JobKind::parse(&job.kind) {
+        Some(JobKind::RotateSecrets) => run_rotate_secrets(bot, state, &job).await,
+        None => Err(anyhow::anyhow!("Неизвестный тип задачи: {}", job.kind)),
+    };
+
+    let (status, error, summary) = match result {
+        Ok(JobOutcome::Done) => (JobStatus::Done, None, "завершена"),
+        Ok(JobOutcome::Cancelled) => {
+            tracing::info!(job_id = job.id, "Job cancelled");
+            return;
+        }
+        Err(error) => {
+            tracing::error!(job_id = job.id, error = %error, "Job failed");
+            (
+                JobStatus::Failed,
+                Some(error.to_string()),
+                "завершилась с ошибкой",
+            )
+        }
+    };
+
+    if let Err(db_error) = state.db.finish_job(job.id, status, error.as_deref()).await {
+        tracing::error!(job_id = job.id, error = %db_error, "Не удалось завершить задачу");
+        return;
+    }
+
+    if let Some(admin_id) = job.created_by {
+        let processed = state
+            .db
+            .get_job(job.id)
+            .await
+            .ok()
+            .flatten()
+            .map(|job| job.processed)
+            .unwrap_or(job.processed);
+        let mut text = format!(
+            "🧰 Задача #{} ({}) {}. Обработано: {}",
+            job.id,
+            JobKind::parse(&job.kind)
+                .map(JobKind::title)
+                .unwrap_or(job.kind.as_str()),
+            summary,
+            processed
+        );
+        if let Some(error) = error {
+            text.push_str(&format!("\nОшибка: {}", error));
+        }
+        if let Err(send_error) = bot.send_message(ChatId(admin_id), text).await {
+            tracing::warn!(
+                admin_id = admin_id,
+                error = %send_error,
+                "Не удалось уведомить админа о завершении задачи"
+            );
+        }
+    }
+}
+
+async fn is_job_cancelled(state: &BotState, job_id: i64) -> Result<bool, anyhow::Error> {
+    Ok(state
+        .db
+        .get_job(job_id)
+        .await?
+        .is_none_or(|job| job.status == JobStatus::Cancelled))
+}
+
+async fn run_rotate_secrets(
+    bot: &Bot,
+    state: &BotState,
+    job: &Job,
+) -> Result<JobOutcome, anyhow::Error> {
+    let batch_size = state.config.jobs.batch_size.max(1);
+    let total = match job.total {
+        Some(total) => total,
+        None => job.processed + state.db.count_active_users().await?,
+    };
+    let mut cursor = job.cursor;
+    let mut processed = job.processed;
+
+    loop {
+        if is_job_cancelled(state, job.id).await? {
+            return Ok(JobOutcome::Cancelled);
+        }
+
+        let batch = state.db.list_active_users_after(cursor, batch_size).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        let batch_cursor = last.tg_user_id;
+
+        let secrets: Vec<(i64, String)> = batch
+            .iter()
+            .map(|user| (user.tg_user_id, generate_user_secret()))
+            .collect();
+        let mutations = secrets
+            .iter()
+            .map(|(tg_user_id, secret)| UserMutation::Upsert {
+                username: telemt_username(*tg_user_id),
+                secret: secret.clone(),
+            })
+            .collect();
+        // Один пакет — одна запись конфига и один рестарт telemt.
+        state.cfg_writer.apply(mutations).await?;
+
+        let mut rotated: Vec<(i64, String)> = Vec::with_capacity(secrets.len());
+        for (tg_user_id, secret) in secrets {
+            if state.db.update_user_secret(tg_user_id, &secret).await? {
+                rotated.push((tg_user_id, secret));
+            }
+        }
+
+        let params = state.telemt_cfg.read_link_params().await?;
+        for (tg_user_id, secret) in &rotated {
+            let link = build_proxy_link(&params, secret)?;
+            let text = format!(
+                "{}\n\n{}",
+                t(user_lang(state, *tg_user_id).await?, "secret_rotated"),
+                render_user_link_message(state, *tg_user_id, &link).await?
+            );
+            if let Err(error) = send_proxy_link(bot, state, ChatId(*tg_user_id), text, false).await
+            {
+                tracing::warn!(
+                    tg_user_id = *tg_user_id,
+                    error = %error,
+                    "Не удалось отправить пользователю новую ссылку"
+                );
+            }
+        }
+
+        cursor = batch_cursor;
+        processed += batch.len() as i64;
+        state
+            .db
+            .checkpoint_job(job.id, cursor, processed, Some(total.max(processed)))
+            .await?;
+        tracing::info!(
+            job_id = job.id,
+            cursor = cursor,
+            processed = processed,
+            total = total,
+            "Job checkpoint saved"
+        );
+    }
+
+    Ok(JobOutcome::Done)
+}
@@ -0,0 +1,135 @@
+//! `/logs [N] [grep]`: хвост журнала telemt из journald. Вывод режется на сообщения
+//! по границам строк, чтобы уложиться в лимит Telegram.
+
+use super::shared::HandlerResult;
+use super::state::{BotState, is_admin_message};
+use crate::bot::Bot;
+use teloxide::prelude::*;
+
+const DEFAULT_LINES: usize = 50;
+const MAX_LINES: usize = 500;
+/// Сколько строк журнала просматривать при фильтре: совпадения берутся из этого окна.
+const GREP_SCAN_LINES: usize = 5000;
+/// Запас под заголовок и UTF-16 от лимита Telegram в 4096 символов.
+const MAX_CHUNK_CHARS: usize = 3800;
+/// Больше сообщений подряд не отправляем: старые строки отбрасываются.
+const MAX_CHUNKS: usize = 5;
+const LOGS_USAGE: &str = "Использование: /logs [N] [текст] — последние N строк журнала telemt \
+(по умолчанию 50, не больше 500); с текстом — только строки, содержащие его (без учёта регистра)";
+
+pub async fn cmd_logs(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .skip(1)
+        .collect();
+    let (lines, pattern) = match args.split_first() {
+        Some((first, rest)) if first.chars().all(|c| c.is_ascii_digit()) => {
+            match first.parse::<usize>().ok().filter(|lines| *lines > 0) {
+                Some(lines) => (lines.min(MAX_LINES), rest.join(" ")),
+                None => {
+                    bot.send_message(msg.chat.id, LOGS_USAGE).await?;
+                    return Ok(());
+                }
+            }
+        }
+        _ => (DEFAULT_LINES, args.join(" ")),
+    };
+    let pattern = pattern.to_lowercase();
+    tracing::info!(lines = lines, grep = %pattern, "Admin command /logs");
+
+    let scan = if pattern.is_empty() {
+        lines
+    } else {
+        GREP_SCAN_LINES
+    };
+    let result = state.service.logs(scan).await;
+    if !result.success {
+        let reason = if result.stderr.is_empty() {
+            format!("код {:?}", result.exit_code)
+        } else {
+            result.stderr
+        };
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "❌ Не удалось прочитать журнал {}: {}",
+                state.service.service_name(),
+                reason
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut matched: Vec<&str> = result
+        .stdout
+        .lines()
+        .filter(|line| pattern.is_empty() || line.to_lowercase().contains(&pattern))
+        .collect();
+    if matched.len() > lines {
+        matched.drain(..matched.len() - lines);
+    }
+    if matched.is_empty() {
+        let text = if pattern.is_empty() {
+            "Журнал пуст.".to_string()
+        } else {
+            format!(
+                "В последних {} строках журнала нет совпадений с «{}».",
+                GREP_SCAN_LINES, pattern
+            )
+        };
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+
+    let mut chunks = chunk_lines(&matched);
+    let dropped = chunks.len().saturating_sub(MAX_CHUNKS);
+    chunks.drain(..dropped);
+    let mut header = format!(
+        "📜 Журнал {}, строк: {}",
+        state.service.service_name(),
+        matched.len()
+    );
+    if !pattern.is_empty() {
+        header.push_str(&format!(", фильтр «{}»", pattern));
+    }
+    if dropped > 0 {
+        header.push_str(", начало обрезано — уменьшите N");
+    }
+    bot.send_message(msg.chat.id, header).await?;
+    for chunk in chunks {
+        bot.send_message(msg.chat.id, chunk).await?;
+    }
+    Ok(())
+}
+
+/// Склеивает строки в куски не длиннее `MAX_CHUNK_CHARS`; слишком длинная строка
+/// обрезается.
+fn chunk_lines(lines: &[&str]) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for line in lines {
+        let line: String = line.chars().take(MAX_CHUNK_CHARS).collect();
+        let line_chars = line.chars().count();
+        if !current.is_empty() && current_chars + 1 + line_chars > MAX_CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if !current.is_empty() {
+            current.push('\n');
+            current_chars += 1;
+        }
+        current.push_str(&line);
+        current_chars += line_chars;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
@@ -2,35 +2,102 @@ use super::commands::{
     admin_show_pending_cmd, admin_show_service_cmd, admin_show_stats_cmd, admin_show_users_cmd,
     cmd_help, try_process_waiting_invite,
 };
-use super::format::usage_guide_text;
-use super::shared::{send_user_link, HandlerResult};
-use super::state::{sender_user_id, BotState};
+use super::pending::try_take_reject_reason;
+use super::referral::send_referral_invite;
+use super::shared::{HandlerResult, pass_cooldown, send_user_link, user_lang};
+use super::state::{BotState, sender_user_id};
+use super::support::try_relay_support;
+use super::viewas::viewed_user;
+use crate::bot::Bot;
+use crate::bot::cooldown::CooldownCommand;
+use crate::bot::keyboards::UserMenuButton;
+use crate::i18n::t;
 use teloxide::prelude::*;
 
 pub async fn handle_menu_buttons(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if try_relay_support(&bot, &msg, &state).await? {
+        return Ok(());
+    }
     let Some(text) = msg.text() else {
         return Ok(());
     };
     let Some(user_id) = sender_user_id(&msg) else {
         return Ok(());
     };
-    let is_admin = state.config.is_admin(user_id);
+    // В группе админов бот отвечает только админам и только на кнопки меню и
+    // ожидаемый ввод: обычная переписка админов остаётся без ответа.
+    let in_admin_chat = state.config.admin_chat_id == Some(msg.chat.id.0);
+    if in_admin_chat && !state.config.can_review_requests(user_id) {
+        return Ok(());
+    }
+    // В режиме /viewas админ получает пользовательские ответы.
+    let viewed = if state.config.is_admin(user_id) {
+        viewed_user(&state, user_id).await
+    } else {
+        None
+    };
+    let is_admin = state.config.is_admin(user_id) && viewed.is_none();
 
+    if try_take_reject_reason(&bot, &msg, &state, user_id).await? {
+        return Ok(());
+    }
     if try_process_waiting_invite(&bot, &msg, &state, user_id).await? {
         return Ok(());
     }
 
-    match text {
-        crate::bot::keyboards::BTN_USER_LINK => {
-            send_user_link(&bot, msg.chat.id, user_id, &state).await?;
+    let target_id = viewed.unwrap_or(user_id);
+    match UserMenuButton::parse(text) {
+        Some(UserMenuButton::Link) => {
+            if pass_cooldown(&bot, msg.chat.id, &state, user_id, CooldownCommand::Link).await? {
+                send_user_link(&bot, msg.chat.id, target_id, &state).await?;
+            }
+            return Ok(());
+        }
+        Some(UserMenuButton::Guide) => {
+            let lang = user_lang(&state, target_id).await?;
+            bot.send_message(msg.chat.id, t(lang, "usage_guide"))
+                .reply_markup(crate::bot::keyboards::user_menu(lang))
+                .await?;
+            return Ok(());
+        }
+        Some(UserMenuButton::Support) => {
+            let lang = user_lang(&state, target_id).await?;
+            let key = if state.config.support.enabled {
+                "support_prompt"
+            } else {
+                "support_unavailable"
+            };
+            bot.send_message(msg.chat.id, t(lang, key))
+                .reply_markup(crate::bot::keyboards::user_menu(lang))
+                .await?;
+            return Ok(());
+        }
+        Some(UserMenuButton::Invite) => {
+            if viewed.is_some() {
+                bot.send_message(msg.chat.id, "В режиме /viewas приглашения не создаются.")
+                    .await?;
+            } else {
+                send_referral_invite(&bot, msg.chat.id, &state, user_id).await?;
+            }
+            return Ok(());
         }
-        crate::bot::keyboards::BTN_USER_GUIDE => {
-            bot.send_message(msg.chat.id, usage_guide_text())
-                .reply_markup(crate::bot::keyboards::user_menu())
+        Some(UserMenuButton::Language) => {
+            let lang = user_lang(&state, target_id).await?.next();
+            // В режиме /viewas язык пользователя не меняется, как и остальные данные.
+            if viewed.is_none() {
+                state.db.set_user_language(user_id, lang.code()).await?;
+            }
+            bot.send_message(msg.chat.id, t(lang, "language_switched"))
+                .reply_markup(crate::bot::keyboards::user_menu(lang))
                 .await?;
+            return Ok(());
         }
+        None => {}
+    }
+
+    match text {
         crate::bot::keyboards::BTN_ADMIN_PENDING if is_admin => {
-            admin_show_pending_cmd(&bot, msg.chat.id, &state).await?;
+            admin_show_pending_cmd(&bot, msg.chat.id, &state, user_id).await?;
         }
         crate::bot::keyboards::BTN_ADMIN_USERS if is_admin => {
             admin_show_users_cmd(&bot, msg.chat.id, &state).await?;
@@ -55,20 +122,21 @@ pub async fn handle_menu_buttons(bot: Bot, msg: Message, state: BotState) -> Han
         crate::bot::keyboards::BTN_ADMIN_HELP if is_admin => {
             cmd_help(bot, msg, state).await?;
         }
+        _ if in_admin_chat => {}
         _ => {
-            let reply_text = if is_admin {
-                "Не понял команду. Используйте кнопки админ-меню ниже."
-            } else {
-                "Не понял запрос. Используйте кнопки меню ниже."
-            };
-            let reply_markup = if is_admin {
-                crate::bot::keyboards::admin_menu()
-            } else {
-                crate::bot::keyboards::user_menu()
-            };
-            bot.send_message(msg.chat.id, reply_text)
-                .reply_markup(reply_markup)
+            if is_admin {
+                bot.send_message(
+                    msg.chat.id,
+                    "Не понял команду. Используйте кнопки админ-меню ниже.",
+                )
+                .reply_markup(crate::bot::keyboards::admin_menu())
                 .await?;
+            } else {
+                let lang = user_lang(&state, target_id).await?;
+                bot.send_message(msg.chat.id, t(lang, "unknown_request"))
+                    .reply_markup(crate::bot::keyboards::user_menu(lang))
+                    .await?;
+            }
         }
     }
     Ok(())
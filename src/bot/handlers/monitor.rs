@@ -0,0 +1,241 @@
+//! Мониторинг прокси (`[monitor]`): фоновая проверка TCP-подключением к порту telemt.
+//! После `failures_before_alert` неудач подряд админы получают уведомление с кнопками
+//! рестарта, после первой успешной проверки — сообщение о восстановлении и время простоя.
+//! С `auto_restart_attempts` перед уведомлением telemt сначала перезапускается сам.
+
+use super::format::{format_timestamp, format_wait};
+use super::shared::admin_destinations;
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::config::{AdminTopic, MonitorConfig};
+use chrono::Utc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
+
+pub fn spawn_proxy_monitor(bot: Bot, state: BotState) -> Option<tokio::task::JoinHandle<()>> {
+    let config = state.config.monitor.clone();
+    if !config.enabled {
+        tracing::info!("Proxy monitor disabled");
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let interval = Duration::from_secs(config.interval_secs.max(1));
+        let mut outage = Outage::default();
+        loop {
+            match probe_address(&state, &config).await {
+                Ok(address) => {
+                    let result = probe(&address, &config).await;
+                    check_result(&bot, &state, &config, &address, result, &mut outage).await;
+                }
+                Err(error) => {
+                    tracing::warn!(error = %error, "Не удалось определить адрес прокси для проверки");
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }))
+}
+
+/// Текущая серия неудачных проверок.
+#[derive(Default)]
+struct Outage {
+    failures: u32,
+    first_failure_at: i64,
+    alerted: bool,
+}
+
+async fn check_result(
+    bot: &Bot,
+    state: &BotState,
+    config: &MonitorConfig,
+    address: &str,
+    result: Result<(), String>,
+    outage: &mut Outage,
+) {
+    let error = match result {
+        Ok(()) => {
+            if outage.alerted {
+                let downtime = Utc::now().timestamp() - outage.first_failure_at;
+                tracing::info!(address = %address, downtime_secs = downtime, "Proxy is back up");
+                let text = format!(
+                    "✅ Прокси telemt снова доступен ({}).\nПростой: около {}.",
+                    address,
+                    format_wait(downtime)
+                );
+                notify(bot, state, text, None).await;
+            }
+            *outage = Outage::default();
+            return;
+        }
+        Err(error) => error,
+    };
+    outage.failures += 1;
+    if outage.failures == 1 {
+        outage.first_failure_at = Utc::now().timestamp();
+    }
+    if outage.alerted || outage.failures < config.failures_before_alert.max(1) {
+        return;
+    }
+    outage.alerted = true;
+    tracing::warn!(
+        address = %address,
+        failures = outage.failures,
+        error = %error,
+        "Прокси telemt недоступен"
+    );
+    let mut watchdog_summary = None;
+    if config.auto_restart_attempts > 0 {
+        match auto_restart(state, config, address).await {
+            Ok(attempt) => {
+                let downtime = Utc::now().timestamp() - outage.first_failure_at;
+                tracing::info!(
+                    address = %address,
+                    attempt = attempt,
+                    downtime_secs = downtime,
+                    "Proxy recovered after automatic restart"
+                );
+                let text = format!(
+                    "♻️ Прокси telemt был недоступен ({}) и поднялся после автоматического \
+                     рестарта (попытка {} из {}).\nПростой: около {}.",
+                    address,
+                    attempt,
+                    config.auto_restart_attempts,
+                    format_wait(downtime)
+                );
+                notify(bot, state, text, None).await;
+                *outage = Outage::default();
+                return;
+            }
+            Err(summary) => watchdog_summary = Some(summary),
+        }
+    }
+    let service_state = if state.service.is_active().await {
+        "активен"
+    } else {
+        "не активен"
+    };
+    let mut text = format!(
+        "🔴 Прокси telemt недоступен ({}) с {}: неудачных проверок подряд — {}.\n\
+         Последняя ошибка: {}\nСервис {}: {}.",
+        address,
+        format_timestamp(outage.first_failure_at),
+        outage.failures,
+        error,
+        state.service.service_name(),
+        service_state
+    );
+    if let Some(summary) = watchdog_summary {
+        text.push_str(&format!("\n{}", summary));
+    }
+    notify(
+        bot,
+        state,
+        text,
+        Some(crate::bot::keyboards::proxy_down_buttons()),
+    )
+    .await;
+}
+
+/// Автоматические рестарты с удвоением паузы между ними (`[monitor] auto_restart_*`).
+/// `Ok(n)` — прокси ответил после n-й попытки, `Err` — итог для уведомления админам.
+async fn auto_restart(
+    state: &BotState,
+    config: &MonitorConfig,
+    address: &str,
+) -> Result<u32, String> {
+    let attempts = config.auto_restart_attempts;
+    let mut backoff = Duration::from_secs(config.auto_restart_backoff_secs.max(1));
+    for attempt in 1..=attempts {
+        let reason = format!(
+            "прокси недоступен, автоматический рестарт {} из {}",
+            attempt, attempts
+        );
+        let result = match state.service.restart(&reason, false).await {
+            Ok(result) => result,
+            Err(denied) => {
+                state
+                    .audit
+                    .record_system(
+                        "service",
+                        &state.config.service_name,
+                        &format!(
+                            "restart: отклонён ({}), watchdog {}/{}",
+                            denied, attempt, attempts
+                        ),
+                    )
+                    .await;
+                return Err(format!("Автоматический рестарт остановлен: {}.", denied));
+            }
+        };
+        state
+            .audit
+            .record_system(
+                "service",
+                &state.config.service_name,
+                &format!(
+                    "restart: {}, watchdog {}/{}",
+                    result.status_label(),
+                    attempt,
+                    attempts
+                ),
+            )
+            .await;
+        tracing::warn!(
+            attempt = attempt,
+            attempts = attempts,
+            result = %result.status_label(),
+            "Автоматический рестарт telemt"
+        );
+        tokio::time::sleep(backoff).await;
+        if probe(address, config).await.is_ok() {
+            return Ok(attempt);
+        }
+        backoff = backoff.saturating_mul(2);
+    }
+    Err(format!(
+        "Автоматический рестарт не помог: попыток — {}.",
+        attempts
+    ))
+}
+
+/// `[monitor] address` или `127.0.0.1` с портом из конфига telemt (читается при
+/// каждой проверке: порт мог поменяться).
+async fn probe_address(state: &BotState, config: &MonitorConfig) -> Result<String, String> {
+    if let Some(address) = &config.address {
+        return Ok(address.clone());
+    }
+    let params = state
+        .telemt_cfg
+        .read_link_params()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(format!("127.0.0.1:{}", params.port))
+}
+
+async fn probe(address: &str, config: &MonitorConfig) -> Result<(), String> {
+    let timeout = Duration::from_secs(config.timeout_secs.max(1));
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err(format!("нет ответа за {} с", timeout.as_secs())),
+    }
+}
+
+async fn notify(bot: &Bot, state: &BotState, text: String, markup: Option<InlineKeyboardMarkup>) {
+    for destination in admin_destinations(state, AdminTopic::Alerts) {
+        let chat_id = destination.chat_id;
+        let request = destination.send_message(bot, text.clone());
+        let result = match markup.clone() {
+            Some(markup) => request.reply_markup(markup).await,
+            None => request.await,
+        };
+        if let Err(error) = result {
+            tracing::warn!(
+                chat_id = chat_id.0,
+                error = %error,
+                "Не удалось отправить админу уведомление о прокси"
+            );
+        }
+    }
+}
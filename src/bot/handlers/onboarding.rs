@@ -0,0 +1,80 @@
+//! Онбординг после одобрения (`[onboarding]`): короткая серия сообщений по
+//! расписанию — инструкция, советы при проблемах, просьба об отзыве.
+//! Расписание хранится в БД и переживает перезапуск бота.
+
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::error::AppError;
+use std::time::Duration;
+use teloxide::prelude::*;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Планирует серию для только что одобренного пользователя. Ошибка не мешает
+/// одобрению: она только логируется.
+pub async fn schedule_onboarding(state: &BotState, tg_user_id: i64) {
+    let onboarding = &state.config.onboarding;
+    if !onboarding.enabled || onboarding.steps.is_empty() {
+        return;
+    }
+    let now = chrono::Utc::now().timestamp();
+    let steps: Vec<(i64, i64)> = onboarding
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| (index as i64, now + step.day.max(0) * 86_400))
+        .collect();
+    if let Err(error) = state.db.schedule_onboarding(tg_user_id, &steps).await {
+        tracing::warn!(
+            tg_user_id = tg_user_id,
+            error = %error,
+            "Не удалось запланировать онбординг пользователя"
+        );
+    }
+}
+
+pub fn spawn_onboarding_drip(bot: Bot, state: BotState) -> Option<tokio::task::JoinHandle<()>> {
+    if !state.config.onboarding.enabled {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        loop {
+            if let Err(error) = send_due_messages(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось разослать сообщения онбординга");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }))
+}
+
+async fn send_due_messages(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    for message in state.db.take_due_onboarding_messages().await? {
+        let Some(step) = usize::try_from(message.step)
+            .ok()
+            .and_then(|index| state.config.onboarding.steps.get(index))
+        else {
+            continue;
+        };
+        // Пользователь мог быть удалён после одобрения — ему серия уже не нужна.
+        if state.db.get_approved(message.tg_user_id).await?.is_none() {
+            continue;
+        }
+        match bot
+            .send_message(ChatId(message.tg_user_id), step.text.clone())
+            .await
+        {
+            Ok(_) => tracing::info!(
+                tg_user_id = message.tg_user_id,
+                step = message.step,
+                "Onboarding message sent"
+            ),
+            Err(error) => tracing::warn!(
+                tg_user_id = message.tg_user_id,
+                step = message.step,
+                error = %error,
+                "Не удалось отправить сообщение онбординга"
+            ),
+        }
+    }
+    Ok(())
+}
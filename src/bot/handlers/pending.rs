@@ -0,0 +1,423 @@
+//! Список ожидающих заявок одним сообщением: карточки страницы с кнопками одобрения,
+//! отклонения и «взять в работу», листание `pending_page:<offset>`. Взятую заявку
+//! другие админы не могут одобрить или отклонить, пока захват не истечёт
+//! (`CLAIM_TTL_SECS`) или держатель его не снимет.
+//!
+//! Отклонение с кнопки сначала спрашивает причину: заявка закрепляется за админом,
+//! а его следующее сообщение уходит пользователю вместе с отказом.
+
+use super::config_preview::{ConfigAction, preview_from_callback};
+use super::format::format_timestamp;
+use super::shared::{
+    HandlerResult, approve_pending_request, callback_message_target, reject_pending_request,
+    render_duplicate_warnings, require_reviewer_callback,
+};
+use super::state::{BotState, RejectPrompt};
+use crate::bot::Bot;
+use crate::db::RequestClaim;
+use crate::error::AppError;
+use anyhow::anyhow;
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+
+/// Заявок на одной странице списка.
+const PAGE_SIZE: i64 = 5;
+
+/// Через сколько захват заявки перестаёт действовать.
+const CLAIM_TTL_SECS: i64 = 30 * 60;
+
+fn claim_stale_before() -> i64 {
+    chrono::Utc::now().timestamp() - CLAIM_TTL_SECS
+}
+
+fn claim_label(claim: &RequestClaim) -> String {
+    claim
+        .admin_name
+        .clone()
+        .unwrap_or_else(|| format!("админ {}", claim.admin_id))
+}
+
+/// Держатель заявки, если это не `admin_id`: такую заявку разбирает другой админ.
+pub async fn claimed_by_other(
+    state: &BotState,
+    request_id: i64,
+    admin_id: i64,
+) -> Result<Option<String>, AppError> {
+    let claim = state
+        .db
+        .get_request_claim(request_id, claim_stale_before())
+        .await?;
+    Ok(claim
+        .filter(|claim| claim.admin_id != admin_id)
+        .map(|claim| claim_label(&claim)))
+}
+
+/// Отвечает на нажатие отказом, если заявку держит другой админ. Возвращает true,
+/// если действие нужно прервать.
+pub async fn refuse_if_claimed_by_other(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &BotState,
+    request_id: i64,
+    admin_id: i64,
+) -> Result<bool, AppError> {
+    let Some(holder) = claimed_by_other(state, request_id, admin_id).await? else {
+        return Ok(false);
+    };
+    bot.answer_callback_query(q.id.clone())
+        .text(format!("🙋 Заявку #{} разбирает {}", request_id, holder))
+        .show_alert(true)
+        .await?;
+    Ok(true)
+}
+
+/// Показывает страницу ожидающих заявок: новым сообщением или правкой `message_id`.
+pub async fn admin_show_pending_page(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    admin_id: i64,
+    offset: i64,
+    message_id: Option<MessageId>,
+) -> HandlerResult {
+    let total = state.db.pending_summary().await?.count;
+    // После одобрения последней заявки страницы возвращаемся на предыдущую.
+    let offset = if offset >= total {
+        ((total - 1) / PAGE_SIZE * PAGE_SIZE).max(0)
+    } else {
+        offset
+    };
+    let requests = state.db.list_pending_requests(PAGE_SIZE, offset).await?;
+    if requests.is_empty() {
+        let text = "Новых заявок нет.";
+        if let Some(message_id) = message_id {
+            bot.edit_message_text(chat_id, message_id, text)
+                .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
+                .await?;
+        } else {
+            bot.send_message(chat_id, text)
+                .reply_markup(crate::bot::keyboards::admin_menu())
+                .await?;
+        }
+        return Ok(());
+    }
+
+    let stale_before = claim_stale_before();
+    let mut text = format!(
+        "📋 Ожидают рассмотрения: {} (стр. {}/{})",
+        total,
+        offset / PAGE_SIZE + 1,
+        (total + PAGE_SIZE - 1) / PAGE_SIZE
+    );
+    let mut cards = Vec::with_capacity(requests.len());
+    for req in &requests {
+        text.push_str(&format!(
+            "\n\n#{} · id {} · @{}\nИмя: {}\nВремя: {}",
+            req.id,
+            req.tg_user_id,
+            req.tg_username.as_deref().unwrap_or("—"),
+            req.tg_display_name.as_deref().unwrap_or("—"),
+            format_timestamp(req.created_at),
+        ));
+        let claim = state.db.get_request_claim(req.id, stale_before).await?;
+        if let Some(claim) = &claim {
+            text.push_str(&format!("\n🙋 В работе у {}", claim_label(claim)));
+        }
+        text.push_str(&render_duplicate_warnings(state, req).await?);
+        let claimed_by_me = claim.is_some_and(|claim| claim.admin_id == admin_id);
+        cards.push((req.id, claimed_by_me));
+    }
+
+    let keyboard = crate::bot::keyboards::pending_page_keyboard(&cards, offset, PAGE_SIZE, total);
+    if let Some(message_id) = message_id {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(keyboard)
+            .await?;
+    } else {
+        bot.send_message(chat_id, text)
+            .reply_markup(keyboard)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Листание списка: `pending_page:<offset>`.
+pub async fn callback_pending_page(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_reviewer_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let offset = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("pending_page:"))
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|offset| *offset >= 0)
+        .ok_or_else(|| anyhow!("Некорректная страница заявок"))?;
+    bot.answer_callback_query(q.id.clone()).await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        admin_show_pending_page(&bot, chat_id, &state, admin_id, offset, Some(message_id)).await?;
+    }
+    Ok(())
+}
+
+/// Действие с карточкой списка: `pending_act:<approve|reject|claim|release>:<id>:<offset>`.
+/// После действия страница перерисовывается на месте.
+pub async fn callback_pending_action(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_reviewer_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let payload = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("pending_act:"))
+        .unwrap_or("");
+    let mut parts = payload.split(':');
+    let action = parts.next().unwrap_or("");
+    let (Some(request_id), Some(offset)) = (
+        parts.next().and_then(|value| value.parse::<i64>().ok()),
+        parts.next().and_then(|value| value.parse::<i64>().ok()),
+    ) else {
+        return Err(anyhow!("Некорректный callback списка заявок").into());
+    };
+
+    let notice = match action {
+        "approve" => {
+            if refuse_if_claimed_by_other(&bot, &q, &state, request_id, admin_id).await? {
+                return Ok(());
+            }
+            let action = ConfigAction::Approve {
+                request_id,
+                duration_secs: None,
+            };
+            if preview_from_callback(&bot, &q, &state, admin_id, action).await? {
+                return Ok(());
+            }
+            match approve_pending_request(&bot, &state, admin_id, request_id, None).await? {
+                Some(_) => format!("✅ Заявка #{} одобрена", request_id),
+                None => "Заявка уже обработана или не найдена".to_string(),
+            }
+        }
+        "reject" => {
+            if !prompt_reject_reason(&bot, &q, &state, admin_id, request_id, false).await? {
+                return Ok(());
+            }
+            "Напишите причину отклонения".to_string()
+        }
+        "claim" => {
+            match state
+                .db
+                .claim_request(
+                    request_id,
+                    admin_id,
+                    Some(&q.from.full_name()),
+                    claim_stale_before(),
+                )
+                .await?
+            {
+                None => "Заявка уже обработана или не найдена".to_string(),
+                Some(claim) if claim.admin_id == admin_id => {
+                    tracing::info!(
+                        admin_id = admin_id,
+                        request_id = request_id,
+                        "Pending request claimed"
+                    );
+                    format!("🙋 Заявка #{} закреплена за вами", request_id)
+                }
+                Some(claim) => format!(
+                    "Заявку #{} уже разбирает {}",
+                    request_id,
+                    claim_label(&claim)
+                ),
+            }
+        }
+        "release" => {
+            state.db.release_request_claim(request_id, admin_id).await?;
+            format!("Заявка #{} снова свободна", request_id)
+        }
+        _ => return Err(anyhow!("Некорректный callback списка заявок").into()),
+    };
+    bot.answer_callback_query(q.id.clone()).text(notice).await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        admin_show_pending_page(&bot, chat_id, &state, admin_id, offset, Some(message_id)).await?;
+    }
+    Ok(())
+}
+
+/// Спрашивает у админа причину отклонения: заявка закрепляется за ним, вопрос
+/// заменяет карточку (`in_place`) или приходит отдельным сообщением. Возвращает false,
+/// если заявку разбирает другой админ или она уже обработана (нажатие уже отвечено).
+pub async fn prompt_reject_reason(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &BotState,
+    admin_id: i64,
+    request_id: i64,
+    in_place: bool,
+) -> Result<bool, AppError> {
+    let claim = state
+        .db
+        .claim_request(
+            request_id,
+            admin_id,
+            Some(&q.from.full_name()),
+            claim_stale_before(),
+        )
+        .await?;
+    let notice = match claim {
+        None => Some("Заявка уже обработана или не найдена".to_string()),
+        Some(claim) if claim.admin_id != admin_id => Some(format!(
+            "🙋 Заявку #{} разбирает {}",
+            request_id,
+            claim_label(&claim)
+        )),
+        Some(_) => None,
+    };
+    if let Some(notice) = notice {
+        bot.answer_callback_query(q.id.clone())
+            .text(notice)
+            .show_alert(true)
+            .await?;
+        return Ok(false);
+    }
+
+    let text = format!(
+        "❌ Отклонение заявки #{}. Напишите причину следующим сообщением — её получит \
+         пользователь — или нажмите «Без причины».",
+        request_id
+    );
+    let markup = crate::bot::keyboards::reject_reason_keyboard(request_id);
+    let prompt = match callback_message_target(q) {
+        Some((chat_id, message_id)) if in_place => {
+            bot.edit_message_text(chat_id, message_id, text)
+                .reply_markup(markup)
+                .await?;
+            Some((chat_id, message_id))
+        }
+        Some((chat_id, _)) => {
+            let sent = bot.send_message(chat_id, text).reply_markup(markup).await?;
+            Some((chat_id, sent.id))
+        }
+        None => None,
+    };
+    if let Some((chat_id, message_id)) = prompt {
+        state.awaiting_reject_reason.lock().await.insert(
+            admin_id,
+            RejectPrompt {
+                request_id,
+                chat_id,
+                message_id,
+            },
+        );
+    }
+    tracing::info!(
+        admin_id = admin_id,
+        request_id = request_id,
+        "Reject reason requested"
+    );
+    Ok(true)
+}
+
+/// Итог отклонения для сообщения с вопросом о причине.
+fn render_reject_outcome(request_id: i64, rejected: bool, reason: Option<&str>) -> String {
+    match (rejected, reason) {
+        (false, _) => "Заявка уже обработана или не найдена".to_string(),
+        (true, Some(reason)) => {
+            format!("❌ Заявка #{} отклонена. Причина: {}", request_id, reason)
+        }
+        (true, None) => format!("❌ Заявка #{} отклонена без причины", request_id),
+    }
+}
+
+/// `reject_reason:skip:<id>` — отклонить без причины, `reject_reason:cancel:<id>` —
+/// передумать: заявка остаётся ожидающей, захват снимается.
+pub async fn callback_reject_reason(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_reviewer_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let payload = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("reject_reason:"))
+        .unwrap_or("");
+    let (action, request_id) = payload
+        .split_once(':')
+        .and_then(|(action, id)| id.parse::<i64>().ok().map(|id| (action, id)))
+        .ok_or_else(|| anyhow!("Некорректный callback причины отклонения"))?;
+    {
+        let mut awaiting = state.awaiting_reject_reason.lock().await;
+        if awaiting
+            .get(&admin_id)
+            .is_some_and(|prompt| prompt.request_id == request_id)
+        {
+            awaiting.remove(&admin_id);
+        }
+    }
+
+    let text = match action {
+        "skip" => {
+            if refuse_if_claimed_by_other(&bot, &q, &state, request_id, admin_id).await? {
+                return Ok(());
+            }
+            let rejected = reject_pending_request(&bot, &state, admin_id, request_id, None)
+                .await?
+                .is_some();
+            bot.answer_callback_query(q.id.clone()).await?;
+            render_reject_outcome(request_id, rejected, None)
+        }
+        "cancel" => {
+            state.db.release_request_claim(request_id, admin_id).await?;
+            bot.answer_callback_query(q.id.clone()).await?;
+            format!(
+                "Отклонение заявки #{} отменено, она снова ожидает рассмотрения.",
+                request_id
+            )
+        }
+        _ => return Err(anyhow!("Некорректный callback причины отклонения").into()),
+    };
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Принимает сообщение админа как причину отклонения, если бот её ждёт. Возвращает
+/// true, если сообщение обработано и дальше его разбирать не нужно.
+pub async fn try_take_reject_reason(
+    bot: &Bot,
+    msg: &Message,
+    state: &BotState,
+    admin_id: i64,
+) -> Result<bool, AppError> {
+    let Some(reason) = msg.text().map(str::trim).filter(|text| !text.is_empty()) else {
+        return Ok(false);
+    };
+    if crate::bot::keyboards::is_admin_menu_button(reason) {
+        return Ok(false);
+    }
+    let Some(prompt) = state.awaiting_reject_reason.lock().await.remove(&admin_id) else {
+        return Ok(false);
+    };
+    if let Some(holder) = claimed_by_other(state, prompt.request_id, admin_id).await? {
+        bot.send_message(
+            msg.chat.id,
+            format!("🙋 Заявку #{} уже разбирает {}", prompt.request_id, holder),
+        )
+        .await?;
+        return Ok(true);
+    }
+    let rejected = reject_pending_request(bot, state, admin_id, prompt.request_id, Some(reason))
+        .await?
+        .is_some();
+    let text = render_reject_outcome(prompt.request_id, rejected, Some(reason));
+    if let Err(error) = bot
+        .edit_message_text(prompt.chat_id, prompt.message_id, text.clone())
+        .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
+        .await
+    {
+        tracing::debug!(error = %error, "Не удалось обновить запрос причины отклонения");
+    }
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(true)
+}
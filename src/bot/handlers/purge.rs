@@ -0,0 +1,104 @@
+//! `/purge`: полное стирание пользователя по запросу на удаление данных. В отличие
+//! от `/delete`, запись не помечается удалённой, а исчезает из всех таблиц вместе с
+//! секретом; в журнал аудита попадает только обезличенная запись.
+
+use super::shared::{HandlerResult, callback_message_target, require_admin_callback};
+use super::state::{BotState, is_admin_message, telemt_username};
+use crate::bot::Bot;
+use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
+
+const PURGE_USAGE: &str =
+    "Использование: /purge <tg_user_id> — безвозвратно стереть пользователя и все его данные";
+
+pub async fn cmd_purge(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let Some(tg_user_id) = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .nth(1)
+        .and_then(|value| value.parse::<i64>().ok())
+    else {
+        bot.send_message(msg.chat.id, PURGE_USAGE).await?;
+        return Ok(());
+    };
+    if state.config.is_admin(tg_user_id) {
+        bot.send_message(msg.chat.id, "Нельзя стереть администратора.")
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🔥 Пользователь {} будет отключён от прокси и стёрт из БД без возможности \
+             восстановления: заявки, секрет (в том числе из резервных копий конфига), \
+             заметки, архив, переписка с поддержкой, ответы опроса. Персональные токены будут отозваны.\nПодтвердите стирание.",
+            tg_user_id
+        ),
+    )
+    .reply_markup(crate::bot::keyboards::purge_confirm_keyboard(tg_user_id))
+    .await?;
+    Ok(())
+}
+
+pub async fn callback_purge(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let target = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("purge:"))
+        .and_then(|value| value.parse::<i64>().ok());
+
+    let text = match target {
+        Some(tg_user_id) => {
+            bot.answer_callback_query(q.id.clone())
+                .text("Стираю…")
+                .await?;
+            // Сначала отзыв доступа: если рестарт telemt не удался, данные остаются на месте.
+            let telemt_user = state
+                .db
+                .get_request_by_tg_user(tg_user_id)
+                .await?
+                .and_then(|request| request.telemt_username)
+                .unwrap_or_else(|| telemt_username(tg_user_id));
+            state.cfg_writer.remove_user(&telemt_user).await?;
+            let scrubbed = state.telemt_cfg.scrub_backups(&telemt_user).await?;
+            let deleted = state.db.purge_user(tg_user_id).await?;
+            crate::metrics::record_deletion("purge");
+            state
+                .audit
+                .record(
+                    admin_id,
+                    "purge",
+                    "tg_user:purged",
+                    &format!("удалено строк: {}", deleted),
+                )
+                .await;
+            tracing::info!(
+                deleted = deleted,
+                scrubbed_backups = scrubbed,
+                "User purged"
+            );
+            format!(
+                "🔥 Пользователь стёрт, удалено записей: {}, очищено резервных копий конфига: {}.",
+                deleted, scrubbed
+            )
+        }
+        None => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            "Стирание отменено.".to_string()
+        }
+    };
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(InlineKeyboardMarkup::default())
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Приглашения друзей (`[referrals]`): одобренный пользователь кнопкой «Пригласить
+//! друга» выписывает себе invite-токен с лимитами из конфига. Автор токена хранится
+//! в `invite_tokens.referrer_tg_user_id`, поэтому админы видят, кто кого пригласил.
+
+use super::shared::{HandlerResult, user_lang};
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::db::NewInviteToken;
+use crate::i18n::{t, tf};
+use crate::link::build_bot_start_link;
+use teloxide::prelude::*;
+
+pub async fn send_referral_invite(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    tg_user_id: i64,
+) -> HandlerResult {
+    let lang = user_lang(state, tg_user_id).await?;
+    let config = &state.config.referrals;
+    let reply = |text: String| {
+        bot.send_message(chat_id, text)
+            .reply_markup(crate::bot::keyboards::user_menu(lang))
+    };
+    let Some(bot_username) = state.bot_username.as_deref().filter(|_| config.enabled) else {
+        reply(t(lang, "referral_unavailable").to_string()).await?;
+        return Ok(());
+    };
+    if state.db.get_approved(tg_user_id).await?.is_none() {
+        reply(t(lang, "referral_not_approved").to_string()).await?;
+        return Ok(());
+    }
+    let issued = state.db.count_referral_tokens(tg_user_id).await?;
+    if issued >= config.max_tokens_per_user {
+        let max = config.max_tokens_per_user;
+        reply(tf(lang, "referral_limit", &[("max", &max)])).await?;
+        return Ok(());
+    }
+
+    let days = config.days.clamp(1, state.config.security.max_token_days);
+    let max_uses = config.max_uses.max(1);
+    let token = state
+        .db
+        .create_invite_token(NewInviteToken {
+            days,
+            auto_approve: config.auto_approve && state.config.security.allow_auto_approve_tokens,
+            max_usage: Some(max_uses),
+            created_by: None,
+            for_tg_user_id: None,
+            plans: &[],
+            label: None,
+            valid_from: None,
+            group_name: None,
+            referrer_tg_user_id: Some(tg_user_id),
+        })
+        .await?;
+    state
+        .audit
+        .record_system(
+            "referral_token_create",
+            &format!("token:{}", token.token),
+            &format!(
+                "referrer=tg_user:{} days={} uses={}",
+                tg_user_id, days, max_uses
+            ),
+        )
+        .await;
+    tracing::info!(tg_user_id = tg_user_id, token = %token.token, "Referral token created");
+
+    let link = build_bot_start_link(bot_username, &token.token);
+    let left = config.max_tokens_per_user - issued - 1;
+    reply(tf(
+        lang,
+        "referral_created",
+        &[
+            ("link", &link),
+            ("days", &days),
+            ("uses", &max_uses),
+            ("left", &left),
+        ],
+    ))
+    .await?;
+    Ok(())
+}
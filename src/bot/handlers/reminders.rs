@@ -0,0 +1,189 @@
+//! Напоминания админам о заявках, которые слишком долго ждут решения, и эскалация
+//! второй линии (`[escalation]`). Сообщения приходят с кнопками одобрения,
+//! чтобы не искать исходное уведомление. Здесь же — напоминание создателю токена
+//! о его скором истечении (`[reminders] token_expiry_hours`).
+
+use super::format::{format_timestamp, format_wait};
+use super::shared::{AdminDestination, admin_destinations};
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::db::{InviteToken, RegistrationRequest};
+use std::time::Duration;
+use teloxide::prelude::*;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+pub fn spawn_pending_reminders(bot: Bot, state: BotState) -> Option<tokio::task::JoinHandle<()>> {
+    if !state.config.reminders.enabled && !state.config.escalation.is_enabled() {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        loop {
+            if state.config.reminders.enabled
+                && let Err(error) = remind_stale_requests(&bot, &state).await
+            {
+                tracing::warn!(error = %error, "Не удалось разослать напоминания о заявках");
+            }
+            if state.config.reminders.enabled
+                && state.config.reminders.token_expiry_hours > 0
+                && let Err(error) = remind_expiring_tokens(&bot, &state).await
+            {
+                tracing::warn!(error = %error, "Не удалось напомнить об истекающих токенах");
+            }
+            if state.config.escalation.is_enabled()
+                && let Err(error) = escalate_stale_requests(&bot, &state).await
+            {
+                tracing::warn!(error = %error, "Не удалось эскалировать заявки");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }))
+}
+
+async fn remind_stale_requests(bot: &Bot, state: &BotState) -> Result<(), crate::error::AppError> {
+    let config = &state.config.reminders;
+    let now = chrono::Utc::now().timestamp();
+    let stale = state
+        .db
+        .list_stale_pending_requests(
+            now - config.pending_after_hours.max(1) * 3_600,
+            now - config.repeat_hours.max(1) * 3_600,
+        )
+        .await?;
+    for request in &stale {
+        let text = render_reminder(request, now);
+        let keyboard = crate::bot::keyboards::approve_reject_buttons(request.id);
+        for destination in admin_destinations(state, AdminTopic::Requests) {
+            if let Err(error) = destination
+                .send_message(bot, text.clone())
+                .reply_markup(keyboard.clone())
+                .await
+            {
+                tracing::warn!(
+                    chat_id = destination.chat_id.0,
+                    error = %error,
+                    "Не удалось отправить админу напоминание о заявке"
+                );
+            }
+        }
+        state.db.mark_request_reminded(request.id).await?;
+        tracing::info!(
+            request_id = request.id,
+            "Stale pending request reminder sent"
+        );
+    }
+    Ok(())
+}
+
+/// Создатель получает напоминание в личку; токены без создателя (из консоли) или
+/// от бывших админов — в группу админов.
+async fn remind_expiring_tokens(bot: &Bot, state: &BotState) -> Result<(), crate::error::AppError> {
+    let now = chrono::Utc::now().timestamp();
+    let tokens = state
+        .db
+        .list_expiring_invite_tokens(now + state.config.reminders.token_expiry_hours * 3_600)
+        .await?;
+    for token in &tokens {
+        let text = render_token_expiry(token, now);
+        let destinations = match token.created_by {
+            Some(admin_id) if state.config.admin_ids.contains(&admin_id) => {
+                vec![AdminDestination {
+                    chat_id: ChatId(admin_id),
+                    thread_id: None,
+                }]
+            }
+            _ => admin_destinations(state, AdminTopic::Alerts),
+        };
+        for destination in destinations {
+            if let Err(error) = destination.send_message(bot, text.clone()).await {
+                tracing::warn!(
+                    chat_id = destination.chat_id.0,
+                    error = %error,
+                    "Не удалось напомнить об истечении токена"
+                );
+            }
+        }
+        state.db.mark_token_expiry_notified(token.id).await?;
+        tracing::info!(token = %token.token, "Token expiry reminder sent");
+    }
+    Ok(())
+}
+
+async fn escalate_stale_requests(
+    bot: &Bot,
+    state: &BotState,
+) -> Result<(), crate::error::AppError> {
+    let escalation = &state.config.escalation;
+    let now = chrono::Utc::now().timestamp();
+    let stale = state
+        .db
+        .list_unescalated_pending_requests(now - escalation.after_hours * 3_600)
+        .await?;
+    let recipients = escalation.recipients();
+    for request in &stale {
+        let text = format!(
+            "🚨 Эскалация: основные админы не обработали заявку.\n\n{}",
+            render_reminder(request, now)
+        );
+        let keyboard = crate::bot::keyboards::approve_reject_buttons(request.id);
+        for chat_id in &recipients {
+            if let Err(error) = bot
+                .send_message(ChatId(*chat_id), text.clone())
+                .reply_markup(keyboard.clone())
+                .await
+            {
+                tracing::warn!(
+                    chat_id = *chat_id,
+                    error = %error,
+                    "Не удалось отправить эскалацию заявки"
+                );
+            }
+        }
+        state.db.mark_request_escalated(request.id).await?;
+        tracing::info!(
+            request_id = request.id,
+            "Pending request escalated to second line"
+        );
+    }
+    Ok(())
+}
+
+fn render_token_expiry(token: &InviteToken, now: i64) -> String {
+    let label = token
+        .label
+        .as_deref()
+        .map(|label| format!(" «{}»", label))
+        .unwrap_or_default();
+    format!(
+        "⌛ Токен {}{} истекает через {} ({}).\n\
+         Использований: {}/{}\n\
+         Продлить: /token edit {} --days N",
+        token.token,
+        label,
+        format_wait(token.expires_at - now),
+        format_timestamp(token.expires_at),
+        token.usage_count,
+        token
+            .max_usage
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "∞".to_string()),
+        token.token,
+    )
+}
+
+fn render_reminder(request: &RegistrationRequest, now: i64) -> String {
+    format!(
+        "⏰ Заявка #{} ждёт уже {}\n\
+         User ID: {}\n\
+         Username: @{}\n\
+         Имя: {}\n\
+         Время: {}",
+        request.id,
+        format_wait(now - request.created_at),
+        request.tg_user_id,
+        request.tg_username.as_deref().unwrap_or("—"),
+        request.tg_display_name.as_deref().unwrap_or("—"),
+        format_timestamp(request.created_at),
+    )
+}
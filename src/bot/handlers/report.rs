@@ -0,0 +1,221 @@
+//! Еженедельный отчёт администраторам (`[report]`): PNG-график регистраций по дням,
+//! итоги недели, самые активные токены и состояние telemt. Отправленные периоды
+//! хранятся в БД, поэтому перезапуск не дублирует отчёт.
+
+use super::format::format_date;
+use super::shared::{HandlerResult, admin_destinations};
+use super::state::{BotState, is_admin_message};
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::error::AppError;
+use chrono::{DateTime, Datelike, Days, Local, Timelike, Utc};
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use std::io::Cursor;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::InputFile;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(600);
+const REPORT_DAYS: u64 = 7;
+const TOP_TOKENS: i64 = 3;
+const CHART_WIDTH: u32 = 700;
+const CHART_HEIGHT: u32 = 360;
+const CHART_MARGIN: u32 = 30;
+const CHART_FILE_NAME: &str = "weekly-report.png";
+
+/// Отчёт за неделю: текст и заявки по дням для графика.
+struct WeeklyReport {
+    text: String,
+    per_day: [i64; REPORT_DAYS as usize],
+}
+
+pub fn spawn_weekly_report(bot: Bot, state: BotState) -> Option<tokio::task::JoinHandle<()>> {
+    if !state.config.report.enabled {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        loop {
+            if let Err(error) = send_report_if_due(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось отправить еженедельный отчёт");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }))
+}
+
+async fn send_report_if_due(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    let report = &state.config.report;
+    let now = Local::now();
+    if now.weekday().number_from_monday() != report.weekday || now.hour() < report.hour {
+        return Ok(());
+    }
+    let week = now.iso_week();
+    let period = format!("{}-W{:02}", week.year(), week.week());
+    if state.db.is_report_sent(&period).await? {
+        return Ok(());
+    }
+
+    let report = render_weekly_report(state).await?;
+    let chart = render_chart_png(&report.per_day)?;
+    let mut delivered = 0;
+    for destination in admin_destinations(state, AdminTopic::Alerts) {
+        let photo = InputFile::memory(chart.clone()).file_name(CHART_FILE_NAME);
+        if let Err(error) = destination.send_photo(bot, photo).await {
+            tracing::warn!(
+                chat_id = destination.chat_id.0,
+                error = %error,
+                "Не удалось отправить админу график еженедельного отчёта"
+            );
+        }
+        match destination.send_message(bot, report.text.clone()).await {
+            Ok(_) => delivered += 1,
+            Err(error) => tracing::warn!(
+                chat_id = destination.chat_id.0,
+                error = %error,
+                "Не удалось отправить админу еженедельный отчёт"
+            ),
+        }
+    }
+    // Период отмечается, только когда отчёт кто-то получил: иначе следующая
+    // проверка отправит его снова.
+    if delivered == 0 {
+        tracing::warn!(period = %period, "Еженедельный отчёт не доставлен ни одному админу");
+        return Ok(());
+    }
+    state.db.mark_report_sent(&period).await?;
+    tracing::info!(period = %period, delivered = delivered, "Weekly report sent");
+    Ok(())
+}
+
+pub async fn cmd_report(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    tracing::info!("Admin command /report");
+    let report = render_weekly_report(&state).await?;
+    let chart = render_chart_png(&report.per_day)?;
+    bot.send_photo(
+        msg.chat.id,
+        InputFile::memory(chart).file_name(CHART_FILE_NAME),
+    )
+    .await?;
+    bot.send_message(msg.chat.id, report.text).await?;
+    Ok(())
+}
+
+async fn render_weekly_report(state: &BotState) -> Result<WeeklyReport, AppError> {
+    let today = Local::now().date_naive();
+    let first_day = today
+        .checked_sub_days(Days::new(REPORT_DAYS - 1))
+        .unwrap_or(today);
+    let since = first_day
+        .and_hms_opt(0, 0, 0)
+        .and_then(|naive| naive.and_local_timezone(Local).earliest())
+        .map(|dt| dt.timestamp())
+        .unwrap_or_else(|| Utc::now().timestamp() - REPORT_DAYS as i64 * 86_400);
+
+    let created = state.db.list_request_created_since(since).await?;
+    let mut per_day = [0i64; REPORT_DAYS as usize];
+    for ts in created {
+        let Some(day) =
+            DateTime::<Utc>::from_timestamp(ts, 0).map(|dt| dt.with_timezone(&Local).date_naive())
+        else {
+            continue;
+        };
+        if let Some(slot) = usize::try_from((day - first_day).num_days())
+            .ok()
+            .and_then(|index| per_day.get_mut(index))
+        {
+            *slot += 1;
+        }
+    }
+
+    let mut text = format!(
+        "📈 Отчёт за неделю с {}\n\nНовые заявки по дням:",
+        format_date(since)
+    );
+    for (index, count) in per_day.iter().enumerate() {
+        let day = first_day
+            .checked_add_days(Days::new(index as u64))
+            .unwrap_or(first_day);
+        text.push_str(&format!("\n{} — {}", day.format("%d.%m"), count));
+    }
+
+    let period = state.db.period_stats(since).await?;
+    let stats = state.db.admin_stats().await?;
+    text.push_str(&format!(
+        "\n\nЗа неделю: заявок {}, одобрено {}, отклонено {}\nАктивных пользователей: {} (удалено за всё время: {})",
+        period.requests, period.approved, period.rejected, stats.approved, stats.deleted
+    ));
+
+    let tokens = state.db.top_invite_tokens(TOP_TOKENS).await?;
+    if !tokens.is_empty() {
+        text.push_str("\n\n🎟 Топ токенов:");
+        for (token, usage) in tokens {
+            text.push_str(&format!("\n{} — {}", token, usage));
+        }
+    }
+
+    let service_state = if state.service.is_active().await {
+        "активен"
+    } else {
+        "не активен"
+    };
+    text.push_str(&format!("\n\n⚙️ telemt сейчас: {}", service_state));
+    Ok(WeeklyReport { text, per_day })
+}
+
+/// Столбчатый график заявок по дням (слева направо — от старого дня к сегодняшнему).
+/// Подписей на картинке нет: даты и числа идут в тексте отчёта.
+fn render_chart_png(per_day: &[i64]) -> Result<Vec<u8>, AppError> {
+    let background = Rgb([255, 255, 255]);
+    let axis = Rgb([120, 120, 120]);
+    let grid = Rgb([225, 225, 225]);
+    let bar = Rgb([42, 171, 238]);
+
+    let mut image = RgbImage::from_pixel(CHART_WIDTH, CHART_HEIGHT, background);
+    let plot_height = CHART_HEIGHT - 2 * CHART_MARGIN;
+    let baseline = CHART_HEIGHT - CHART_MARGIN;
+    for step in 1..=4 {
+        let y = baseline - plot_height * step / 4;
+        fill_rect(
+            &mut image,
+            CHART_MARGIN,
+            y,
+            CHART_WIDTH - 2 * CHART_MARGIN,
+            1,
+            grid,
+        );
+    }
+
+    let max = per_day.iter().copied().max().unwrap_or(0).max(1) as u64;
+    let slot = (CHART_WIDTH - 2 * CHART_MARGIN) / per_day.len().max(1) as u32;
+    let bar_width = slot * 2 / 3;
+    for (index, count) in per_day.iter().enumerate() {
+        let height = ((*count).max(0) as u64 * plot_height as u64 / max) as u32;
+        let x = CHART_MARGIN + slot * index as u32 + (slot - bar_width) / 2;
+        fill_rect(&mut image, x, baseline - height, bar_width, height, bar);
+    }
+    fill_rect(
+        &mut image,
+        CHART_MARGIN,
+        baseline,
+        CHART_WIDTH - 2 * CHART_MARGIN,
+        2,
+        axis,
+    );
+
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgb8(image)
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(anyhow::Error::from)?;
+    Ok(bytes)
+}
+
+fn fill_rect(image: &mut RgbImage, x: u32, y: u32, width: u32, height: u32, color: Rgb<u8>) {
+    for px in x..(x + width).min(image.width()) {
+        for py in y..(y + height).min(image.height()) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Плановый перезапуск telemt с предупреждением пользователей.
+//!
+//! Бот заранее сообщает одобренным пользователям о перезапуске, ждёт,
+//! перезапускает сервис, дожидается его активного состояния и подтверждает
+//! восстановление. Одновременно может быть запланирован только один перезапуск.
+
+use super::shared::user_lang;
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::error::AppError;
+use crate::i18n::tf;
+use crate::service::RestartError;
+use std::time::Duration;
+use teloxide::prelude::*;
+
+/// Размер страницы при обходе одобренных пользователей.
+const USERS_PAGE: i64 = 100;
+
+/// Планирует перезапуск через `minutes` минут. Возвращает `false`, если
+/// перезапуск уже запланирован. Рассылка предупреждения идёт в фоновой задаче:
+/// блокировка `pending_restart` держится только на проверку и сохранение задачи.
+pub async fn schedule_restart_with_notice(
+    bot: &Bot,
+    state: &BotState,
+    minutes: u64,
+    admin_chat: ChatId,
+) -> Result<bool, AppError> {
+    let mut pending = state.pending_restart.lock().await;
+    if pending.as_ref().is_some_and(|handle| !handle.is_finished()) {
+        return Ok(false);
+    }
+
+    let task_bot = bot.clone();
+    let task_state = state.clone();
+    let handle = tokio::spawn(async move {
+        if let Err(error) = notify_and_restart(&task_bot, &task_state, minutes, admin_chat).await {
+            tracing::error!(error = %error, "Restart with notice failed");
+        }
+    });
+    *pending = Some(handle.abort_handle());
+    Ok(true)
+}
+
+async fn notify_and_restart(
+    bot: &Bot,
+    state: &BotState,
+    minutes: u64,
+    admin_chat: ChatId,
+) -> Result<(), AppError> {
+    let notified =
+        broadcast_to_users(bot, state, "restart_notice", &[("minutes", &minutes)]).await?;
+    tracing::info!(
+        minutes = minutes,
+        notified = notified,
+        "Restart with notice scheduled"
+    );
+    bot.send_message(
+        admin_chat,
+        format!(
+            "⏳ Перезапуск telemt через {} мин. Предупреждено пользователей: {}.\n\
+             Отмена: /service cancel",
+            minutes, notified
+        ),
+    )
+    .await?;
+
+    tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+    run_restart(bot, state, admin_chat).await
+}
+
+/// Отменяет запланированный перезапуск. Возвращает `false`, если отменять нечего.
+pub async fn cancel_scheduled_restart(bot: &Bot, state: &BotState) -> Result<bool, AppError> {
+    let Some(handle) = state.pending_restart.lock().await.take() else {
+        return Ok(false);
+    };
+    if handle.is_finished() {
+        return Ok(false);
+    }
+    handle.abort();
+    tracing::info!("Scheduled restart cancelled");
+    broadcast_to_users(bot, state, "restart_cancelled", &[]).await?;
+    Ok(true)
+}
+
+async fn run_restart(bot: &Bot, state: &BotState, admin_chat: ChatId) -> Result<(), AppError> {
+    // После истечения ожидания перезапуск уже нельзя отменить.
+    state.pending_restart.lock().await.take();
+    let restart = state
+        .service
+        .restart_checked("плановый рестарт с предупреждением", false)
+        .await;
+
+    let recovered = match restart {
+        Ok(_) => wait_until_active(state).await,
+        Err(RestartError::Denied(denied)) => {
+            bot.send_message(
+                admin_chat,
+                format!("🚦 Плановый перезапуск не выполнен: {}", denied),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(RestartError::Failed(error)) => {
+            tracing::error!(error = %error, "Scheduled restart failed");
+            false
+        }
+    };
+
+    if recovered {
+        broadcast_to_users(bot, state, "restart_recovered", &[]).await?;
+        bot.send_message(
+            admin_chat,
+            "✅ Плановый перезапуск выполнен, telemt активен.",
+        )
+        .await?;
+    } else {
+        bot.send_message(
+            admin_chat,
+            "❌ Плановый перезапуск: telemt не вернулся в состояние active. Проверьте /service status.",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn wait_until_active(state: &BotState) -> bool {
+    let timeout = Duration::from_secs(state.config.restart.recovery_timeout_secs);
+    let started = tokio::time::Instant::now();
+    loop {
+        if state.service.is_active().await {
+            return true;
+        }
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+/// Отправляет сообщение по ключу локализации всем одобренным пользователям, каждому
+/// на его языке. Возвращает число успешно доставленных сообщений.
+async fn broadcast_to_users(
+    bot: &Bot,
+    state: &BotState,
+    key: &str,
+    args: &[(&str, &(dyn std::fmt::Display + Sync))],
+) -> Result<usize, AppError> {
+    let mut delivered = 0;
+    let mut cursor = 0;
+    loop {
+        let users = state.db.list_active_users_after(cursor, USERS_PAGE).await?;
+        let Some(last) = users.last() else {
+            break;
+        };
+        cursor = last.tg_user_id;
+        for user in &users {
+            let text = tf(user_lang(state, user.tg_user_id).await?, key, args);
+            match bot.send_message(ChatId(user.tg_user_id), text).await {
+                Ok(_) => delivered += 1,
+                Err(error) => tracing::warn!(
+                    tg_user_id = user.tg_user_id,
+                    error = %error,
+                    "Не удалось отправить уведомление о перезапуске"
+                ),
+            }
+        }
+    }
+    Ok(delivered)
+}
@@ -1,17 +1,110 @@
-use super::format::{format_timestamp, user_display_name};
-use super::state::{sender_user_id, telemt_username, BotState};
+use super::allowlist::registration_allowed;
+use super::channel_gate::check_channel_membership;
+use super::ephemeral::{send_proxy_link, send_proxy_link_with_qr};
+use super::format::{
+    format_percent, format_timestamp, format_traffic, format_wait, user_display_name,
+};
+use super::onboarding::schedule_onboarding;
+use super::state::{BotState, sender_user_id, telemt_username};
+use super::survey::{render_survey_stats, schedule_survey};
+use super::token_guard::{locked_text, record_token_failure, token_entry_locked};
+use crate::bot::Bot;
+use crate::bot::cooldown::CooldownCommand;
+use crate::config::AdminTopic;
 use crate::db::{
-    ConsumedInviteToken, RegisterResult, RegistrationRequest, TokenConsumeError, TokenMode,
+    ConsumedInviteToken, DbError, RegisterResult, RegistrationRequest, RequestStatus,
+    TokenConsumeError, TokenMode, UserCursor, UsersPageRequest,
 };
-use crate::link::{build_proxy_link, generate_user_secret};
+use crate::error::AppError;
+use crate::i18n::{Lang, t, tf};
+use crate::link::{build_proxy_link, generate_user_secret, split_start_payload};
+use crate::service::ServiceResult;
 use anyhow::anyhow;
 use image::{DynamicImage, ImageFormat, Luma};
 use qrcode::QrCode;
+use std::future::Future;
 use std::io::Cursor;
+use std::pin::Pin;
+use std::time::Duration;
 use teloxide::prelude::*;
-use teloxide::types::{InlineKeyboardMarkup, InputFile};
+use teloxide::types::{InlineKeyboardMarkup, InputFile, MessageId, ThreadId};
+
+pub type HandlerResult = Result<(), AppError>;
 
-pub type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+type HandlerFuture = Pin<Box<dyn Future<Output = HandlerResult> + Send>>;
+
+/// Оборачивает обработчик сообщений: при ошибке отправляет в чат безопасный текст
+/// по классу ошибки и пробрасывает её дальше в общий обработчик ошибок диспетчера.
+pub fn reply_on_error<F, Fut>(
+    handler: F,
+) -> impl Fn(Bot, Message, BotState) -> HandlerFuture + Send + Sync + 'static
+where
+    F: Fn(Bot, Message, BotState) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = HandlerResult> + Send + 'static,
+{
+    move |bot: Bot, msg: Message, state: BotState| {
+        let handler = handler.clone();
+        Box::pin(async move {
+            let chat_id = msg.chat.id;
+            let result = handler(bot.clone(), msg, state).await;
+            if let Err(error) = &result {
+                tracing::warn!(
+                    chat_id = chat_id.0,
+                    class = error.class(),
+                    error = %error,
+                    "Message handler failed"
+                );
+                crate::metrics::record_handler_error(error.class());
+                if let Err(send_error) = bot.send_message(chat_id, error.user_message()).await {
+                    tracing::warn!(
+                        chat_id = chat_id.0,
+                        error = %send_error,
+                        "Не удалось отправить пользователю сообщение об ошибке"
+                    );
+                }
+            }
+            result
+        })
+    }
+}
+
+/// То же, что [`reply_on_error`], для callback-запросов: ошибка показывается alert-ом.
+pub fn answer_on_error<F, Fut>(
+    handler: F,
+) -> impl Fn(Bot, CallbackQuery, BotState) -> HandlerFuture + Send + Sync + 'static
+where
+    F: Fn(Bot, CallbackQuery, BotState) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = HandlerResult> + Send + 'static,
+{
+    move |bot: Bot, q: CallbackQuery, state: BotState| {
+        let handler = handler.clone();
+        Box::pin(async move {
+            let query_id = q.id.clone();
+            let result = handler(bot.clone(), q, state).await;
+            if let Err(error) = &result {
+                tracing::warn!(
+                    class = error.class(),
+                    error = %error,
+                    "Callback handler failed"
+                );
+                crate::metrics::record_handler_error(error.class());
+                // Запрос мог уже получить ответ до ошибки — тогда Telegram вернёт ошибку, это штатно.
+                if let Err(send_error) = bot
+                    .answer_callback_query(query_id)
+                    .text(error.user_message())
+                    .show_alert(true)
+                    .await
+                {
+                    tracing::debug!(
+                        error = %send_error,
+                        "Не удалось показать alert об ошибке callback"
+                    );
+                }
+            }
+            result
+        })
+    }
+}
 
 pub enum CreateTarget {
     UserId(i64),
@@ -66,39 +159,56 @@ pub fn parse_callback_request_id(data: &str, prefix: &str) -> Result<i64, anyhow
         .map_err(|_| anyhow!("Некорректный request_id"))
 }
 
-pub fn parse_callback_user_action(data: &str, prefix: &str) -> Result<(i64, i64), anyhow::Error> {
+pub fn parse_callback_user_action(
+    data: &str,
+    prefix: &str,
+) -> Result<(i64, UsersPageRequest), anyhow::Error> {
     let payload = data
         .strip_prefix(prefix)
         .ok_or_else(|| anyhow!("Некорректный callback payload"))?;
-    let mut parts = payload.split(':');
-    let tg_user_id = parts
-        .next()
-        .ok_or_else(|| anyhow!("Не указан tg_user_id"))?
+    let (tg_user_id, page) = payload
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Не указана страница списка"))?;
+    let tg_user_id = tg_user_id
         .parse::<i64>()
         .map_err(|_| anyhow!("Некорректный tg_user_id"))?;
-    let page = parts
-        .next()
-        .ok_or_else(|| anyhow!("Не указан номер страницы"))?
-        .parse::<i64>()
-        .map_err(|_| anyhow!("Некорректный номер страницы"))?;
-    Ok((tg_user_id, page.max(1)))
+    Ok((tg_user_id, parse_users_page_request(page)?))
 }
 
-pub fn parse_callback_page(data: &str, prefix: &str) -> Result<i64, anyhow::Error> {
-    data.strip_prefix(prefix)
-        .ok_or_else(|| anyhow!("Некорректный callback payload"))?
-        .parse::<i64>()
-        .map(|page| page.max(1))
-        .map_err(|_| anyhow!("Некорректный номер страницы"))
+pub fn parse_callback_page(data: &str, prefix: &str) -> Result<UsersPageRequest, anyhow::Error> {
+    let payload = data
+        .strip_prefix(prefix)
+        .ok_or_else(|| anyhow!("Некорректный callback payload"))?;
+    parse_users_page_request(payload)
 }
 
-pub fn callback_message_target(q: &CallbackQuery) -> Option<(ChatId, teloxide::types::MessageId)> {
-    q.message.as_ref().map(|msg| (msg.chat().id, msg.id()))
+/// Разбирает курсор страницы, закодированный [`crate::bot::keyboards::users_page_payload`].
+pub fn parse_users_page_request(payload: &str) -> Result<UsersPageRequest, anyhow::Error> {
+    let mut parts = payload.split(':');
+    let kind = parts.next().unwrap_or("");
+    if kind == "f" {
+        return Ok(UsersPageRequest::First);
+    }
+    let mut next_number = || {
+        parts
+            .next()
+            .and_then(|value| value.parse::<i64>().ok())
+            .ok_or_else(|| anyhow!("Некорректный курсор страницы"))
+    };
+    let cursor = UserCursor {
+        created_at: next_number()?,
+        id: next_number()?,
+    };
+    match kind {
+        "n" => Ok(UsersPageRequest::After(cursor)),
+        "p" => Ok(UsersPageRequest::Before(cursor)),
+        "a" => Ok(UsersPageRequest::From(cursor)),
+        _ => Err(anyhow!("Некорректный курсор страницы")),
+    }
 }
 
-pub fn build_bot_start_link(bot_username: &str, token: &str) -> String {
-    let normalized = bot_username.trim_start_matches('@');
-    format!("https://t.me/{}?start={}", normalized, token)
+pub fn callback_message_target(q: &CallbackQuery) -> Option<(ChatId, teloxide::types::MessageId)> {
+    q.message.as_ref().map(|msg| (msg.chat().id, msg.id()))
 }
 
 pub async fn mark_user_waiting_for_invite(state: &BotState, tg_user_id: i64) {
@@ -124,12 +234,15 @@ pub async fn notify_auto_approve(
     tg_username: Option<&str>,
     tg_display_name: Option<&str>,
     token: &ConsumedInviteToken,
+    plan: Option<&str>,
 ) {
-    let mode_label = match token.mode {
-        TokenMode::AutoApprove => "auto",
-        TokenMode::Manual => "manual",
+    let mode_label = match (&token.mode, token.for_tg_user_id.is_some()) {
+        (TokenMode::AutoApprove, false) => "auto",
+        (TokenMode::AutoApprove, true) => "auto, personal",
+        (TokenMode::Manual, false) => "manual",
+        (TokenMode::Manual, true) => "manual, personal",
     };
-    let text = format!(
+    let mut text = format!(
         "✅ Автоподключение по токену\n\
          User ID: {}\n\
          Username: @{}\n\
@@ -157,16 +270,25 @@ pub async fn notify_auto_approve(
             .map(|value| value.to_string())
             .unwrap_or_else(|| "—".to_string())
     );
-
-    for admin_id in &state.config.admin_ids {
-        if let Err(error) = bot.send_message(ChatId(*admin_id), text.clone()).await {
-            tracing::warn!(
-                admin_id = *admin_id,
-                error = %error,
-                "Не удалось отправить аудит автоподключения"
-            );
-        }
+    if let Some(label) = &token.label {
+        text.push_str(&format!("\nLabel: {}", label));
+    }
+    if let Some(referrer) = token.referrer_tg_user_id {
+        text.push_str(&format!("\nReferrer: {}", referrer));
+    }
+    if let Some(plan) = plan {
+        text.push_str(&format!("\nGroup: {}", plan));
     }
+
+    fan_out_to_admins(
+        bot,
+        state,
+        &text,
+        None,
+        "auto_approve",
+        &format!("tg_user:{}", tg_user_id),
+    )
+    .await;
 }
 
 pub async fn notify_admins(
@@ -186,23 +308,201 @@ pub async fn notify_admins(
         req.tg_display_name.as_deref().unwrap_or("—"),
         format_timestamp(req.created_at),
     );
+    let referrer = state
+        .db
+        .get_referrer(req.tg_user_id)
+        .await?
+        .map(|referrer| format!("\nПригласил: {}", referrer))
+        .unwrap_or_default();
+    let text = format!(
+        "{}{}{}",
+        text,
+        referrer,
+        render_duplicate_warnings(state, req).await?
+    );
 
     let kb = crate::bot::keyboards::approve_reject_buttons(req.id);
 
-    for admin_id in &state.config.admin_ids {
-        if let Err(e) = bot
-            .send_message(ChatId(*admin_id), text.clone())
-            .reply_markup(kb.clone())
-            .await
-        {
+    fan_out_to_admins(
+        bot,
+        state,
+        &text,
+        Some(kb),
+        "new_request",
+        &format!("tg_user:{}", req.tg_user_id),
+    )
+    .await;
+    Ok(())
+}
+
+/// Сколько ждать ответа Telegram на одно уведомление админу.
+const ADMIN_SEND_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Чат (и тема форума), куда доставляется уведомление админам.
+#[derive(Debug, Clone, Copy)]
+pub struct AdminDestination {
+    pub chat_id: ChatId,
+    pub thread_id: Option<ThreadId>,
+}
+
+impl AdminDestination {
+    pub fn send_message(
+        self,
+        bot: &Bot,
+        text: impl Into<String>,
+    ) -> <Bot as Requester>::SendMessage {
+        let request = bot.send_message(self.chat_id, text);
+        match self.thread_id {
+            Some(thread_id) => request.message_thread_id(thread_id),
+            None => request,
+        }
+    }
+
+    pub fn send_photo(self, bot: &Bot, photo: InputFile) -> <Bot as Requester>::SendPhoto {
+        let request = bot.send_photo(self.chat_id, photo);
+        match self.thread_id {
+            Some(thread_id) => request.message_thread_id(thread_id),
+            None => request,
+        }
+    }
+}
+
+/// Получатели уведомления: группа админов (в теме для `topic`, если она задана),
+/// а без `admin_chat_id` — каждый админ в личку.
+pub fn admin_destinations(state: &BotState, topic: AdminTopic) -> Vec<AdminDestination> {
+    match state.config.admin_chat_id {
+        Some(chat_id) => vec![AdminDestination {
+            chat_id: ChatId(chat_id),
+            thread_id: state
+                .config
+                .admin_chat_topics
+                .thread_id(topic)
+                .map(|id| ThreadId(MessageId(id))),
+        }],
+        None => state
+            .config
+            .admin_ids
+            .iter()
+            .map(|admin_id| AdminDestination {
+                chat_id: ChatId(*admin_id),
+                thread_id: None,
+            })
+            .collect(),
+    }
+}
+
+/// Рассылает уведомление всем админам (или в группу админов) параллельно: медленный
+/// или недоступный чат одного админа не задерживает остальных. Недоставленные
+/// уведомления учитываются в метриках и одной записью на рассылку попадают в журнал
+/// аудита (`admin_notify_failed`).
+async fn fan_out_to_admins(
+    bot: &Bot,
+    state: &BotState,
+    text: &str,
+    markup: Option<InlineKeyboardMarkup>,
+    kind: &str,
+    target: &str,
+) {
+    let destinations = admin_destinations(state, AdminTopic::Requests);
+    let sends = destinations.iter().map(|destination| {
+        let chat_id = destination.chat_id;
+        let mut request = destination.send_message(bot, text.to_string());
+        if let Some(markup) = markup.clone() {
+            request = request.reply_markup(markup);
+        }
+        async move {
+            let outcome = match tokio::time::timeout(ADMIN_SEND_TIMEOUT, request.send()).await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(error)) => Err(error.to_string()),
+                Err(_) => Err("таймаут".to_string()),
+            };
+            (chat_id.0, outcome)
+        }
+    });
+    let results = futures::future::join_all(sends).await;
+
+    let mut failures = Vec::new();
+    for (admin_id, outcome) in results {
+        if let Err(error) = outcome {
             tracing::warn!(
-                "Не удалось отправить уведомление админу {}: {}",
-                admin_id,
-                e
+                admin_id = admin_id,
+                kind = kind,
+                error = %error,
+                "Не удалось отправить уведомление админу"
             );
+            crate::metrics::record_admin_delivery_failure();
+            failures.push(format!("{}: {}", admin_id, error));
         }
     }
-    Ok(())
+    if !failures.is_empty() {
+        state
+            .audit
+            .record_system(
+                "admin_notify_failed",
+                target,
+                &format!(
+                    "{}: не доставлено {} из {} ({})",
+                    kind,
+                    failures.len(),
+                    destinations.len(),
+                    failures.join("; ")
+                ),
+            )
+            .await;
+    }
+}
+
+const MAX_DUPLICATE_WARNINGS: i64 = 3;
+
+/// Предупреждения для карточки заявки о совпадении username или имени с другими
+/// (в том числе удалёнными) пользователями; пустая строка, если совпадений нет.
+pub async fn render_duplicate_warnings(
+    state: &BotState,
+    req: &RegistrationRequest,
+) -> Result<String, AppError> {
+    let similar = state
+        .db
+        .find_similar_users(req, MAX_DUPLICATE_WARNINGS)
+        .await?;
+    let mut text = String::new();
+    for other in &similar {
+        let whom = match other.status {
+            RequestStatus::Deleted => "ранее удалённого пользователя",
+            RequestStatus::Approved => "активного пользователя",
+            _ => "пользователя с другой заявкой",
+        };
+        let same_username = matches!(
+            (&req.tg_username, &other.tg_username),
+            (Some(a), Some(b)) if a.trim().eq_ignore_ascii_case(b.trim())
+        );
+        let reason = if same_username {
+            format!(
+                "тот же username @{}",
+                other.tg_username.as_deref().unwrap_or("")
+            )
+        } else {
+            format!(
+                "то же имя «{}»",
+                other.tg_display_name.as_deref().unwrap_or("").trim()
+            )
+        };
+        text.push_str(&format!(
+            "\n⚠️ Похоже на {} {} ({}) — /find {}",
+            whom,
+            telemt_username(other.tg_user_id),
+            reason,
+            telemt_username(other.tg_user_id)
+        ));
+    }
+    if !text.is_empty() {
+        tracing::info!(
+            request_id = req.id,
+            matches = similar.len(),
+            "Possible duplicate account in new request"
+        );
+        text.insert(0, '\n');
+    }
+    Ok(text)
 }
 
 pub fn build_user_qr_png_bytes(payload: &str) -> Result<Vec<u8>, anyhow::Error> {
@@ -220,21 +520,17 @@ pub fn build_user_qr_png_bytes(payload: &str) -> Result<Vec<u8>, anyhow::Error>
     Ok(bytes)
 }
 
-pub fn restart_telemt_service(state: &BotState, context: &'static str) {
-    let restart_result = state.service.restart();
-    if !restart_result.success {
-        tracing::warn!(
-            stderr = %restart_result.stderr,
-            "Не удалось перезапустить telemt после {}",
-            context
-        );
-    }
+/// Всё, что планируется пользователю после одобрения: онбординг и опрос.
+pub async fn schedule_post_approval(state: &BotState, tg_user_id: i64) {
+    crate::metrics::record_approval();
+    schedule_onboarding(state, tg_user_id).await;
+    schedule_survey(state, tg_user_id).await;
 }
 
 pub async fn approve_request_and_build_link(
     state: &BotState,
     request_id: i64,
-) -> Result<Option<(RegistrationRequest, String)>, anyhow::Error> {
+) -> Result<Option<(RegistrationRequest, String)>, AppError> {
     let request = match state.db.get_pending_by_id(request_id).await? {
         Some(request) => request,
         None => return Ok(None),
@@ -243,7 +539,10 @@ pub async fn approve_request_and_build_link(
     let telemt_user = telemt_username(request.tg_user_id);
     let user_secret = generate_user_secret();
 
-    state.telemt_cfg.upsert_user(&telemt_user, &user_secret)?;
+    state
+        .cfg_writer
+        .upsert_user(&telemt_user, &user_secret)
+        .await?;
     if state
         .db
         .approve(request_id, &telemt_user, &user_secret)
@@ -253,22 +552,120 @@ pub async fn approve_request_and_build_link(
         return Ok(None);
     }
 
-    restart_telemt_service(state, "одобрения заявки");
+    schedule_post_approval(state, request.tg_user_id).await;
 
-    let link_params = state.telemt_cfg.read_link_params()?;
+    let link_params = state.telemt_cfg.read_link_params().await?;
     let proxy_link = build_proxy_link(&link_params, &user_secret)?;
     Ok(Some((request, proxy_link)))
 }
 
+/// Одобренная админом заявка: ссылка пользователя и срок доступа, если он задан.
+pub struct ApprovedRequest {
+    pub link: String,
+    pub expires_at: Option<i64>,
+}
+
+/// Одобрение заявки админом: запись в конфиг и БД, срок доступа (`duration_secs`,
+/// без него — бессрочно), аудит и ссылка пользователю. `None` — заявка уже
+/// обработана или не найдена.
+pub async fn approve_pending_request(
+    bot: &Bot,
+    state: &BotState,
+    admin_id: i64,
+    request_id: i64,
+    duration_secs: Option<i64>,
+) -> Result<Option<ApprovedRequest>, AppError> {
+    let Some((request, link)) = approve_request_and_build_link(state, request_id).await? else {
+        return Ok(None);
+    };
+    // Без явного срока действует профиль группы пользователя (`[groups.<имя>]`).
+    let duration_secs = match duration_secs {
+        Some(seconds) => Some(seconds),
+        None => {
+            let group = state.db.get_user_group(request.tg_user_id).await?;
+            state.config.group_access_secs(group.as_deref())
+        }
+    };
+    let expires_at = duration_secs.map(|seconds| chrono::Utc::now().timestamp() + seconds);
+    if let Some(expires_at) = expires_at {
+        state
+            .db
+            .set_user_expiry(request.tg_user_id, Some(expires_at))
+            .await?;
+    }
+    let mut details = format!("tg_user:{}", request.tg_user_id);
+    if let Some(expires_at) = expires_at {
+        details.push_str(&format!(", до {}", format_timestamp(expires_at)));
+    }
+    state
+        .audit
+        .record(
+            admin_id,
+            "approve",
+            &format!("request:{}", request_id),
+            &details,
+        )
+        .await;
+    let lang = user_lang(state, request.tg_user_id).await?;
+    let mut text = tf(lang, "link_message", &[("link", &link)]);
+    if let Some(expires_at) = expires_at {
+        text.push('\n');
+        text.push_str(&tf(
+            lang,
+            "access_until",
+            &[("date", &format_timestamp(expires_at))],
+        ));
+    }
+    send_proxy_link(bot, state, ChatId(request.tg_user_id), text, false).await?;
+    tracing::info!("Admin {} approved request #{}", admin_id, request_id);
+    Ok(Some(ApprovedRequest { link, expires_at }))
+}
+
+/// Отклонение заявки админом: причина (если есть) сохраняется в БД и передаётся
+/// пользователю, действие пишется в аудит. `None` — заявка уже обработана или не найдена.
+pub async fn reject_pending_request(
+    bot: &Bot,
+    state: &BotState,
+    admin_id: i64,
+    request_id: i64,
+    reason: Option<&str>,
+) -> Result<Option<RegistrationRequest>, AppError> {
+    let Some(request) = state.db.reject(request_id, reason).await? else {
+        return Ok(None);
+    };
+    crate::metrics::record_rejection();
+    let mut details = format!("tg_user:{}", request.tg_user_id);
+    if let Some(reason) = reason {
+        details.push_str(&format!(", причина: {}", reason));
+    }
+    state
+        .audit
+        .record(
+            admin_id,
+            "reject",
+            &format!("request:{}", request_id),
+            &details,
+        )
+        .await;
+    let lang = user_lang(state, request.tg_user_id).await?;
+    let text = match reason {
+        Some(reason) => tf(lang, "rejected_with_reason", &[("reason", &reason)]),
+        None => t(lang, "rejected").to_string(),
+    };
+    bot.send_message(ChatId(request.tg_user_id), text).await?;
+    tracing::info!("Admin {} rejected request #{}", admin_id, request_id);
+    Ok(Some(request))
+}
+
 pub async fn approve_user_direct_and_build_link(
     state: &BotState,
     tg_user_id: i64,
     tg_username: Option<&str>,
     tg_display_name: Option<&str>,
-) -> Result<String, anyhow::Error> {
+) -> Result<String, AppError> {
     let telemt_user = telemt_username(tg_user_id);
     let secret = generate_user_secret();
-    state.telemt_cfg.upsert_user(&telemt_user, &secret)?;
+    state.cfg_writer.upsert_user(&telemt_user, &secret).await?;
     state
         .db
         .set_approved(
@@ -279,11 +676,10 @@ pub async fn approve_user_direct_and_build_link(
             &secret,
         )
         .await?;
+    schedule_post_approval(state, tg_user_id).await;
 
-    restart_telemt_service(state, "выдачи доступа");
-
-    let params = state.telemt_cfg.read_link_params()?;
-    build_proxy_link(&params, &secret).map_err(anyhow::Error::from)
+    let params = state.telemt_cfg.read_link_params().await?;
+    build_proxy_link(&params, &secret).map_err(AppError::from)
 }
 
 pub async fn process_invite_token(
@@ -293,33 +689,91 @@ pub async fn process_invite_token(
     tg_user_id: i64,
     tg_username: Option<&str>,
     tg_display_name: Option<&str>,
-    token: &str,
+    payload: &str,
 ) -> HandlerResult {
-    let consumed = match state.db.consume_invite_token(token).await {
-        Ok(token_payload) => token_payload,
-        Err(TokenConsumeError::NotFound) => {
-            bot.send_message(
-                msg.chat.id,
-                "Токен не найден. Проверьте код и попробуйте снова.",
-            )
+    let (token, plan) = split_start_payload(payload);
+    let lang = user_lang(state, tg_user_id).await?;
+    // Токен не снимает блокировку: без этой проверки заблокированный зарегистрировался
+    // бы повторно через ожидание токена или ссылку с токеном.
+    if state.db.is_user_blocked(tg_user_id).await? {
+        tracing::info!(
+            tg_user_id = tg_user_id,
+            "Blocked user tried an invite token"
+        );
+        bot.send_message(msg.chat.id, blocked_text(state, lang))
             .await?;
+        return Ok(());
+    }
+    if !registration_allowed(state, tg_user_id).await? {
+        tracing::info!(
+            tg_user_id = tg_user_id,
+            "User outside the allowlist tried an invite token"
+        );
+        bot.send_message(msg.chat.id, blocked_text(state, lang))
+            .await?;
+        return Ok(());
+    }
+    if !check_channel_membership(bot, msg.chat.id, state, tg_user_id, lang).await? {
+        return Ok(());
+    }
+    if token_entry_locked(bot, msg.chat.id, state, tg_user_id, lang).await? {
+        return Ok(());
+    }
+    let consumed = state
+        .db
+        .consume_invite_token(token, tg_user_id, tg_username, tg_display_name)
+        .await;
+    let (consumed, registered) = match consumed {
+        Ok(consumed) => {
+            state.db.reset_token_failures(tg_user_id).await?;
+            consumed
+        }
+        Err(TokenConsumeError::NotFound) => {
+            // Подбором считаются только несуществующие токены: отозванный или
+            // истёкший токен пользователь получил честно.
+            let text = match record_token_failure(bot, state, tg_user_id, tg_username).await? {
+                Some(lockout_secs) => locked_text(lang, lockout_secs),
+                None => t(lang, "token_not_found").to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
             return Ok(());
         }
         Err(TokenConsumeError::Revoked) => {
-            bot.send_message(msg.chat.id, "Этот токен отозван администратором.")
+            bot.send_message(msg.chat.id, t(lang, "token_revoked"))
                 .await?;
             return Ok(());
         }
         Err(TokenConsumeError::Expired) => {
-            bot.send_message(msg.chat.id, "Срок действия токена истёк.")
+            bot.send_message(msg.chat.id, t(lang, "token_expired"))
                 .await?;
             return Ok(());
         }
         Err(TokenConsumeError::UsageLimitReached) => {
-            bot.send_message(msg.chat.id, "Лимит использований токена исчерпан.")
+            bot.send_message(msg.chat.id, t(lang, "token_usage_limit"))
                 .await?;
             return Ok(());
         }
+        Err(TokenConsumeError::NotYetActive(valid_from)) => {
+            let text = tf(
+                lang,
+                "token_not_yet_active",
+                &[("date", &format_timestamp(valid_from))],
+            );
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        Err(TokenConsumeError::NotForYou) => {
+            tracing::warn!(
+                tg_user_id = tg_user_id,
+                token = %token,
+                "Попытка применить персональный токен другого пользователя"
+            );
+            bot.send_message(msg.chat.id, t(lang, "token_not_for_you"))
+                .await?;
+            return Ok(());
+        }
+        Err(TokenConsumeError::Db(error)) => return Err(DbError::from(error).into()),
+        Err(TokenConsumeError::Other(error)) => return Err(error.into()),
     };
 
     tracing::info!(
@@ -332,59 +786,91 @@ pub async fn process_invite_token(
         expires_at = consumed.expires_at,
         "Токен успешно применён"
     );
+    // Вариант ссылки принимается, только если он объявлен в токене: иначе
+    // пользователь мог бы сам дописать к ссылке любую группу.
+    let plan = match plan.and_then(super::groups::normalize_group_name) {
+        Some(plan) if consumed.plans.contains(&plan) => Some(plan),
+        Some(plan) => {
+            tracing::warn!(
+                tg_user_id = tg_user_id,
+                token = %consumed.token,
+                plan = %plan,
+                "Вариант ссылки не объявлен в токене, группа не назначена"
+            );
+            None
+        }
+        None => None,
+    };
+    let plan = plan.or_else(|| consumed.group_name.clone());
 
     match consumed.mode {
-        TokenMode::Manual => {
-            let result = state
-                .db
-                .register_or_get(tg_user_id, tg_username, tg_display_name)
-                .await?;
-            match result {
-                RegisterResult::Approved(secret) => {
-                    let params = state.telemt_cfg.read_link_params()?;
-                    let link = build_proxy_link(&params, &secret)?;
-                    bot.send_message(msg.chat.id, format!("Ваша ссылка на прокси:\n\n{}", link))
-                        .reply_markup(crate::bot::keyboards::user_menu())
-                        .await?;
-                    unmark_user_waiting_for_invite(state, tg_user_id).await;
-                }
-                RegisterResult::Rejected => {
-                    bot.send_message(
-                        msg.chat.id,
-                        "Ваша заявка на регистрацию отклонена администратором.",
-                    )
-                    .reply_markup(crate::bot::keyboards::user_menu())
+        TokenMode::Manual => match registered {
+            RegisterResult::Approved(secret) => {
+                let params = state.telemt_cfg.read_link_params().await?;
+                let link = build_proxy_link(&params, &secret)?;
+                let text = render_user_link_message(state, tg_user_id, &link).await?;
+                send_proxy_link(bot, state, msg.chat.id, text, true).await?;
+                unmark_user_waiting_for_invite(state, tg_user_id).await;
+            }
+            RegisterResult::Rejected => {
+                bot.send_message(msg.chat.id, t(lang, "rejected"))
+                    .reply_markup(crate::bot::keyboards::user_menu(lang))
                     .await?;
-                    unmark_user_waiting_for_invite(state, tg_user_id).await;
-                }
-                RegisterResult::AlreadyPending => {
-                    bot.send_message(
-                        msg.chat.id,
-                        "Ваша заявка уже на рассмотрении. Ожидайте подтверждения администратора.",
-                    )
-                    .reply_markup(crate::bot::keyboards::user_menu())
+                unmark_user_waiting_for_invite(state, tg_user_id).await;
+            }
+            RegisterResult::AlreadyPending => {
+                bot.send_message(msg.chat.id, t(lang, "pending"))
+                    .reply_markup(crate::bot::keyboards::user_menu(lang))
                     .await?;
-                    unmark_user_waiting_for_invite(state, tg_user_id).await;
-                }
-                RegisterResult::NewPending(ref req) => {
-                    bot.send_message(msg.chat.id, "Заявка отправлена. Ожидайте подтверждения.")
-                        .reply_markup(crate::bot::keyboards::user_menu())
-                        .await?;
-                    notify_admins(bot, state, req).await?;
-                    unmark_user_waiting_for_invite(state, tg_user_id).await;
+                unmark_user_waiting_for_invite(state, tg_user_id).await;
+            }
+            RegisterResult::NewPending(ref req) => {
+                crate::metrics::record_token_consumed();
+                if let Some(plan) = &plan {
+                    state.db.set_user_group(tg_user_id, Some(plan)).await?;
                 }
+                bot.send_message(msg.chat.id, t(lang, "request_sent"))
+                    .reply_markup(crate::bot::keyboards::user_menu(lang))
+                    .await?;
+                notify_admins(bot, state, req).await?;
+                unmark_user_waiting_for_invite(state, tg_user_id).await;
             }
-        }
+        },
         TokenMode::AutoApprove => {
+            // Уже одобренному пользователю токен не нужен: отдаём прежнюю ссылку, не
+            // перевыпуская секрет (использование транзакция уже не засчитала).
+            if let RegisterResult::Approved(secret) = registered {
+                let params = state.telemt_cfg.read_link_params().await?;
+                let link = build_proxy_link(&params, &secret)?;
+                let text = render_user_link_message(state, tg_user_id, &link).await?;
+                send_proxy_link(bot, state, msg.chat.id, text, true).await?;
+                unmark_user_waiting_for_invite(state, tg_user_id).await;
+                return Ok(());
+            }
+            // Заявка с токеном уже записана: если конфиг обновить не удалось, она
+            // остаётся на рассмотрении у админов.
             let link =
                 approve_user_direct_and_build_link(state, tg_user_id, tg_username, tg_display_name)
                     .await?;
-            bot.send_message(
-                msg.chat.id,
-                format!("Доступ одобрен! Ваша ссылка для подключения:\n\n{}", link),
-            )
-            .reply_markup(crate::bot::keyboards::user_menu())
-            .await?;
+            crate::metrics::record_token_consumed();
+            if let Some(plan) = &plan {
+                state.db.set_user_group(tg_user_id, Some(plan)).await?;
+            }
+            let mut text = tf(lang, "access_approved", &[("link", &link)]);
+            if let Some(seconds) = state.config.group_access_secs(plan.as_deref()) {
+                let expires_at = chrono::Utc::now().timestamp() + seconds;
+                state
+                    .db
+                    .set_user_expiry(tg_user_id, Some(expires_at))
+                    .await?;
+                text.push('\n');
+                text.push_str(&tf(
+                    lang,
+                    "access_until",
+                    &[("date", &format_timestamp(expires_at))],
+                ));
+            }
+            send_proxy_link(bot, state, msg.chat.id, text, true).await?;
             notify_auto_approve(
                 bot,
                 state,
@@ -392,6 +878,7 @@ pub async fn process_invite_token(
                 tg_username,
                 tg_display_name,
                 &consumed,
+                plan.as_deref(),
             )
             .await;
             unmark_user_waiting_for_invite(state, tg_user_id).await;
@@ -401,28 +888,99 @@ pub async fn process_invite_token(
     Ok(())
 }
 
+/// Текст со ссылкой для одобренного пользователя с действующим объявлением (если есть).
+pub async fn render_user_link_message(
+    state: &BotState,
+    tg_user_id: i64,
+    link: &str,
+) -> Result<String, AppError> {
+    let lang = user_lang(state, tg_user_id).await?;
+    let mut text = tf(lang, "link_message", &[("link", &link)]);
+    if let Some(short_link) =
+        crate::web::short_link_url(&state.config.web, &state.db, tg_user_id).await?
+    {
+        text.push_str("\n\n");
+        text.push_str(&tf(lang, "link_short", &[("url", &short_link)]));
+    }
+    if let Some(announcement) = state.db.get_active_announcement().await? {
+        text.push_str("\n\n📢 ");
+        text.push_str(&announcement.text);
+    }
+    Ok(text)
+}
+
+/// Проверяет кулдаун команды для обычного пользователя и при необходимости
+/// отвечает «повторите через N секунд». Возвращает false, если команду выполнять не нужно.
+pub async fn pass_cooldown(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    user_id: i64,
+    command: CooldownCommand,
+) -> Result<bool, AppError> {
+    if state.config.is_admin(user_id) {
+        return Ok(true);
+    }
+    match state.cooldowns.check(user_id, command).await {
+        Ok(()) => Ok(true),
+        Err(remaining) => {
+            tracing::debug!(
+                user_id = user_id,
+                command = ?command,
+                remaining_secs = remaining,
+                "Command rejected by cooldown"
+            );
+            let lang = user_lang(state, user_id).await?;
+            bot.send_message(chat_id, tf(lang, "cooldown", &[("secs", &remaining)]))
+                .await?;
+            Ok(false)
+        }
+    }
+}
+
+/// Ответ заблокированному пользователю: `blocked_text` из конфига или текст по умолчанию.
+pub fn blocked_text(state: &BotState, lang: Lang) -> &str {
+    state
+        .config
+        .blocked_text
+        .as_deref()
+        .unwrap_or_else(|| t(lang, "blocked"))
+}
+
+/// Язык пользователя: выбранный кнопкой меню или `default_language` из конфига.
+pub async fn user_lang(state: &BotState, tg_user_id: i64) -> Result<Lang, AppError> {
+    let stored = state.db.get_user_language(tg_user_id).await?;
+    Ok(stored
+        .as_deref()
+        .and_then(Lang::from_code)
+        .unwrap_or(state.config.default_language))
+}
+
 pub async fn send_user_link(
     bot: &Bot,
     chat_id: ChatId,
     tg_user_id: i64,
     state: &BotState,
 ) -> HandlerResult {
+    let lang = user_lang(state, tg_user_id).await?;
+    if state.db.is_user_suspended(tg_user_id).await? {
+        bot.send_message(chat_id, t(lang, "suspended"))
+            .reply_markup(crate::bot::keyboards::user_menu(lang))
+            .await?;
+        return Ok(());
+    }
     let maybe = state.db.get_approved(tg_user_id).await?;
     match maybe {
         Some((_, secret)) => {
-            let params = state.telemt_cfg.read_link_params()?;
+            let params = state.telemt_cfg.read_link_params().await?;
             let link = build_proxy_link(&params, &secret)?;
-            bot.send_message(chat_id, format!("Ваша ссылка на прокси:\n\n{}", link))
-                .reply_markup(crate::bot::keyboards::user_menu())
-                .await?;
+            let text = render_user_link_message(state, tg_user_id, &link).await?;
+            send_proxy_link_with_qr(bot, state, chat_id, text, &link).await?;
         }
         None => {
-            bot.send_message(
-                chat_id,
-                "У вас нет доступа к прокси. Отправьте /start для регистрации.",
-            )
-            .reply_markup(crate::bot::keyboards::user_menu())
-            .await?;
+            bot.send_message(chat_id, t(lang, "no_access"))
+                .reply_markup(crate::bot::keyboards::user_menu(lang))
+                .await?;
         }
     }
     Ok(())
@@ -432,7 +990,7 @@ pub async fn require_admin_callback(
     bot: &Bot,
     q: &CallbackQuery,
     state: &BotState,
-) -> Result<Option<i64>, anyhow::Error> {
+) -> Result<Option<i64>, AppError> {
     let admin_id = q.from.id.0 as i64;
     if !state.config.is_admin(admin_id) {
         bot.answer_callback_query(q.id.clone())
@@ -444,13 +1002,33 @@ pub async fn require_admin_callback(
     Ok(Some(admin_id))
 }
 
-pub async fn perform_hard_ban(state: &BotState, tg_user_id: i64) -> Result<String, anyhow::Error> {
-    let telemt_user = telemt_username(tg_user_id);
-    let removed_from_cfg = state.telemt_cfg.remove_user(&telemt_user)?;
-    let removed_from_db = state.db.deactivate_user(tg_user_id).await?;
+/// Как [`require_admin_callback`], но пропускает и админов второй линии (`[escalation]`).
+pub async fn require_reviewer_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &BotState,
+) -> Result<Option<i64>, AppError> {
+    let reviewer_id = q.from.id.0 as i64;
+    if !state.config.can_review_requests(reviewer_id) {
+        bot.answer_callback_query(q.id.clone())
+            .text("Недостаточно прав")
+            .show_alert(true)
+            .await?;
+        return Ok(None);
+    }
+    Ok(Some(reviewer_id))
+}
 
-    if removed_from_cfg {
-        restart_telemt_service(state, "удаления пользователя");
+pub async fn perform_hard_ban(state: &BotState, tg_user_id: i64) -> Result<String, AppError> {
+    // Импортированные из конфига пользователи могут жить там под своим прежним именем.
+    let telemt_user = match state.db.get_approved(tg_user_id).await? {
+        Some((telemt_user, _)) => telemt_user,
+        None => telemt_username(tg_user_id),
+    };
+    let removed_from_cfg = state.cfg_writer.remove_user(&telemt_user).await?;
+    let removed_from_db = state.db.deactivate_user(tg_user_id).await?;
+    if removed_from_db {
+        crate::metrics::record_deletion("admin");
     }
 
     if removed_from_cfg || removed_from_db {
@@ -460,36 +1038,92 @@ pub async fn perform_hard_ban(state: &BotState, tg_user_id: i64) -> Result<Strin
     }
 }
 
-pub async fn admin_show_pending(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
-    let pending = state.db.list_pending_requests(10).await?;
-    if pending.is_empty() {
-        bot.send_message(chat_id, "Новых заявок нет.")
-            .reply_markup(crate::bot::keyboards::admin_menu())
-            .await?;
-        return Ok(());
+/// Перевыпускает секрет одного пользователя (утечка ссылки): новый секрет в конфиг
+/// telemt с рестартом, затем в БД, и свежая ссылка пользователю. Возвращает текст для админа.
+pub async fn perform_secret_rotation(
+    bot: &Bot,
+    state: &BotState,
+    tg_user_id: i64,
+) -> Result<String, AppError> {
+    let telemt_user = telemt_username(tg_user_id);
+    if state
+        .db
+        .get_active_user_by_tg_user(tg_user_id)
+        .await?
+        .is_none()
+    {
+        return Ok(format!(
+            "Пользователь {} не найден среди активных",
+            telemt_user
+        ));
+    }
+    if tg_user_id < 0 {
+        return Ok(
+            "Пользователь импортирован и не привязан к Telegram: новую ссылку некуда отправить. \
+             Сначала выполните /bind."
+                .to_string(),
+        );
+    }
+    if state.db.is_user_suspended(tg_user_id).await? {
+        return Ok(format!(
+            "Доступ {} приостановлен — секрет не перевыпущен",
+            telemt_user
+        ));
     }
 
-    bot.send_message(chat_id, format!("Найдено новых заявок: {}", pending.len()))
-        .reply_markup(crate::bot::keyboards::admin_menu())
-        .await?;
+    let secret = generate_user_secret();
+    state.cfg_writer.upsert_user(&telemt_user, &secret).await?;
+    state.db.update_user_secret(tg_user_id, &secret).await?;
+    tracing::info!(tg_user_id = tg_user_id, "User secret rotated");
 
-    for req in pending {
-        let text = format!(
-            "📋 Заявка #{}:\n\
-             User ID: {}\n\
-             Username: @{}\n\
-             Имя: {}\n\
-             Время: {}",
-            req.id,
-            req.tg_user_id,
-            req.tg_username.as_deref().unwrap_or("—"),
-            req.tg_display_name.as_deref().unwrap_or("—"),
-            format_timestamp(req.created_at),
+    let params = state.telemt_cfg.read_link_params().await?;
+    let link = build_proxy_link(&params, &secret)?;
+    let text = format!(
+        "{}\n\n{}",
+        t(user_lang(state, tg_user_id).await?, "secret_rotated"),
+        render_user_link_message(state, tg_user_id, &link).await?
+    );
+    if let Err(error) = send_proxy_link(bot, state, ChatId(tg_user_id), text, false).await {
+        tracing::warn!(
+            tg_user_id = tg_user_id,
+            error = %error,
+            "Не удалось отправить пользователю новую ссылку"
         );
-        bot.send_message(chat_id, text)
-            .reply_markup(crate::bot::keyboards::approve_reject_buttons(req.id))
-            .await?;
+        return Ok(format!(
+            "Секрет {} перевыпущен, но отправить ссылку пользователю не удалось",
+            telemt_user
+        ));
     }
+    Ok(format!(
+        "Секрет {} перевыпущен, новая ссылка отправлена пользователю",
+        telemt_user
+    ))
+}
+
+/// Короткая сводка для `/pending`: «Ожидают: 7 (старейшая 3 дня)».
+pub async fn admin_show_pending_summary(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+) -> HandlerResult {
+    let summary = state.db.pending_summary().await?;
+    let Some(oldest_created_at) = summary.oldest_created_at.filter(|_| summary.count > 0) else {
+        bot.send_message(chat_id, "Новых заявок нет.").await?;
+        return Ok(());
+    };
+    let waited = chrono::Utc::now().timestamp() - oldest_created_at;
+    bot.send_message(
+        chat_id,
+        format!(
+            "📥 Ожидают: {} (старейшая {})",
+            summary.count,
+            format_wait(waited)
+        ),
+    )
+    .reply_markup(crate::bot::keyboards::pending_summary_keyboard(
+        summary.count,
+    ))
+    .await?;
     Ok(())
 }
 
@@ -497,12 +1131,15 @@ pub async fn admin_show_users_page(
     bot: &Bot,
     chat_id: ChatId,
     state: &BotState,
-    requested_page: i64,
+    request: UsersPageRequest,
     message_id: Option<teloxide::types::MessageId>,
 ) -> HandlerResult {
     let total_users = state.db.count_active_users().await?;
-    let users_page_size = state.config.users_page_size.max(1);
-    if total_users <= 0 {
+    let page = state
+        .db
+        .list_active_users_page(state.config.users_page_size, request)
+        .await?;
+    if page.users.is_empty() {
         let text = "Активных пользователей нет.";
         if let Some(message_id) = message_id {
             bot.edit_message_text(chat_id, message_id, text)
@@ -516,15 +1153,16 @@ pub async fn admin_show_users_page(
         return Ok(());
     }
 
-    let total_pages = ((total_users + users_page_size - 1) / users_page_size).max(1);
-    let page = requested_page.clamp(1, total_pages);
-    let offset = (page - 1) * users_page_size;
-    let users = state
+    // Пользователи в льготном периоде после истечения срока отмечаются «⏳».
+    let expiring: std::collections::HashSet<i64> = state
         .db
-        .list_active_users_page(users_page_size, offset)
-        .await?;
-
-    let titles: Vec<(i64, String)> = users
+        .list_grace_users(chrono::Utc::now().timestamp())
+        .await?
+        .into_iter()
+        .map(|user| user.tg_user_id)
+        .collect();
+    let titles: Vec<(i64, String)> = page
+        .users
         .iter()
         .map(|user| {
             let display_name = user_display_name(user);
@@ -533,29 +1171,53 @@ pub async fn admin_show_users_page(
             } else {
                 display_name
             };
-            (user.tg_user_id, format!("{} (id {})", short, user.tg_user_id))
+            let marker = if expiring.contains(&user.tg_user_id) {
+                "⏳ "
+            } else {
+                ""
+            };
+            (
+                user.tg_user_id,
+                format!("{}{} (id {})", marker, short, user.tg_user_id),
+            )
         })
         .collect();
 
+    let first = page.users[0].cursor();
+    let last = page.users[page.users.len() - 1].cursor();
+    let current = if page.has_prev {
+        UsersPageRequest::From(first)
+    } else {
+        UsersPageRequest::First
+    };
+    let prev = page.has_prev.then_some(UsersPageRequest::Before(first));
+    let next = page.has_next.then_some(UsersPageRequest::After(last));
+
     let text = format!(
-        "👥 Активные пользователи\nВсего: {}\nСтраница: {}/{}\n\nНажмите на пользователя, чтобы открыть карточку.",
-        total_users, page, total_pages
+        "👥 Активные пользователи\nВсего: {}\nНа странице: {}\n\nНажмите на пользователя, чтобы открыть карточку.",
+        total_users,
+        page.users.len()
     );
-    let keyboard = crate::bot::keyboards::users_page_keyboard(&titles, page, total_pages);
+    let keyboard = crate::bot::keyboards::users_page_keyboard(&titles, current, prev, next);
 
     if let Some(message_id) = message_id {
         bot.edit_message_text(chat_id, message_id, text)
             .reply_markup(keyboard)
             .await?;
     } else {
-        bot.send_message(chat_id, text).reply_markup(keyboard).await?;
+        bot.send_message(chat_id, text)
+            .reply_markup(keyboard)
+            .await?;
     }
     Ok(())
 }
 
+/// Сколько токенов показывать в воронке `/stats`; подробности — `/token stats`.
+const STATS_TOP_TOKENS: i64 = 5;
+
 pub async fn admin_show_stats(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
     let stats = state.db.admin_stats().await?;
-    let text = format!(
+    let mut text = format!(
         "📊 Статистика:\n\
          Всего записей: {}\n\
          Ожидают: {}\n\
@@ -564,17 +1226,80 @@ pub async fn admin_show_stats(bot: &Bot, chat_id: ChatId, state: &BotState) -> H
          Удалённые: {}",
         stats.total, stats.pending, stats.approved, stats.rejected, stats.deleted
     );
+    if let Some(survey) = render_survey_stats(state).await? {
+        text.push_str("\n\n");
+        text.push_str(&survey);
+    }
+    let campaigns = state.db.token_campaigns(STATS_TOP_TOKENS).await?;
+    if !campaigns.is_empty() {
+        text.push_str("\n\n🎟 Токены (переходы → применения → доступ):");
+        for campaign in &campaigns {
+            let label = campaign
+                .label
+                .as_deref()
+                .map(|label| format!(" «{}»", label))
+                .unwrap_or_default();
+            text.push_str(&format!(
+                "\n{}{}: {} → {} ({}) → {} ({})",
+                campaign.token,
+                label,
+                campaign.views,
+                campaign.consumed,
+                format_percent(campaign.consumed, campaign.views),
+                campaign.approved,
+                format_percent(campaign.approved, campaign.consumed),
+            ));
+        }
+    }
+    if state.config.traffic.stats_url.is_some() {
+        let top = state
+            .db
+            .top_traffic_users(state.config.traffic.top_users.max(1))
+            .await?;
+        if !top.is_empty() {
+            text.push_str("\n\n📶 Топ по трафику:");
+            for (index, traffic) in top.iter().enumerate() {
+                let name = traffic
+                    .telemt_username
+                    .clone()
+                    .unwrap_or_else(|| telemt_username(traffic.tg_user_id));
+                text.push_str(&format!(
+                    "\n{}. {} — {}",
+                    index + 1,
+                    name,
+                    format_traffic(traffic)
+                ));
+            }
+        }
+    }
     bot.send_message(chat_id, text)
         .reply_markup(crate::bot::keyboards::admin_menu())
         .await?;
     Ok(())
 }
 
-pub async fn admin_show_service_panel(bot: &Bot, chat_id: ChatId, state: &BotState) -> HandlerResult {
-    let result = state.service.status();
+/// Результат действия над сервисом вместе со строкой об автозапуске.
+pub async fn render_service_report(
+    state: &BotState,
+    action: &str,
+    result: &ServiceResult,
+) -> String {
+    format!(
+        "{}\n\n{}",
+        state.service.format_result(action, result),
+        state.service.format_enabled_line().await
+    )
+}
+
+pub async fn admin_show_service_panel(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+) -> HandlerResult {
+    let result = state.service.status().await;
     let text = format!(
         "⚙️ Сервис telemt\n\n{}",
-        state.service.format_result("status", &result)
+        render_service_report(state, "status", &result).await
     );
     bot.send_message(chat_id, text)
         .reply_markup(crate::bot::keyboards::service_control_buttons())
@@ -587,12 +1312,12 @@ pub async fn send_user_qr_to_admin(
     q: &CallbackQuery,
     user: &RegistrationRequest,
     state: &BotState,
-) -> Result<(), anyhow::Error> {
+) -> Result<(), AppError> {
     let Some(secret) = user.secret.as_deref() else {
-        return Err(anyhow!("Не найден секрет пользователя"));
+        return Err(anyhow!("Не найден секрет пользователя").into());
     };
 
-    let params = state.telemt_cfg.read_link_params()?;
+    let params = state.telemt_cfg.read_link_params().await?;
     let link = build_proxy_link(&params, secret)?;
     let qr_png = build_user_qr_png_bytes(&link)?;
     let caption = super::format::render_user_proxy_for_forward(user, &link);
@@ -608,9 +1333,14 @@ pub async fn send_user_qr_to_admin(
     Ok(())
 }
 
-pub fn callback_prefix_filter(prefix: &'static str) -> impl Fn(CallbackQuery) -> Option<CallbackQuery> {
+pub fn callback_prefix_filter(
+    prefix: &'static str,
+) -> impl Fn(CallbackQuery) -> Option<CallbackQuery> {
     move |q: CallbackQuery| {
-        if q.data.as_deref().is_some_and(|payload| payload.starts_with(prefix)) {
+        if q.data
+            .as_deref()
+            .is_some_and(|payload| payload.starts_with(prefix))
+        {
             Some(q)
         } else {
             None
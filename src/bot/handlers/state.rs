@@ -1,20 +1,47 @@
+use crate::audit::AuditLog;
+use crate::bot::cooldown::Cooldowns;
 use crate::config::Config;
 use crate::db::Db;
 use crate::service::ServiceController;
 use crate::telemt_cfg::TelemtConfig;
-use std::collections::HashSet;
+use crate::telemt_writer::ConfigWriter;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use teloxide::types::Message;
-use tokio::sync::Mutex;
+use teloxide::types::{ChatId, Message, MessageId};
+use tokio::sync::{Mutex, Notify};
 
 #[derive(Clone)]
 pub struct BotState {
     pub config: Arc<Config>,
     pub db: Arc<Db>,
     pub telemt_cfg: Arc<TelemtConfig>,
+    /// Единственный путь изменения пользователей в конфиге telemt (с рестартом и откатом).
+    pub cfg_writer: ConfigWriter,
     pub service: ServiceController,
     pub bot_username: Option<String>,
     pub awaiting_invite_users: Arc<Mutex<HashSet<i64>>>,
+    /// Будит исполнитель очереди задач сразу после постановки новой задачи.
+    pub job_notify: Arc<Notify>,
+    /// Кулдауны дорогих команд для обычных пользователей.
+    pub cooldowns: Arc<Cooldowns>,
+    /// Журнал аудита действий администраторов.
+    pub audit: AuditLog,
+    /// Запрос перечитать токен бота (`/reloadcfg`), обрабатывается в `main`.
+    pub token_reload: Arc<Notify>,
+    /// Запланированный перезапуск с предупреждением пользователей.
+    pub pending_restart: Arc<Mutex<Option<tokio::task::AbortHandle>>>,
+    /// Режим `/viewas`: админ → пользователь, глазами которого он видит бота.
+    pub view_as: Arc<Mutex<HashMap<i64, i64>>>,
+    /// Админ → заявка, для отклонения которой он сейчас пишет причину.
+    pub awaiting_reject_reason: Arc<Mutex<HashMap<i64, RejectPrompt>>>,
+}
+
+/// Запрос причины отклонения: заявка и сообщение с вопросом, которое правится по итогу.
+#[derive(Debug, Clone, Copy)]
+pub struct RejectPrompt {
+    pub request_id: i64,
+    pub chat_id: ChatId,
+    pub message_id: MessageId,
 }
 
 pub fn telemt_username(tg_user_id: i64) -> String {
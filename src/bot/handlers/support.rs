@@ -0,0 +1,409 @@
+//! Переписка с поддержкой (`[support]`): сообщения пользователя — текст, фото,
+//! скриншоты и документы с подписями — копируются админам, а ответ админа
+//! (reply на скопированное сообщение) уходит пользователю тем же типом.
+//! Переписку можно «взять в работу»: остальные админы видят, кто её ведёт, а
+//! их ответы требуют подтверждения перехвата. Каждая переписка — обращение со
+//! статусом open/answered/closed; отвеченные обращения без активности
+//! закрываются автоматически, незакрытые видны в `/tickets`.
+
+use super::format::format_wait;
+use super::shared::{
+    HandlerResult, callback_message_target, is_user_waiting_for_invite, require_admin_callback,
+    user_lang,
+};
+use super::state::{BotState, is_admin_message};
+use crate::bot::Bot;
+use crate::bot::keyboards::{is_user_menu_button, support_keyboard, support_takeover_keyboard};
+use crate::db::{SupportAssignee, TicketStatus};
+use crate::error::AppError;
+use crate::i18n::t;
+use anyhow::anyhow;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{ForceReply, MessageId};
+
+const AUTO_CLOSE_INTERVAL: Duration = Duration::from_secs(600);
+const TICKETS_LIMIT: i64 = 30;
+
+fn assignee_label(assignee: &SupportAssignee) -> String {
+    assignee
+        .admin_name
+        .clone()
+        .unwrap_or_else(|| format!("админ {}", assignee.admin_id))
+}
+
+/// Пытается обработать сообщение как переписку с поддержкой. Возвращает true,
+/// если сообщение переслано и дальше его обрабатывать не нужно.
+pub async fn try_relay_support(
+    bot: &Bot,
+    msg: &Message,
+    state: &BotState,
+) -> Result<bool, AppError> {
+    if !state.config.support.enabled || !msg.chat.is_private() {
+        return Ok(false);
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(false);
+    };
+    let user_id = user.id.0 as i64;
+
+    if state.config.is_admin(user_id) {
+        return relay_admin_reply(bot, msg, state).await;
+    }
+
+    let is_media = msg.photo().is_some() || msg.document().is_some();
+    let is_free_text = msg
+        .text()
+        .is_some_and(|text| !text.starts_with('/') && !is_user_menu_button(text));
+    if !(is_media || is_free_text) || is_user_waiting_for_invite(state, user_id).await {
+        return Ok(false);
+    }
+    let Some(request) = state.db.get_request_by_tg_user(user_id).await? else {
+        return Ok(false);
+    };
+
+    state.db.touch_ticket_from_user(user_id).await?;
+    let assignee = state.db.get_support_assignee(user_id).await?;
+    let mut header = format!(
+        "💬 Сообщение от {} (@{}, id {}). Ответьте на сообщение или нажмите «✍️ Ответить», \
+         чтобы написать пользователю.",
+        request.tg_display_name.as_deref().unwrap_or("—"),
+        request.tg_username.as_deref().unwrap_or("—"),
+        user_id
+    );
+    let markup = match &assignee {
+        Some(assignee) => {
+            header.push_str(&format!("\n🙋 В работе у {}", assignee_label(assignee)));
+            support_keyboard(user_id, false)
+        }
+        None => support_keyboard(user_id, true),
+    };
+    let mut delivered = 0;
+    for admin_id in &state.config.admin_ids {
+        let admin_chat = ChatId(*admin_id);
+        let result = async {
+            let header_msg = bot
+                .send_message(admin_chat, header.clone())
+                .reply_markup(markup.clone())
+                .await?;
+            state
+                .db
+                .add_support_message(*admin_id, header_msg.id.0, user_id)
+                .await?;
+            let copied = bot.copy_message(admin_chat, msg.chat.id, msg.id).await?;
+            state
+                .db
+                .add_support_message(*admin_id, copied.0, user_id)
+                .await?;
+            Ok::<(), AppError>(())
+        }
+        .await;
+        match result {
+            Ok(()) => delivered += 1,
+            Err(error) => tracing::warn!(
+                admin_id = *admin_id,
+                error = %error,
+                "Не удалось переслать админу сообщение поддержки"
+            ),
+        }
+    }
+
+    tracing::info!(
+        user_id = user_id,
+        media = is_media,
+        delivered = delivered,
+        "Support message relayed to admins"
+    );
+    let lang = user_lang(state, user_id).await?;
+    let reply = if delivered > 0 {
+        t(lang, "support_relayed")
+    } else {
+        t(lang, "support_relay_failed")
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(true)
+}
+
+async fn relay_admin_reply(bot: &Bot, msg: &Message, state: &BotState) -> Result<bool, AppError> {
+    let Some(original) = msg.reply_to_message() else {
+        return Ok(false);
+    };
+    let Some(tg_user_id) = state
+        .db
+        .find_support_user(msg.chat.id.0, original.id.0)
+        .await?
+    else {
+        return Ok(false);
+    };
+    let Some(admin) = msg.from.as_ref() else {
+        return Ok(false);
+    };
+    let admin_id = admin.id.0 as i64;
+
+    match state.db.get_support_assignee(tg_user_id).await? {
+        Some(assignee) if assignee.admin_id != admin_id => {
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "Переписку с {} ведёт {}. Перехватить и отправить ваш ответ?",
+                    tg_user_id,
+                    assignee_label(&assignee)
+                ),
+            )
+            .reply_markup(support_takeover_keyboard(tg_user_id, Some(msg.id.0)))
+            .await?;
+            return Ok(true);
+        }
+        Some(_) => {}
+        None => {
+            take_conversation(bot, state, tg_user_id, admin_id, &admin.full_name()).await?;
+        }
+    }
+
+    deliver_admin_reply(bot, state, msg.chat.id, msg.id, tg_user_id).await?;
+    Ok(true)
+}
+
+async fn deliver_admin_reply(
+    bot: &Bot,
+    state: &BotState,
+    admin_chat: ChatId,
+    message_id: MessageId,
+    tg_user_id: i64,
+) -> HandlerResult {
+    let user_chat = ChatId(tg_user_id);
+    let lang = user_lang(state, tg_user_id).await?;
+    bot.send_message(user_chat, t(lang, "support_reply_header"))
+        .await?;
+    bot.copy_message(user_chat, admin_chat, message_id).await?;
+    state.db.mark_ticket_answered(tg_user_id).await?;
+    tracing::info!(
+        admin_id = admin_chat.0,
+        tg_user_id = tg_user_id,
+        "Support reply relayed to user"
+    );
+    bot.send_message(
+        admin_chat,
+        format!("✅ Отправлено пользователю {}.", tg_user_id),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Назначает переписку админу и сообщает об этом остальным админам.
+async fn take_conversation(
+    bot: &Bot,
+    state: &BotState,
+    tg_user_id: i64,
+    admin_id: i64,
+    admin_name: &str,
+) -> HandlerResult {
+    state
+        .db
+        .assign_support(tg_user_id, admin_id, Some(admin_name))
+        .await?;
+    tracing::info!(
+        tg_user_id = tg_user_id,
+        admin_id = admin_id,
+        "Support conversation assigned"
+    );
+    let text = format!(
+        "🙋 {} взял(а) в работу переписку с пользователем {}.",
+        admin_name, tg_user_id
+    );
+    for other in state.config.admin_ids.iter().filter(|id| **id != admin_id) {
+        if let Err(error) = bot.send_message(ChatId(*other), text.clone()).await {
+            tracing::warn!(
+                admin_id = *other,
+                error = %error,
+                "Не удалось уведомить админа о назначении переписки"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// «Взять в работу» и перехват: `support_take:<tg_user_id>[:force[:<message_id>]]`.
+pub async fn callback_support_take(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let payload = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("support_take:"))
+        .unwrap_or("");
+    let mut parts = payload.split(':');
+    let tg_user_id = parts
+        .next()
+        .and_then(|value| value.parse::<i64>().ok())
+        .ok_or_else(|| anyhow!("Некорректный callback переписки"))?;
+    let force = parts.next() == Some("force");
+    let pending_reply = parts.next().and_then(|value| value.parse::<i32>().ok());
+
+    match state.db.get_support_assignee(tg_user_id).await? {
+        Some(assignee) if assignee.admin_id != admin_id && !force => {
+            bot.answer_callback_query(q.id.clone())
+                .text(format!("Уже в работе у {}", assignee_label(&assignee)))
+                .show_alert(true)
+                .await?;
+            if let Some((chat_id, message_id)) = callback_message_target(&q) {
+                bot.edit_message_reply_markup(chat_id, message_id)
+                    .reply_markup(support_takeover_keyboard(tg_user_id, None))
+                    .await?;
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    take_conversation(&bot, &state, tg_user_id, admin_id, &q.from.full_name()).await?;
+    bot.answer_callback_query(q.id.clone())
+        .text("Переписка закреплена за вами")
+        .await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_reply_markup(chat_id, message_id)
+            .reply_markup(support_keyboard(tg_user_id, false))
+            .await?;
+        if let Some(reply_id) = pending_reply {
+            deliver_admin_reply(&bot, &state, chat_id, MessageId(reply_id), tg_user_id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// «Ответить»: `support_reply:<tg_user_id>`. Бот присылает сообщение с принудительным
+/// ответом; ответ на него уходит пользователю так же, как reply на его сообщение.
+pub async fn callback_support_reply(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    if require_admin_callback(&bot, &q, &state).await?.is_none() {
+        return Ok(());
+    }
+    let tg_user_id = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("support_reply:"))
+        .and_then(|value| value.parse::<i64>().ok())
+        .ok_or_else(|| anyhow!("Некорректный callback переписки"))?;
+    let Some((chat_id, _)) = callback_message_target(&q) else {
+        bot.answer_callback_query(q.id.clone()).await?;
+        return Ok(());
+    };
+    bot.answer_callback_query(q.id.clone()).await?;
+    let prompt = bot
+        .send_message(
+            chat_id,
+            format!(
+                "✍️ Ответ пользователю {}: напишите его ответом на это сообщение.",
+                tg_user_id
+            ),
+        )
+        .reply_markup(ForceReply::new())
+        .await?;
+    state
+        .db
+        .add_support_message(chat_id.0, prompt.id.0, tg_user_id)
+        .await?;
+    Ok(())
+}
+
+pub fn spawn_ticket_closer(bot: Bot, state: BotState) -> Option<tokio::task::JoinHandle<()>> {
+    let support = &state.config.support;
+    if !support.enabled || support.auto_close_hours <= 0 {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        loop {
+            if let Err(error) = close_stale_tickets(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось закрыть неактивные обращения");
+            }
+            tokio::time::sleep(AUTO_CLOSE_INTERVAL).await;
+        }
+    }))
+}
+
+async fn close_stale_tickets(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    let before = chrono::Utc::now().timestamp() - state.config.support.auto_close_hours * 3_600;
+    for tg_user_id in state.db.close_stale_tickets(before).await? {
+        tracing::info!(tg_user_id = tg_user_id, "Support ticket auto-closed");
+        let lang = user_lang(state, tg_user_id).await?;
+        if let Err(error) = bot
+            .send_message(ChatId(tg_user_id), t(lang, "support_closed"))
+            .await
+        {
+            tracing::warn!(
+                tg_user_id = tg_user_id,
+                error = %error,
+                "Не удалось уведомить пользователя о закрытии обращения"
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn cmd_tickets(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let args: Vec<&str> = msg
+        .text()
+        .unwrap_or("")
+        .split_whitespace()
+        .skip(1)
+        .collect();
+    if let ["close", tg_user_id] = args.as_slice() {
+        let Ok(tg_user_id) = tg_user_id.parse::<i64>() else {
+            bot.send_message(msg.chat.id, "Использование: /tickets close <tg_user_id>")
+                .await?;
+            return Ok(());
+        };
+        let reply = if state.db.close_ticket(tg_user_id).await? {
+            tracing::info!(tg_user_id = tg_user_id, "Support ticket closed by admin");
+            format!("Обращение {} закрыто.", tg_user_id)
+        } else {
+            "Открытое обращение не найдено.".to_string()
+        };
+        bot.send_message(msg.chat.id, reply).await?;
+        return Ok(());
+    }
+
+    let tickets = state.db.list_open_tickets(TICKETS_LIMIT).await?;
+    if tickets.is_empty() {
+        bot.send_message(msg.chat.id, "Открытых обращений нет.")
+            .await?;
+        return Ok(());
+    }
+    let now = chrono::Utc::now().timestamp();
+    let mut text = String::from("🎫 Обращения:");
+    for ticket in tickets {
+        let status = match ticket.status {
+            TicketStatus::Open => "🔴 ждёт ответа",
+            TicketStatus::Answered => "🟡 отвечено",
+            TicketStatus::Closed => "закрыто",
+        };
+        let age = ticket
+            .opened_at
+            .map(|opened_at| format_wait(now - opened_at))
+            .unwrap_or_else(|| "—".to_string());
+        let owner = match (ticket.assigned_to, ticket.assigned_name) {
+            (_, Some(name)) => name,
+            (Some(admin_id), None) => format!("админ {}", admin_id),
+            (None, None) => "не назначено".to_string(),
+        };
+        let idle = ticket
+            .last_activity_at
+            .map(|last_activity_at| {
+                format!(
+                    ", последнее сообщение {} назад",
+                    format_wait(now - last_activity_at)
+                )
+            })
+            .unwrap_or_default();
+        text.push_str(&format!(
+            "\n{} — {}, открыто {} назад{}, {}",
+            ticket.tg_user_id, status, age, idle, owner
+        ));
+    }
+    text.push_str("\n\n/tickets close <tg_user_id> — закрыть обращение");
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Опрос после одобрения (`[survey]`): новому пользователю по очереди задаются
+//! один-два вопроса с кнопками, ответы сводятся на экране статистики.
+
+use super::shared::{HandlerResult, callback_message_target};
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::error::AppError;
+use anyhow::anyhow;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Планирует опрос только что одобренному пользователю; ошибка только логируется.
+pub async fn schedule_survey(state: &BotState, tg_user_id: i64) {
+    let survey = &state.config.survey;
+    if !survey.enabled || survey.questions.is_empty() {
+        return;
+    }
+    let due_at = chrono::Utc::now().timestamp() + survey.delay_hours.max(0) * 3_600;
+    if let Err(error) = state.db.schedule_survey(tg_user_id, due_at).await {
+        tracing::warn!(
+            tg_user_id = tg_user_id,
+            error = %error,
+            "Не удалось запланировать опрос пользователя"
+        );
+    }
+}
+
+pub fn spawn_survey_worker(bot: Bot, state: BotState) -> Option<tokio::task::JoinHandle<()>> {
+    if !state.config.survey.enabled {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        loop {
+            if let Err(error) = send_due_surveys(&bot, &state).await {
+                tracing::warn!(error = %error, "Не удалось разослать опрос");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }))
+}
+
+async fn send_due_surveys(bot: &Bot, state: &BotState) -> Result<(), AppError> {
+    for tg_user_id in state.db.take_due_survey_invites().await? {
+        if let Err(error) = send_question(bot, state, ChatId(tg_user_id), 0).await {
+            tracing::warn!(
+                tg_user_id = tg_user_id,
+                error = %error,
+                "Не удалось отправить пользователю вопрос опроса"
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn send_question(
+    bot: &Bot,
+    state: &BotState,
+    chat_id: ChatId,
+    question: usize,
+) -> Result<(), AppError> {
+    let Some(item) = state.config.survey.questions.get(question) else {
+        return Ok(());
+    };
+    bot.send_message(chat_id, item.text.clone())
+        .reply_markup(crate::bot::keyboards::survey_question_keyboard(
+            question,
+            &item.options,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Ответ на вопрос: `survey:<вопрос>:<вариант>`.
+pub async fn callback_survey_answer(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let data = q.data.as_deref().unwrap_or("");
+    let (question, answer) = data
+        .strip_prefix("survey:")
+        .and_then(|payload| payload.split_once(':'))
+        .and_then(|(question, answer)| {
+            Some((
+                question.parse::<usize>().ok()?,
+                answer.parse::<usize>().ok()?,
+            ))
+        })
+        .ok_or_else(|| anyhow!("Некорректный ответ опроса"))?;
+    let Some(option) = state
+        .config
+        .survey
+        .questions
+        .get(question)
+        .and_then(|item| item.options.get(answer))
+    else {
+        bot.answer_callback_query(q.id.clone())
+            .text("Опрос изменился, спасибо!")
+            .await?;
+        return Ok(());
+    };
+
+    let tg_user_id = q.from.id.0 as i64;
+    let recorded = state
+        .db
+        .record_survey_answer(tg_user_id, question as i64, answer as i64)
+        .await?;
+    bot.answer_callback_query(q.id.clone())
+        .text("Спасибо!")
+        .await?;
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, format!("✅ {}", option))
+            .reply_markup(InlineKeyboardMarkup::default())
+            .await?;
+        if recorded {
+            tracing::info!(
+                tg_user_id = tg_user_id,
+                question = question,
+                answer = answer,
+                "Survey answer recorded"
+            );
+            send_question(&bot, &state, chat_id, question + 1).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Сводка ответов для экрана статистики; `None`, если опрос выключен или ответов нет.
+pub async fn render_survey_stats(state: &BotState) -> Result<Option<String>, AppError> {
+    if !state.config.survey.enabled {
+        return Ok(None);
+    }
+    let counts = state.db.survey_answer_counts().await?;
+    if counts.is_empty() {
+        return Ok(None);
+    }
+    let mut text = String::from("📝 Опрос:");
+    for (index, item) in state.config.survey.questions.iter().enumerate() {
+        let answers: Vec<_> = counts
+            .iter()
+            .filter(|count| count.question == index as i64)
+            .collect();
+        if answers.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("\n{}", item.text));
+        for count in answers {
+            let option = usize::try_from(count.answer)
+                .ok()
+                .and_then(|answer| item.options.get(answer))
+                .map(String::as_str)
+                .unwrap_or("—");
+            text.push_str(&format!("\n  {} — {}", option, count.count));
+        }
+    }
+    Ok(Some(text))
+}
@@ -0,0 +1,271 @@
+//! `/sync`: сверка одобренных пользователей в БД с `[access.users]` конфига telemt.
+//! Расхождения (правка конфига вручную, восстановление из бэкапа, сбой между записью
+//! конфига и БД) показываются админу с кнопками исправления; при старте бота та же
+//! сверка выполняется один раз и присылается админам, только если что-то нашлось.
+//! Записи конфига не вида `tg_<id>` считаются ручными и не трогаются. Та же сверка
+//! доступна без Telegram: `telemt-admin sync [--fix]`.
+
+use super::shared::{
+    HandlerResult, admin_destinations, callback_message_target, require_admin_callback,
+};
+use super::state::{BotState, is_admin_message, sender_user_id, telemt_username};
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::db::Db;
+use crate::error::AppError;
+use crate::telemt_cfg::{TelemtConfig, UserMutation};
+use std::collections::HashMap;
+use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
+
+/// Сколько имён каждой категории показывать в отчёте.
+const MAX_LISTED: usize = 20;
+
+/// Расхождения между БД и конфигом telemt.
+pub struct Drift {
+    /// Активные в БД, но отсутствующие в конфиге: имя и секрет из БД.
+    pub missing: Vec<(String, String)>,
+    /// Есть в обоих местах, но секрет в конфиге отличается от БД.
+    pub mismatched: Vec<(String, String)>,
+    /// Записи `tg_<id>` в конфиге без активного пользователя в БД.
+    pub dangling: Vec<String>,
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty() && self.dangling.is_empty()
+    }
+
+    /// Вернуть в конфиг одобренных пользователей с секретами из БД.
+    pub fn readd_mutations(&self) -> Vec<UserMutation> {
+        self.missing
+            .iter()
+            .chain(&self.mismatched)
+            .map(|(username, secret)| UserMutation::Upsert {
+                username: username.clone(),
+                secret: secret.clone(),
+            })
+            .collect()
+    }
+
+    /// Убрать из конфига записи без активного пользователя в БД.
+    pub fn remove_mutations(&self) -> Vec<UserMutation> {
+        self.dangling
+            .iter()
+            .map(|username| UserMutation::Remove {
+                username: username.clone(),
+            })
+            .collect()
+    }
+}
+
+pub async fn detect_drift(db: &Db, telemt_cfg: &TelemtConfig) -> Result<Drift, AppError> {
+    let configured: HashMap<String, String> = telemt_cfg.read_users().await?.into_iter().collect();
+    let mut expected: HashMap<String, String> = HashMap::new();
+    for user in db.list_users_expected_in_config().await? {
+        let Some(secret) = user.secret else {
+            continue;
+        };
+        let username = user
+            .telemt_username
+            .unwrap_or_else(|| telemt_username(user.tg_user_id));
+        expected.insert(username, secret);
+    }
+
+    let mut drift = Drift {
+        missing: Vec::new(),
+        mismatched: Vec::new(),
+        dangling: Vec::new(),
+    };
+    for (username, secret) in &expected {
+        match configured.get(username) {
+            None => drift.missing.push((username.clone(), secret.clone())),
+            Some(current) if current != secret => {
+                drift.mismatched.push((username.clone(), secret.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for username in configured.keys() {
+        let managed = username
+            .strip_prefix("tg_")
+            .is_some_and(|id| id.parse::<i64>().is_ok());
+        if managed && !expected.contains_key(username) {
+            drift.dangling.push(username.clone());
+        }
+    }
+    drift.missing.sort();
+    drift.mismatched.sort();
+    drift.dangling.sort();
+    Ok(drift)
+}
+
+fn render_names<'a>(names: impl Iterator<Item = &'a String>, total: usize) -> String {
+    let mut listed: Vec<&str> = names.take(MAX_LISTED).map(String::as_str).collect();
+    if total > MAX_LISTED {
+        listed.push("…");
+    }
+    listed.join(" ")
+}
+
+pub fn render_drift(drift: &Drift) -> String {
+    if drift.is_empty() {
+        return "✅ БД и конфиг telemt совпадают.".to_string();
+    }
+    let mut text = String::from("🔍 Расхождения между БД и конфигом telemt:");
+    if !drift.missing.is_empty() {
+        text.push_str(&format!(
+            "\n\nНет в конфиге, хотя доступ одобрен ({}):\n{}",
+            drift.missing.len(),
+            render_names(
+                drift.missing.iter().map(|(name, _)| name),
+                drift.missing.len()
+            )
+        ));
+    }
+    if !drift.mismatched.is_empty() {
+        text.push_str(&format!(
+            "\n\nСекрет в конфиге отличается от БД ({}):\n{}",
+            drift.mismatched.len(),
+            render_names(
+                drift.mismatched.iter().map(|(name, _)| name),
+                drift.mismatched.len()
+            )
+        ));
+    }
+    if !drift.dangling.is_empty() {
+        text.push_str(&format!(
+            "\n\nЕсть в конфиге, но нет активного пользователя в БД ({}):\n{}",
+            drift.dangling.len(),
+            render_names(drift.dangling.iter(), drift.dangling.len())
+        ));
+    }
+    text
+}
+
+fn drift_keyboard(drift: &Drift) -> InlineKeyboardMarkup {
+    crate::bot::keyboards::sync_fix_keyboard(
+        drift.missing.len() + drift.mismatched.len(),
+        drift.dangling.len(),
+    )
+}
+
+pub async fn cmd_sync(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    tracing::info!(
+        admin_id = sender_user_id(&msg).unwrap_or_default(),
+        "Admin command /sync"
+    );
+    let drift = detect_drift(&state.db, &state.telemt_cfg).await?;
+    let mut request = bot.send_message(msg.chat.id, render_drift(&drift));
+    if !drift.is_empty() {
+        request = request.reply_markup(drift_keyboard(&drift));
+    }
+    request.await?;
+    Ok(())
+}
+
+/// Сверка при старте бота: админы получают отчёт, только если есть расхождения.
+pub async fn check_config_drift_on_startup(bot: Bot, state: BotState) {
+    let drift = match detect_drift(&state.db, &state.telemt_cfg).await {
+        Ok(drift) => drift,
+        Err(error) => {
+            tracing::warn!(error = %error, "Не удалось сверить БД с конфигом telemt при старте");
+            return;
+        }
+    };
+    if drift.is_empty() {
+        tracing::info!("Startup sync check: DB and telemt config match");
+        return;
+    }
+    tracing::warn!(
+        missing = drift.missing.len(),
+        mismatched = drift.mismatched.len(),
+        dangling = drift.dangling.len(),
+        "БД и конфиг telemt расходятся"
+    );
+    let text = format!("{}\n\n(проверка при запуске бота)", render_drift(&drift));
+    for destination in admin_destinations(&state, AdminTopic::Alerts) {
+        if let Err(error) = destination
+            .send_message(&bot, text.clone())
+            .reply_markup(drift_keyboard(&drift))
+            .await
+        {
+            tracing::warn!(
+                chat_id = destination.chat_id.0,
+                error = %error,
+                "Не удалось отправить админу отчёт о расхождениях"
+            );
+        }
+    }
+}
+
+/// `sync:readd`, `sync:remove` или `sync:cancel`. Расхождения пересчитываются в момент
+/// нажатия, чтобы не применить устаревший отчёт.
+pub async fn callback_sync(bot: Bot, q: CallbackQuery, state: BotState) -> HandlerResult {
+    let Some(admin_id) = require_admin_callback(&bot, &q, &state).await? else {
+        return Ok(());
+    };
+    let action = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("sync:"))
+        .unwrap_or("");
+
+    let (text, markup) = match action {
+        "readd" | "remove" => {
+            bot.answer_callback_query(q.id.clone())
+                .text("Исправляю…")
+                .await?;
+            let drift = detect_drift(&state.db, &state.telemt_cfg).await?;
+            let (mutations, audit_action) = if action == "readd" {
+                (drift.readd_mutations(), "sync_readd")
+            } else {
+                (drift.remove_mutations(), "sync_remove")
+            };
+            let count = mutations.len();
+            if count > 0 {
+                // Одна запись и один рестарт на все исправления.
+                state.cfg_writer.apply(mutations).await?;
+                state
+                    .audit
+                    .record(
+                        admin_id,
+                        audit_action,
+                        "telemt:access.users",
+                        &format!("записей: {}", count),
+                    )
+                    .await;
+            }
+            tracing::info!(action = action, count = count, "Config drift fixed");
+            let remaining = detect_drift(&state.db, &state.telemt_cfg).await?;
+            let text = format!(
+                "🔧 Исправлено записей: {}.\n\n{}",
+                count,
+                render_drift(&remaining)
+            );
+            // Оставшиеся расхождения другой категории можно исправить тем же сообщением.
+            let markup = if remaining.is_empty() {
+                InlineKeyboardMarkup::default()
+            } else {
+                drift_keyboard(&remaining)
+            };
+            (text, markup)
+        }
+        _ => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            (
+                "Сверка закрыта без изменений.".to_string(),
+                InlineKeyboardMarkup::default(),
+            )
+        }
+    };
+    if let Some((chat_id, message_id)) = callback_message_target(&q) {
+        bot.edit_message_text(chat_id, message_id, text)
+            .reply_markup(markup)
+            .await?;
+    }
+    Ok(())
+}
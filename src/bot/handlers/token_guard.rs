@@ -0,0 +1,124 @@
+//! Защита от подбора invite-токенов (`[security] token_max_failures`): неверные
+//! токены подряд считаются в `token_attempts`, после лимита ввод блокируется с
+//! удвоением срока на каждую следующую ошибку, а админы получают уведомление.
+
+use super::format::format_wait;
+use super::shared::admin_destinations;
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::config::AdminTopic;
+use crate::error::AppError;
+use crate::i18n::{Lang, tf};
+use chrono::Utc;
+use teloxide::prelude::*;
+
+/// Серия неудач начинается заново после суток без ошибок.
+const FAILURE_RESET_SECS: i64 = 86_400;
+/// Дольше суток ввод не блокируется.
+const MAX_LOCKOUT_SECS: i64 = 86_400;
+
+/// `true` — ввод токенов временно запрещён, пользователю уже отправлен отказ.
+pub async fn token_entry_locked(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    tg_user_id: i64,
+    lang: Lang,
+) -> Result<bool, AppError> {
+    if state.config.security.token_max_failures <= 0 {
+        return Ok(false);
+    }
+    let Some(locked_until) = state.db.token_locked_until(tg_user_id).await? else {
+        return Ok(false);
+    };
+    let remaining = locked_until - Utc::now().timestamp();
+    bot.send_message(chat_id, locked_text(lang, remaining))
+        .await?;
+    Ok(true)
+}
+
+/// Учитывает неверный токен. `Some(secs)` — после этой ошибки ввод заблокирован.
+pub async fn record_token_failure(
+    bot: &Bot,
+    state: &BotState,
+    tg_user_id: i64,
+    tg_username: Option<&str>,
+) -> Result<Option<i64>, AppError> {
+    let security = &state.config.security;
+    if security.token_max_failures <= 0 {
+        return Ok(None);
+    }
+    let failures = state
+        .db
+        .record_token_failure(tg_user_id, FAILURE_RESET_SECS)
+        .await?;
+    let over = failures - security.token_max_failures;
+    if over < 0 {
+        return Ok(None);
+    }
+    let lockout_secs = security
+        .token_lockout_secs
+        .max(1)
+        .saturating_mul(1_i64 << over.min(20))
+        .min(MAX_LOCKOUT_SECS);
+    state
+        .db
+        .lock_token_attempts(tg_user_id, Utc::now().timestamp() + lockout_secs)
+        .await?;
+    tracing::warn!(
+        tg_user_id = tg_user_id,
+        failures = failures,
+        lockout_secs = lockout_secs,
+        "Ввод токенов заблокирован после серии неверных попыток"
+    );
+    state
+        .audit
+        .record_system(
+            "token_lockout",
+            &format!("tg_user:{}", tg_user_id),
+            &format!(
+                "неудач подряд: {}, блокировка: {} с",
+                failures, lockout_secs
+            ),
+        )
+        .await;
+    // Админам — только о начале серии блокировок, дальше срок просто удваивается.
+    if over == 0 {
+        notify_admins(bot, state, tg_user_id, tg_username, failures, lockout_secs).await;
+    }
+    Ok(Some(lockout_secs))
+}
+
+pub fn locked_text(lang: Lang, remaining_secs: i64) -> String {
+    let minutes = (remaining_secs.max(1) + 59) / 60;
+    tf(lang, "token_locked", &[("minutes", &minutes)])
+}
+
+async fn notify_admins(
+    bot: &Bot,
+    state: &BotState,
+    tg_user_id: i64,
+    tg_username: Option<&str>,
+    failures: i64,
+    lockout_secs: i64,
+) {
+    let text = format!(
+        "🛡 Похоже на подбор invite-токена: пользователь {} (@{}) ввёл неверный токен \
+         {} раз подряд. Ввод заблокирован на {}, при новых ошибках срок удваивается.\n\
+         Заблокировать пользователя: /ban {}",
+        tg_user_id,
+        tg_username.unwrap_or("—"),
+        failures,
+        format_wait(lockout_secs),
+        tg_user_id
+    );
+    for destination in admin_destinations(state, AdminTopic::Alerts) {
+        if let Err(error) = destination.send_message(bot, text.clone()).await {
+            tracing::warn!(
+                chat_id = destination.chat_id.0,
+                error = %error,
+                "Не удалось отправить админу уведомление о подборе токена"
+            );
+        }
+    }
+}
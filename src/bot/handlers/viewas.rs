@@ -0,0 +1,138 @@
+//! `/viewas`: режим «глазами пользователя» для разбора жалоб вида «у меня нет кнопки».
+//! Пока режим включён, /start, /link и кнопки меню админа отвечают так, как ответили бы
+//! выбранному пользователю, но без изменений в БД и конфиге telemt. Режим хранится в
+//! памяти и сбрасывается перезапуском бота.
+
+use super::allowlist::registration_allowed;
+use super::shared::{HandlerResult, blocked_text, send_user_link, user_lang};
+use super::state::{BotState, is_admin_message, sender_user_id, telemt_username};
+use crate::bot::Bot;
+use crate::db::RequestStatus;
+use crate::i18n::t;
+use teloxide::prelude::*;
+
+const VIEWAS_USAGE: &str = "Использование:
+/viewas <tg_user_id> — видеть бота глазами пользователя (/start, /link, меню)
+/viewas off — вернуться в режим администратора";
+
+/// Пользователь, глазами которого админ сейчас смотрит на бота.
+pub async fn viewed_user(state: &BotState, admin_id: i64) -> Option<i64> {
+    state.view_as.lock().await.get(&admin_id).copied()
+}
+
+pub async fn cmd_viewas(bot: Bot, msg: Message, state: BotState) -> HandlerResult {
+    if !is_admin_message(&msg, &state) {
+        return Ok(());
+    }
+    let Some(admin_id) = sender_user_id(&msg) else {
+        return Ok(());
+    };
+    let arg = msg.text().unwrap_or("").split_whitespace().nth(1);
+
+    match arg {
+        Some("off") => {
+            let previous = state.view_as.lock().await.remove(&admin_id);
+            let text = match previous {
+                Some(tg_user_id) => format!(
+                    "Режим просмотра глазами {} выключен.",
+                    telemt_username(tg_user_id)
+                ),
+                None => "Режим просмотра не был включён.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text)
+                .reply_markup(crate::bot::keyboards::admin_menu())
+                .await?;
+        }
+        Some(value) => {
+            let Ok(tg_user_id) = value.parse::<i64>() else {
+                bot.send_message(msg.chat.id, VIEWAS_USAGE).await?;
+                return Ok(());
+            };
+            if state.config.is_admin(tg_user_id) {
+                bot.send_message(
+                    msg.chat.id,
+                    "Это администратор — его экраны совпадают с вашими.",
+                )
+                .await?;
+                return Ok(());
+            }
+            state.view_as.lock().await.insert(admin_id, tg_user_id);
+            tracing::info!(
+                admin_id = admin_id,
+                tg_user_id = tg_user_id,
+                "Admin entered view-as-user mode"
+            );
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "👁 Вы видите бота глазами {}: /start, /link и кнопки меню отвечают как ему. \
+                     Действия ничего не меняют. /viewas off — выйти.\n\nЭкран /start:",
+                    telemt_username(tg_user_id)
+                ),
+            )
+            .reply_markup(crate::bot::keyboards::user_menu(
+                user_lang(&state, tg_user_id).await?,
+            ))
+            .await?;
+            preview_start(&bot, msg.chat.id, &state, tg_user_id).await?;
+        }
+        None => {
+            let text = match viewed_user(&state, admin_id).await {
+                Some(tg_user_id) => format!(
+                    "👁 Сейчас вы видите бота глазами {}.\n\n{}",
+                    telemt_username(tg_user_id),
+                    VIEWAS_USAGE
+                ),
+                None => VIEWAS_USAGE.to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Экран /start пользователя без побочных эффектов: токены не применяются,
+/// провижининг и ожидание токена не запускаются.
+pub async fn preview_start(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &BotState,
+    tg_user_id: i64,
+) -> HandlerResult {
+    let request = state
+        .db
+        .get_request_by_tg_user(tg_user_id)
+        .await?
+        .map(|request| (request.status, request.secret.is_some()));
+    let lang = user_lang(state, tg_user_id).await?;
+    let text = if state.db.is_user_blocked(tg_user_id).await? {
+        blocked_text(state, lang)
+    } else {
+        match request {
+            Some((RequestStatus::Approved, _))
+                if state.db.is_user_suspended(tg_user_id).await? =>
+            {
+                t(lang, "suspended")
+            }
+            Some((RequestStatus::Approved, true)) => {
+                return send_user_link(bot, chat_id, tg_user_id, state).await;
+            }
+            Some((RequestStatus::Pending, _)) => t(lang, "pending"),
+            Some((RequestStatus::Rejected, _)) => t(lang, "rejected"),
+            _ if state
+                .db
+                .get_unconsumed_provision(tg_user_id)
+                .await?
+                .is_some() =>
+            {
+                "(Пользователь есть в [[provision]]: на /start доступ будет одобрен автоматически.)"
+            }
+            _ if !registration_allowed(state, tg_user_id).await? => blocked_text(state, lang),
+            _ => t(lang, "invite_prompt"),
+        }
+    };
+    bot.send_message(chat_id, text)
+        .reply_markup(crate::bot::keyboards::user_menu(lang))
+        .await?;
+    Ok(())
+}
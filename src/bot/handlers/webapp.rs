@@ -0,0 +1,577 @@
+//! Mini App для админов (`[web] admin_app`): страница `/admin` открывается кнопкой
+//! меню бота и работает через JSON API `/admin/api/...` встроенного веб-сервера.
+//! Каждый запрос подписан initData Telegram WebApp (заголовок `X-Telegram-Init-Data`):
+//! подпись проверяется ключом от токена бота, пускаются только основные админы.
+//! Действия идут через те же функции, что и кнопки в чате, с записью в аудит.
+
+use super::config_preview::{ConfigAction, preview_if_enabled};
+use super::expiry::parse_access_duration;
+use super::format::user_display_name;
+use super::pending::claimed_by_other;
+use super::shared::{
+    approve_pending_request, parse_users_page_request, perform_hard_ban, reject_pending_request,
+};
+use super::state::BotState;
+use crate::bot::Bot;
+use crate::bot::keyboards::users_page_payload;
+use crate::db::{NewInviteToken, UsersPageRequest};
+use crate::error::AppError;
+use crate::link::build_bot_start_link;
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::collections::HashMap;
+use teloxide::prelude::*;
+use teloxide::types::{MenuButton, WebAppInfo};
+
+/// Страница Mini App: разметка и скрипт без внешних зависимостей, кроме SDK Telegram.
+pub const ADMIN_APP_PAGE: &str = include_str!("webapp.html");
+
+/// Сколько действует подпись initData.
+const INIT_DATA_MAX_AGE_SECS: i64 = 24 * 60 * 60;
+
+/// Размер страницы списка пользователей.
+const USERS_PAGE_SIZE: i64 = 50;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Ответ API: HTTP-статус и JSON-тело.
+pub struct ApiResponse {
+    pub status: &'static str,
+    pub body: String,
+}
+
+impl ApiResponse {
+    fn ok(value: Value) -> Self {
+        Self {
+            status: "200 OK",
+            body: value.to_string(),
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::error(
+            "401 Unauthorized",
+            "Откройте панель кнопкой меню бота; доступ только для админов",
+        )
+    }
+
+    fn error(status: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }).to_string(),
+        }
+    }
+}
+
+/// Бот и состояние для действий Mini App; создаётся заново при каждом переподключении бота.
+#[derive(Clone)]
+pub struct AdminApp {
+    bot: Bot,
+    state: BotState,
+}
+
+impl AdminApp {
+    /// `None`, если Mini App выключена в конфиге.
+    pub fn new(bot: Bot, state: BotState) -> Option<Self> {
+        state.config.web.admin_app_url()?;
+        Some(Self { bot, state })
+    }
+
+    /// Проверяет initData и возвращает id админа, от имени которого пришёл запрос.
+    pub fn authorize(&self, init_data: &str) -> Option<i64> {
+        let admin_id = verify_init_data(
+            self.bot.inner().token(),
+            init_data,
+            chrono::Utc::now().timestamp(),
+        )?;
+        self.state.config.is_admin(admin_id).then_some(admin_id)
+    }
+
+    /// Запрос к API: `route` — путь после `/admin/api/`, `query` — строка параметров.
+    pub async fn handle(
+        &self,
+        admin_id: i64,
+        method: &str,
+        route: &str,
+        query: &str,
+    ) -> ApiResponse {
+        let params = parse_query(query);
+        let result = match (method, route) {
+            ("GET", "overview") => self.overview().await,
+            ("GET", "users") => self.users(&params).await,
+            ("POST", "users/delete") => self.delete_user(admin_id, &params).await,
+            ("GET", "pending") => self.pending().await,
+            ("POST", "pending/approve") => self.approve(admin_id, &params).await,
+            ("POST", "pending/reject") => self.reject(admin_id, &params).await,
+            ("GET", "tokens") => self.tokens().await,
+            ("POST", "tokens/create") => self.create_token(admin_id, &params).await,
+            ("POST", "tokens/revoke") => self.revoke_token(admin_id, &params).await,
+            ("GET", "service") => self.service_status().await,
+            ("POST", "service/restart" | "service/reload") => {
+                self.service_action(admin_id, route.trim_start_matches("service/"))
+                    .await
+            }
+            _ => return ApiResponse::error("404 Not Found", "Неизвестный запрос"),
+        };
+        result.unwrap_or_else(|error| {
+            tracing::warn!(admin_id = admin_id, route = %route, error = %error, "Ошибка запроса Mini App");
+            ApiResponse::error("500 Internal Server Error", error.to_string())
+        })
+    }
+
+    async fn overview(&self) -> Result<ApiResponse, AppError> {
+        let stats = self.state.db.admin_stats().await?;
+        Ok(ApiResponse::ok(json!({
+            "total": stats.total,
+            "pending": stats.pending,
+            "approved": stats.approved,
+            "rejected": stats.rejected,
+            "deleted": stats.deleted,
+            "service_active": self.state.service.is_active().await,
+        })))
+    }
+
+    async fn users(&self, params: &HashMap<String, String>) -> Result<ApiResponse, AppError> {
+        let query = params.get("q").map(|q| q.trim()).unwrap_or("");
+        if !query.is_empty() {
+            let hits = self.state.db.search_users(query, USERS_PAGE_SIZE).await?;
+            let users: Vec<Value> = hits
+                .iter()
+                .map(|hit| {
+                    json!({
+                        "tg_user_id": hit.tg_user_id,
+                        "name": super::format::search_hit_title(hit),
+                        "username": hit.tg_username,
+                        "status": hit.status.to_string(),
+                        "note": hit.note,
+                    })
+                })
+                .collect();
+            return Ok(ApiResponse::ok(
+                json!({ "users": users, "prev": null, "next": null }),
+            ));
+        }
+
+        // Тот же keyset-курсор, что у кнопок списка в чате (`users_page_payload`).
+        let request = match params.get("page").filter(|value| !value.is_empty()) {
+            Some(payload) => match parse_users_page_request(payload) {
+                Ok(request) => request,
+                Err(_) => {
+                    return Ok(ApiResponse::error(
+                        "400 Bad Request",
+                        "Некорректный курсор страницы",
+                    ));
+                }
+            },
+            None => UsersPageRequest::First,
+        };
+        let page = self
+            .state
+            .db
+            .list_active_users_page(USERS_PAGE_SIZE, request)
+            .await?;
+        let (prev, next) = match (page.users.first(), page.users.last()) {
+            (Some(first), Some(last)) => (
+                page.has_prev
+                    .then(|| users_page_payload(UsersPageRequest::Before(first.cursor()))),
+                page.has_next
+                    .then(|| users_page_payload(UsersPageRequest::After(last.cursor()))),
+            ),
+            _ => (None, None),
+        };
+        let users: Vec<Value> = page
+            .users
+            .iter()
+            .map(|user| {
+                json!({
+                    "tg_user_id": user.tg_user_id,
+                    "name": user_display_name(user),
+                    "username": user.tg_username,
+                    "status": user.status.to_string(),
+                    "telemt_username": user.telemt_username,
+                    "created_at": user.created_at,
+                })
+            })
+            .collect();
+        Ok(ApiResponse::ok(
+            json!({ "users": users, "prev": prev, "next": next }),
+        ))
+    }
+
+    async fn delete_user(
+        &self,
+        admin_id: i64,
+        params: &HashMap<String, String>,
+    ) -> Result<ApiResponse, AppError> {
+        let Some(tg_user_id) = int_param(params, "id") else {
+            return Ok(ApiResponse::error("400 Bad Request", "Не указан id"));
+        };
+        if self
+            .preview(admin_id, ConfigAction::Delete { tg_user_id })
+            .await?
+        {
+            return Ok(preview_sent());
+        }
+        let status_text = perform_hard_ban(&self.state, tg_user_id).await?;
+        self.state
+            .audit
+            .record(
+                admin_id,
+                "delete",
+                &format!("tg_user:{}", tg_user_id),
+                "mini app",
+            )
+            .await;
+        Ok(ApiResponse::ok(json!({ "message": status_text })))
+    }
+
+    async fn pending(&self) -> Result<ApiResponse, AppError> {
+        let requests = self
+            .state
+            .db
+            .list_pending_requests(USERS_PAGE_SIZE, 0)
+            .await?;
+        let requests: Vec<Value> = requests
+            .iter()
+            .map(|request| {
+                json!({
+                    "id": request.id,
+                    "tg_user_id": request.tg_user_id,
+                    "name": user_display_name(request),
+                    "username": request.tg_username,
+                    "created_at": request.created_at,
+                })
+            })
+            .collect();
+        Ok(ApiResponse::ok(json!({ "requests": requests })))
+    }
+
+    async fn approve(
+        &self,
+        admin_id: i64,
+        params: &HashMap<String, String>,
+    ) -> Result<ApiResponse, AppError> {
+        let Some(request_id) = int_param(params, "id") else {
+            return Ok(ApiResponse::error("400 Bad Request", "Не указан id"));
+        };
+        let duration_secs = match params.get("duration").filter(|value| !value.is_empty()) {
+            Some(value) => match parse_access_duration(value) {
+                Some(seconds) => Some(seconds),
+                None => {
+                    return Ok(ApiResponse::error(
+                        "400 Bad Request",
+                        "Некорректный срок доступа",
+                    ));
+                }
+            },
+            None => None,
+        };
+        if let Some(holder) = claimed_by_other(&self.state, request_id, admin_id).await? {
+            return Ok(ApiResponse::error(
+                "409 Conflict",
+                format!("Заявку #{} разбирает {}", request_id, holder),
+            ));
+        }
+        let action = ConfigAction::Approve {
+            request_id,
+            duration_secs,
+        };
+        if self.preview(admin_id, action).await? {
+            return Ok(preview_sent());
+        }
+        let approved =
+            approve_pending_request(&self.bot, &self.state, admin_id, request_id, duration_secs)
+                .await?;
+        Ok(match approved {
+            Some(approved) => ApiResponse::ok(json!({
+                "message": format!("Заявка #{} одобрена", request_id),
+                "expires_at": approved.expires_at,
+            })),
+            None => ApiResponse::error("409 Conflict", "Заявка уже обработана или не найдена"),
+        })
+    }
+
+    /// С `preview_config_changes` действие не применяется сразу: предпросмотр с
+    /// кнопками подтверждения уходит админу в личный чат с ботом.
+    async fn preview(&self, admin_id: i64, action: ConfigAction) -> Result<bool, AppError> {
+        preview_if_enabled(&self.bot, ChatId(admin_id), &self.state, admin_id, action).await
+    }
+
+    async fn reject(
+        &self,
+        admin_id: i64,
+        params: &HashMap<String, String>,
+    ) -> Result<ApiResponse, AppError> {
+        let Some(request_id) = int_param(params, "id") else {
+            return Ok(ApiResponse::error("400 Bad Request", "Не указан id"));
+        };
+        if let Some(holder) = claimed_by_other(&self.state, request_id, admin_id).await? {
+            return Ok(ApiResponse::error(
+                "409 Conflict",
+                format!("Заявку #{} разбирает {}", request_id, holder),
+            ));
+        }
+        let reason = params
+            .get("reason")
+            .map(|reason| reason.trim())
+            .filter(|reason| !reason.is_empty());
+        let rejected =
+            reject_pending_request(&self.bot, &self.state, admin_id, request_id, reason).await?;
+        Ok(match rejected {
+            Some(_) => ApiResponse::ok(json!({
+                "message": format!("Заявка #{} отклонена", request_id),
+            })),
+            None => ApiResponse::error("409 Conflict", "Заявка уже обработана или не найдена"),
+        })
+    }
+
+    async fn tokens(&self) -> Result<ApiResponse, AppError> {
+        let tokens = self.state.db.list_active_invite_tokens(50).await?;
+        let tokens: Vec<Value> = tokens
+            .iter()
+            .map(|token| {
+                json!({
+                    "token": token.token,
+                    "link": self.invite_link(&token.token),
+                    "auto_approve": token.auto_approve,
+                    "usage_count": token.usage_count,
+                    "max_usage": token.max_usage,
+                    "expires_at": token.expires_at,
+                    "for_tg_user_id": token.for_tg_user_id,
+                    "label": token.label,
+                })
+            })
+            .collect();
+        Ok(ApiResponse::ok(json!({ "tokens": tokens })))
+    }
+
+    async fn create_token(
+        &self,
+        admin_id: i64,
+        params: &HashMap<String, String>,
+    ) -> Result<ApiResponse, AppError> {
+        let security = &self.state.config.security;
+        let days = int_param(params, "days").unwrap_or(security.default_token_days);
+        if days < 1 || days > security.max_token_days {
+            return Ok(ApiResponse::error(
+                "400 Bad Request",
+                format!("Срок действия — от 1 до {} дней", security.max_token_days),
+            ));
+        }
+        let auto_approve = params.get("auto").is_some_and(|value| value == "1");
+        if auto_approve && !security.allow_auto_approve_tokens {
+            return Ok(ApiResponse::error(
+                "403 Forbidden",
+                "Автоподтверждение токенов запрещено в конфигурации",
+            ));
+        }
+        let max_usage = int_param(params, "max_uses").filter(|value| *value > 0);
+        let token = self
+            .state
+            .db
+            .create_invite_token(NewInviteToken {
+                days,
+                auto_approve,
+                max_usage,
+                created_by: Some(admin_id),
+                for_tg_user_id: None,
+                plans: &[],
+                label: None,
+                valid_from: None,
+                group_name: None,
+                referrer_tg_user_id: None,
+            })
+            .await?;
+        self.state
+            .audit
+            .record(
+                admin_id,
+                "token_create",
+                &format!("token:{}", token.token),
+                &format!("days={} auto={} mini app", days, auto_approve),
+            )
+            .await;
+        let link = self.invite_link(&token.token);
+        Ok(ApiResponse::ok(json!({
+            "message": format!("Токен {} создан\n{}", token.token, link.as_deref().unwrap_or("")),
+            "token": token.token,
+            "link": link,
+            "expires_at": token.expires_at,
+        })))
+    }
+
+    async fn revoke_token(
+        &self,
+        admin_id: i64,
+        params: &HashMap<String, String>,
+    ) -> Result<ApiResponse, AppError> {
+        let Some(token) = params.get("token").filter(|token| !token.is_empty()) else {
+            return Ok(ApiResponse::error("400 Bad Request", "Не указан токен"));
+        };
+        if !self.state.db.revoke_invite_token(token).await? {
+            return Ok(ApiResponse::error(
+                "404 Not Found",
+                "Токен не найден или уже отозван",
+            ));
+        }
+        self.state
+            .audit
+            .record(
+                admin_id,
+                "token_revoke",
+                &format!("token:{}", token),
+                "mini app",
+            )
+            .await;
+        Ok(ApiResponse::ok(
+            json!({ "message": format!("Токен {} отозван", token) }),
+        ))
+    }
+
+    async fn service_status(&self) -> Result<ApiResponse, AppError> {
+        let result = self.state.service.status().await;
+        Ok(ApiResponse::ok(json!({
+            "active": result.success,
+            "report": super::shared::render_service_report(&self.state, "status", &result).await,
+        })))
+    }
+
+    async fn service_action(&self, admin_id: i64, action: &str) -> Result<ApiResponse, AppError> {
+        let (action, result) = match action {
+            "restart" => {
+                let reason = format!("Mini App от админа {}", admin_id);
+                match self.state.service.restart(&reason, false).await {
+                    Ok(result) => ("restart", result),
+                    Err(denied) => {
+                        return Ok(ApiResponse::error(
+                            "429 Too Many Requests",
+                            denied.to_string(),
+                        ));
+                    }
+                }
+            }
+            _ => ("reload", self.state.service.reload().await),
+        };
+        self.state
+            .audit
+            .record(
+                admin_id,
+                "service",
+                &self.state.config.service_name,
+                &format!("{}: {} (mini app)", action, result.status_label()),
+            )
+            .await;
+        Ok(ApiResponse::ok(json!({
+            "active": result.success,
+            "report": super::shared::render_service_report(&self.state, action, &result).await,
+        })))
+    }
+
+    fn invite_link(&self, token: &str) -> Option<String> {
+        self.state
+            .bot_username
+            .as_deref()
+            .map(|bot_username| build_bot_start_link(bot_username, token))
+    }
+}
+
+/// Ставит кнопку меню с Mini App в личных чатах админов.
+pub async fn setup_admin_app_menu_button(bot: &Bot, state: &BotState) {
+    let Some(url) = state.config.web.admin_app_url() else {
+        return;
+    };
+    let url = match reqwest::Url::parse(&url) {
+        Ok(url) => url,
+        Err(error) => {
+            tracing::warn!(url = %url, error = %error, "Некорректный адрес Mini App админов");
+            return;
+        }
+    };
+    for admin_id in &state.config.admin_ids {
+        let button = MenuButton::WebApp {
+            text: "🛠 Админка".to_string(),
+            web_app: WebAppInfo { url: url.clone() },
+        };
+        if let Err(error) = bot
+            .set_chat_menu_button()
+            .chat_id(ChatId(*admin_id))
+            .menu_button(button)
+            .await
+        {
+            tracing::warn!(
+                admin_id = admin_id,
+                error = %error,
+                "Не удалось установить кнопку Mini App админу"
+            );
+        }
+    }
+}
+
+/// Проверка initData по алгоритму Telegram: HMAC-SHA256 отсортированных пар
+/// `key=value` ключом HMAC-SHA256("WebAppData", токен бота). Возвращает id
+/// пользователя, если подпись верна и не старше [`INIT_DATA_MAX_AGE_SECS`].
+fn verify_init_data(bot_token: &str, init_data: &str, now: i64) -> Option<i64> {
+    let mut pairs = Vec::new();
+    let mut hash = None;
+    for pair in init_data.split('&') {
+        let (key, value) = pair.split_once('=')?;
+        let value = urlencoding::decode(value).ok()?.into_owned();
+        if key == "hash" {
+            hash = Some(value);
+        } else {
+            pairs.push((key.to_string(), value));
+        }
+    }
+    let hash = hex::decode(hash?).ok()?;
+    pairs.sort();
+    let data_check_string = pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut secret = HmacSha256::new_from_slice(b"WebAppData").ok()?;
+    secret.update(bot_token.as_bytes());
+    let secret = secret.finalize().into_bytes();
+    let mut mac = HmacSha256::new_from_slice(&secret).ok()?;
+    mac.update(data_check_string.as_bytes());
+    mac.verify_slice(&hash).ok()?;
+
+    let field = |name: &str| {
+        pairs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let auth_date = field("auth_date")?.parse::<i64>().ok()?;
+    if now - auth_date > INIT_DATA_MAX_AGE_SECS {
+        return None;
+    }
+    let user: Value = serde_json::from_str(field("user")?).ok()?;
+    user.get("id")?.as_i64()
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .filter_map(|(key, value)| {
+            let value = urlencoding::decode(&value.replace('+', " "))
+                .ok()?
+                .into_owned();
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+fn preview_sent() -> ApiResponse {
+    ApiResponse::ok(json!({
+        "message": "Предпросмотр изменения отправлен в чат с ботом — подтвердите его там",
+        "preview": true,
+    }))
+}
+
+fn int_param(params: &HashMap<String, String>, name: &str) -> Option<i64> {
+    params.get(name).and_then(|value| value.parse::<i64>().ok())
+}
@@ -1,10 +1,9 @@
 //! Клавиатуры бота: inline и постоянные reply-кнопки.
 
+use crate::db::UsersPageRequest;
+use crate::i18n::{Lang, matches_any, t};
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup};
 
-pub const BTN_USER_LINK: &str = "🔗 Моя ссылка";
-pub const BTN_USER_GUIDE: &str = "❓ Инструкция";
-
 pub const BTN_ADMIN_PENDING: &str = "📥 Новые заявки";
 pub const BTN_ADMIN_USERS: &str = "👥 Список пользователей";
 pub const BTN_ADMIN_SERVICE: &str = "⚙️ Статус сервиса";
@@ -12,11 +11,62 @@ pub const BTN_ADMIN_STATS: &str = "📊 Статистика";
 pub const BTN_ADMIN_CREATE_HINT: &str = "➕ Создать @username";
 pub const BTN_ADMIN_HELP: &str = "❓ Справка";
 
-pub fn user_menu() -> KeyboardMarkup {
-    KeyboardMarkup::new(vec![vec![
-        KeyboardButton::new(BTN_USER_LINK),
-        KeyboardButton::new(BTN_USER_GUIDE),
-    ]])
+/// Кнопки пользовательского меню. Распознаются на любом языке: после смены языка
+/// у пользователя может остаться клавиатура со старыми подписями.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserMenuButton {
+    Link,
+    Guide,
+    Support,
+    Language,
+    Invite,
+}
+
+impl UserMenuButton {
+    pub fn parse(text: &str) -> Option<Self> {
+        [
+            (Self::Link, "btn_link"),
+            (Self::Guide, "btn_guide"),
+            (Self::Support, "btn_support"),
+            (Self::Language, "btn_language"),
+            (Self::Invite, "btn_invite"),
+        ]
+        .into_iter()
+        .find(|(_, key)| matches_any(text, key))
+        .map(|(button, _)| button)
+    }
+}
+
+/// Текст кнопки пользовательского меню (а не свободное сообщение).
+pub fn is_user_menu_button(text: &str) -> bool {
+    UserMenuButton::parse(text).is_some()
+}
+
+/// Текст кнопки админ-меню (а не свободное сообщение).
+pub fn is_admin_menu_button(text: &str) -> bool {
+    [
+        BTN_ADMIN_PENDING,
+        BTN_ADMIN_USERS,
+        BTN_ADMIN_SERVICE,
+        BTN_ADMIN_STATS,
+        BTN_ADMIN_CREATE_HINT,
+        BTN_ADMIN_HELP,
+    ]
+    .contains(&text)
+}
+
+pub fn user_menu(lang: Lang) -> KeyboardMarkup {
+    KeyboardMarkup::new(vec![
+        vec![
+            KeyboardButton::new(t(lang, "btn_link")),
+            KeyboardButton::new(t(lang, "btn_guide")),
+        ],
+        vec![
+            KeyboardButton::new(t(lang, "btn_support")),
+            KeyboardButton::new(t(lang, "btn_language")),
+        ],
+        vec![KeyboardButton::new(t(lang, "btn_invite"))],
+    ])
     .resize_keyboard()
     .persistent()
 }
@@ -40,55 +90,375 @@ pub fn admin_menu() -> KeyboardMarkup {
     .persistent()
 }
 
+/// Карточка новой заявки: одобрение со сроком (`approve:<id>:<срок>`) или бессрочно
+/// (`approve:<id>`), отклонение и корзина.
 pub fn approve_reject_buttons(request_id: i64) -> InlineKeyboardMarkup {
+    let approve = |label: &str, duration: Option<&str>| {
+        let data = match duration {
+            Some(duration) => format!("approve:{}:{}", request_id, duration),
+            None => format!("approve:{}", request_id),
+        };
+        InlineKeyboardButton::callback(format!("✅ {}", label), data)
+    };
+    InlineKeyboardMarkup::default()
+        .append_row(vec![
+            approve("7 дней", Some("7d")),
+            approve("30 дней", Some("30d")),
+            approve("90 дней", Some("90d")),
+        ])
+        .append_row(vec![
+            approve("Навсегда", None),
+            InlineKeyboardButton::callback("❌ Отклонить", format!("reject:{}", request_id)),
+        ])
+        .append_row(vec![InlineKeyboardButton::callback(
+            "🧺 В корзину",
+            format!("stage:{}", request_id),
+        )])
+}
+
+/// Страница списка заявок: по строке кнопок на заявку (`pending_act:<действие>:<id>:<offset>`)
+/// и листание `pending_page:<offset>`. `cards` — id заявки и взята ли она текущим админом.
+pub fn pending_page_keyboard(
+    cards: &[(i64, bool)],
+    offset: i64,
+    limit: i64,
+    total: i64,
+) -> InlineKeyboardMarkup {
+    let mut keyboard = InlineKeyboardMarkup::default();
+    for (request_id, claimed_by_me) in cards {
+        let (claim_label, claim_action) = if *claimed_by_me {
+            ("↩️ Отпустить", "release")
+        } else {
+            ("🙋 Взять", "claim")
+        };
+        keyboard = keyboard.append_row(vec![
+            InlineKeyboardButton::callback(
+                format!("✅ #{}", request_id),
+                format!("pending_act:approve:{}:{}", request_id, offset),
+            ),
+            InlineKeyboardButton::callback(
+                format!("❌ #{}", request_id),
+                format!("pending_act:reject:{}:{}", request_id, offset),
+            ),
+            InlineKeyboardButton::callback(
+                claim_label,
+                format!("pending_act:{}:{}:{}", claim_action, request_id, offset),
+            ),
+        ]);
+    }
+    let mut navigation = Vec::new();
+    if offset > 0 {
+        navigation.push(InlineKeyboardButton::callback(
+            "⬅️ Назад",
+            format!("pending_page:{}", (offset - limit).max(0)),
+        ));
+    }
+    navigation.push(InlineKeyboardButton::callback(
+        "🔄",
+        format!("pending_page:{}", offset),
+    ));
+    if offset + limit < total {
+        navigation.push(InlineKeyboardButton::callback(
+            "Вперёд ➡️",
+            format!("pending_page:{}", offset + limit),
+        ));
+    }
+    keyboard.append_row(navigation)
+}
+
+/// Запрос причины отклонения: `reject_reason:skip:<id>` или `reject_reason:cancel:<id>`.
+pub fn reject_reason_keyboard(request_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback(
+            "❌ Без причины",
+            format!("reject_reason:skip:{}", request_id),
+        ),
+        InlineKeyboardButton::callback("Отмена", format!("reject_reason:cancel:{}", request_id)),
+    ])
+}
+
+pub fn pending_summary_keyboard(pending: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback("📋 Показать список", "pending:list"),
+        InlineKeyboardButton::callback(
+            format!("✅ Одобрить все ({})", pending),
+            "pending:approve_all",
+        ),
+    ])
+}
+
+/// Подтверждение `/cleanup`: `cleanup:<фильтр>` или `cleanup:cancel`.
+pub fn cleanup_confirm_keyboard(filter: &str, count: usize) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback(
+            format!("🗑 Удалить ({})", count),
+            format!("cleanup:{}", filter),
+        ),
+        InlineKeyboardButton::callback("Отмена", "cleanup:cancel"),
+    ])
+}
+
+/// Подтверждение `/purge`: `purge:<tg_user_id>` или `purge:cancel`.
+pub fn purge_confirm_keyboard(tg_user_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback("🔥 Стереть навсегда", format!("purge:{}", tg_user_id)),
+        InlineKeyboardButton::callback("Отмена", "purge:cancel"),
+    ])
+}
+
+/// Подтверждение изменения конфига после предпросмотра: `cfg_preview:<действие>` или
+/// `cfg_preview:cancel`.
+pub fn config_preview_keyboard(action: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback("✅ Применить", format!("cfg_preview:{}", action)),
+        InlineKeyboardButton::callback("Отмена", "cfg_preview:cancel"),
+    ])
+}
+
+/// Исправление расхождений `/sync`: `sync:readd`, `sync:remove`, `sync:cancel`.
+/// Кнопки показываются только для непустых категорий.
+pub fn sync_fix_keyboard(to_readd: usize, to_remove: usize) -> InlineKeyboardMarkup {
+    let mut keyboard = InlineKeyboardMarkup::default();
+    if to_readd > 0 {
+        keyboard = keyboard.append_row(vec![InlineKeyboardButton::callback(
+            format!("➕ Вернуть в конфиг из БД ({})", to_readd),
+            "sync:readd",
+        )]);
+    }
+    if to_remove > 0 {
+        keyboard = keyboard.append_row(vec![InlineKeyboardButton::callback(
+            format!("➖ Убрать лишние из конфига ({})", to_remove),
+            "sync:remove",
+        )]);
+    }
+    keyboard.append_row(vec![InlineKeyboardButton::callback(
+        "Закрыть",
+        "sync:cancel",
+    )])
+}
+
+/// Черновик рассылки: выбор аудитории `bcast:aud:<id>:<approved|pending|expiring|g:<группа>>`,
+/// отправка `bcast:send:<id>` и отмена `bcast:cancel:<id>`. `selected` — ключ текущей аудитории.
+pub fn broadcast_draft_keyboard(
+    broadcast_id: i64,
+    selected: &str,
+    groups: &[String],
+    scheduled: bool,
+) -> InlineKeyboardMarkup {
+    let button = |key: String, label: String| {
+        let label = if key == selected {
+            format!("● {}", label)
+        } else {
+            label
+        };
+        InlineKeyboardButton::callback(label, format!("bcast:aud:{}:{}", broadcast_id, key))
+    };
+    let mut keyboard = InlineKeyboardMarkup::default()
+        .append_row(vec![
+            button("approved".to_string(), "👥 Все одобренные".to_string()),
+            button("pending".to_string(), "📥 Ожидающие".to_string()),
+        ])
+        .append_row(vec![button(
+            "expiring".to_string(),
+            "⌛️ Скоро истекает доступ".to_string(),
+        )]);
+    for chunk in groups.chunks(3) {
+        keyboard = keyboard.append_row(
+            chunk
+                .iter()
+                .map(|group| button(format!("g:{}", group), format!("🏷 {}", group)))
+                .collect::<Vec<_>>(),
+        );
+    }
+    let send_label = if scheduled {
+        "🗓 Запланировать"
+    } else {
+        "📣 Отправить"
+    };
+    keyboard.append_row(vec![
+        InlineKeyboardButton::callback(send_label, format!("bcast:send:{}", broadcast_id)),
+        InlineKeyboardButton::callback("Отмена", format!("bcast:cancel:{}", broadcast_id)),
+    ])
+}
+
+/// Запланированная или идущая рассылка: `bcast:cancel:<id>`.
+pub fn broadcast_cancel_keyboard(broadcast_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+        "✖️ Отменить рассылку",
+        format!("bcast:cancel:{}", broadcast_id),
+    )])
+}
+
+/// Листание журнала аудита: `audit_page:<limit>:<offset>`.
+pub fn audit_page_keyboard(limit: i64, offset: i64, total: i64) -> InlineKeyboardMarkup {
+    let mut navigation = Vec::new();
+    if offset > 0 {
+        navigation.push(InlineKeyboardButton::callback(
+            "⬅️ Новее",
+            format!("audit_page:{}:{}", limit, (offset - limit).max(0)),
+        ));
+    }
+    if offset + limit < total {
+        navigation.push(InlineKeyboardButton::callback(
+            "Старее ➡️",
+            format!("audit_page:{}:{}", limit, offset + limit),
+        ));
+    }
+    let mut keyboard = InlineKeyboardMarkup::default();
+    if !navigation.is_empty() {
+        keyboard = keyboard.append_row(navigation);
+    }
+    keyboard.append_row(vec![InlineKeyboardButton::callback(
+        "🔄 Обновить",
+        format!("audit_page:{}:{}", limit, offset),
+    )])
+}
+
+/// Переписка с пользователем: ответ `support_reply:<tg_user_id>` и, пока переписку
+/// никто не ведёт, `support_take:<tg_user_id>`.
+/// Кнопка перехода в обязательный канал (`required_channel_url`).
+pub fn join_channel_keyboard(lang: Lang, url: reqwest::Url) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::url(
+        t(lang, "btn_join_channel"),
+        url,
+    )])
+}
+
+pub fn support_keyboard(tg_user_id: i64, can_take: bool) -> InlineKeyboardMarkup {
+    let mut row = vec![InlineKeyboardButton::callback(
+        "✍️ Ответить",
+        format!("support_reply:{}", tg_user_id),
+    )];
+    if can_take {
+        row.push(InlineKeyboardButton::callback(
+            "🙋 Взять в работу",
+            format!("support_take:{}", tg_user_id),
+        ));
+    }
+    InlineKeyboardMarkup::default().append_row(row)
+}
+
+/// Подтверждение перехвата: `support_take:<tg_user_id>:force[:<message_id>]`;
+/// `message_id` — ответ админа, который нужно отправить после перехвата.
+pub fn support_takeover_keyboard(tg_user_id: i64, message_id: Option<i32>) -> InlineKeyboardMarkup {
+    let payload = match message_id {
+        Some(message_id) => format!("support_take:{}:force:{}", tg_user_id, message_id),
+        None => format!("support_take:{}:force", tg_user_id),
+    };
+    InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+        "🔁 Перехватить",
+        payload,
+    )])
+}
+
+/// Варианты ответа на вопрос опроса: `survey:<вопрос>:<вариант>`.
+pub fn survey_question_keyboard(question: usize, options: &[String]) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(options.iter().enumerate().map(|(index, option)| {
+        vec![InlineKeyboardButton::callback(
+            option.clone(),
+            format!("survey:{}:{}", question, index),
+        )]
+    }))
+}
+
+pub fn reveal_link_button(token: &str, lang: Lang) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+        t(lang, "btn_reveal_link"),
+        format!("reveal:{}", token),
+    )])
+}
+
+/// Повторная отправка ссылки после автоудаления: `resend_link`.
+pub fn resend_link_button(lang: Lang) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+        t(lang, "btn_resend_link"),
+        "resend_link",
+    )])
+}
+
+pub fn staged_request_buttons(request_id: i64, basket_size: usize) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default()
+        .append_row(vec![InlineKeyboardButton::callback(
+            format!("🧺 Применить корзину: {} (1 рестарт)", basket_size),
+            "basket:apply",
+        )])
+        .append_row(vec![InlineKeyboardButton::callback(
+            "↩️ Убрать из корзины",
+            format!("unstage:{}", request_id),
+        )])
+}
+
+pub fn basket_keyboard(basket_size: usize) -> InlineKeyboardMarkup {
     InlineKeyboardMarkup::default().append_row(vec![
-        InlineKeyboardButton::callback("✅ Одобрить", format!("approve:{}", request_id)),
-        InlineKeyboardButton::callback("❌ Отклонить", format!("reject:{}", request_id)),
+        InlineKeyboardButton::callback(
+            format!("✅ Применить ({} шт., 1 рестарт)", basket_size),
+            "basket:apply",
+        ),
+        InlineKeyboardButton::callback("🗑 Очистить", "basket:clear"),
     ])
 }
 
+/// Курсор страницы в callback-данных: `f`, `n:<created_at>:<id>`,
+/// `p:<created_at>:<id>` или `a:<created_at>:<id>`.
+pub fn users_page_payload(request: UsersPageRequest) -> String {
+    match request {
+        UsersPageRequest::First => "f".to_string(),
+        UsersPageRequest::After(cursor) => format!("n:{}:{}", cursor.created_at, cursor.id),
+        UsersPageRequest::Before(cursor) => format!("p:{}:{}", cursor.created_at, cursor.id),
+        UsersPageRequest::From(cursor) => format!("a:{}:{}", cursor.created_at, cursor.id),
+    }
+}
+
 pub fn users_page_keyboard(
     users: &[(i64, String)],
-    page: i64,
-    total_pages: i64,
+    current: UsersPageRequest,
+    prev: Option<UsersPageRequest>,
+    next: Option<UsersPageRequest>,
 ) -> InlineKeyboardMarkup {
+    let current = users_page_payload(current);
     let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
     for (tg_user_id, title) in users {
         rows.push(vec![InlineKeyboardButton::callback(
             format!("👤 {}", title),
-            format!("user_open:{}:{}", tg_user_id, page),
+            format!("user_open:{}:{}", tg_user_id, current),
         )]);
     }
 
-    let prev_page = if page > 1 { page - 1 } else { 1 };
-    let next_page = if page < total_pages {
-        page + 1
-    } else {
-        total_pages
-    };
-
-    rows.push(vec![
-        InlineKeyboardButton::callback("⬅️", format!("users_page:{}", prev_page)),
-        InlineKeyboardButton::callback(
-            format!("📄 {}/{}", page, total_pages.max(1)),
-            format!("users_page:{}", page),
-        ),
-        InlineKeyboardButton::callback("➡️", format!("users_page:{}", next_page)),
-    ]);
+    let mut navigation = Vec::new();
+    if let Some(prev) = prev {
+        navigation.push(InlineKeyboardButton::callback(
+            "⬅️",
+            format!("users_page:{}", users_page_payload(prev)),
+        ));
+    }
+    if let Some(next) = next {
+        navigation.push(InlineKeyboardButton::callback(
+            "➡️",
+            format!("users_page:{}", users_page_payload(next)),
+        ));
+    }
+    if !navigation.is_empty() {
+        rows.push(navigation);
+    }
     rows.push(vec![InlineKeyboardButton::callback(
         "🔄 Обновить",
-        format!("users_page:{}", page),
+        format!("users_page:{}", current),
     )]);
 
     InlineKeyboardMarkup::new(rows)
 }
 
-pub fn user_card_keyboard(tg_user_id: i64, page: i64) -> InlineKeyboardMarkup {
+pub fn user_card_keyboard(tg_user_id: i64, page: UsersPageRequest) -> InlineKeyboardMarkup {
+    let page = users_page_payload(page);
     InlineKeyboardMarkup::default()
         .append_row(vec![InlineKeyboardButton::callback(
             "🔗 Данные + QR",
             format!("user_view:{}:{}", tg_user_id, page),
         )])
+        .append_row(vec![InlineKeyboardButton::callback(
+            "🔁 Перевыпустить секрет",
+            format!("user_rotate:{}:{}", tg_user_id, page),
+        )])
         .append_row(vec![InlineKeyboardButton::callback(
             "⛔ Забанить (удалить)",
             format!("user_ban:{}:{}", tg_user_id, page),
@@ -99,6 +469,19 @@ pub fn user_card_keyboard(tg_user_id: i64, page: i64) -> InlineKeyboardMarkup {
         )])
 }
 
+/// Кнопки уведомления о недоступности прокси: те же `service:*`, что и в меню сервиса.
+pub fn proxy_down_buttons() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default()
+        .append_row(vec![
+            InlineKeyboardButton::callback("♻️ Рестарт", "service:restart"),
+            InlineKeyboardButton::callback("🔄 Статус", "service:status"),
+        ])
+        .append_row(vec![InlineKeyboardButton::callback(
+            "⏳ Рестарт с предупреждением",
+            "service:notice_restart",
+        )])
+}
+
 pub fn service_control_buttons() -> InlineKeyboardMarkup {
     InlineKeyboardMarkup::default()
         .append_row(vec![
@@ -109,4 +492,12 @@ pub fn service_control_buttons() -> InlineKeyboardMarkup {
             "📖 Перечитать конфиг",
             "service:reload",
         )])
+        .append_row(vec![InlineKeyboardButton::callback(
+            "⏳ Рестарт с предупреждением",
+            "service:notice_restart",
+        )])
+        .append_row(vec![
+            InlineKeyboardButton::callback("✅ Автозапуск вкл.", "service:enable"),
+            InlineKeyboardButton::callback("🚫 Автозапуск выкл.", "service:disable"),
+        ])
 }
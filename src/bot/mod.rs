@@ -1,2 +1,9 @@
+pub mod client;
+pub mod cooldown;
 pub mod handlers;
 pub mod keyboards;
+
+/// Бот с ограничением частоты исходящих запросов по лимитам `[telegram.throttle]`:
+/// очередь teloxide задерживает отправку, поэтому обработчикам не нужно
+/// ничего ждать перед каждым сообщением.
+pub type Bot = teloxide::adaptors::Throttle<teloxide::Bot>;
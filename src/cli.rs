@@ -0,0 +1,197 @@
+//! Разбор аргументов командной строки.
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/telemt-admin.toml";
+
+/// Telegram-бот для администрирования MTProxy telemt
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Путь к конфигу (по умолчанию /etc/telemt-admin.toml)
+    #[arg(short, long = "config", value_name = "PATH", global = true)]
+    config_path: Option<PathBuf>,
+    /// Путь к конфигу (то же, что --config)
+    #[arg(value_name = "CONFIG", conflicts_with = "config_path")]
+    config_arg: Option<PathBuf>,
+    /// Проверить конфиг бота и конфиг telemt и выйти
+    #[arg(long, conflicts_with = "migrate_only")]
+    check: bool,
+    /// Запустить бота без записи в конфиг telemt и без рестартов
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Применить миграции БД и выйти
+    #[arg(long)]
+    migrate_only: bool,
+    /// Откатить обратимые миграции новее VERSION и выйти
+    #[arg(
+        long,
+        value_name = "VERSION",
+        conflicts_with_all = ["check", "migrate_only"]
+    )]
+    migrate_undo: Option<i64>,
+    /// Уровень логирования: error, warn, info, debug, trace или директива tracing
+    /// (например, telemt_admin=debug)
+    #[arg(long, value_name = "LEVEL", global = true)]
+    log_level: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Проверить, что БД читается и heartbeat работающего бота свежий;
+    /// код выхода 0 — здоров, 1 — нет (для Docker HEALTHCHECK)
+    Healthcheck {
+        /// Путь к конфигу (то же, что --config)
+        #[arg(value_name = "CONFIG")]
+        config_arg: Option<PathBuf>,
+    },
+    /// Пользователи: список, выдача и удаление доступа
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Invite-токены
+    #[command(subcommand)]
+    Token(TokenCommand),
+    /// Сверить БД с конфигом telemt
+    Sync {
+        /// Исправить найденные расхождения
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum UserCommand {
+    /// Все пользователи: tg_user_id, статус, имя в конфиге, username
+    List,
+    /// Выдать доступ и вывести ссылку (с @username — если пользователь уже писал боту)
+    Create {
+        #[arg(value_name = "ID|@USER")]
+        target: String,
+    },
+    /// Удалить пользователя из конфига telemt и деактивировать
+    Delete {
+        /// Отрицательные id — импортированные пользователи без Telegram
+        #[arg(value_name = "ID", allow_negative_numbers = true)]
+        tg_user_id: i64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TokenCommand {
+    /// Создать invite-токен
+    Create {
+        /// Срок действия в днях
+        #[arg(long, value_name = "N")]
+        days: Option<i64>,
+        /// Лимит использований
+        #[arg(long, value_name = "N")]
+        max_uses: Option<i64>,
+        /// Одобрять заявки по токену автоматически
+        #[arg(long = "auto")]
+        auto_approve: bool,
+    },
+}
+
+/// Параметры запуска бота.
+#[derive(Debug, Clone)]
+pub struct CliArgs {
+    /// Путь к telemt-admin.toml
+    pub config_path: PathBuf,
+    /// Проверить конфиги и выйти
+    pub check: bool,
+    /// Не изменять конфиг telemt и не перезапускать сервис
+    pub dry_run: bool,
+    /// Применить миграции БД и выйти
+    pub migrate_only: bool,
+    /// Откатить миграции новее указанной версии и выйти
+    pub migrate_undo: Option<i64>,
+    /// Проверить БД и heartbeat работающего бота, выйти с кодом 0/1
+    pub healthcheck: bool,
+    /// Директива уровня логирования (например, `debug` или `telemt_admin=trace`)
+    pub log_level: Option<String>,
+    /// Подкоманда управления без Telegram (`user`, `token`, `sync`)
+    pub manage: Option<ManageCommand>,
+}
+
+/// Подкоманды управления из консоли сервера.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManageCommand {
+    UserList,
+    /// `tg_user_id` или `@username`
+    UserCreate(String),
+    UserDelete(i64),
+    TokenCreate {
+        days: Option<i64>,
+        max_uses: Option<i64>,
+        auto_approve: bool,
+    },
+    Sync {
+        fix: bool,
+    },
+}
+
+/// Разбирает аргументы процесса. `--help`, `--version` и ошибки разбора clap
+/// обрабатывает сам и завершает процесс.
+pub fn parse_args() -> CliArgs {
+    let cli = Cli::parse();
+    if (cli.check || cli.migrate_only || cli.migrate_undo.is_some()) && cli.command.is_some() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "Подкоманды нельзя использовать вместе с --check, --migrate-only и --migrate-undo",
+            )
+            .exit();
+    }
+    let mut config_path = cli.config_path.or(cli.config_arg);
+    let mut healthcheck = false;
+    let mut manage = None;
+    match cli.command {
+        Some(Command::Healthcheck { config_arg }) => {
+            if config_path.is_some() && config_arg.is_some() {
+                Cli::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "Путь к конфигу указан несколько раз",
+                    )
+                    .exit();
+            }
+            healthcheck = true;
+            config_path = config_path.or(config_arg);
+        }
+        Some(Command::User(UserCommand::List)) => manage = Some(ManageCommand::UserList),
+        Some(Command::User(UserCommand::Create { target })) => {
+            manage = Some(ManageCommand::UserCreate(target))
+        }
+        Some(Command::User(UserCommand::Delete { tg_user_id })) => {
+            manage = Some(ManageCommand::UserDelete(tg_user_id))
+        }
+        Some(Command::Token(TokenCommand::Create {
+            days,
+            max_uses,
+            auto_approve,
+        })) => {
+            manage = Some(ManageCommand::TokenCreate {
+                days,
+                max_uses,
+                auto_approve,
+            })
+        }
+        Some(Command::Sync { fix }) => manage = Some(ManageCommand::Sync { fix }),
+        None => {}
+    }
+
+    CliArgs {
+        config_path: config_path.unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH)),
+        check: cli.check,
+        dry_run: cli.dry_run,
+        migrate_only: cli.migrate_only,
+        migrate_undo: cli.migrate_undo,
+        healthcheck,
+        log_level: cli.log_level,
+        manage,
+    }
+}
@@ -1,13 +1,101 @@
-//! SQLite-слой для заявок на регистрацию и связей tg_user_id -> telemt_user.
+//! Слой данных для заявок на регистрацию и связей tg_user_id -> telemt_user.
+//!
+//! По умолчанию данные лежат в файле SQLite (`db_path`); сборка с feature `postgres`
+//! подключается и к Postgres (`db_url`). Запросы идут через пул sqlx `Any` и написаны
+//! на общем для обеих СУБД подмножестве SQL: параметры `$N`, `ON CONFLICT` вместо
+//! `INSERT OR …`, `RETURNING id` вместо `last_insert_rowid()`, флаги — числа 0/1.
+//! Различия (FTS5, `VACUUM`, схема старых БД) разведены по [`Backend`].
 
 use rand::distr::{Alphanumeric, SampleString};
-use sqlx::FromRow;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::any::{AnyConnectOptions, AnyPoolOptions, AnyRow};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{AnyConnection, AnyPool, ConnectOptions, Database, FromRow, Row};
 use std::fmt;
-use std::path::Path;
-use std::str::FromStr;
+use std::path::PathBuf;
 use thiserror::Error;
 
+/// Версионированные миграции SQLite из `migrations/`, вшитые в бинарник; применённые
+/// версии и их контрольные суммы sqlx хранит в таблице `_sqlx_migrations`.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Те же миграции для Postgres (`migrations/postgres/`): версии и смысл совпадают
+/// с SQLite, так что `--migrate-undo <версия>` одинаково работает на обеих СУБД.
+#[cfg(feature = "postgres")]
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("migrations/postgres");
+
+/// Колонки, которые до версионированных миграций добавлялись через `ALTER TABLE`
+/// при старте; нужны только для обновления таких БД (см. `upgrade_legacy_schema`).
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("registration_requests", "tg_display_name", "TEXT"),
+    ("registration_requests", "note", "TEXT"),
+    ("registration_requests", "invite_token", "TEXT"),
+    ("registration_requests", "user_group", "TEXT"),
+    (
+        "registration_requests",
+        "suspended",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("registration_requests", "reminded_at", "INTEGER"),
+    ("registration_requests", "escalated_at", "INTEGER"),
+    ("registration_requests", "expires_at", "INTEGER"),
+    ("registration_requests", "expiry_warned_at", "INTEGER"),
+    ("invite_tokens", "max_usage", "INTEGER"),
+    ("invite_tokens", "is_active", "INTEGER NOT NULL DEFAULT 1"),
+    ("invite_tokens", "revoked_at", "INTEGER"),
+    ("invite_tokens", "for_tg_user_id", "INTEGER"),
+    ("invite_tokens", "web_visits", "INTEGER NOT NULL DEFAULT 0"),
+    ("invite_tokens", "plans", "TEXT"),
+    ("provisioned_users", "user_group", "TEXT"),
+    ("provisioned_users", "expires_in_days", "INTEGER"),
+    (
+        "support_conversations",
+        "status",
+        "TEXT NOT NULL DEFAULT 'open'",
+    ),
+    ("support_conversations", "opened_at", "INTEGER"),
+    ("support_conversations", "last_activity_at", "INTEGER"),
+];
+
+/// Перечисления, которые хранятся строкой в колонке TEXT. Derive `sqlx::Type` не
+/// поддерживает пул `Any`, поэтому преобразование в строку и обратно описано здесь.
+macro_rules! text_enum {
+    ($name:ident { $($variant:ident => $text:literal),+ $(,)? }) => {
+        impl $name {
+            /// Значение в колонке БД.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $text),+
+                }
+            }
+        }
+
+        impl sqlx::Type<sqlx::Any> for $name {
+            fn type_info() -> sqlx::any::AnyTypeInfo {
+                <str as sqlx::Type<sqlx::Any>>::type_info()
+            }
+        }
+
+        impl<'q> sqlx::Encode<'q, sqlx::Any> for $name {
+            fn encode_by_ref(
+                &self,
+                buf: &mut <sqlx::Any as Database>::ArgumentBuffer<'q>,
+            ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+                <&str as sqlx::Encode<'q, sqlx::Any>>::encode(self.as_str(), buf)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, sqlx::Any> for $name {
+            fn decode(value: sqlx::any::AnyValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+                match <&str as sqlx::Decode<'r, sqlx::Any>>::decode(value)? {
+                    $($text => Ok(Self::$variant),)+
+                    other => Err(format!("Неизвестное значение {}: {}", stringify!($name), other).into()),
+                }
+            }
+        }
+    };
+}
+
 /// Результат регистрации.
 #[derive(Debug)]
 pub enum RegisterResult {
@@ -33,8 +121,55 @@ pub struct RegistrationRequest {
     pub created_at: i64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+impl RegistrationRequest {
+    pub fn cursor(&self) -> UserCursor {
+        UserCursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+/// Позиция в списке активных пользователей (порядок `created_at DESC, id DESC`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserCursor {
+    pub created_at: i64,
+    pub id: i64,
+}
+
+/// Какую страницу списка активных пользователей выбрать относительно курсора.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsersPageRequest {
+    /// Начало списка
+    First,
+    /// Следующая страница: записи строго после курсора
+    After(UserCursor),
+    /// Предыдущая страница: записи строго до курсора
+    Before(UserCursor),
+    /// Страница, начинающаяся с курсора (обновление, возврат из карточки)
+    From(UserCursor),
+}
+
+/// Страница активных пользователей для keyset-пагинации.
+#[derive(Debug, Clone)]
+pub struct UsersPage {
+    pub users: Vec<RegistrationRequest>,
+    pub has_prev: bool,
+    pub has_next: bool,
+}
+
+/// Результат поиска по пользователям.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserSearchHit {
+    pub id: i64,
+    pub tg_user_id: i64,
+    pub tg_username: Option<String>,
+    pub tg_display_name: Option<String>,
+    pub status: RequestStatus,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RequestStatus {
     Pending,
     Approved,
@@ -42,6 +177,13 @@ pub enum RequestStatus {
     Deleted,
 }
 
+text_enum!(RequestStatus {
+    Pending => "pending",
+    Approved => "approved",
+    Rejected => "rejected",
+    Deleted => "deleted",
+});
+
 impl fmt::Display for RequestStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let value = match self {
@@ -54,7 +196,7 @@ impl fmt::Display for RequestStatus {
     }
 }
 
-#[derive(Debug, Clone, FromRow)]
+#[derive(Debug, Clone)]
 pub struct InviteToken {
     pub id: i64,
     pub token: String,
@@ -65,6 +207,70 @@ pub struct InviteToken {
     pub usage_count: i64,
     pub max_usage: Option<i64>,
     pub is_active: bool,
+    /// Персональный токен: применить его может только этот tg_user_id
+    pub for_tg_user_id: Option<i64>,
+    /// Допустимые варианты ссылки (`<токен>-<группа>`) через запятую
+    pub plans: Option<String>,
+    /// Подпись админа: для чего выписан токен
+    pub label: Option<String>,
+    /// До этого момента токен не применяется
+    pub valid_from: Option<i64>,
+    /// Группа, в которую попадают пришедшие по токену
+    pub group_name: Option<String>,
+    /// Пользователь, выписавший реферальный токен
+    pub referrer_tg_user_id: Option<i64>,
+}
+
+// Флаги хранятся числом 0/1 в обеих СУБД, а `Any` не приводит число к `bool`,
+// поэтому строка разбирается вручную.
+impl FromRow<'_, AnyRow> for InviteToken {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            token: row.try_get("token")?,
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+            auto_approve: row.try_get::<i64, _>("auto_approve")? != 0,
+            created_by: row.try_get("created_by")?,
+            usage_count: row.try_get("usage_count")?,
+            max_usage: row.try_get("max_usage")?,
+            is_active: row.try_get::<i64, _>("is_active")? != 0,
+            for_tg_user_id: row.try_get("for_tg_user_id")?,
+            plans: row.try_get("plans")?,
+            label: row.try_get("label")?,
+            valid_from: row.try_get("valid_from")?,
+            group_name: row.try_get("group_name")?,
+            referrer_tg_user_id: row.try_get("referrer_tg_user_id")?,
+        })
+    }
+}
+
+/// Параметры нового invite-токена.
+#[derive(Debug, Clone)]
+pub struct NewInviteToken<'a> {
+    pub days: i64,
+    pub auto_approve: bool,
+    pub max_usage: Option<i64>,
+    pub created_by: Option<i64>,
+    pub for_tg_user_id: Option<i64>,
+    pub plans: &'a [String],
+    pub label: Option<&'a str>,
+    /// Начало действия; срок `days` отсчитывается от него
+    pub valid_from: Option<i64>,
+    pub group_name: Option<&'a str>,
+    pub referrer_tg_user_id: Option<i64>,
+}
+
+impl InviteToken {
+    pub fn plan_names(&self) -> Vec<String> {
+        self.plans
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .filter(|plan| !plan.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -82,6 +288,23 @@ pub struct ConsumedInviteToken {
     pub created_by: Option<i64>,
     pub usage_count: i64,
     pub max_usage: Option<i64>,
+    pub for_tg_user_id: Option<i64>,
+    /// Группы, которые можно выбрать вариантом ссылки этого токена.
+    pub plans: Vec<String>,
+    pub label: Option<String>,
+    /// Группа по умолчанию; вариант ссылки из `plans` её перекрывает.
+    pub group_name: Option<String>,
+    /// Пользователь, выписавший реферальный токен.
+    pub referrer_tg_user_id: Option<i64>,
+}
+
+/// Ошибка слоя данных.
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("Ошибка БД: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("{0}")]
+    Other(#[from] anyhow::Error),
 }
 
 #[derive(Debug, Error)]
@@ -94,12 +317,21 @@ pub enum TokenConsumeError {
     Expired,
     #[error("Лимит использований токена исчерпан")]
     UsageLimitReached,
+    #[error("Токен выписан другому пользователю")]
+    NotForYou,
+    #[error("Токен ещё не действует")]
+    NotYetActive(i64),
+    #[error("Ошибка БД: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error("{0}")]
+    Other(#[from] DbError),
 }
 
 const STATUS_APPROVED: &str = "approved";
 const STATUS_PENDING: &str = "pending";
 const STATUS_REJECTED: &str = "rejected";
 const STATUS_DELETED: &str = "deleted";
+const SELECT_INVITE_TOKEN: &str = "SELECT id, token, created_at, expires_at, auto_approve, created_by, usage_count, max_usage, is_active, for_tg_user_id, plans, label, valid_from, group_name, referrer_tg_user_id FROM invite_tokens";
 const SELECT_REQUEST: &str = "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at FROM registration_requests";
 
 #[derive(Debug, Clone)]
@@ -111,264 +343,773 @@ pub struct AdminStats {
     pub deleted: i64,
 }
 
-pub struct Db {
-    pool: SqlitePool,
+/// Сводка по ожидающим заявкам для `/pending`.
+#[derive(Debug, Clone, Copy)]
+pub struct PendingSummary {
+    pub count: i64,
+    /// Время создания самой старой ожидающей заявки
+    pub oldest_created_at: Option<i64>,
 }
 
-fn current_unix_timestamp() -> Result<i64, anyhow::Error> {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|duration| duration.as_secs() as i64)
-        .map_err(|err| anyhow::anyhow!("Системное время меньше UNIX_EPOCH: {}", err))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
 }
 
-impl Db {
-    pub async fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
-        let path = path.as_ref();
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| anyhow::anyhow!("Не удалось создать директорию для БД: {}", e))?;
-        }
+text_enum!(JobStatus {
+    Queued => "queued",
+    Running => "running",
+    Done => "done",
+    Failed => "failed",
+    Cancelled => "cancelled",
+});
 
-        let opts = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))?
-            .create_if_missing(true);
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
-        let pool = SqlitePool::connect_with(opts)
-            .await
-            .map_err(|e| anyhow::anyhow!("Не удалось подключиться к SQLite: {}", e))?;
+/// Фоновая задача с контрольной точкой прогресса.
+#[derive(Debug, Clone, FromRow)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    pub status: JobStatus,
+    /// Последний обработанный ключ (например, tg_user_id) — с него задача продолжится после рестарта.
+    pub cursor: i64,
+    pub processed: i64,
+    pub total: Option<i64>,
+    pub error: Option<String>,
+    pub created_by: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
 
-        let db = Self { pool };
-        db.migrate().await?;
-        Ok(db)
-    }
+const SELECT_JOB: &str = "SELECT id, kind, status, cursor, processed, total, error, created_by, created_at, updated_at FROM jobs";
 
-    async fn migrate(&self) -> Result<(), anyhow::Error> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS registration_requests (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                tg_user_id INTEGER NOT NULL,
-                tg_username TEXT,
-                tg_display_name TEXT,
-                status TEXT NOT NULL DEFAULT 'pending',
-                telemt_username TEXT,
-                secret TEXT,
-                created_at INTEGER NOT NULL,
-                resolved_at INTEGER,
-                UNIQUE(tg_user_id)
-            );
-            CREATE INDEX IF NOT EXISTS idx_requests_status ON registration_requests(status);
-            CREATE INDEX IF NOT EXISTS idx_requests_tg_user ON registration_requests(tg_user_id);
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| anyhow::anyhow!("Миграция БД: {}", e))?;
+#[derive(Debug, Clone, FromRow)]
+pub struct Announcement {
+    pub id: i64,
+    pub text: String,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub created_by: Option<i64>,
+}
 
-        let has_display_name_column = sqlx::query_scalar::<_, i64>(
-            "SELECT COUNT(*) FROM pragma_table_info('registration_requests') WHERE name = 'tg_display_name'",
-        )
-        .fetch_one(&self.pool)
-        .await?;
+/// Заявка, перенесённая в архив политикой хранения.
+#[derive(Debug, Clone, FromRow)]
+pub struct ArchivedRequest {
+    pub id: i64,
+    pub tg_user_id: i64,
+    pub tg_username: Option<String>,
+    pub tg_display_name: Option<String>,
+    pub status: RequestStatus,
+    pub telemt_username: Option<String>,
+    pub note: Option<String>,
+    pub created_at: i64,
+    pub resolved_at: Option<i64>,
+    pub archived_at: i64,
+}
 
-        if has_display_name_column == 0 {
-            sqlx::query("ALTER TABLE registration_requests ADD COLUMN tg_display_name TEXT")
-                .execute(&self.pool)
-                .await?;
-        }
+/// Сообщение с одноразовой ссылкой, которое пора удалить из чата.
+#[derive(Debug, Clone, FromRow)]
+pub struct ExpiredLinkMessage {
+    pub chat_id: i64,
+    pub message_id: Option<i32>,
+}
 
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS invite_tokens (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                token TEXT UNIQUE NOT NULL,
-                created_at INTEGER NOT NULL,
-                expires_at INTEGER NOT NULL,
-                auto_approve INTEGER NOT NULL DEFAULT 0,
-                created_by INTEGER,
-                usage_count INTEGER NOT NULL DEFAULT 0,
-                max_usage INTEGER,
-                is_active INTEGER NOT NULL DEFAULT 1,
-                revoked_at INTEGER
-            );
-            CREATE INDEX IF NOT EXISTS idx_invite_tokens_token ON invite_tokens(token);
-            CREATE INDEX IF NOT EXISTS idx_invite_tokens_active ON invite_tokens(is_active);
-            CREATE INDEX IF NOT EXISTS idx_invite_tokens_expires_at ON invite_tokens(expires_at);
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| anyhow::anyhow!("Миграция invite_tokens: {}", e))?;
+/// Сообщение серии онбординга, время отправки которого наступило.
+#[derive(Debug, Clone, FromRow)]
+pub struct DueOnboardingMessage {
+    pub tg_user_id: i64,
+    /// Номер шага в `[onboarding] steps`
+    pub step: i64,
+}
 
-        self.ensure_column_exists("invite_tokens", "max_usage", "INTEGER")
-            .await?;
-        self.ensure_column_exists("invite_tokens", "is_active", "INTEGER NOT NULL DEFAULT 1")
-            .await?;
-        self.ensure_column_exists("invite_tokens", "revoked_at", "INTEGER")
-            .await?;
+/// Сколько пользователей выбрали вариант ответа на вопрос опроса.
+#[derive(Debug, Clone, FromRow)]
+pub struct SurveyAnswerCount {
+    pub question: i64,
+    pub answer: i64,
+    pub count: i64,
+}
 
-        Ok(())
-    }
+/// Применение invite-токена пользователем (`token_usages`).
+#[derive(Debug, Clone, FromRow)]
+pub struct TokenUsage {
+    pub tg_user_id: i64,
+    pub used_at: i64,
+    pub tg_username: Option<String>,
+    pub tg_display_name: Option<String>,
+    /// Текущий статус заявки пользователя; `None` — заявки нет
+    pub status: Option<String>,
+}
 
-    async fn ensure_column_exists(
-        &self,
-        table: &str,
-        column: &str,
-        sql_type: &str,
-    ) -> Result<(), anyhow::Error> {
-        let count = sqlx::query_scalar::<_, i64>(&format!(
-            "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = '{}'",
-            table, column
-        ))
-        .fetch_one(&self.pool)
-        .await?;
-        if count == 0 {
-            sqlx::query(&format!(
-                "ALTER TABLE {} ADD COLUMN {} {}",
-                table, column, sql_type
-            ))
-            .execute(&self.pool)
-            .await?;
-        }
-        Ok(())
-    }
+/// Строка сводной воронки токенов в `/stats`.
+#[derive(Debug, Clone, FromRow)]
+pub struct TokenCampaign {
+    pub token: String,
+    pub label: Option<String>,
+    /// Уникальные пользователи, открывшие deep-link с токеном
+    pub views: i64,
+    /// Успешные применения токена
+    pub consumed: i64,
+    /// Пользователи, получившие доступ по токену
+    pub approved: i64,
+}
 
-    fn generate_invite_token() -> String {
-        Alphanumeric.sample_string(&mut rand::rng(), 10)
-    }
+/// Воронка invite-токена: посещения веб-страницы и переходы по ссылке →
+/// применения → одобрения → активные.
+#[derive(Debug, Clone, FromRow)]
+pub struct TokenFunnel {
+    /// Посещения веб-страницы приглашения ([`crate::web`])
+    pub web_visits: i64,
+    /// Уникальные пользователи, открывшие deep-link с токеном
+    pub views: i64,
+    /// Успешные применения токена
+    pub consumed: i64,
+    /// Пользователи, получившие доступ по токену
+    pub approved: i64,
+    /// Из них всё ещё активны
+    pub active: i64,
+}
 
-    /// Создаёт или возвращает существующую pending-заявку.
-    pub async fn register_or_get(
-        &self,
-        tg_user_id: i64,
-        tg_username: Option<&str>,
-        tg_display_name: Option<&str>,
-    ) -> Result<RegisterResult, anyhow::Error> {
-        let now = current_unix_timestamp()?;
+/// Запись журнала аудита.
+#[derive(Debug, Clone, FromRow, serde::Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: i64,
+    pub actor_id: Option<i64>,
+    /// Действие: `approve`, `delete`, `token_create`, …
+    pub action: String,
+    /// Объект действия: `request:14`, `tg_user:123`, `token:ABC`, …
+    pub target: String,
+    pub details: String,
+}
 
-        let existing_sql = format!("{} WHERE tg_user_id = ?", SELECT_REQUEST);
-        let existing = sqlx::query_as::<_, RegistrationRequest>(&existing_sql)
-        .bind(tg_user_id)
-        .fetch_optional(&self.pool)
-        .await?;
+/// Статус обращения в поддержку.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketStatus {
+    /// Ждёт ответа админа
+    Open,
+    /// Админ ответил, ждём пользователя
+    Answered,
+    Closed,
+}
 
-        if let Some(r) = existing {
-            return match r.status {
-                RequestStatus::Approved => {
-                    if let Some(s) = r.secret {
-                        Ok(RegisterResult::Approved(s))
-                    } else {
-                        Ok(RegisterResult::AlreadyPending)
-                    }
-                }
-                RequestStatus::Rejected => Ok(RegisterResult::Rejected),
-                _ => {
-                    sqlx::query(
-                        "UPDATE registration_requests SET tg_username = ?, tg_display_name = ?, created_at = ? WHERE tg_user_id = ?",
-                    )
-                        .bind(tg_username)
-                        .bind(tg_display_name)
-                        .bind(now)
-                        .bind(tg_user_id)
-                        .execute(&self.pool)
-                        .await?;
-                    Ok(RegisterResult::AlreadyPending)
-                }
-            };
-        }
+text_enum!(TicketStatus {
+    Open => "open",
+    Answered => "answered",
+    Closed => "closed",
+});
 
-        sqlx::query(
-            "INSERT INTO registration_requests (tg_user_id, tg_username, tg_display_name, status, created_at) VALUES (?, ?, ?, 'pending', ?)",
-        )
-        .bind(tg_user_id)
-        .bind(tg_username)
-        .bind(tg_display_name)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+/// Обращение в поддержку: текущий цикл переписки с пользователем.
+#[derive(Debug, Clone, FromRow)]
+pub struct SupportTicket {
+    pub tg_user_id: i64,
+    pub status: TicketStatus,
+    pub opened_at: Option<i64>,
+    pub last_activity_at: Option<i64>,
+    pub assigned_to: Option<i64>,
+    pub assigned_name: Option<String>,
+}
 
-        let req = self
-            .get_pending_by_tg_user(tg_user_id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("только что создали заявку"))?;
-        Ok(RegisterResult::NewPending(req))
-    }
+/// Запись белого списка (`/allow`) со статусом последней заявки пользователя.
+#[derive(Debug, Clone, FromRow)]
+pub struct AllowedUser {
+    pub tg_user_id: i64,
+    pub added_at: i64,
+    pub status: Option<RequestStatus>,
+}
 
-    /// Получает pending-заявку по tg_user_id.
-    pub async fn get_pending_by_tg_user(
-        &self,
-        tg_user_id: i64,
-    ) -> Result<Option<RegistrationRequest>, anyhow::Error> {
-        let sql = format!("{} WHERE tg_user_id = ? AND status = '{}'", SELECT_REQUEST, STATUS_PENDING);
-        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
-        .bind(tg_user_id)
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(r)
-    }
+/// Ручная блокировка (`/ban`).
+#[derive(Debug, Clone, FromRow)]
+pub struct BannedUser {
+    pub tg_user_id: i64,
+    pub banned_by: Option<i64>,
+    pub reason: Option<String>,
+    pub banned_at: i64,
+    pub tg_username: Option<String>,
+}
 
-    /// Получает pending-заявку по id.
-    pub async fn get_pending_by_id(
-        &self,
-        id: i64,
-    ) -> Result<Option<RegistrationRequest>, anyhow::Error> {
-        let sql = format!("{} WHERE id = ? AND status = '{}'", SELECT_REQUEST, STATUS_PENDING);
-        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(r)
-    }
+/// Админ, взявший переписку с пользователем в работу.
+#[derive(Debug, Clone, FromRow)]
+pub struct SupportAssignee {
+    pub admin_id: i64,
+    pub admin_name: Option<String>,
+}
 
-    /// Помечает заявку как approved и сохраняет telemt_username и secret.
-    pub async fn approve(
-        &self,
-        id: i64,
-        telemt_username: &str,
-        secret: &str,
-    ) -> Result<Option<RegistrationRequest>, anyhow::Error> {
-        let now = current_unix_timestamp()?;
+/// Админ, взявший заявку в работу из списка ожидающих.
+#[derive(Debug, Clone, FromRow)]
+pub struct RequestClaim {
+    pub admin_id: i64,
+    pub admin_name: Option<String>,
+}
 
-        let sql = format!("{} WHERE id = ? AND status = '{}'", SELECT_REQUEST, STATUS_PENDING);
-        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await?;
+/// Итоги периода для еженедельного отчёта.
+#[derive(Debug, Clone, FromRow)]
+pub struct PeriodStats {
+    /// Новые заявки за период
+    pub requests: i64,
+    /// Одобрены за период
+    pub approved: i64,
+    /// Отклонены за период
+    pub rejected: i64,
+}
 
-        let req = match r {
-            Some(req) => req,
-            None => return Ok(None),
-        };
+/// Действие расписания группы.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupAction {
+    Disable,
+    Enable,
+}
 
-        sqlx::query(
-            "UPDATE registration_requests SET status = 'approved', telemt_username = ?, secret = ?, resolved_at = ? WHERE id = ?",
-        )
-        .bind(telemt_username)
-        .bind(secret)
-        .bind(now)
-        .bind(id)
-        .execute(&self.pool)
-        .await?;
+text_enum!(GroupAction {
+    Disable => "disable",
+    Enable => "enable",
+});
 
-        Ok(Some(req))
+impl fmt::Display for GroupAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
     }
+}
 
-    /// Помечает заявку как rejected.
-    pub async fn reject(&self, id: i64) -> Result<Option<RegistrationRequest>, anyhow::Error> {
-        let now = current_unix_timestamp()?;
+/// Запланированное отключение или включение группы пользователей.
+#[derive(Debug, Clone, FromRow)]
+pub struct GroupSchedule {
+    pub id: i64,
+    pub group_name: String,
+    pub action: GroupAction,
+    pub run_at: i64,
+    pub created_by: Option<i64>,
+}
 
-        let sql = format!("{} WHERE id = ? AND status = '{}'", SELECT_REQUEST, STATUS_PENDING);
-        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
-        .bind(id)
-        .fetch_optional(&self.pool)
+/// Пользователь в льготном периоде: срок истёк, но доступ ещё не отозван.
+#[derive(Debug, Clone, FromRow)]
+pub struct GraceUser {
+    pub tg_user_id: i64,
+    pub expires_at: i64,
+    pub expiry_warned_at: Option<i64>,
+}
+
+/// Аудитория рассылки.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastAudience {
+    /// Все одобренные пользователи.
+    Approved,
+    /// Пользователи с необработанной заявкой.
+    Pending,
+    /// Одобренные, у которых скоро истекает срок доступа.
+    Expiring,
+    /// Одобренные пользователи одной группы (`group_name`).
+    Group,
+}
+
+text_enum!(BroadcastAudience {
+    Approved => "approved",
+    Pending => "pending",
+    Expiring => "expiring",
+    Group => "group",
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastStatus {
+    Draft,
+    Scheduled,
+    Sending,
+    Done,
+    Cancelled,
+}
+
+text_enum!(BroadcastStatus {
+    Draft => "draft",
+    Scheduled => "scheduled",
+    Sending => "sending",
+    Done => "done",
+    Cancelled => "cancelled",
+});
+
+/// Результат доставки рассылки одному получателю.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Queued,
+    Sent,
+    Failed,
+    /// Пользователь заблокировал бота или удалил аккаунт.
+    Blocked,
+}
+
+text_enum!(DeliveryStatus {
+    Queued => "queued",
+    Sent => "sent",
+    Failed => "failed",
+    Blocked => "blocked",
+});
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Broadcast {
+    pub id: i64,
+    pub text: String,
+    pub audience: BroadcastAudience,
+    pub group_name: Option<String>,
+    pub status: BroadcastStatus,
+    /// Время отправки; у черновика — желаемое время (`None` — сразу после подтверждения).
+    pub scheduled_at: Option<i64>,
+    pub created_by: i64,
+    pub created_at: i64,
+}
+
+const SELECT_BROADCAST: &str = "SELECT id, text, audience, group_name, status, scheduled_at, created_by, created_at FROM broadcasts";
+
+/// Итоги доставки рассылки.
+#[derive(Debug, Clone, Default)]
+pub struct BroadcastReport {
+    pub queued: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub blocked: i64,
+}
+
+/// Накопленный трафик пользователя по метрикам telemt.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserTraffic {
+    pub tg_user_id: i64,
+    pub telemt_username: Option<String>,
+    pub bytes_from_client: i64,
+    pub bytes_to_client: i64,
+    pub updated_at: i64,
+}
+
+impl UserTraffic {
+    pub fn total(&self) -> i64 {
+        self.bytes_from_client.saturating_add(self.bytes_to_client)
+    }
+}
+
+const SELECT_USER_TRAFFIC: &str = "SELECT t.tg_user_id, r.telemt_username, t.bytes_from_client,
+       t.bytes_to_client, t.updated_at
+FROM user_traffic t
+JOIN registration_requests r ON r.tg_user_id = t.tg_user_id";
+
+/// Заранее разрешённый пользователь из `[[provision]]`, ещё не получивший доступ.
+#[derive(Debug, Clone, FromRow)]
+pub struct ProvisionedUser {
+    pub note: Option<String>,
+    pub user_group: Option<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+/// Сколько записей перенесено в архив за один проход очистки.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionReport {
+    pub requests: u64,
+    pub tokens: u64,
+}
+
+/// Где хранятся данные бота: файл SQLite (`db_path`) или сервер Postgres (`db_url`).
+#[derive(Debug, Clone)]
+pub enum DbLocation {
+    Sqlite(PathBuf),
+    Postgres(String),
+}
+
+impl fmt::Display for DbLocation {
+    /// URL Postgres выводится без логина и пароля: строка попадает в логи.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite(path) => write!(f, "{}", path.display()),
+            Self::Postgres(url) => match url.rsplit_once('@') {
+                Some((_, host)) => write!(f, "postgres://***@{}", host),
+                None => f.write_str(url),
+            },
+        }
+    }
+}
+
+/// СУБД за пулом `Any`: от неё зависят миграции и запросы без общего синтаксиса.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+/// Как открыть файл SQLite; на Postgres не влияет.
+#[derive(Debug, Clone, Copy)]
+enum OpenMode {
+    /// Создать файл, если его нет
+    Create,
+    ReadWrite,
+    ReadOnly,
+}
+
+impl DbLocation {
+    fn backend(&self) -> Backend {
+        match self {
+            Self::Sqlite(_) => Backend::Sqlite,
+            Self::Postgres(_) => Backend::Postgres,
+        }
+    }
+
+    async fn connect(&self, mode: OpenMode) -> Result<AnyPool, DbError> {
+        sqlx::any::install_default_drivers();
+        let url = match self {
+            Self::Sqlite(path) => SqliteConnectOptions::new()
+                .filename(path)
+                .create_if_missing(matches!(mode, OpenMode::Create))
+                .read_only(matches!(mode, OpenMode::ReadOnly))
+                .to_url_lossy(),
+            Self::Postgres(url) => url
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Некорректный db_url: {}", e))?,
+        };
+        let options = AnyConnectOptions::from_url(&url)?;
+        Ok(AnyPoolOptions::new().connect_with(options).await?)
+    }
+
+    fn migrator(&self) -> Result<&'static Migrator, DbError> {
+        match self.backend() {
+            Backend::Sqlite => Ok(&MIGRATOR),
+            #[cfg(feature = "postgres")]
+            Backend::Postgres => Ok(&POSTGRES_MIGRATOR),
+            #[cfg(not(feature = "postgres"))]
+            Backend::Postgres => Err(anyhow::anyhow!(
+                "Бот собран без поддержки Postgres: пересоберите с --features postgres"
+            )
+            .into()),
+        }
+    }
+}
+
+pub struct Db {
+    pool: AnyPool,
+    backend: Backend,
+}
+
+/// Минимальная длина фрагмента секрета в `/find`, чтобы не находить всех подряд.
+const SECRET_FRAGMENT_MIN_LEN: usize = 6;
+
+/// Добавляет результаты поиска без повторов.
+fn merge_search_hits(hits: &mut Vec<UserSearchHit>, matched: Vec<UserSearchHit>) {
+    for hit in matched {
+        if !hits.iter().any(|existing| existing.id == hit.id) {
+            hits.push(hit);
+        }
+    }
+}
+
+/// Условие отбора получателей рассылки в `registration_requests`. У `Group` и
+/// `Expiring` один параметр с номером `param`: имя группы и граница срока доступа
+/// соответственно.
+fn broadcast_audience_filter(audience: BroadcastAudience, param: usize) -> String {
+    match audience {
+        BroadcastAudience::Approved => "status = 'approved'".to_string(),
+        BroadcastAudience::Pending => "status = 'pending'".to_string(),
+        BroadcastAudience::Expiring => {
+            format!("status = 'approved' AND expires_at IS NOT NULL AND expires_at <= ${param}")
+        }
+        BroadcastAudience::Group => format!("status = 'approved' AND user_group = ${param}"),
+    }
+}
+
+fn current_unix_timestamp() -> Result<i64, anyhow::Error> {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .map_err(|err| anyhow::anyhow!("Системное время меньше UNIX_EPOCH: {}", err))
+}
+
+/// Создаёт или возвращает существующую pending-заявку. Принимает соединение, чтобы
+/// регистрация шла в одной транзакции с применением токена.
+async fn register_or_get_on(
+    conn: &mut AnyConnection,
+    tg_user_id: i64,
+    tg_username: Option<&str>,
+    tg_display_name: Option<&str>,
+) -> Result<RegisterResult, DbError> {
+    let now = current_unix_timestamp()?;
+
+    let existing_sql = format!("{} WHERE tg_user_id = $1", SELECT_REQUEST);
+    let existing = sqlx::query_as::<_, RegistrationRequest>(&existing_sql)
+        .bind(tg_user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    if let Some(r) = existing {
+        return match r.status {
+            RequestStatus::Approved => {
+                if let Some(s) = r.secret {
+                    Ok(RegisterResult::Approved(s))
+                } else {
+                    Ok(RegisterResult::AlreadyPending)
+                }
+            }
+            RequestStatus::Rejected => Ok(RegisterResult::Rejected),
+            _ => {
+                sqlx::query(
+                    "UPDATE registration_requests SET tg_username = $1, tg_display_name = $2, created_at = $3 WHERE tg_user_id = $4",
+                )
+                .bind(tg_username)
+                .bind(tg_display_name)
+                .bind(now)
+                .bind(tg_user_id)
+                .execute(&mut *conn)
+                .await?;
+                Ok(RegisterResult::AlreadyPending)
+            }
+        };
+    }
+
+    sqlx::query(
+        "INSERT INTO registration_requests (tg_user_id, tg_username, tg_display_name, status, created_at) VALUES ($1, $2, $3, 'pending', $4)",
+    )
+    .bind(tg_user_id)
+    .bind(tg_username)
+    .bind(tg_display_name)
+    .bind(now)
+    .execute(&mut *conn)
+    .await?;
+
+    let sql = format!(
+        "{} WHERE tg_user_id = $1 AND status = '{}'",
+        SELECT_REQUEST, STATUS_PENDING
+    );
+    let req = sqlx::query_as::<_, RegistrationRequest>(&sql)
+        .bind(tg_user_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("только что создали заявку"))?;
+    Ok(RegisterResult::NewPending(req))
+}
+
+impl Db {
+    pub async fn open(location: &DbLocation) -> Result<Self, DbError> {
+        if let DbLocation::Sqlite(path) = location
+            && let Some(parent) = path.parent()
+        {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| anyhow::anyhow!("Не удалось создать директорию для БД: {}", e))?;
+        }
+
+        let migrator = location.migrator()?;
+        let pool = location.connect(OpenMode::Create).await?;
+        let db = Self {
+            pool,
+            backend: location.backend(),
+        };
+        db.migrate(migrator).await?;
+        Ok(db)
+    }
+
+    /// Проверяет, что БД открывается на чтение, не создавая файл и не применяя миграции.
+    pub async fn probe(location: &DbLocation) -> Result<(), DbError> {
+        let pool = location.connect(OpenMode::ReadOnly).await?;
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM registration_requests")
+            .fetch_one(&pool)
+            .await?;
+        pool.close().await;
+        Ok(())
+    }
+
+    async fn migrate(&self, migrator: &Migrator) -> Result<(), DbError> {
+        if self.backend == Backend::Sqlite {
+            self.upgrade_legacy_schema().await?;
+        }
+        migrator
+            .run(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Миграция БД: {}", e))?;
+        Ok(())
+    }
+
+    /// Откатывает обратимые миграции новее версии `target` (`--migrate-undo`).
+    /// Базовая миграция необратима: откатить можно только то, что добавлено после неё.
+    pub async fn undo_migrations(location: &DbLocation, target: i64) -> Result<(), DbError> {
+        let migrator = location.migrator()?;
+        let pool = location.connect(OpenMode::ReadWrite).await?;
+        migrator
+            .undo(&pool, target)
+            .await
+            .map_err(|e| anyhow::anyhow!("Откат миграций БД: {}", e))?;
+        pool.close().await;
+        Ok(())
+    }
+
+    /// БД, созданная до версионированных миграций: дописывает колонки, которые раньше
+    /// добавлялись при каждом старте, чтобы базовая миграция легла поверх без ошибок.
+    /// Новые изменения схемы сюда не добавляются — только файлами в `migrations/`.
+    /// Только SQLite: в Postgres бот работает с версионированными миграциями с самого начала.
+    async fn upgrade_legacy_schema(&self) -> Result<(), DbError> {
+        if self.table_exists("_sqlx_migrations").await?
+            || !self.table_exists("registration_requests").await?
+        {
+            return Ok(());
+        }
+        tracing::info!("Upgrading database created before versioned migrations");
+        for (table, column, sql_type) in LEGACY_COLUMNS {
+            if self.table_exists(table).await? {
+                self.ensure_column_exists(table, column, sql_type).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn table_exists(&self, table: &str) -> Result<bool, DbError> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = $1",
+        )
+        .bind(table)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count > 0)
+    }
+
+    async fn ensure_column_exists(
+        &self,
+        table: &str,
+        column: &str,
+        sql_type: &str,
+    ) -> Result<(), DbError> {
+        let count = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = '{}'",
+            table, column
+        ))
+        .fetch_one(&self.pool)
+        .await?;
+        if count == 0 {
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, sql_type
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Поиск подстроки без учёта регистра: `LIKE` в SQLite уже такой, в Postgres — `ILIKE`.
+    fn like(&self) -> &'static str {
+        match self.backend {
+            Backend::Sqlite => "LIKE",
+            Backend::Postgres => "ILIKE",
+        }
+    }
+
+    fn generate_invite_token() -> String {
+        Alphanumeric.sample_string(&mut rand::rng(), 10)
+    }
+
+    /// Получает pending-заявку по tg_user_id.
+    pub async fn get_pending_by_tg_user(
+        &self,
+        tg_user_id: i64,
+    ) -> Result<Option<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE tg_user_id = $1 AND status = '{}'",
+            SELECT_REQUEST, STATUS_PENDING
+        );
+        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(tg_user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(r)
+    }
+
+    /// Получает pending-заявку по id.
+    pub async fn get_pending_by_id(&self, id: i64) -> Result<Option<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE id = $1 AND status = '{}'",
+            SELECT_REQUEST, STATUS_PENDING
+        );
+        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(r)
+    }
+
+    /// Помечает заявку как approved и сохраняет telemt_username и secret.
+    pub async fn approve(
+        &self,
+        id: i64,
+        telemt_username: &str,
+        secret: &str,
+    ) -> Result<Option<RegistrationRequest>, DbError> {
+        let now = current_unix_timestamp()?;
+
+        let sql = format!(
+            "{} WHERE id = $1 AND status = '{}'",
+            SELECT_REQUEST, STATUS_PENDING
+        );
+        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let req = match r {
+            Some(req) => req,
+            None => return Ok(None),
+        };
+
+        sqlx::query(
+            "UPDATE registration_requests SET status = 'approved', telemt_username = $1, secret = $2, resolved_at = $3, suspended = 0, expires_at = NULL, expiry_warned_at = NULL WHERE id = $4",
+        )
+        .bind(telemt_username)
+        .bind(secret)
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some(req))
+    }
+
+    /// Возвращает одобренную заявку в ожидание, если её секрет всё ещё `secret`:
+    /// одобрение, которое не удалось применить к конфигу telemt, не должно остаться в БД.
+    pub async fn revert_approval(&self, id: i64, secret: &str) -> Result<bool, DbError> {
+        let result = sqlx::query(
+            "UPDATE registration_requests
+             SET status = 'pending', telemt_username = NULL, secret = NULL, resolved_at = NULL
+             WHERE id = $1 AND status = 'approved' AND secret = $2",
+        )
+        .bind(id)
+        .bind(secret)
+        .execute(&self.pool)
         .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Помечает заявку как rejected.
+    /// Отклоняет ожидающую заявку; `reason` сохраняется для истории и сообщения пользователю.
+    pub async fn reject(
+        &self,
+        id: i64,
+        reason: Option<&str>,
+    ) -> Result<Option<RegistrationRequest>, DbError> {
+        let now = current_unix_timestamp()?;
+
+        let sql = format!(
+            "{} WHERE id = $1 AND status = '{}'",
+            SELECT_REQUEST, STATUS_PENDING
+        );
+        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
 
         let req = r.clone();
         if r.is_some() {
             sqlx::query(
-                "UPDATE registration_requests SET status = 'rejected', resolved_at = ? WHERE id = ?",
+                "UPDATE registration_requests SET status = 'rejected', resolved_at = $1, reject_reason = $2
+                 WHERE id = $3",
             )
             .bind(now)
+            .bind(reason)
             .bind(id)
             .execute(&self.pool)
             .await?;
@@ -377,9 +1118,9 @@ impl Db {
     }
 
     /// Деактивирует пользователя (помечает как удалённого для истории; сама запись остаётся).
-    pub async fn deactivate_user(&self, tg_user_id: i64) -> Result<bool, anyhow::Error> {
+    pub async fn deactivate_user(&self, tg_user_id: i64) -> Result<bool, DbError> {
         let r = sqlx::query(
-            "UPDATE registration_requests SET status = ? WHERE tg_user_id = ? AND status = ?",
+            "UPDATE registration_requests SET status = $1 WHERE tg_user_id = $2 AND status = $3",
         )
         .bind(STATUS_DELETED)
         .bind(tg_user_id)
@@ -397,11 +1138,11 @@ impl Db {
         tg_display_name: Option<&str>,
         telemt_username: &str,
         secret: &str,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<(), DbError> {
         let now = current_unix_timestamp()?;
 
         let exists = sqlx::query_scalar::<_, i64>(
-            "SELECT 1 FROM registration_requests WHERE tg_user_id = ?",
+            "SELECT 1 FROM registration_requests WHERE tg_user_id = $1",
         )
         .bind(tg_user_id)
         .fetch_optional(&self.pool)
@@ -411,12 +1152,15 @@ impl Db {
             sqlx::query(
                 "UPDATE registration_requests
                  SET status = 'approved',
-                     tg_username = ?,
-                     tg_display_name = ?,
-                     telemt_username = ?,
-                     secret = ?,
-                     resolved_at = ?
-                 WHERE tg_user_id = ?",
+                     tg_username = $1,
+                     tg_display_name = $2,
+                     telemt_username = $3,
+                     secret = $4,
+                     resolved_at = $5,
+                     suspended = 0,
+                     expires_at = NULL,
+                     expiry_warned_at = NULL
+                 WHERE tg_user_id = $6",
             )
             .bind(tg_username)
             .bind(tg_display_name)
@@ -430,7 +1174,7 @@ impl Db {
             sqlx::query(
                 "INSERT INTO registration_requests
                  (tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at, resolved_at)
-                 VALUES (?, ?, ?, 'approved', ?, ?, ?, ?)",
+                 VALUES ($1, $2, $3, 'approved', $4, $5, $6, $7)",
             )
             .bind(tg_user_id)
             .bind(tg_username)
@@ -446,45 +1190,43 @@ impl Db {
     }
 
     /// Получает approved-пользователя по tg_user_id.
-    pub async fn get_approved(
-        &self,
-        tg_user_id: i64,
-    ) -> Result<Option<(String, String)>, anyhow::Error> {
+    pub async fn get_approved(&self, tg_user_id: i64) -> Result<Option<(String, String)>, DbError> {
         let sql = format!(
-            "{} WHERE tg_user_id = ? AND status = '{}'",
+            "{} WHERE tg_user_id = $1 AND status = '{}'",
             SELECT_REQUEST, STATUS_APPROVED
         );
         let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
-        .bind(tg_user_id)
-        .fetch_optional(&self.pool)
-        .await?;
+            .bind(tg_user_id)
+            .fetch_optional(&self.pool)
+            .await?;
         Ok(r.and_then(|x| x.telemt_username.zip(x.secret)))
     }
 
     pub async fn get_request_by_tg_user(
         &self,
         tg_user_id: i64,
-    ) -> Result<Option<RegistrationRequest>, anyhow::Error> {
-        let sql = format!("{} WHERE tg_user_id = ?", SELECT_REQUEST);
+    ) -> Result<Option<RegistrationRequest>, DbError> {
+        let sql = format!("{} WHERE tg_user_id = $1", SELECT_REQUEST);
         let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
-        .bind(tg_user_id)
-        .fetch_optional(&self.pool)
-        .await?;
+            .bind(tg_user_id)
+            .fetch_optional(&self.pool)
+            .await?;
         Ok(r)
     }
 
     pub async fn create_invite_token(
         &self,
-        days: i64,
-        auto_approve: bool,
-        max_usage: Option<i64>,
-        created_by: Option<i64>,
-    ) -> Result<InviteToken, anyhow::Error> {
+        params: NewInviteToken<'_>,
+    ) -> Result<InviteToken, DbError> {
         let now = current_unix_timestamp()?;
-        let ttl_seconds = days
+        let ttl_seconds = params
+            .days
             .checked_mul(86_400)
             .ok_or_else(|| anyhow::anyhow!("Срок действия токена слишком большой"))?;
-        let expires_at = now
+        let expires_at = params
+            .valid_from
+            .unwrap_or(now)
+            .max(now)
             .checked_add(ttl_seconds)
             .ok_or_else(|| anyhow::anyhow!("Некорректное время истечения токена"))?;
 
@@ -492,25 +1234,30 @@ impl Db {
         for _ in 0..8 {
             let token = Self::generate_invite_token();
             let result = sqlx::query(
-                "INSERT INTO invite_tokens (token, created_at, expires_at, auto_approve, created_by, max_usage) VALUES (?, ?, ?, ?, ?, ?)",
+                "INSERT INTO invite_tokens (token, created_at, expires_at, auto_approve, created_by, max_usage, for_tg_user_id, plans, label, valid_from, group_name, referrer_tg_user_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
             )
             .bind(&token)
             .bind(now)
             .bind(expires_at)
-            .bind(auto_approve)
-            .bind(created_by)
-            .bind(max_usage)
+            .bind(i64::from(params.auto_approve))
+            .bind(params.created_by)
+            .bind(params.max_usage)
+            .bind(params.for_tg_user_id)
+            .bind((!params.plans.is_empty()).then(|| params.plans.join(",")))
+            .bind(params.label)
+            .bind(params.valid_from)
+            .bind(params.group_name)
+            .bind(params.referrer_tg_user_id)
             .execute(&self.pool)
             .await;
 
             match result {
                 Ok(_) => {
-                    created = sqlx::query_as::<_, InviteToken>(
-                        "SELECT id, token, created_at, expires_at, auto_approve, created_by, usage_count, max_usage, is_active FROM invite_tokens WHERE token = ?",
-                    )
-                    .bind(token)
-                    .fetch_optional(&self.pool)
-                    .await?;
+                    let sql = format!("{} WHERE token = $1", SELECT_INVITE_TOKEN);
+                    created = sqlx::query_as::<_, InviteToken>(&sql)
+                        .bind(token)
+                        .fetch_optional(&self.pool)
+                        .await?;
                     if created.is_some() {
                         break;
                     }
@@ -520,27 +1267,24 @@ impl Db {
                     if message.contains("unique") {
                         continue;
                     }
-                    return Err(anyhow::anyhow!("Не удалось создать invite-токен: {}", err));
+                    return Err(anyhow::anyhow!("Не удалось создать invite-токен: {}", err).into());
                 }
             }
         }
 
-        created.ok_or_else(|| anyhow::anyhow!("Не удалось сгенерировать уникальный токен"))
+        Ok(created.ok_or_else(|| anyhow::anyhow!("Не удалось сгенерировать уникальный токен"))?)
     }
 
-    pub async fn list_active_invite_tokens(
-        &self,
-        limit: i64,
-    ) -> Result<Vec<InviteToken>, anyhow::Error> {
+    pub async fn list_active_invite_tokens(&self, limit: i64) -> Result<Vec<InviteToken>, DbError> {
         let now = current_unix_timestamp()?;
         let rows = sqlx::query_as::<_, InviteToken>(
-            "SELECT id, token, created_at, expires_at, auto_approve, created_by, usage_count, max_usage, is_active
+            "SELECT id, token, created_at, expires_at, auto_approve, created_by, usage_count, max_usage, is_active, for_tg_user_id, plans, label, valid_from, group_name, referrer_tg_user_id
              FROM invite_tokens
              WHERE is_active = 1
-               AND expires_at > ?
+               AND expires_at > $1
                AND (max_usage IS NULL OR usage_count < max_usage)
              ORDER BY expires_at ASC
-             LIMIT ?",
+             LIMIT $2",
         )
         .bind(now)
         .bind(limit)
@@ -549,11 +1293,81 @@ impl Db {
         Ok(rows)
     }
 
-    pub async fn revoke_invite_token(&self, token: &str) -> Result<bool, anyhow::Error> {
-        let now = current_unix_timestamp()?;
-        let result = sqlx::query(
-            "UPDATE invite_tokens SET is_active = 0, revoked_at = ? WHERE token = ? AND is_active = 1",
-        )
+    pub async fn get_invite_token(&self, token: &str) -> Result<Option<InviteToken>, DbError> {
+        let sql = format!("{} WHERE token = $1", SELECT_INVITE_TOKEN);
+        let row = sqlx::query_as::<_, InviteToken>(&sql)
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row)
+    }
+
+    /// Меняет срок и/или лимит неотозванного токена. `max_usage: Some(None)` снимает лимит.
+    /// Возвращает обновлённый токен; `None` — токен не найден или отозван.
+    pub async fn update_invite_token(
+        &self,
+        token: &str,
+        expires_at: Option<i64>,
+        max_usage: Option<Option<i64>>,
+    ) -> Result<Option<InviteToken>, DbError> {
+        let result = sqlx::query(
+            "UPDATE invite_tokens
+             SET expires_at = CASE WHEN $1 THEN $2 ELSE expires_at END,
+                 expiry_notified_at = CASE WHEN $1 THEN NULL ELSE expiry_notified_at END,
+                 max_usage = CASE WHEN $3 THEN $4 ELSE max_usage END
+             WHERE token = $5 AND is_active = 1",
+        )
+        .bind(expires_at.is_some())
+        .bind(expires_at)
+        .bind(max_usage.is_some())
+        .bind(max_usage.flatten())
+        .bind(token)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get_invite_token(token).await
+    }
+
+    /// Действующие токены, истекающие до `expires_before`, о которых создателю ещё не напоминали.
+    pub async fn list_expiring_invite_tokens(
+        &self,
+        expires_before: i64,
+    ) -> Result<Vec<InviteToken>, DbError> {
+        let now = current_unix_timestamp()?;
+        let sql = format!(
+            "{} WHERE is_active = 1
+               AND expires_at > $1 AND expires_at <= $2
+               AND expiry_notified_at IS NULL
+               AND referrer_tg_user_id IS NULL
+               AND (max_usage IS NULL OR usage_count < max_usage)
+             ORDER BY expires_at ASC",
+            SELECT_INVITE_TOKEN
+        );
+        let rows = sqlx::query_as::<_, InviteToken>(&sql)
+            .bind(now)
+            .bind(expires_before)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    pub async fn mark_token_expiry_notified(&self, token_id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query("UPDATE invite_tokens SET expiry_notified_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(token_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn revoke_invite_token(&self, token: &str) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "UPDATE invite_tokens SET is_active = 0, revoked_at = $1 WHERE token = $2 AND is_active = 1",
+        )
         .bind(now)
         .bind(token)
         .execute(&self.pool)
@@ -561,58 +1375,93 @@ impl Db {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Применяет токен от имени `tg_user_id` и регистрирует пользователя одной
+    /// транзакцией. Использование засчитывается (счётчик, запись в `token_usages`,
+    /// токен в заявке), только если токен дал новый доступ: новая заявка, а для токена с автоподтверждением — любой ещё не
+    /// одобренный пользователь. Иначе транзакция откатывается целиком. Персональный
+    /// токен чужого пользователя использование не расходует.
     pub async fn consume_invite_token(
         &self,
         token: &str,
-    ) -> Result<ConsumedInviteToken, TokenConsumeError> {
-        let now = current_unix_timestamp().map_err(|_| TokenConsumeError::NotFound)?;
+        tg_user_id: i64,
+        tg_username: Option<&str>,
+        tg_display_name: Option<&str>,
+    ) -> Result<(ConsumedInviteToken, RegisterResult), TokenConsumeError> {
+        let now = chrono::Utc::now().timestamp();
+        let mut tx = self.pool.begin().await?;
         let update_result = sqlx::query(
             "UPDATE invite_tokens
              SET usage_count = usage_count + 1
-             WHERE token = ?
+             WHERE token = $1
                AND is_active = 1
-               AND expires_at > ?
-               AND (max_usage IS NULL OR usage_count < max_usage)",
+               AND expires_at > $2
+               AND (max_usage IS NULL OR usage_count < max_usage)
+               AND (for_tg_user_id IS NULL OR for_tg_user_id = $3)
+               AND (valid_from IS NULL OR valid_from <= $4)",
         )
         .bind(token)
         .bind(now)
-        .execute(&self.pool)
-        .await
-        .map_err(|_| TokenConsumeError::NotFound)?;
+        .bind(tg_user_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
 
-        if update_result.rows_affected() == 0 {
-            let token_row = sqlx::query_as::<_, InviteToken>(
-                "SELECT id, token, created_at, expires_at, auto_approve, created_by, usage_count, max_usage, is_active FROM invite_tokens WHERE token = ?",
-            )
+        let sql = format!("{} WHERE token = $1", SELECT_INVITE_TOKEN);
+        let token_row = sqlx::query_as::<_, InviteToken>(&sql)
             .bind(token)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|_| TokenConsumeError::NotFound)?;
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(row) = token_row else {
+            return Err(TokenConsumeError::NotFound);
+        };
 
-            let Some(row) = token_row else {
-                return Err(TokenConsumeError::NotFound);
-            };
+        if update_result.rows_affected() == 0 {
             if !row.is_active {
                 return Err(TokenConsumeError::Revoked);
             }
+            if row.for_tg_user_id.is_some_and(|owner| owner != tg_user_id) {
+                return Err(TokenConsumeError::NotForYou);
+            }
             if row.expires_at <= now {
                 return Err(TokenConsumeError::Expired);
             }
             if row.max_usage.is_some_and(|max| row.usage_count >= max) {
                 return Err(TokenConsumeError::UsageLimitReached);
             }
+            if let Some(valid_from) = row.valid_from.filter(|valid_from| *valid_from > now) {
+                return Err(TokenConsumeError::NotYetActive(valid_from));
+            }
             return Err(TokenConsumeError::NotFound);
         }
 
-        let row = sqlx::query_as::<_, InviteToken>(
-            "SELECT id, token, created_at, expires_at, auto_approve, created_by, usage_count, max_usage, is_active FROM invite_tokens WHERE token = ?",
-        )
-        .bind(token)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|_| TokenConsumeError::NotFound)?;
-        let row = row.ok_or(TokenConsumeError::NotFound)?;
-        Ok(ConsumedInviteToken {
+        let registered =
+            register_or_get_on(&mut tx, tg_user_id, tg_username, tg_display_name).await?;
+        let grants_access = match &registered {
+            RegisterResult::NewPending(_) => true,
+            RegisterResult::Approved(_) => false,
+            RegisterResult::AlreadyPending | RegisterResult::Rejected => row.auto_approve,
+        };
+        if grants_access {
+            sqlx::query(
+                "INSERT INTO token_usages (token, tg_user_id, used_at) VALUES ($1, $2, $3)",
+            )
+            .bind(token)
+            .bind(tg_user_id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("UPDATE registration_requests SET invite_token = $1 WHERE tg_user_id = $2")
+                .bind(token)
+                .bind(tg_user_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        } else {
+            tx.rollback().await?;
+        }
+
+        let plans = row.plan_names();
+        let consumed = ConsumedInviteToken {
             id: row.id,
             token: row.token,
             mode: if row.auto_approve {
@@ -624,14 +1473,45 @@ impl Db {
             created_by: row.created_by,
             usage_count: row.usage_count,
             max_usage: row.max_usage,
-        })
+            for_tg_user_id: row.for_tg_user_id,
+            plans,
+            label: row.label,
+            group_name: row.group_name,
+            referrer_tg_user_id: row.referrer_tg_user_id,
+        };
+        Ok((consumed, registered))
+    }
+
+    /// Сколько реферальных токенов выписал пользователь (включая отозванные и истёкшие).
+    pub async fn count_referral_tokens(&self, tg_user_id: i64) -> Result<i64, DbError> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM invite_tokens WHERE referrer_tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+
+    /// Кто пригласил пользователя: автор реферального токена, по которому создана заявка.
+    pub async fn get_referrer(&self, tg_user_id: i64) -> Result<Option<i64>, DbError> {
+        let referrer = sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT t.referrer_tg_user_id
+             FROM registration_requests r
+             JOIN invite_tokens t ON t.token = r.invite_token
+             WHERE r.tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(referrer.flatten())
     }
 
     /// Ищет tg_user_id по tg_username (без учёта регистра, без @).
     pub async fn find_tg_user_id_by_username(
         &self,
         username: &str,
-    ) -> Result<Option<i64>, anyhow::Error> {
+    ) -> Result<Option<i64>, DbError> {
         let normalized = username.trim_start_matches('@');
         if normalized.is_empty() {
             return Ok(None);
@@ -639,7 +1519,7 @@ impl Db {
 
         let user_id = sqlx::query_scalar::<_, i64>(
             "SELECT tg_user_id FROM registration_requests
-             WHERE lower(tg_username) = lower(?)
+             WHERE lower(tg_username) = lower($1)
              ORDER BY created_at DESC
              LIMIT 1",
         )
@@ -652,24 +1532,39 @@ impl Db {
     pub async fn list_pending_requests(
         &self,
         limit: i64,
-    ) -> Result<Vec<RegistrationRequest>, anyhow::Error> {
+        offset: i64,
+    ) -> Result<Vec<RegistrationRequest>, DbError> {
         let rows = sqlx::query_as::<_, RegistrationRequest>(
             "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at
              FROM registration_requests
-             WHERE status = ?
-             ORDER BY created_at ASC
-             LIMIT ?",
+             WHERE status = $1
+             ORDER BY created_at ASC, id ASC
+             LIMIT $2 OFFSET $3",
         )
         .bind(STATUS_PENDING)
         .bind(limit)
+        .bind(offset)
         .fetch_all(&self.pool)
         .await?;
         Ok(rows)
     }
 
-    pub async fn count_active_users(&self) -> Result<i64, anyhow::Error> {
+    pub async fn pending_summary(&self) -> Result<PendingSummary, DbError> {
+        let (count, oldest_created_at) = sqlx::query_as::<_, (i64, Option<i64>)>(
+            "SELECT COUNT(*), MIN(created_at) FROM registration_requests WHERE status = $1",
+        )
+        .bind(STATUS_PENDING)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(PendingSummary {
+            count,
+            oldest_created_at,
+        })
+    }
+
+    pub async fn count_active_users(&self) -> Result<i64, DbError> {
         let total = sqlx::query_scalar::<_, i64>(
-            "SELECT COUNT(*) FROM registration_requests WHERE status = ?",
+            "SELECT COUNT(*) FROM registration_requests WHERE status = $1",
         )
         .bind(STATUS_APPROVED)
         .fetch_one(&self.pool)
@@ -677,34 +1572,135 @@ impl Db {
         Ok(total)
     }
 
+    /// Keyset-пагинация: страница не «съезжает», если между нажатиями
+    /// пользователи добавились или удалились, и не замедляется в конце списка.
     pub async fn list_active_users_page(
         &self,
         limit: i64,
-        offset: i64,
-    ) -> Result<Vec<RegistrationRequest>, anyhow::Error> {
-        let rows = sqlx::query_as::<_, RegistrationRequest>(
-            "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at
+        request: UsersPageRequest,
+    ) -> Result<UsersPage, DbError> {
+        let limit = limit.max(1);
+        let mut request = request;
+        loop {
+            let (mut users, overflow) = self.fetch_active_users_page(limit, request).await?;
+            if users.is_empty() {
+                // Записи на странице исчезли — откатываемся к соседней.
+                request = match request {
+                    UsersPageRequest::After(cursor) | UsersPageRequest::From(cursor) => {
+                        UsersPageRequest::Before(cursor)
+                    }
+                    UsersPageRequest::Before(_) => UsersPageRequest::First,
+                    UsersPageRequest::First => {
+                        return Ok(UsersPage {
+                            users,
+                            has_prev: false,
+                            has_next: false,
+                        });
+                    }
+                };
+                continue;
+            }
+            if let UsersPageRequest::Before(_) = request {
+                if !overflow {
+                    // Дошли до начала списка — показываем полную первую страницу.
+                    request = UsersPageRequest::First;
+                    continue;
+                }
+                users.reverse();
+            }
+
+            let first = users[0].cursor();
+            let last = users[users.len() - 1].cursor();
+            let (has_prev, has_next) = match request {
+                UsersPageRequest::First => (false, overflow),
+                UsersPageRequest::After(_) | UsersPageRequest::From(_) => {
+                    (self.active_user_exists_before(first).await?, overflow)
+                }
+                UsersPageRequest::Before(_) => (true, self.active_user_exists_after(last).await?),
+            };
+            return Ok(UsersPage {
+                users,
+                has_prev,
+                has_next,
+            });
+        }
+    }
+
+    /// Возвращает не больше `limit` записей и признак того, что в этом направлении есть ещё.
+    async fn fetch_active_users_page(
+        &self,
+        limit: i64,
+        request: UsersPageRequest,
+    ) -> Result<(Vec<RegistrationRequest>, bool), DbError> {
+        const COLUMNS: &str = "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at
              FROM registration_requests
-             WHERE status = ?
-             ORDER BY created_at DESC
-             LIMIT ? OFFSET ?",
+             WHERE status = $1";
+        let (filter, order, cursor) = match request {
+            UsersPageRequest::First => ("", "DESC", None),
+            UsersPageRequest::After(cursor) => {
+                (" AND (created_at, id) < ($2, $3)", "DESC", Some(cursor))
+            }
+            UsersPageRequest::From(cursor) => {
+                (" AND (created_at, id) <= ($2, $3)", "DESC", Some(cursor))
+            }
+            UsersPageRequest::Before(cursor) => {
+                (" AND (created_at, id) > ($2, $3)", "ASC", Some(cursor))
+            }
+        };
+        let limit_param = if cursor.is_some() { 4 } else { 2 };
+        let sql = format!(
+            "{COLUMNS}{filter} ORDER BY created_at {order}, id {order} LIMIT ${limit_param}"
+        );
+        let mut query = sqlx::query_as::<_, RegistrationRequest>(&sql).bind(STATUS_APPROVED);
+        if let Some(cursor) = cursor {
+            query = query.bind(cursor.created_at).bind(cursor.id);
+        }
+        let mut rows = query.bind(limit + 1).fetch_all(&self.pool).await?;
+        let overflow = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+        Ok((rows, overflow))
+    }
+
+    /// Есть ли активные пользователи выше курсора (ближе к началу списка).
+    async fn active_user_exists_before(&self, cursor: UserCursor) -> Result<bool, DbError> {
+        let exists = sqlx::query_scalar::<_, i64>(
+            "SELECT CASE WHEN EXISTS(
+                SELECT 1 FROM registration_requests
+                WHERE status = $1 AND (created_at, id) > ($2, $3)
+             ) THEN 1 ELSE 0 END",
         )
         .bind(STATUS_APPROVED)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&self.pool)
+        .bind(cursor.created_at)
+        .bind(cursor.id)
+        .fetch_one(&self.pool)
         .await?;
-        Ok(rows)
+        Ok(exists != 0)
+    }
+
+    /// Есть ли активные пользователи ниже курсора (ближе к концу списка).
+    async fn active_user_exists_after(&self, cursor: UserCursor) -> Result<bool, DbError> {
+        let exists = sqlx::query_scalar::<_, i64>(
+            "SELECT CASE WHEN EXISTS(
+                SELECT 1 FROM registration_requests
+                WHERE status = $1 AND (created_at, id) < ($2, $3)
+             ) THEN 1 ELSE 0 END",
+        )
+        .bind(STATUS_APPROVED)
+        .bind(cursor.created_at)
+        .bind(cursor.id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists != 0)
     }
 
     pub async fn get_active_user_by_tg_user(
         &self,
         tg_user_id: i64,
-    ) -> Result<Option<RegistrationRequest>, anyhow::Error> {
+    ) -> Result<Option<RegistrationRequest>, DbError> {
         let row = sqlx::query_as::<_, RegistrationRequest>(
             "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at
              FROM registration_requests
-             WHERE status = ? AND tg_user_id = ?
+             WHERE status = $1 AND tg_user_id = $2
              LIMIT 1",
         )
         .bind(STATUS_APPROVED)
@@ -714,7 +1710,7 @@ impl Db {
         Ok(row)
     }
 
-    pub async fn admin_stats(&self) -> Result<AdminStats, anyhow::Error> {
+    pub async fn admin_stats(&self) -> Result<AdminStats, DbError> {
         let row = sqlx::query_as::<_, (i64, i64, i64, i64, i64)>(
             "SELECT
                 COUNT(*) AS total,
@@ -735,4 +1731,2393 @@ impl Db {
             deleted: row.4,
         })
     }
+
+    /// Публикует новое объявление, снимая предыдущее.
+    pub async fn set_announcement(
+        &self,
+        text: &str,
+        expires_at: Option<i64>,
+        created_by: Option<i64>,
+    ) -> Result<Announcement, DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE announcements SET cleared_at = $1 WHERE cleared_at IS NULL")
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        let id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO announcements (text, created_at, expires_at, created_by) VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(text)
+        .bind(now)
+        .bind(expires_at)
+        .bind(created_by)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Announcement {
+            id,
+            text: text.to_string(),
+            created_at: now,
+            expires_at,
+            created_by,
+        })
+    }
+
+    /// Снимает текущее объявление. Возвращает false, если снимать было нечего.
+    pub async fn clear_announcement(&self) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result =
+            sqlx::query("UPDATE announcements SET cleared_at = $1 WHERE cleared_at IS NULL")
+                .bind(now)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Возвращает действующее объявление (не снятое и не истёкшее).
+    pub async fn get_active_announcement(&self) -> Result<Option<Announcement>, DbError> {
+        let now = current_unix_timestamp()?;
+        let row = sqlx::query_as::<_, Announcement>(
+            "SELECT id, text, created_at, expires_at, created_by
+             FROM announcements
+             WHERE cleared_at IS NULL
+               AND (expires_at IS NULL OR expires_at > $1)
+             ORDER BY created_at DESC
+             LIMIT 1",
+        )
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    /// Ставит задачу в очередь.
+    pub async fn enqueue_job(&self, kind: &str, created_by: Option<i64>) -> Result<Job, DbError> {
+        let now = current_unix_timestamp()?;
+        let id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO jobs (kind, status, created_by, created_at, updated_at) VALUES ($1, 'queued', $2, $3, $4) RETURNING id",
+        )
+        .bind(kind)
+        .bind(created_by)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(self
+            .get_job(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("только что создали задачу"))?)
+    }
+
+    pub async fn get_job(&self, id: i64) -> Result<Option<Job>, DbError> {
+        let sql = format!("{} WHERE id = $1", SELECT_JOB);
+        let job = sqlx::query_as::<_, Job>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(job)
+    }
+
+    pub async fn list_recent_jobs(&self, limit: i64) -> Result<Vec<Job>, DbError> {
+        let sql = format!("{} ORDER BY id DESC LIMIT $1", SELECT_JOB);
+        let rows = sqlx::query_as::<_, Job>(&sql)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Берёт следующую незавершённую задачу. Задачи в статусе running, прерванные
+    /// падением или рестартом процесса, возвращаются первыми и продолжаются с cursor.
+    pub async fn claim_next_job(&self) -> Result<Option<Job>, DbError> {
+        let now = current_unix_timestamp()?;
+        let sql = format!(
+            "{} WHERE status IN ('running', 'queued') ORDER BY status = 'running' DESC, id ASC LIMIT 1",
+            SELECT_JOB
+        );
+        let job = sqlx::query_as::<_, Job>(&sql)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(mut job) = job else {
+            return Ok(None);
+        };
+        sqlx::query("UPDATE jobs SET status = 'running', updated_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(job.id)
+            .execute(&self.pool)
+            .await?;
+        job.status = JobStatus::Running;
+        job.updated_at = now;
+        Ok(Some(job))
+    }
+
+    /// Сохраняет контрольную точку прогресса задачи.
+    pub async fn checkpoint_job(
+        &self,
+        id: i64,
+        cursor: i64,
+        processed: i64,
+        total: Option<i64>,
+    ) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "UPDATE jobs SET cursor = $1, processed = $2, total = $3, updated_at = $4 WHERE id = $5 AND status = 'running'",
+        )
+        .bind(cursor)
+        .bind(processed)
+        .bind(total)
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Завершает running-задачу с итоговым статусом.
+    pub async fn finish_job(
+        &self,
+        id: i64,
+        status: JobStatus,
+        error: Option<&str>,
+    ) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "UPDATE jobs SET status = $1, error = $2, updated_at = $3 WHERE id = $4 AND status = 'running'",
+        )
+        .bind(status)
+        .bind(error)
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Отменяет незавершённую задачу.
+    pub async fn cancel_job(&self, id: i64) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "UPDATE jobs SET status = 'cancelled', updated_at = $1 WHERE id = $2 AND status IN ('queued', 'running')",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Активные пользователи с tg_user_id больше курсора — для пакетной обработки в задачах.
+    pub async fn list_active_users_after(
+        &self,
+        after_tg_user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE status = $1 AND suspended = 0 AND tg_user_id > $2 ORDER BY tg_user_id ASC LIMIT $3",
+            SELECT_REQUEST
+        );
+        let rows = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(STATUS_APPROVED)
+            .bind(after_tg_user_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Все известные боту пользователи (в любом статусе) с tg_user_id больше курсора.
+    pub async fn list_users_after(
+        &self,
+        after_tg_user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE tg_user_id > $1 ORDER BY tg_user_id ASC LIMIT $2",
+            SELECT_REQUEST
+        );
+        let rows = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(after_tg_user_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Обновляет секрет активного пользователя.
+    pub async fn update_user_secret(&self, tg_user_id: i64, secret: &str) -> Result<bool, DbError> {
+        let result = sqlx::query(
+            "UPDATE registration_requests SET secret = $1 WHERE tg_user_id = $2 AND status = $3",
+        )
+        .bind(secret)
+        .bind(tg_user_id)
+        .bind(STATUS_APPROVED)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Кладёт ожидающую заявку в корзину одобрения. Возвращает false, если
+    /// заявка не ожидает решения.
+    pub async fn stage_request(&self, request_id: i64, staged_by: i64) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "INSERT INTO approval_basket (request_id, staged_by, staged_at)
+             SELECT id, $1, $2 FROM registration_requests WHERE id = $3 AND status = $4
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(staged_by)
+        .bind(now)
+        .bind(request_id)
+        .bind(STATUS_PENDING)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            return Ok(true);
+        }
+        // Уже в корзине — тоже успех.
+        let staged = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM approval_basket WHERE request_id = $1",
+        )
+        .bind(request_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(staged > 0)
+    }
+
+    /// Кладёт в корзину все ожидающие заявки. Возвращает число добавленных.
+    pub async fn stage_all_pending(&self, staged_by: i64) -> Result<u64, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "INSERT INTO approval_basket (request_id, staged_by, staged_at)
+             SELECT id, $1, $2 FROM registration_requests WHERE status = $3
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(staged_by)
+        .bind(now)
+        .bind(STATUS_PENDING)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Убирает заявку из корзины.
+    pub async fn unstage_request(&self, request_id: i64) -> Result<bool, DbError> {
+        let result = sqlx::query("DELETE FROM approval_basket WHERE request_id = $1")
+            .bind(request_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Ожидающие заявки из корзины. Заявки, решённые иначе, из корзины удаляются.
+    pub async fn list_staged_requests(&self) -> Result<Vec<RegistrationRequest>, DbError> {
+        sqlx::query(
+            "DELETE FROM approval_basket WHERE request_id NOT IN
+             (SELECT id FROM registration_requests WHERE status = $1)",
+        )
+        .bind(STATUS_PENDING)
+        .execute(&self.pool)
+        .await?;
+        let sql = format!(
+            "{} WHERE id IN (SELECT request_id FROM approval_basket) ORDER BY created_at ASC",
+            SELECT_REQUEST
+        );
+        let rows = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Очищает корзину одобрения.
+    pub async fn clear_basket(&self) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM approval_basket")
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Поиск по username, отображаемому имени, заметке и tg_user_id.
+    ///
+    /// Запросы от 3 символов идут через FTS5 (trigram — подстрока в любом месте,
+    /// без учёта регистра), более короткие — через LIKE. В Postgres индекса FTS5
+    /// нет: там любой запрос ищется через ILIKE.
+    pub async fn search_users(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<UserSearchHit>, DbError> {
+        const SELECT_HIT: &str = "SELECT r.id, r.tg_user_id, r.tg_username, r.tg_display_name, r.status, r.note FROM registration_requests r";
+        let query = query.trim().trim_start_matches('@');
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut hits = Vec::new();
+        if let Ok(tg_user_id) = query.parse::<i64>() {
+            let sql = format!("{} WHERE r.tg_user_id = $1", SELECT_HIT);
+            hits = sqlx::query_as::<_, UserSearchHit>(&sql)
+                .bind(tg_user_id)
+                .fetch_all(&self.pool)
+                .await?;
+        }
+
+        // Имя из конфига/логов telemt: `tg_123`, в том числе обрезанное `tg_123…`.
+        let telemt_prefix = query.trim_end_matches(['…', '.']).to_ascii_lowercase();
+        if let Some(digits) = telemt_prefix.strip_prefix("tg_")
+            && !digits.is_empty()
+            && digits.chars().all(|c| c.is_ascii_digit())
+        {
+            let sql = format!(
+                "{} WHERE r.telemt_username LIKE $1 ORDER BY r.created_at DESC LIMIT $2",
+                SELECT_HIT
+            );
+            let matched = sqlx::query_as::<_, UserSearchHit>(&sql)
+                .bind(format!("tg_{}%", digits))
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+            merge_search_hits(&mut hits, matched);
+        }
+
+        // Фрагмент секрета: начало или конец. Из полного fake-TLS секрета
+        // (`ee` + 32 hex + домен) берётся секрет пользователя.
+        let fragment = query.trim_end_matches(['…', '.']).to_ascii_lowercase();
+        if fragment.len() >= SECRET_FRAGMENT_MIN_LEN
+            && fragment.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            let fragment = if fragment.len() > 34 && fragment.starts_with("ee") {
+                fragment[2..34].to_string()
+            } else {
+                fragment
+            };
+            let sql = format!(
+                "{} WHERE r.secret LIKE $1 || '%' OR r.secret LIKE '%' || $1
+                 ORDER BY r.created_at DESC LIMIT $2",
+                SELECT_HIT
+            );
+            let matched = sqlx::query_as::<_, UserSearchHit>(&sql)
+                .bind(fragment)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+            merge_search_hits(&mut hits, matched);
+        }
+
+        let matched = if self.backend == Backend::Sqlite && query.chars().count() >= 3 {
+            let sql = format!(
+                "{} JOIN users_fts f ON f.rowid = r.id WHERE users_fts MATCH $1 ORDER BY f.rank LIMIT $2",
+                SELECT_HIT
+            );
+            let phrase = format!("\"{}\"", query.replace('"', "\"\""));
+            sqlx::query_as::<_, UserSearchHit>(&sql)
+                .bind(phrase)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            let like = self.like();
+            let sql = format!(
+                "{} WHERE r.tg_username {like} $1 OR r.tg_display_name {like} $1 OR r.note {like} $1
+                 ORDER BY r.created_at DESC LIMIT $2",
+                SELECT_HIT
+            );
+            sqlx::query_as::<_, UserSearchHit>(&sql)
+                .bind(format!("%{}%", query))
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?
+        };
+        merge_search_hits(&mut hits, matched);
+        hits.truncate(limit.max(0) as usize);
+        Ok(hits)
+    }
+
+    /// Устанавливает или снимает заметку администратора о пользователе.
+    pub async fn set_user_note(
+        &self,
+        tg_user_id: i64,
+        note: Option<&str>,
+    ) -> Result<bool, DbError> {
+        let result =
+            sqlx::query("UPDATE registration_requests SET note = $1 WHERE tg_user_id = $2")
+                .bind(note)
+                .bind(tg_user_id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Переносит в архив отклонённые/удалённые заявки, решённые до `requests_before`,
+    /// и токены, истёкшие или отозванные до `tokens_before`.
+    /// При `keep_personal = false` username, имя и заметка в архив не попадают.
+    pub async fn apply_retention(
+        &self,
+        requests_before: i64,
+        tokens_before: i64,
+        keep_personal: bool,
+    ) -> Result<RetentionReport, DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO archived_requests
+                 (id, tg_user_id, tg_username, tg_display_name, status, telemt_username, note,
+                  created_at, resolved_at, archived_at)
+             SELECT id, tg_user_id,
+                    CASE WHEN $1 THEN tg_username END,
+                    CASE WHEN $1 THEN tg_display_name END,
+                    status, telemt_username,
+                    CASE WHEN $1 THEN note END,
+                    created_at, resolved_at, $2
+             FROM registration_requests
+             WHERE status IN ('rejected', 'deleted') AND COALESCE(resolved_at, created_at) < $3
+             ON CONFLICT (id) DO UPDATE SET
+                 tg_user_id = excluded.tg_user_id, tg_username = excluded.tg_username,
+                 tg_display_name = excluded.tg_display_name, status = excluded.status,
+                 telemt_username = excluded.telemt_username, note = excluded.note,
+                 created_at = excluded.created_at, resolved_at = excluded.resolved_at,
+                 archived_at = excluded.archived_at",
+        )
+        .bind(keep_personal)
+        .bind(now)
+        .bind(requests_before)
+        .execute(&mut *tx)
+        .await?;
+        let requests = sqlx::query(
+            "DELETE FROM registration_requests
+             WHERE status IN ('rejected', 'deleted') AND COALESCE(resolved_at, created_at) < $1",
+        )
+        .bind(requests_before)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        const STALE_TOKENS: &str = "expires_at < $1 OR (is_active = 0 AND revoked_at < $1)";
+        sqlx::query(&format!(
+            "INSERT INTO archived_tokens
+                 (id, token, created_at, expires_at, auto_approve, created_by, usage_count,
+                  max_usage, revoked_at, label, archived_at)
+             SELECT id, token, created_at, expires_at, auto_approve, created_by, usage_count,
+                    max_usage, revoked_at, label, $2
+             FROM invite_tokens
+             WHERE {}
+             ON CONFLICT (id) DO UPDATE SET
+                 token = excluded.token, created_at = excluded.created_at,
+                 expires_at = excluded.expires_at, auto_approve = excluded.auto_approve,
+                 created_by = excluded.created_by, usage_count = excluded.usage_count,
+                 max_usage = excluded.max_usage, revoked_at = excluded.revoked_at,
+                 label = excluded.label, archived_at = excluded.archived_at",
+            STALE_TOKENS
+        ))
+        .bind(tokens_before)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        let tokens = sqlx::query(&format!("DELETE FROM invite_tokens WHERE {}", STALE_TOKENS))
+            .bind(tokens_before)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(RetentionReport { requests, tokens })
+    }
+
+    /// Поиск по архиву заявок: точный `tg_user_id` или подстрока username/имени/заметки.
+    pub async fn search_archive(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<ArchivedRequest>, DbError> {
+        const SELECT_ARCHIVED: &str = "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, note, created_at, resolved_at, archived_at
+             FROM archived_requests";
+        let query = query.trim().trim_start_matches('@');
+        let rows = if let Ok(tg_user_id) = query.parse::<i64>() {
+            sqlx::query_as::<_, ArchivedRequest>(&format!(
+                "{} WHERE tg_user_id = $1 ORDER BY archived_at DESC LIMIT $2",
+                SELECT_ARCHIVED
+            ))
+            .bind(tg_user_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?
+        } else {
+            let like = self.like();
+            sqlx::query_as::<_, ArchivedRequest>(&format!(
+                "{} WHERE tg_username {like} $1 OR tg_display_name {like} $1 OR note {like} $1
+                    OR telemt_username {like} $1
+                 ORDER BY archived_at DESC LIMIT $2",
+                SELECT_ARCHIVED
+            ))
+            .bind(format!("%{}%", query))
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?
+        };
+        Ok(rows)
+    }
+
+    /// Сохраняет скрытую ссылку; `delete_at` — когда удалить непросмотренное сообщение.
+    pub async fn create_link_reveal(
+        &self,
+        tg_user_id: i64,
+        chat_id: i64,
+        text: &str,
+        delete_at: i64,
+    ) -> Result<String, DbError> {
+        let now = current_unix_timestamp()?;
+        let token = Alphanumeric.sample_string(&mut rand::rng(), 24);
+        sqlx::query(
+            "INSERT INTO link_reveals (token, tg_user_id, chat_id, text, created_at, delete_at)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&token)
+        .bind(tg_user_id)
+        .bind(chat_id)
+        .bind(text)
+        .bind(now)
+        .bind(delete_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    pub async fn set_link_reveal_message(
+        &self,
+        token: &str,
+        message_id: i32,
+    ) -> Result<(), DbError> {
+        sqlx::query("UPDATE link_reveals SET message_id = $1 WHERE token = $2")
+            .bind(message_id)
+            .bind(token)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Отдаёт текст ссылки ровно один раз: повторный вызов вернёт `None`.
+    /// После просмотра текст стирается из БД, а удаление сообщения переносится на `delete_at`.
+    pub async fn take_link_reveal(
+        &self,
+        token: &str,
+        tg_user_id: i64,
+        delete_at: i64,
+    ) -> Result<Option<String>, DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+        let text = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT text FROM link_reveals WHERE token = $1 AND tg_user_id = $2 AND revealed_at IS NULL",
+        )
+        .bind(token)
+        .bind(tg_user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        let Some(text) = text else {
+            return Ok(None);
+        };
+        let updated = sqlx::query(
+            "UPDATE link_reveals SET text = NULL, revealed_at = $1, delete_at = $2
+             WHERE token = $3 AND revealed_at IS NULL",
+        )
+        .bind(now)
+        .bind(delete_at)
+        .bind(token)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok((updated > 0).then_some(text))
+    }
+
+    /// Сообщения со ссылками, срок жизни которых истёк (просмотренные и непросмотренные).
+    pub async fn take_expired_link_messages(&self) -> Result<Vec<ExpiredLinkMessage>, DbError> {
+        let now = current_unix_timestamp()?;
+        let rows = sqlx::query_as::<_, ExpiredLinkMessage>(
+            "DELETE FROM link_reveals WHERE delete_at <= $1 RETURNING chat_id, message_id",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Заявки, ожидающие дольше `created_before`, по которым не напоминали после `reminded_before`.
+    pub async fn list_stale_pending_requests(
+        &self,
+        created_before: i64,
+        reminded_before: i64,
+    ) -> Result<Vec<RegistrationRequest>, DbError> {
+        let rows = sqlx::query_as::<_, RegistrationRequest>(
+            "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at
+             FROM registration_requests
+             WHERE status = $1 AND created_at < $2
+               AND (reminded_at IS NULL OR reminded_at < $3)
+             ORDER BY created_at ASC",
+        )
+        .bind(STATUS_PENDING)
+        .bind(created_before)
+        .bind(reminded_before)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn mark_request_reminded(&self, request_id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query("UPDATE registration_requests SET reminded_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(request_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Заявки, ожидающие дольше `created_before` и ещё не переданные второй линии.
+    pub async fn list_unescalated_pending_requests(
+        &self,
+        created_before: i64,
+    ) -> Result<Vec<RegistrationRequest>, DbError> {
+        let rows = sqlx::query_as::<_, RegistrationRequest>(
+            "SELECT id, tg_user_id, tg_username, tg_display_name, status, telemt_username, secret, created_at
+             FROM registration_requests
+             WHERE status = $1 AND created_at < $2 AND escalated_at IS NULL
+             ORDER BY created_at ASC",
+        )
+        .bind(STATUS_PENDING)
+        .bind(created_before)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn mark_request_escalated(&self, request_id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query("UPDATE registration_requests SET escalated_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(request_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Добавляет пользователя из `[[provision]]`. Получившие доступ записи не меняются,
+    /// поэтому повторный старт не выдаёт доступ заново; у ещё не использованных
+    /// обновляются заметка, группа и срок из конфига. Возвращает true для новой записи.
+    pub async fn add_provisioned_user(
+        &self,
+        tg_user_id: i64,
+        note: Option<&str>,
+        user_group: Option<&str>,
+        expires_in_days: Option<i64>,
+    ) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "INSERT INTO provisioned_users \
+             (tg_user_id, note, user_group, expires_in_days, created_at) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(tg_user_id)
+        .bind(note)
+        .bind(user_group)
+        .bind(expires_in_days)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            return Ok(true);
+        }
+        sqlx::query(
+            "UPDATE provisioned_users SET note = $1, user_group = $2, expires_in_days = $3 \
+             WHERE tg_user_id = $4 AND consumed_at IS NULL",
+        )
+        .bind(note)
+        .bind(user_group)
+        .bind(expires_in_days)
+        .bind(tg_user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(false)
+    }
+
+    /// Заранее разрешённый пользователь, ещё не получивший доступ.
+    pub async fn get_unconsumed_provision(
+        &self,
+        tg_user_id: i64,
+    ) -> Result<Option<ProvisionedUser>, DbError> {
+        let row = sqlx::query_as::<_, ProvisionedUser>(
+            "SELECT note, user_group, expires_in_days FROM provisioned_users \
+             WHERE tg_user_id = $1 AND consumed_at IS NULL",
+        )
+        .bind(tg_user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn mark_provision_consumed(&self, tg_user_id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query("UPDATE provisioned_users SET consumed_at = $1 WHERE tg_user_id = $2")
+            .bind(now)
+            .bind(tg_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Планирует серию онбординга пользователю: `steps` — пары (номер шага, время отправки).
+    /// Прежнее расписание пользователя заменяется.
+    pub async fn schedule_onboarding(
+        &self,
+        tg_user_id: i64,
+        steps: &[(i64, i64)],
+    ) -> Result<(), DbError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM onboarding_messages WHERE tg_user_id = $1")
+            .bind(tg_user_id)
+            .execute(&mut *tx)
+            .await?;
+        for (step, due_at) in steps {
+            sqlx::query(
+                "INSERT INTO onboarding_messages (tg_user_id, step, due_at) VALUES ($1, $2, $3)",
+            )
+            .bind(tg_user_id)
+            .bind(step)
+            .bind(due_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Забирает сообщения онбординга, время которых наступило (каждое — ровно один раз).
+    pub async fn take_due_onboarding_messages(&self) -> Result<Vec<DueOnboardingMessage>, DbError> {
+        let now = current_unix_timestamp()?;
+        let rows = sqlx::query_as::<_, DueOnboardingMessage>(
+            "DELETE FROM onboarding_messages WHERE due_at <= $1 RETURNING tg_user_id, step",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Планирует опрос пользователю. Пользователь, уже отвечавший на опрос, его не получит.
+    pub async fn schedule_survey(&self, tg_user_id: i64, due_at: i64) -> Result<(), DbError> {
+        sqlx::query(
+            "INSERT INTO survey_invites (tg_user_id, due_at)
+             SELECT $1, $2 WHERE NOT EXISTS (SELECT 1 FROM survey_answers WHERE tg_user_id = $1)
+             ON CONFLICT (tg_user_id) DO UPDATE SET due_at = excluded.due_at",
+        )
+        .bind(tg_user_id)
+        .bind(due_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Забирает пользователей, которым пора задать первый вопрос опроса.
+    pub async fn take_due_survey_invites(&self) -> Result<Vec<i64>, DbError> {
+        let now = current_unix_timestamp()?;
+        let rows = sqlx::query_scalar::<_, i64>(
+            "DELETE FROM survey_invites WHERE due_at <= $1 RETURNING tg_user_id",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Сохраняет ответ. Возвращает false, если на этот вопрос пользователь уже ответил.
+    pub async fn record_survey_answer(
+        &self,
+        tg_user_id: i64,
+        question: i64,
+        answer: i64,
+    ) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "INSERT INTO survey_answers (tg_user_id, question, answer, answered_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(tg_user_id)
+        .bind(question)
+        .bind(answer)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn survey_answer_counts(&self) -> Result<Vec<SurveyAnswerCount>, DbError> {
+        let rows = sqlx::query_as::<_, SurveyAnswerCount>(
+            "SELECT question, answer, COUNT(*) AS count
+             FROM survey_answers
+             GROUP BY question, answer
+             ORDER BY question ASC, count DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Назначает пользователю группу (`None` — убрать из группы).
+    pub async fn set_user_group(
+        &self,
+        tg_user_id: i64,
+        group: Option<&str>,
+    ) -> Result<bool, DbError> {
+        let result =
+            sqlx::query("UPDATE registration_requests SET user_group = $1 WHERE tg_user_id = $2")
+                .bind(group)
+                .bind(tg_user_id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_user_group(&self, tg_user_id: i64) -> Result<Option<String>, DbError> {
+        let group = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT user_group FROM registration_requests WHERE tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(group.flatten())
+    }
+
+    /// Активные пользователи группы с признаком приостановки доступа.
+    pub async fn list_group_members(
+        &self,
+        group: &str,
+    ) -> Result<Vec<(RegistrationRequest, bool)>, DbError> {
+        let sql = format!(
+            "{} WHERE user_group = $1 AND status = $2 ORDER BY id ASC",
+            SELECT_REQUEST
+        );
+        let members = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(group)
+            .bind(STATUS_APPROVED)
+            .fetch_all(&self.pool)
+            .await?;
+        let suspended: Vec<i64> = sqlx::query_scalar::<_, i64>(
+            "SELECT tg_user_id FROM registration_requests WHERE user_group = $1 AND suspended = 1",
+        )
+        .bind(group)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members
+            .into_iter()
+            .map(|member| {
+                let is_suspended = suspended.contains(&member.tg_user_id);
+                (member, is_suspended)
+            })
+            .collect())
+    }
+
+    /// Группы с числом активных пользователей.
+    pub async fn list_groups(&self) -> Result<Vec<(String, i64)>, DbError> {
+        let rows = sqlx::query_as::<_, (String, i64)>(
+            "SELECT user_group, COUNT(*) FROM registration_requests
+             WHERE user_group IS NOT NULL AND status = $1
+             GROUP BY user_group ORDER BY user_group ASC",
+        )
+        .bind(STATUS_APPROVED)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn set_user_suspended(
+        &self,
+        tg_user_id: i64,
+        suspended: bool,
+    ) -> Result<(), DbError> {
+        sqlx::query("UPDATE registration_requests SET suspended = $1 WHERE tg_user_id = $2")
+            .bind(i64::from(suspended))
+            .bind(tg_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn is_user_suspended(&self, tg_user_id: i64) -> Result<bool, DbError> {
+        let suspended = sqlx::query_scalar::<_, i64>(
+            "SELECT suspended FROM registration_requests WHERE tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(suspended.unwrap_or(0) != 0)
+    }
+
+    pub async fn add_group_schedule(
+        &self,
+        group: &str,
+        action: GroupAction,
+        run_at: i64,
+        created_by: Option<i64>,
+    ) -> Result<i64, DbError> {
+        let now = current_unix_timestamp()?;
+        let id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO group_schedules (group_name, action, run_at, created_by, created_at)
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        )
+        .bind(group)
+        .bind(action)
+        .bind(run_at)
+        .bind(created_by)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    /// Невыполненные расписания групп; `due_only` — только те, время которых наступило.
+    pub async fn list_group_schedules(
+        &self,
+        due_only: bool,
+    ) -> Result<Vec<GroupSchedule>, DbError> {
+        let now = current_unix_timestamp()?;
+        let rows = sqlx::query_as::<_, GroupSchedule>(
+            "SELECT id, group_name, action, run_at, created_by FROM group_schedules
+             WHERE done_at IS NULL AND ($1 = 0 OR run_at <= $2)
+             ORDER BY run_at ASC, id ASC",
+        )
+        .bind(i64::from(due_only))
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn mark_group_schedule_done(&self, id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query("UPDATE group_schedules SET done_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Удаляет невыполненное расписание группы.
+    pub async fn cancel_group_schedule(&self, id: i64) -> Result<bool, DbError> {
+        let result = sqlx::query("DELETE FROM group_schedules WHERE id = $1 AND done_at IS NULL")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn create_broadcast(
+        &self,
+        text: &str,
+        scheduled_at: Option<i64>,
+        created_by: i64,
+    ) -> Result<i64, DbError> {
+        let now = current_unix_timestamp()?;
+        let id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO broadcasts (text, scheduled_at, created_by, created_at) VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(text)
+        .bind(scheduled_at)
+        .bind(created_by)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    pub async fn get_broadcast(&self, id: i64) -> Result<Option<Broadcast>, DbError> {
+        let sql = format!("{} WHERE id = $1", SELECT_BROADCAST);
+        let broadcast = sqlx::query_as::<_, Broadcast>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(broadcast)
+    }
+
+    /// Рассылки, кроме черновиков, начиная с последних.
+    pub async fn list_recent_broadcasts(&self, limit: i64) -> Result<Vec<Broadcast>, DbError> {
+        let sql = format!(
+            "{} WHERE status != 'draft' ORDER BY id DESC LIMIT $1",
+            SELECT_BROADCAST
+        );
+        let rows = sqlx::query_as::<_, Broadcast>(&sql)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Меняет аудиторию черновика.
+    pub async fn set_broadcast_audience(
+        &self,
+        id: i64,
+        audience: BroadcastAudience,
+        group: Option<&str>,
+    ) -> Result<bool, DbError> {
+        let result = sqlx::query(
+            "UPDATE broadcasts SET audience = $1, group_name = $2 WHERE id = $3 AND status = 'draft'",
+        )
+        .bind(audience)
+        .bind(group)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Число получателей аудитории на текущий момент; `expiring_before` — граница
+    /// срока доступа для [`BroadcastAudience::Expiring`].
+    pub async fn count_broadcast_audience(
+        &self,
+        audience: BroadcastAudience,
+        group: Option<&str>,
+        expiring_before: i64,
+    ) -> Result<i64, DbError> {
+        let sql = format!(
+            "SELECT COUNT(*) FROM registration_requests WHERE tg_user_id > 0 AND {}",
+            broadcast_audience_filter(audience, 1)
+        );
+        let mut query = sqlx::query_scalar::<_, i64>(&sql);
+        match audience {
+            BroadcastAudience::Group => query = query.bind(group),
+            BroadcastAudience::Expiring => query = query.bind(expiring_before),
+            BroadcastAudience::Approved | BroadcastAudience::Pending => {}
+        }
+        Ok(query.fetch_one(&self.pool).await?)
+    }
+
+    /// Ставит черновик в очередь на отправку в `run_at`.
+    pub async fn schedule_broadcast(&self, id: i64, run_at: i64) -> Result<bool, DbError> {
+        let result = sqlx::query(
+            "UPDATE broadcasts SET status = 'scheduled', scheduled_at = $1 WHERE id = $2 AND status = 'draft'",
+        )
+        .bind(run_at)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Отменяет черновик, запланированную или идущую рассылку; неотправленные
+    /// получатели остаются в статусе `queued`.
+    pub async fn cancel_broadcast(&self, id: i64) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "UPDATE broadcasts SET status = 'cancelled', finished_at = $1
+             WHERE id = $2 AND status IN ('draft', 'scheduled', 'sending')",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Идущие рассылки (продолжаются после рестарта) и запланированные, время которых наступило.
+    pub async fn list_due_broadcasts(&self) -> Result<Vec<Broadcast>, DbError> {
+        let now = current_unix_timestamp()?;
+        let sql = format!(
+            "{} WHERE status = 'sending' OR (status = 'scheduled' AND scheduled_at <= $1) ORDER BY id ASC",
+            SELECT_BROADCAST
+        );
+        let rows = sqlx::query_as::<_, Broadcast>(&sql)
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Переводит рассылку в `sending` и фиксирует список получателей, чтобы после
+    /// рестарта бота она продолжилась тем же составом.
+    pub async fn start_broadcast(
+        &self,
+        broadcast: &Broadcast,
+        expiring_before: i64,
+    ) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+        let started = sqlx::query(
+            "UPDATE broadcasts SET status = 'sending', started_at = $1 WHERE id = $2 AND status = 'scheduled'",
+        )
+        .bind(now)
+        .bind(broadcast.id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if !started {
+            return Ok(false);
+        }
+        let sql = format!(
+            "INSERT INTO broadcast_deliveries (broadcast_id, tg_user_id)
+             SELECT $1, tg_user_id FROM registration_requests WHERE tg_user_id > 0 AND {}
+             ON CONFLICT DO NOTHING",
+            broadcast_audience_filter(broadcast.audience, 2)
+        );
+        let mut query = sqlx::query(&sql).bind(broadcast.id);
+        match broadcast.audience {
+            BroadcastAudience::Group => query = query.bind(broadcast.group_name.as_deref()),
+            BroadcastAudience::Expiring => query = query.bind(expiring_before),
+            BroadcastAudience::Approved | BroadcastAudience::Pending => {}
+        }
+        query.execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Следующие получатели, которым рассылка ещё не отправлялась.
+    pub async fn list_queued_deliveries(&self, id: i64, limit: i64) -> Result<Vec<i64>, DbError> {
+        let ids = sqlx::query_scalar::<_, i64>(
+            "SELECT tg_user_id FROM broadcast_deliveries
+             WHERE broadcast_id = $1 AND status = 'queued'
+             ORDER BY tg_user_id ASC LIMIT $2",
+        )
+        .bind(id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+
+    pub async fn set_delivery_status(
+        &self,
+        id: i64,
+        tg_user_id: i64,
+        status: DeliveryStatus,
+    ) -> Result<(), DbError> {
+        sqlx::query(
+            "UPDATE broadcast_deliveries SET status = $1 WHERE broadcast_id = $2 AND tg_user_id = $3",
+        )
+        .bind(status)
+        .bind(id)
+        .bind(tg_user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn finish_broadcast(&self, id: i64) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "UPDATE broadcasts SET status = 'done', finished_at = $1 WHERE id = $2 AND status = 'sending'",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn broadcast_report(&self, id: i64) -> Result<BroadcastReport, DbError> {
+        let rows = sqlx::query_as::<_, (DeliveryStatus, i64)>(
+            "SELECT status, COUNT(*) FROM broadcast_deliveries WHERE broadcast_id = $1 GROUP BY status",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut report = BroadcastReport::default();
+        for (status, count) in rows {
+            match status {
+                DeliveryStatus::Queued => report.queued = count,
+                DeliveryStatus::Sent => report.sent = count,
+                DeliveryStatus::Failed => report.failed = count,
+                DeliveryStatus::Blocked => report.blocked = count,
+            }
+        }
+        Ok(report)
+    }
+
+    /// Добавляет к накопленному трафику прирост счётчиков telemt: `(tg_user_id,
+    /// байт от клиента, байт к клиенту)`. Значение меньше прошлого означает рестарт
+    /// telemt — тогда приростом считается само значение.
+    pub async fn record_traffic_samples(&self, samples: &[(i64, i64, i64)]) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+        for &(tg_user_id, from_client, to_client) in samples {
+            sqlx::query(
+                "INSERT INTO user_traffic (tg_user_id, bytes_from_client, bytes_to_client,
+                     last_raw_from_client, last_raw_to_client, updated_at)
+                 VALUES ($1, $2, $3, $2, $3, $4)
+                 ON CONFLICT(tg_user_id) DO UPDATE SET
+                     bytes_from_client = user_traffic.bytes_from_client + CASE
+                         WHEN excluded.last_raw_from_client >= user_traffic.last_raw_from_client
+                         THEN excluded.last_raw_from_client - user_traffic.last_raw_from_client
+                         ELSE excluded.last_raw_from_client END,
+                     bytes_to_client = user_traffic.bytes_to_client + CASE
+                         WHEN excluded.last_raw_to_client >= user_traffic.last_raw_to_client
+                         THEN excluded.last_raw_to_client - user_traffic.last_raw_to_client
+                         ELSE excluded.last_raw_to_client END,
+                     last_raw_from_client = excluded.last_raw_from_client,
+                     last_raw_to_client = excluded.last_raw_to_client,
+                     updated_at = excluded.updated_at",
+            )
+            .bind(tg_user_id)
+            .bind(from_client)
+            .bind(to_client)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_user_traffic(&self, tg_user_id: i64) -> Result<Option<UserTraffic>, DbError> {
+        let sql = format!("{} WHERE t.tg_user_id = $1", SELECT_USER_TRAFFIC);
+        let traffic = sqlx::query_as::<_, UserTraffic>(&sql)
+            .bind(tg_user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(traffic)
+    }
+
+    /// Активные пользователи с наибольшим накопленным трафиком.
+    pub async fn top_traffic_users(&self, limit: i64) -> Result<Vec<UserTraffic>, DbError> {
+        let sql = format!(
+            "{} WHERE r.status = $1
+             ORDER BY t.bytes_from_client + t.bytes_to_client DESC LIMIT $2",
+            SELECT_USER_TRAFFIC
+        );
+        let rows = sqlx::query_as::<_, UserTraffic>(&sql)
+            .bind(STATUS_APPROVED)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// `tg_user_id` отклонённых заявок, решённых раньше `before`.
+    pub async fn list_rejected_before(&self, before: i64) -> Result<Vec<i64>, DbError> {
+        let ids = sqlx::query_scalar::<_, i64>(
+            "SELECT tg_user_id FROM registration_requests
+             WHERE status = 'rejected' AND COALESCE(resolved_at, created_at) < $1
+             ORDER BY tg_user_id ASC",
+        )
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+
+    /// Удаляет отклонённые заявки, решённые раньше `before`; возвращает их `tg_user_id`.
+    pub async fn delete_rejected_before(&self, before: i64) -> Result<Vec<i64>, DbError> {
+        let ids = sqlx::query_scalar::<_, i64>(
+            "DELETE FROM registration_requests
+             WHERE status = 'rejected' AND COALESCE(resolved_at, created_at) < $1
+             RETURNING tg_user_id",
+        )
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+
+    /// Время создания заявок начиная с `since` — для графика регистраций по дням.
+    pub async fn list_request_created_since(&self, since: i64) -> Result<Vec<i64>, DbError> {
+        let rows = sqlx::query_scalar::<_, i64>(
+            "SELECT created_at FROM registration_requests WHERE created_at >= $1 ORDER BY created_at ASC",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn period_stats(&self, since: i64) -> Result<PeriodStats, DbError> {
+        let stats = sqlx::query_as::<_, PeriodStats>(
+            "SELECT
+                COALESCE(SUM(CASE WHEN created_at >= $1 THEN 1 ELSE 0 END), 0) AS requests,
+                COALESCE(SUM(CASE WHEN status IN ('approved', 'deleted') AND resolved_at >= $1 THEN 1 ELSE 0 END), 0) AS approved,
+                COALESCE(SUM(CASE WHEN status = 'rejected' AND resolved_at >= $1 THEN 1 ELSE 0 END), 0) AS rejected
+             FROM registration_requests",
+        )
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(stats)
+    }
+
+    /// Токены с наибольшим числом активаций (включая отозванные и истёкшие).
+    pub async fn top_invite_tokens(&self, limit: i64) -> Result<Vec<(String, i64)>, DbError> {
+        let rows = sqlx::query_as::<_, (String, i64)>(
+            "SELECT token, usage_count FROM invite_tokens
+             WHERE usage_count > 0 ORDER BY usage_count DESC, id DESC LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Воронки токенов с переходами или применениями (включая отозванные и истёкшие),
+    /// сначала самые результативные.
+    pub async fn token_campaigns(&self, limit: i64) -> Result<Vec<TokenCampaign>, DbError> {
+        let rows = sqlx::query_as::<_, TokenCampaign>(
+            "SELECT * FROM (
+                 SELECT t.token, t.label,
+                     (SELECT COUNT(*) FROM token_views v WHERE v.token = t.token) AS views,
+                     t.usage_count AS consumed,
+                     (SELECT COUNT(*) FROM registration_requests r
+                       WHERE r.invite_token = t.token AND r.status IN ('approved', 'deleted')) AS approved
+                 FROM invite_tokens t
+             ) AS campaigns
+             WHERE views > 0 OR consumed > 0
+             ORDER BY approved DESC, consumed DESC, views DESC
+             LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Был ли уже отправлен отчёт за период.
+    pub async fn is_report_sent(&self, period: &str) -> Result<bool, DbError> {
+        let sent = sqlx::query_scalar::<_, i64>("SELECT 1 FROM sent_reports WHERE period = $1")
+            .bind(period)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(sent.is_some())
+    }
+
+    /// Отмечает отчёт за период отправленным. Возвращает false, если он уже был отправлен.
+    pub async fn mark_report_sent(&self, period: &str) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "INSERT INTO sent_reports (period, sent_at) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(period)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Запоминает переход пользователя по deep-link с существующим токеном.
+    pub async fn record_token_view(&self, token: &str, tg_user_id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "INSERT INTO token_views (token, tg_user_id, viewed_at)
+             SELECT token, $1, $2 FROM invite_tokens WHERE token = $3
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(tg_user_id)
+        .bind(now)
+        .bind(token)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Последние применения токена (сначала новые) и их общее число.
+    pub async fn token_usages(
+        &self,
+        token: &str,
+        limit: i64,
+    ) -> Result<(Vec<TokenUsage>, i64), DbError> {
+        let rows = sqlx::query_as::<_, TokenUsage>(
+            "SELECT u.tg_user_id, u.used_at, r.tg_username, r.tg_display_name, r.status
+             FROM token_usages u
+             LEFT JOIN registration_requests r ON r.tg_user_id = u.tg_user_id
+             WHERE u.token = $1
+             ORDER BY u.used_at DESC, u.id DESC
+             LIMIT $2",
+        )
+        .bind(token)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        let total =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM token_usages WHERE token = $1")
+                .bind(token)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok((rows, total))
+    }
+
+    /// Воронка токена; `None`, если токена нет.
+    pub async fn token_funnel(&self, token: &str) -> Result<Option<TokenFunnel>, DbError> {
+        let funnel = sqlx::query_as::<_, TokenFunnel>(
+            "SELECT
+                t.web_visits AS web_visits,
+                (SELECT COUNT(*) FROM token_views WHERE token = $1) AS views,
+                t.usage_count AS consumed,
+                (SELECT COUNT(*) FROM registration_requests
+                  WHERE invite_token = $1 AND status IN ('approved', 'deleted')) AS approved,
+                (SELECT COUNT(*) FROM registration_requests
+                  WHERE invite_token = $1 AND status = 'approved') AS active
+             FROM invite_tokens t WHERE t.token = $1",
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(funnel)
+    }
+
+    /// Запоминает сообщение поддержки, пересланное админу, чтобы ответ на него ушёл пользователю.
+    pub async fn add_support_message(
+        &self,
+        admin_chat_id: i64,
+        message_id: i32,
+        tg_user_id: i64,
+    ) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "INSERT INTO support_messages (admin_chat_id, message_id, tg_user_id, created_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (admin_chat_id, message_id) DO UPDATE
+             SET tg_user_id = excluded.tg_user_id, created_at = excluded.created_at",
+        )
+        .bind(admin_chat_id)
+        .bind(message_id)
+        .bind(tg_user_id)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Пользователь, чьё сообщение переслано админу как `message_id`.
+    pub async fn find_support_user(
+        &self,
+        admin_chat_id: i64,
+        message_id: i32,
+    ) -> Result<Option<i64>, DbError> {
+        let tg_user_id = sqlx::query_scalar::<_, i64>(
+            "SELECT tg_user_id FROM support_messages WHERE admin_chat_id = $1 AND message_id = $2",
+        )
+        .bind(admin_chat_id)
+        .bind(message_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(tg_user_id)
+    }
+
+    pub async fn get_support_assignee(
+        &self,
+        tg_user_id: i64,
+    ) -> Result<Option<SupportAssignee>, DbError> {
+        let assignee = sqlx::query_as::<_, SupportAssignee>(
+            "SELECT assigned_to AS admin_id, assigned_name AS admin_name
+             FROM support_conversations WHERE tg_user_id = $1 AND assigned_to IS NOT NULL",
+        )
+        .bind(tg_user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(assignee)
+    }
+
+    /// Назначает переписку с пользователем админу (перехват — тот же вызов).
+    pub async fn assign_support(
+        &self,
+        tg_user_id: i64,
+        admin_id: i64,
+        admin_name: Option<&str>,
+    ) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "INSERT INTO support_conversations (tg_user_id, assigned_to, assigned_name, assigned_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT(tg_user_id) DO UPDATE
+             SET assigned_to = $2, assigned_name = $3, assigned_at = $4",
+        )
+        .bind(tg_user_id)
+        .bind(admin_id)
+        .bind(admin_name)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Сообщение пользователя: открывает обращение (закрытое — заново) и обновляет активность.
+    pub async fn touch_ticket_from_user(&self, tg_user_id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "INSERT INTO support_conversations (tg_user_id, status, opened_at, last_activity_at)
+             VALUES ($1, 'open', $2, $2)
+             ON CONFLICT(tg_user_id) DO UPDATE
+             SET opened_at = CASE
+                     WHEN support_conversations.status = 'closed' OR support_conversations.opened_at IS NULL
+                     THEN $2 ELSE support_conversations.opened_at END,
+                 status = 'open',
+                 last_activity_at = $2",
+        )
+        .bind(tg_user_id)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Ответ админа доставлен: обращение ждёт пользователя.
+    pub async fn mark_ticket_answered(&self, tg_user_id: i64) -> Result<(), DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "UPDATE support_conversations SET status = 'answered', last_activity_at = $1
+             WHERE tg_user_id = $2 AND status != 'closed'",
+        )
+        .bind(now)
+        .bind(tg_user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn close_ticket(&self, tg_user_id: i64) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "UPDATE support_conversations SET status = 'closed', last_activity_at = $1
+             WHERE tg_user_id = $2 AND status != 'closed'",
+        )
+        .bind(now)
+        .bind(tg_user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Закрывает отвеченные обращения без активности с `before`; возвращает их `tg_user_id`.
+    pub async fn close_stale_tickets(&self, before: i64) -> Result<Vec<i64>, DbError> {
+        let ids = sqlx::query_scalar::<_, i64>(
+            "UPDATE support_conversations SET status = 'closed'
+             WHERE status = 'answered' AND last_activity_at < $1
+             RETURNING tg_user_id",
+        )
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+
+    /// Незакрытые обращения, старые сверху.
+    pub async fn list_open_tickets(&self, limit: i64) -> Result<Vec<SupportTicket>, DbError> {
+        let tickets = sqlx::query_as::<_, SupportTicket>(
+            "SELECT tg_user_id, status, opened_at, last_activity_at, assigned_to, assigned_name
+             FROM support_conversations
+             WHERE status != 'closed'
+             ORDER BY status = 'answered' ASC, opened_at ASC
+             LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tickets)
+    }
+
+    pub async fn add_audit_entry(
+        &self,
+        actor_id: Option<i64>,
+        action: &str,
+        target: &str,
+        details: &str,
+    ) -> Result<AuditEntry, DbError> {
+        let now = current_unix_timestamp()?;
+        let entry = sqlx::query_as::<_, AuditEntry>(
+            "INSERT INTO audit_log (at, actor_id, action, target, details)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING id, at, actor_id, action, target, details",
+        )
+        .bind(now)
+        .bind(actor_id)
+        .bind(action)
+        .bind(target)
+        .bind(details)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(entry)
+    }
+
+    /// Записи аудита за `[from, to)` по возрастанию времени.
+    pub async fn list_audit_entries(&self, from: i64, to: i64) -> Result<Vec<AuditEntry>, DbError> {
+        let entries = sqlx::query_as::<_, AuditEntry>(
+            "SELECT id, at, actor_id, action, target, details FROM audit_log
+             WHERE at >= $1 AND at < $2 ORDER BY at ASC, id ASC",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(entries)
+    }
+
+    /// Последние записи аудита, новые сверху; `offset` пропускает более свежие.
+    pub async fn recent_audit_entries(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditEntry>, DbError> {
+        let entries = sqlx::query_as::<_, AuditEntry>(
+            "SELECT id, at, actor_id, action, target, details FROM audit_log
+             ORDER BY id DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(entries)
+    }
+
+    pub async fn count_audit_entries(&self) -> Result<i64, DbError> {
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM audit_log")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    /// Учитывает посещение веб-страницы приглашения. `false`, если токен
+    /// не найден, отозван, истёк или исчерпан — страницу тогда не показываем.
+    pub async fn record_token_web_visit(&self, token: &str) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "UPDATE invite_tokens SET web_visits = web_visits + 1
+             WHERE token = $1
+               AND is_active = 1
+               AND expires_at > $2
+               AND (max_usage IS NULL OR usage_count < max_usage)",
+        )
+        .bind(token)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Короткий slug пользователя; создаётся при первом запросе и не меняется
+    /// при ротации секрета.
+    pub async fn ensure_short_link(&self, tg_user_id: i64) -> Result<String, DbError> {
+        let now = current_unix_timestamp()?;
+        for _ in 0..5 {
+            let existing: Option<String> =
+                sqlx::query_scalar("SELECT slug FROM short_links WHERE tg_user_id = $1")
+                    .bind(tg_user_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+            if let Some(slug) = existing {
+                return Ok(slug);
+            }
+            // При коллизии slug вставка игнорируется и попытка повторяется.
+            let slug = Alphanumeric.sample_string(&mut rand::rng(), 8);
+            sqlx::query(
+                "INSERT INTO short_links (slug, tg_user_id, created_at) VALUES ($1, $2, $3)
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(slug)
+            .bind(tg_user_id)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        }
+        Err(anyhow::anyhow!("Не удалось сгенерировать короткую ссылку").into())
+    }
+
+    /// Текущий секрет по короткой ссылке: только одобренные и не приостановленные пользователи.
+    pub async fn resolve_short_link(&self, slug: &str) -> Result<Option<String>, DbError> {
+        let secret = sqlx::query_scalar::<_, String>(
+            "SELECT r.secret FROM short_links s
+             JOIN registration_requests r ON r.tg_user_id = s.tg_user_id
+             WHERE s.slug = $1 AND r.status = 'approved' AND r.suspended = 0 AND r.secret IS NOT NULL
+             ORDER BY r.id DESC LIMIT 1",
+        )
+        .bind(slug)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(secret)
+    }
+
+    /// Заменяет список заблокированных свежей выгрузкой и возвращает ID,
+    /// которых в нём раньше не было.
+    pub async fn replace_blocklist(&self, tg_user_ids: &[i64]) -> Result<Vec<i64>, DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+        let existing: std::collections::HashSet<i64> =
+            sqlx::query_scalar::<_, i64>("SELECT tg_user_id FROM blocked_users")
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .collect();
+        let fresh: std::collections::HashSet<i64> = tg_user_ids.iter().copied().collect();
+
+        for tg_user_id in existing.difference(&fresh) {
+            sqlx::query("DELETE FROM blocked_users WHERE tg_user_id = $1")
+                .bind(tg_user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let mut added: Vec<i64> = fresh.difference(&existing).copied().collect();
+        added.sort_unstable();
+        for tg_user_id in &added {
+            sqlx::query("INSERT INTO blocked_users (tg_user_id, added_at) VALUES ($1, $2)")
+                .bind(tg_user_id)
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    /// Заблокирован ли пользователь внешним списком (`[blocklist]`) или вручную (`/ban`).
+    pub async fn is_user_blocked(&self, tg_user_id: i64) -> Result<bool, DbError> {
+        let blocked = sqlx::query_scalar::<_, i64>(
+            "SELECT (SELECT COUNT(*) FROM blocked_users WHERE tg_user_id = $1)
+                  + (SELECT COUNT(*) FROM banned_users WHERE tg_user_id = $2)",
+        )
+        .bind(tg_user_id)
+        .bind(tg_user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(blocked > 0)
+    }
+
+    /// До какого времени пользователю запрещён ввод токенов после серии неверных.
+    pub async fn token_locked_until(&self, tg_user_id: i64) -> Result<Option<i64>, DbError> {
+        let now = current_unix_timestamp()?;
+        let locked_until = sqlx::query_scalar::<_, i64>(
+            "SELECT locked_until FROM token_attempts WHERE tg_user_id = $1 AND locked_until > $2",
+        )
+        .bind(tg_user_id)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(locked_until)
+    }
+
+    /// Учитывает неверный токен и возвращает число неудач подряд. Серия начинается
+    /// заново, если с прошлой неудачи прошло больше `reset_after_secs`.
+    pub async fn record_token_failure(
+        &self,
+        tg_user_id: i64,
+        reset_after_secs: i64,
+    ) -> Result<i64, DbError> {
+        let now = current_unix_timestamp()?;
+        let failures = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO token_attempts (tg_user_id, failures, last_failure_at)
+             VALUES ($1, 1, $2)
+             ON CONFLICT(tg_user_id) DO UPDATE
+             SET failures = CASE WHEN token_attempts.last_failure_at < $2 - $3 THEN 1
+                                 ELSE token_attempts.failures + 1 END,
+                 last_failure_at = $2
+             RETURNING failures",
+        )
+        .bind(tg_user_id)
+        .bind(now)
+        .bind(reset_after_secs)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(failures)
+    }
+
+    pub async fn lock_token_attempts(&self, tg_user_id: i64, until: i64) -> Result<(), DbError> {
+        sqlx::query("UPDATE token_attempts SET locked_until = $1 WHERE tg_user_id = $2")
+            .bind(until)
+            .bind(tg_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Верный токен обнуляет серию неудач.
+    pub async fn reset_token_failures(&self, tg_user_id: i64) -> Result<(), DbError> {
+        sqlx::query("DELETE FROM token_attempts WHERE tg_user_id = $1")
+            .bind(tg_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Входит ли пользователь в белый список (`registration_mode = "whitelist"`).
+    pub async fn is_user_allowed(&self, tg_user_id: i64) -> Result<bool, DbError> {
+        let allowed = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM allowed_users WHERE tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(allowed > 0)
+    }
+
+    /// Добавляет ID в белый список одной транзакцией и возвращает число новых записей.
+    pub async fn allow_users(&self, tg_user_ids: &[i64], added_by: i64) -> Result<u64, DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+        let mut added = 0;
+        for tg_user_id in tg_user_ids {
+            added += sqlx::query(
+                "INSERT INTO allowed_users (tg_user_id, added_by, added_at) VALUES ($1, $2, $3)
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(tg_user_id)
+            .bind(added_by)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    /// Убирает ID из белого списка; `false` — его там не было.
+    pub async fn disallow_user(&self, tg_user_id: i64) -> Result<bool, DbError> {
+        let result = sqlx::query("DELETE FROM allowed_users WHERE tg_user_id = $1")
+            .bind(tg_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Белый список, свежие записи первыми, и его общий размер.
+    pub async fn list_allowed_users(&self, limit: i64) -> Result<(Vec<AllowedUser>, i64), DbError> {
+        let rows = sqlx::query_as::<_, AllowedUser>(
+            "SELECT a.tg_user_id, a.added_at,
+                    (SELECT r.status FROM registration_requests r
+                     WHERE r.tg_user_id = a.tg_user_id ORDER BY r.id DESC LIMIT 1) AS status
+             FROM allowed_users a
+             ORDER BY a.added_at DESC, a.tg_user_id
+             LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM allowed_users")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok((rows, total))
+    }
+
+    /// Добавляет ручную блокировку; `false` — пользователь уже заблокирован.
+    pub async fn ban_user(
+        &self,
+        tg_user_id: i64,
+        banned_by: i64,
+        reason: Option<&str>,
+    ) -> Result<bool, DbError> {
+        let now = current_unix_timestamp()?;
+        let result = sqlx::query(
+            "INSERT INTO banned_users (tg_user_id, banned_by, reason, banned_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(tg_user_id)
+        .bind(banned_by)
+        .bind(reason)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Снимает ручную блокировку; `false` — её не было.
+    pub async fn unban_user(&self, tg_user_id: i64) -> Result<bool, DbError> {
+        let result = sqlx::query("DELETE FROM banned_users WHERE tg_user_id = $1")
+            .bind(tg_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Ручные блокировки, свежие первыми, и их общее число.
+    pub async fn list_banned_users(&self, limit: i64) -> Result<(Vec<BannedUser>, i64), DbError> {
+        let rows = sqlx::query_as::<_, BannedUser>(
+            "SELECT b.tg_user_id, b.banned_by, b.reason, b.banned_at,
+                    (SELECT r.tg_username FROM registration_requests r
+                     WHERE r.tg_user_id = b.tg_user_id ORDER BY r.id DESC LIMIT 1) AS tg_username
+             FROM banned_users b
+             ORDER BY b.banned_at DESC, b.tg_user_id
+             LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM banned_users")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok((rows, total))
+    }
+
+    /// Другие пользователи с тем же username или отображаемым именем (без учёта
+    /// регистра ASCII): активные, ожидающие и удалённые — удалённые первыми.
+    pub async fn find_similar_users(
+        &self,
+        request: &RegistrationRequest,
+        limit: i64,
+    ) -> Result<Vec<RegistrationRequest>, DbError> {
+        let username = request
+            .tg_username
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty());
+        let display_name = request
+            .tg_display_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty());
+        if username.is_none() && display_name.is_none() {
+            return Ok(Vec::new());
+        }
+        let sql = format!(
+            "{} WHERE tg_user_id != $1
+               AND status IN ('{}', '{}', '{}')
+               AND (($2 IS NOT NULL AND LOWER(tg_username) = LOWER($3))
+                 OR ($4 IS NOT NULL AND LOWER(TRIM(tg_display_name)) = LOWER($5)))
+             ORDER BY status = '{}' DESC, id DESC
+             LIMIT $6",
+            SELECT_REQUEST, STATUS_APPROVED, STATUS_PENDING, STATUS_DELETED, STATUS_DELETED
+        );
+        let rows = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(request.tg_user_id)
+            .bind(username)
+            .bind(username)
+            .bind(display_name)
+            .bind(display_name)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Безвозвратно удаляет все записи о пользователе: заявки (секрет, имя и
+    /// заметка перед удалением затираются), архив, ссылки, опрос, онбординг,
+    /// переписку с поддержкой; персональные токены отзываются и отвязываются,
+    /// цель записей аудита обезличивается. После транзакции выполняется `VACUUM`,
+    /// чтобы старые значения не остались в свободных страницах файла БД (в Postgres
+    /// он освобождает место удалённых строк для перезаписи).
+    /// Возвращает число удалённых строк.
+    pub async fn purge_user(&self, tg_user_id: i64) -> Result<u64, DbError> {
+        let now = current_unix_timestamp()?;
+        let mut tx = self.pool.begin().await?;
+
+        for table in ["approval_basket", "request_claims"] {
+            sqlx::query(&format!(
+                "DELETE FROM {}
+                 WHERE request_id IN (SELECT id FROM registration_requests WHERE tg_user_id = $1)",
+                table
+            ))
+            .bind(tg_user_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "UPDATE registration_requests
+             SET secret = NULL, tg_username = NULL, tg_display_name = NULL, note = NULL
+             WHERE tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let mut deleted = 0;
+        for table in [
+            "registration_requests",
+            "archived_requests",
+            "link_reveals",
+            "provisioned_users",
+            "onboarding_messages",
+            "survey_invites",
+            "survey_answers",
+            "support_messages",
+            "support_conversations",
+            "broadcast_deliveries",
+            "user_traffic",
+            "token_views",
+            "token_usages",
+            "short_links",
+            "user_settings",
+            "allowed_users",
+            "token_attempts",
+        ] {
+            deleted += sqlx::query(&format!("DELETE FROM {} WHERE tg_user_id = $1", table))
+                .bind(tg_user_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+
+        sqlx::query(
+            "UPDATE invite_tokens
+             SET for_tg_user_id = NULL, is_active = 0, revoked_at = COALESCE(revoked_at, $1)
+             WHERE for_tg_user_id = $2",
+        )
+        .bind(now)
+        .bind(tg_user_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("UPDATE audit_log SET target = 'tg_user:purged' WHERE target = $1")
+            .bind(format!("tg_user:{}", tg_user_id))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        if self.backend == Backend::Sqlite {
+            // Удалённые из FTS-индекса строки физически исчезают только после слияния сегментов.
+            sqlx::query("INSERT INTO users_fts(users_fts) VALUES('optimize')")
+                .execute(&self.pool)
+                .await?;
+        }
+        sqlx::raw_sql("VACUUM").execute(&self.pool).await?;
+        Ok(deleted)
+    }
+
+    /// Планирует удаление открыто отправленного сообщения со ссылкой (`[links] auto_delete_minutes`).
+    pub async fn schedule_link_auto_delete(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+        delete_at: i64,
+    ) -> Result<(), DbError> {
+        sqlx::query(
+            "INSERT INTO link_auto_deletes (chat_id, message_id, delete_at) VALUES ($1, $2, $3)
+             ON CONFLICT (chat_id, message_id) DO UPDATE SET delete_at = excluded.delete_at",
+        )
+        .bind(chat_id)
+        .bind(message_id)
+        .bind(delete_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Сообщения со ссылками, время автоудаления которых наступило.
+    pub async fn take_due_link_auto_deletes(&self) -> Result<Vec<ExpiredLinkMessage>, DbError> {
+        let now = current_unix_timestamp()?;
+        let rows = sqlx::query_as::<_, ExpiredLinkMessage>(
+            "DELETE FROM link_auto_deletes WHERE delete_at <= $1 RETURNING chat_id, message_id",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Задаёт или снимает срок доступа одобренного пользователя. Предупреждения
+    /// льготного периода для нового срока начинаются заново.
+    pub async fn set_user_expiry(
+        &self,
+        tg_user_id: i64,
+        expires_at: Option<i64>,
+    ) -> Result<bool, DbError> {
+        let result = sqlx::query(
+            "UPDATE registration_requests SET expires_at = $1, expiry_warned_at = NULL \
+             WHERE tg_user_id = $2 AND status = $3",
+        )
+        .bind(expires_at)
+        .bind(tg_user_id)
+        .bind(STATUS_APPROVED)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Срок доступа одобренного пользователя, если он задан.
+    pub async fn get_user_expiry(&self, tg_user_id: i64) -> Result<Option<i64>, DbError> {
+        let expires_at = sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT expires_at FROM registration_requests WHERE tg_user_id = $1 AND status = $2",
+        )
+        .bind(tg_user_id)
+        .bind(STATUS_APPROVED)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(expires_at.flatten())
+    }
+
+    /// Одобренные пользователи, срок доступа которых уже истёк к `now`, но которые
+    /// ещё не удалены: льготный период `[expiry] grace_days`.
+    pub async fn list_grace_users(&self, now: i64) -> Result<Vec<GraceUser>, DbError> {
+        let rows = sqlx::query_as::<_, GraceUser>(
+            "SELECT tg_user_id, expires_at, expiry_warned_at FROM registration_requests \
+             WHERE status = $1 AND expires_at IS NOT NULL AND expires_at <= $2 \
+             ORDER BY expires_at ASC",
+        )
+        .bind(STATUS_APPROVED)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn mark_expiry_warned(&self, tg_user_id: i64, now: i64) -> Result<(), DbError> {
+        sqlx::query("UPDATE registration_requests SET expiry_warned_at = $1 WHERE tg_user_id = $2")
+            .bind(now)
+            .bind(tg_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Одобренные пользователи, срок доступа которых истёк к `now`.
+    pub async fn list_expired_users(&self, now: i64) -> Result<Vec<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE status = $1 AND expires_at IS NOT NULL AND expires_at <= $2 ORDER BY expires_at ASC",
+            SELECT_REQUEST
+        );
+        let rows = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(STATUS_APPROVED)
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Имена в конфиге telemt всех пользователей, когда-либо получавших доступ
+    /// (в любом статусе): по ним видно, что запись в конфиге принадлежит боту.
+    pub async fn list_known_telemt_usernames(&self) -> Result<Vec<String>, DbError> {
+        let names = sqlx::query_scalar::<_, String>(
+            "SELECT telemt_username FROM registration_requests WHERE telemt_username IS NOT NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(names)
+    }
+
+    /// Одобренные пользователи, которые должны быть в конфиге telemt (без приостановленных).
+    pub async fn list_users_expected_in_config(&self) -> Result<Vec<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE status = $1 AND suspended = 0 AND secret IS NOT NULL ORDER BY tg_user_id ASC",
+            SELECT_REQUEST
+        );
+        let rows = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(STATUS_APPROVED)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Есть ли в БД хоть одна заявка: пустая БД означает первый запуск бота.
+    pub async fn has_any_requests(&self) -> Result<bool, DbError> {
+        let exists = sqlx::query_scalar::<_, i64>("SELECT 1 FROM registration_requests LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(exists.is_some())
+    }
+
+    /// Создаёт approved-запись для пользователя, найденного в конфиге telemt.
+    /// Если `tg_user_id` не известен, выдаётся отрицательный id-заглушка (меньше всех
+    /// существующих), который позже заменяется настоящим через [`Db::bind_imported_user`].
+    /// Возвращает выданный tg_user_id.
+    pub async fn import_config_user(
+        &self,
+        tg_user_id: Option<i64>,
+        telemt_username: &str,
+        secret: &str,
+    ) -> Result<i64, DbError> {
+        let now = current_unix_timestamp()?;
+        let tg_user_id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO registration_requests
+             (tg_user_id, tg_display_name, status, telemt_username, secret, created_at, resolved_at, note)
+             VALUES (
+                 COALESCE($1, (SELECT CASE WHEN MIN(tg_user_id) < 0 THEN MIN(tg_user_id) ELSE 0 END - 1
+                               FROM registration_requests)),
+                 $2, 'approved', $3, $4, $5, $6, 'импортирован из конфига telemt'
+             )
+             RETURNING tg_user_id",
+        )
+        .bind(tg_user_id)
+        .bind(telemt_username)
+        .bind(telemt_username)
+        .bind(secret)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(tg_user_id)
+    }
+
+    /// Последняя запись с данным именем пользователя в конфиге telemt.
+    pub async fn find_request_by_telemt_username(
+        &self,
+        telemt_username: &str,
+    ) -> Result<Option<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE telemt_username = $1 ORDER BY created_at DESC LIMIT 1",
+            SELECT_REQUEST
+        );
+        let r = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(telemt_username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(r)
+    }
+
+    /// Импортированные пользователи, ещё не привязанные к аккаунту Telegram.
+    pub async fn list_unbound_imports(&self) -> Result<Vec<RegistrationRequest>, DbError> {
+        let sql = format!(
+            "{} WHERE status = $1 AND tg_user_id < 0 ORDER BY tg_user_id DESC",
+            SELECT_REQUEST
+        );
+        let rows = sqlx::query_as::<_, RegistrationRequest>(&sql)
+            .bind(STATUS_APPROVED)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Переносит импортированного пользователя с id-заглушки на настоящий tg_user_id.
+    /// Прежние неактивные записи этого аккаунта (отклонённая заявка, удалённый доступ)
+    /// заменяются, но имя и username из них сохраняются. Возвращает `false`, если
+    /// заглушка не найдена или у аккаунта уже есть активный доступ.
+    pub async fn bind_imported_user(
+        &self,
+        placeholder_id: i64,
+        tg_user_id: i64,
+        telemt_username: &str,
+    ) -> Result<bool, DbError> {
+        let mut tx = self.pool.begin().await?;
+
+        let active = sqlx::query_scalar::<_, i64>(
+            "SELECT 1 FROM registration_requests WHERE tg_user_id = $1 AND status = $2",
+        )
+        .bind(tg_user_id)
+        .bind(STATUS_APPROVED)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if active.is_some() {
+            return Ok(false);
+        }
+        let previous = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+            "SELECT tg_username, tg_display_name FROM registration_requests WHERE tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM approval_basket
+             WHERE request_id IN (SELECT id FROM registration_requests WHERE tg_user_id = $1)",
+        )
+        .bind(tg_user_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM registration_requests WHERE tg_user_id = $1")
+            .bind(tg_user_id)
+            .execute(&mut *tx)
+            .await?;
+        // Накопленный трафик переходит к настоящему аккаунту.
+        sqlx::query("DELETE FROM user_traffic WHERE tg_user_id = $1")
+            .bind(tg_user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE user_traffic SET tg_user_id = $1 WHERE tg_user_id = $2")
+            .bind(tg_user_id)
+            .bind(placeholder_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let (tg_username, tg_display_name) = previous.unwrap_or_default();
+        let r = sqlx::query(
+            "UPDATE registration_requests
+             SET tg_user_id = $1,
+                 telemt_username = $2,
+                 tg_username = COALESCE($3, tg_username),
+                 tg_display_name = COALESCE($4, tg_display_name)
+             WHERE tg_user_id = $5 AND tg_user_id < 0 AND status = $6",
+        )
+        .bind(tg_user_id)
+        .bind(telemt_username)
+        .bind(tg_username)
+        .bind(tg_display_name)
+        .bind(placeholder_id)
+        .bind(STATUS_APPROVED)
+        .execute(&mut *tx)
+        .await?;
+        if r.rows_affected() == 0 {
+            return Ok(false);
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Язык, выбранный пользователем кнопкой меню (код `ru`, `en`).
+    pub async fn get_user_language(&self, tg_user_id: i64) -> Result<Option<String>, DbError> {
+        let language = sqlx::query_scalar::<_, String>(
+            "SELECT language FROM user_settings WHERE tg_user_id = $1",
+        )
+        .bind(tg_user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(language)
+    }
+
+    pub async fn set_user_language(&self, tg_user_id: i64, language: &str) -> Result<(), DbError> {
+        sqlx::query(
+            "INSERT INTO user_settings (tg_user_id, language) VALUES ($1, $2)
+             ON CONFLICT(tg_user_id) DO UPDATE SET language = excluded.language",
+        )
+        .bind(tg_user_id)
+        .bind(language)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Кто держит ожидающую заявку; захваты старше `stale_before` не учитываются.
+    pub async fn get_request_claim(
+        &self,
+        request_id: i64,
+        stale_before: i64,
+    ) -> Result<Option<RequestClaim>, DbError> {
+        let claim = sqlx::query_as::<_, RequestClaim>(
+            "SELECT c.admin_id, c.admin_name FROM request_claims c
+             JOIN registration_requests r ON r.id = c.request_id
+             WHERE c.request_id = $1 AND c.claimed_at >= $2 AND r.status = $3",
+        )
+        .bind(request_id)
+        .bind(stale_before)
+        .bind(STATUS_PENDING)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(claim)
+    }
+
+    /// Берёт ожидающую заявку в работу, если её не держит другой админ (или его захват
+    /// старше `stale_before`). Возвращает держателя после попытки; `None` — заявка уже
+    /// обработана или не найдена.
+    pub async fn claim_request(
+        &self,
+        request_id: i64,
+        admin_id: i64,
+        admin_name: Option<&str>,
+        stale_before: i64,
+    ) -> Result<Option<RequestClaim>, DbError> {
+        let now = current_unix_timestamp()?;
+        sqlx::query(
+            "INSERT INTO request_claims (request_id, admin_id, admin_name, claimed_at)
+             SELECT id, $2, $3, $4 FROM registration_requests WHERE id = $1 AND status = $6
+             ON CONFLICT(request_id) DO UPDATE
+             SET admin_id = excluded.admin_id, admin_name = excluded.admin_name,
+                 claimed_at = excluded.claimed_at
+             WHERE request_claims.admin_id = excluded.admin_id OR request_claims.claimed_at < $5",
+        )
+        .bind(request_id)
+        .bind(admin_id)
+        .bind(admin_name)
+        .bind(now)
+        .bind(stale_before)
+        .bind(STATUS_PENDING)
+        .execute(&self.pool)
+        .await?;
+        self.get_request_claim(request_id, stale_before).await
+    }
+
+    /// Снимает захват заявки, если его держит этот админ.
+    pub async fn release_request_claim(
+        &self,
+        request_id: i64,
+        admin_id: i64,
+    ) -> Result<bool, DbError> {
+        let result =
+            sqlx::query("DELETE FROM request_claims WHERE request_id = $1 AND admin_id = $2")
+                .bind(request_id)
+                .bind(admin_id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
 }
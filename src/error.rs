@@ -0,0 +1,71 @@
+//! Ошибки уровня приложения и их классификация для ответов пользователям.
+
+use crate::db::DbError;
+use crate::service::ServiceError;
+use crate::telemt_cfg::TelemtCfgError;
+use crate::telemt_writer::ConfigWriteError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("Ошибка БД: {0}")]
+    Db(#[from] DbError),
+    #[error("Ошибка конфига telemt: {0}")]
+    TelemtCfg(#[from] TelemtCfgError),
+    #[error("Ошибка сервиса telemt: {0}")]
+    Service(#[from] ServiceError),
+    #[error("Ошибка Telegram API: {0}")]
+    Telegram(#[from] teloxide::RequestError),
+    #[error("{0}")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl From<std::fmt::Error> for AppError {
+    fn from(error: std::fmt::Error) -> Self {
+        Self::Internal(anyhow::Error::from(error))
+    }
+}
+
+impl From<ConfigWriteError> for AppError {
+    fn from(error: ConfigWriteError) -> Self {
+        match error {
+            ConfigWriteError::Cfg(error) => Self::TelemtCfg(error),
+            ConfigWriteError::Service(error) => Self::Service(error),
+            ConfigWriteError::Closed => Self::Internal(anyhow::anyhow!(error)),
+        }
+    }
+}
+
+impl AppError {
+    /// Короткое имя класса ошибки для логов и отчётов админам.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Self::Db(_) => "db",
+            Self::TelemtCfg(_) => "telemt_cfg",
+            Self::Service(_) => "service",
+            Self::Telegram(_) => "telegram",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    /// Безопасный текст для пользователя без внутренних подробностей.
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            Self::Db(_) => "⚠️ Временная ошибка хранилища. Попробуйте позже.",
+            Self::TelemtCfg(_) => {
+                "⚠️ Не удалось прочитать или обновить настройки прокси. Администратор уже уведомлён."
+            }
+            Self::Service(_) => {
+                "⚠️ Не удалось перезапустить прокси-сервис. Администратор уже уведомлён."
+            }
+            Self::Telegram(_) => "⚠️ Ошибка связи с Telegram. Попробуйте ещё раз.",
+            Self::Internal(_) => "⚠️ Не удалось обработать запрос. Попробуйте позже.",
+        }
+    }
+
+    /// Нужно ли сообщать об ошибке администраторам. Ошибки Telegram API
+    /// (например, пользователь заблокировал бота) штатные и только логируются.
+    pub fn is_unexpected(&self) -> bool {
+        !matches!(self, Self::Telegram(_))
+    }
+}
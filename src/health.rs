@@ -0,0 +1,60 @@
+//! Проверка живости для контейнеров: работающий диспетчер периодически пишет
+//! heartbeat в файл, а `telemt-admin healthcheck` проверяет его свежесть и
+//! доступность БД без HTTP-эндпоинта.
+
+use crate::config::Config;
+use crate::db::Db;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Как часто обновлять heartbeat относительно `max_age_secs`.
+const HEARTBEAT_DIVISOR: u64 = 4;
+
+pub fn spawn_heartbeat(path: PathBuf, max_age_secs: u64) -> tokio::task::JoinHandle<()> {
+    let interval = Duration::from_secs((max_age_secs / HEARTBEAT_DIVISOR).max(1));
+    tokio::spawn(async move {
+        loop {
+            let now = unix_now().unwrap_or_default();
+            if let Err(error) = tokio::fs::write(&path, now.to_string()).await {
+                tracing::warn!(
+                    path = %path.display(),
+                    error = %error,
+                    "Не удалось записать heartbeat"
+                );
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
+
+/// Выполняет проверки; `Err` содержит причину для вывода в лог контейнера.
+pub async fn check(config: &Config) -> Result<(), String> {
+    let location = config.db_location().map_err(|e| e.to_string())?;
+    Db::probe(&location)
+        .await
+        .map_err(|e| format!("БД {} недоступна: {}", location, e))?;
+
+    let path = config.heartbeat_path();
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("heartbeat {} не прочитан: {}", path.display(), e))?;
+    let beat = content
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| format!("heartbeat {} повреждён", path.display()))?;
+    let age = unix_now()?.saturating_sub(beat);
+    if age > config.health.max_age_secs {
+        return Err(format!(
+            "heartbeat устарел: {} с (допустимо {} с)",
+            age, config.health.max_age_secs
+        ));
+    }
+    Ok(())
+}
+
+fn unix_now() -> Result<u64, String> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,82 @@
+//! Тексты для пользователей на их языке. Бандлы `locales/<код>.toml` вшиваются в
+//! бинарник; ключи плоские, плейсхолдеры вида `{name}`. Если в бандле нет ключа,
+//! берётся русский текст. Сообщения админам не локализуются.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    Ru,
+    En,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::Ru, Lang::En];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::Ru => "ru",
+            Lang::En => "en",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lang| lang.code() == code)
+    }
+
+    /// Язык, на который переключает кнопка меню.
+    pub fn next(self) -> Self {
+        match self {
+            Lang::Ru => Lang::En,
+            Lang::En => Lang::Ru,
+        }
+    }
+
+    fn bundle_source(self) -> &'static str {
+        match self {
+            Lang::Ru => include_str!("../locales/ru.toml"),
+            Lang::En => include_str!("../locales/en.toml"),
+        }
+    }
+}
+
+static BUNDLES: LazyLock<HashMap<Lang, HashMap<String, String>>> = LazyLock::new(|| {
+    Lang::ALL
+        .into_iter()
+        .map(|lang| {
+            let bundle = toml::from_str(lang.bundle_source()).unwrap_or_else(|error| {
+                panic!("Некорректный бандл locales/{}.toml: {}", lang.code(), error)
+            });
+            (lang, bundle)
+        })
+        .collect()
+});
+
+/// Текст по ключу; для неизвестного ключа возвращается сам ключ.
+pub fn t(lang: Lang, key: &str) -> &str {
+    BUNDLES
+        .get(&lang)
+        .and_then(|bundle| bundle.get(key))
+        .or_else(|| BUNDLES.get(&Lang::Ru).and_then(|bundle| bundle.get(key)))
+        .map(String::as_str)
+        .unwrap_or(key)
+}
+
+/// Текст по ключу с подстановкой `{name}` → значение. Аргументы `Sync`, чтобы массив
+/// можно было держать через `.await` в обработчиках.
+pub fn tf(lang: Lang, key: &str, args: &[(&str, &(dyn std::fmt::Display + Sync))]) -> String {
+    let mut text = t(lang, key).to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    text
+}
+
+/// Совпадает ли текст с переводом ключа на любом языке (кнопки reply-меню).
+pub fn matches_any(text: &str, key: &str) -> bool {
+    Lang::ALL.into_iter().any(|lang| t(lang, key) == text)
+}
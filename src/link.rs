@@ -1,4 +1,4 @@
-//! Генерация fake-TLS ссылок на прокси telemt.
+//! Генерация fake-TLS ссылок на прокси telemt и deep-link на бота.
 
 use crate::telemt_cfg::TelemtLinkParams;
 use rand::RngCore;
@@ -21,17 +21,59 @@ pub fn build_fake_tls_secret(user_secret: &str, tls_domain: &str) -> String {
     s
 }
 
+/// Deep-link на бота с invite-токеном: `https://t.me/<bot>?start=<token>`.
+pub fn build_bot_start_link(bot_username: &str, token: &str) -> String {
+    let normalized = bot_username.trim_start_matches('@');
+    format!("https://t.me/{}?start={}", normalized, token)
+}
+
+/// Payload варианта ссылки `<token>-<group>`. В deep-link Telegram допускает только
+/// `A-Za-z0-9_-`, поэтому разделитель — дефис; вручную введённую точку
+/// (`<token>.<group>`) бот тоже понимает.
+pub fn build_start_payload(token: &str, plan: &str) -> String {
+    format!("{}-{}", token, plan)
+}
+
+/// Делит payload на токен и вариант: токен состоит только из букв и цифр,
+/// поэтому вариант начинается после первого `.`, `-` или `_`.
+pub fn split_start_payload(payload: &str) -> (&str, Option<&str>) {
+    match payload.find(['.', '-', '_']) {
+        Some(index) => {
+            let plan = &payload[index + 1..];
+            (&payload[..index], (!plan.is_empty()).then_some(plan))
+        }
+        None => (payload, None),
+    }
+}
+
 /// Формирует tg://proxy ссылку.
 pub fn build_proxy_link(
     params: &TelemtLinkParams,
     user_secret: &str,
+) -> Result<String, std::fmt::Error> {
+    build_proxy_url("tg://proxy", params, user_secret)
+}
+
+/// То же в виде `https://t.me/proxy?...` — открывается из браузера
+/// (редирект коротких ссылок [`crate::web`]).
+pub fn build_proxy_web_link(
+    params: &TelemtLinkParams,
+    user_secret: &str,
+) -> Result<String, std::fmt::Error> {
+    build_proxy_url("https://t.me/proxy", params, user_secret)
+}
+
+fn build_proxy_url(
+    base: &str,
+    params: &TelemtLinkParams,
+    user_secret: &str,
 ) -> Result<String, std::fmt::Error> {
     let secret = build_fake_tls_secret(user_secret, &params.tls_domain);
     let mut url = String::new();
     write!(
         url,
-        "tg://proxy?server={}&port={}&secret={}",
-        params.host, params.port, secret
+        "{}?server={}&port={}&secret={}",
+        base, params.host, params.port, secret
     )?;
     Ok(url)
 }
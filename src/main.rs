@@ -1,13 +1,27 @@
 //! telemt-admin — Telegram-бот для администрирования MTProxy telemt.
 
+mod alerts;
+mod audit;
 mod bot;
+mod cli;
 mod config;
 mod db;
+mod error;
+mod health;
+mod i18n;
 mod link;
+mod manage;
+mod metrics;
+mod provision;
+mod retention;
+mod secrets;
 mod service;
 mod telemt_cfg;
+mod telemt_writer;
+mod traffic;
+mod web;
 
-use std::path::PathBuf;
+use crate::bot::Bot;
 use std::sync::Arc;
 use teloxide::dispatching::Dispatcher;
 use teloxide::prelude::*;
@@ -15,65 +29,449 @@ use tokio::sync::Mutex;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let args = cli::parse_args();
+
+    let default_directive = match args.log_level.as_deref() {
+        Some(level) => level
+            .parse::<tracing_subscriber::filter::Directive>()
+            .map_err(|e| format!("Некорректный --log-level {}: {}", level, e))?,
+        // Вывод проб и подкоманд управления читают скрипты: лишние логи в нём мешают.
+        None if args.healthcheck || args.manage.is_some() => tracing::Level::WARN.into(),
+        None => tracing::Level::INFO.into(),
+    };
     tracing_subscriber::fmt()
         .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
+            tracing_subscriber::EnvFilter::from_default_env().add_directive(default_directive),
         )
         .init();
 
-    let config_path = std::env::args()
-        .nth(1)
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("/etc/telemt-admin.toml"));
+    let config_path = args.config_path;
     tracing::info!(
+        dry_run = args.dry_run,
         "Starting telemt-admin with config {}",
         config_path.display()
     );
 
-    let config = Arc::new(config::Config::load(&config_path)?);
-    let token = config.bot_token()?;
+    let mut config = config::Config::load(&config_path)?;
+    // Проба контейнера не обращается к хранилищу секретов и не требует токена бота.
+    if args.healthcheck {
+        match health::check(&config).await {
+            Ok(()) => {
+                println!("OK");
+                return Ok(());
+            }
+            Err(reason) => {
+                eprintln!("UNHEALTHY: {}", reason);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    config.set_secret_values(secrets::load(&config.secrets).await?);
+    let config = Arc::new(config);
+    let db_location = config.db_location()?;
     tracing::info!(
         admin_count = config.admin_ids.len(),
-        db_path = %config.db_path.display(),
+        db = %db_location,
         telemt_config_path = %config.telemt_config_path.display(),
         service_name = %config.service_name,
         users_page_size = config.users_page_size,
         "Configuration loaded"
     );
 
-    let db = Arc::new(db::Db::open(&config.db_path).await?);
-    let telemt_cfg = Arc::new(telemt_cfg::TelemtConfig::new(&config.telemt_config_path));
-    let service = service::ServiceController::new(&config.service_name);
+    if args.check {
+        config.bot_token()?;
+        let params = telemt_cfg::TelemtConfig::new(&config.telemt_config_path)
+            .read_link_params()
+            .await?;
+        println!(
+            "OK: конфиг {} корректен, telemt: {}:{} (tls_domain {})",
+            config_path.display(),
+            params.host,
+            params.port,
+            params.tls_domain
+        );
+        return Ok(());
+    }
+
+    if let Some(target) = args.migrate_undo {
+        db::Db::undo_migrations(&db_location, target).await?;
+        tracing::info!(
+            db = %db_location,
+            target = target,
+            "Migrations rolled back, exiting"
+        );
+        return Ok(());
+    }
+    let db = Arc::new(db::Db::open(&db_location).await?);
+    if args.migrate_only {
+        tracing::info!(db = %db_location, "Migrations applied, exiting");
+        return Ok(());
+    }
+    if let Some(command) = args.manage {
+        manage::run(command, config.clone(), db, args.dry_run).await?;
+        return Ok(());
+    }
+    provision::sync(&db, &config.provision).await?;
+    let _retention = retention::spawn(db.clone(), config.retention.clone());
+
+    let telemt_cfg = Arc::new(
+        telemt_cfg::TelemtConfig::new(&config.telemt_config_path)
+            .with_dry_run(args.dry_run)
+            .with_backups(config.telemt_backup_dir(), config.telemt_backups.keep),
+    );
+    let (admin_alerts, admin_alerts_rx) = alerts::channel();
+    let service = service::ServiceController::new(&config.service_name)
+        .with_timeout(std::time::Duration::from_secs(
+            config.systemctl_timeout_secs,
+        ))
+        .with_config_check(config.config_check_command())
+        .with_restart_limit(
+            config.restart.max_restarts,
+            std::time::Duration::from_secs(config.restart.window_minutes * 60),
+            admin_alerts.clone(),
+        );
+    let cfg_writer = telemt_writer::ConfigWriter::spawn(
+        telemt_cfg.clone(),
+        service.clone(),
+        admin_alerts.clone(),
+        std::time::Duration::from_secs(config.restart.debounce_secs),
+    );
+    let _metrics = metrics::spawn_pusher(db.clone(), service.clone(), config.metrics.clone());
+    let _metrics_server = metrics::spawn_server(db.clone(), service.clone(), &config.metrics);
+    let _traffic = traffic::spawn(db.clone(), config.traffic.clone());
+
+    let mut token = config.bot_token()?;
+    let http_client = bot::client::build_http_client(&config.telegram)?;
+    let job_notify = Arc::new(tokio::sync::Notify::new());
+    let awaiting_invite_users = Arc::new(Mutex::new(std::collections::HashSet::new()));
+    let pending_restart = Arc::new(Mutex::new(None));
+    let view_as = Arc::new(Mutex::new(std::collections::HashMap::new()));
+    let awaiting_reject_reason = Arc::new(Mutex::new(std::collections::HashMap::new()));
+    let cooldowns = Arc::new(bot::cooldown::Cooldowns::new(config.cooldowns.clone()));
+    let mut audit = audit::AuditLog::new(db.clone(), config.audit.clone());
+    if config.admin_chat_id.is_some() {
+        audit = audit.with_admin_chat(admin_alerts.clone());
+    }
+    let token_reload = Arc::new(tokio::sync::Notify::new());
+    let mut token_rotated = false;
+    let mut drift_checked = false;
+    loop {
+        let bot = bot::client::build_bot(&config.telegram, http_client.clone(), token.clone())?;
+        let backup_token = config.backup_bot_token().unwrap_or_else(|error| {
+            tracing::warn!(error = %error, "Не удалось прочитать резервный токен бота");
+            None
+        });
+        let backup_token = backup_token.filter(|backup| *backup != token);
+        let bot_username = match bot.get_me().await {
+            Ok(me) => me.user.username.clone(),
+            Err(error) => {
+                if is_invalid_token(&error)
+                    && let Some(backup) = backup_token
+                {
+                    tracing::error!(
+                        "Bot token rejected by Telegram at startup, switching to backup token"
+                    );
+                    admin_alerts.send(
+                        "⚠️ Основной токен бота недействителен — бот запущен с резервным токеном.",
+                    );
+                    token = backup;
+                    continue;
+                }
+                tracing::warn!(
+                    error = %error,
+                    "Не удалось получить username бота через getMe"
+                );
+                None
+            }
+        };
+        if std::mem::take(&mut token_rotated) {
+            admin_alerts.send(format!(
+                "🔑 Токен бота обновлён, бот переподключён{}. Очередь задач сохранена.",
+                bot_username
+                    .as_deref()
+                    .map(|username| format!(" как @{}", username))
+                    .unwrap_or_default()
+            ));
+        }
+
+        let state = bot::handlers::BotState {
+            config: config.clone(),
+            db: db.clone(),
+            telemt_cfg: telemt_cfg.clone(),
+            cfg_writer: cfg_writer.clone(),
+            service: service.clone(),
+            bot_username,
+            awaiting_invite_users: awaiting_invite_users.clone(),
+            job_notify: job_notify.clone(),
+            cooldowns: cooldowns.clone(),
+            audit: audit.clone(),
+            token_reload: token_reload.clone(),
+            pending_restart: pending_restart.clone(),
+            view_as: view_as.clone(),
+            awaiting_reject_reason: awaiting_reject_reason.clone(),
+        };
+        if !drift_checked {
+            drift_checked = true;
+            let (bot, state) = (bot.clone(), state.clone());
+            tokio::spawn(async move {
+                // Сначала импорт: иначе на первом запуске все пользователи telemt
+                // попали бы в отчёт о расхождениях.
+                bot::handlers::import_on_first_run(&bot, &state).await;
+                bot::handlers::check_config_drift_on_startup(bot, state).await;
+            });
+        }
+        {
+            // Кнопка меню привязана к боту, поэтому ставится после каждой смены токена.
+            let (bot, state) = (bot.clone(), state.clone());
+            tokio::spawn(async move {
+                bot::handlers::setup_admin_app_menu_button(&bot, &state).await;
+            });
+        }
+        let job_worker = bot::handlers::spawn_job_worker(bot.clone(), state.clone());
+        let alerts_worker =
+            bot::handlers::spawn_admin_alerts(bot.clone(), state.clone(), admin_alerts_rx.clone());
+        let ephemeral_sweeper = bot::handlers::spawn_ephemeral_sweeper(bot.clone(), state.clone());
+        let reminders_worker = bot::handlers::spawn_pending_reminders(bot.clone(), state.clone());
+        let onboarding_worker = bot::handlers::spawn_onboarding_drip(bot.clone(), state.clone());
+        let survey_worker = bot::handlers::spawn_survey_worker(bot.clone(), state.clone());
+        let group_scheduler = bot::handlers::spawn_group_scheduler(bot.clone(), state.clone());
+        let expiry_worker = bot::handlers::spawn_expiry_worker(bot.clone(), state.clone());
+        let broadcast_worker = bot::handlers::spawn_broadcast_worker(bot.clone(), state.clone());
+        let weekly_report = bot::handlers::spawn_weekly_report(bot.clone(), state.clone());
+        let ticket_closer = bot::handlers::spawn_ticket_closer(bot.clone(), state.clone());
+        let blocklist_sync = bot::handlers::spawn_blocklist_sync(bot.clone(), state.clone());
+        let proxy_monitor = bot::handlers::spawn_proxy_monitor(bot.clone(), state.clone());
+        let web_server = web::spawn(
+            db.clone(),
+            telemt_cfg.clone(),
+            config.web.clone(),
+            state.bot_username.clone(),
+            bot::handlers::AdminApp::new(bot.clone(), state.clone()),
+        );
+        let heartbeat =
+            health::spawn_heartbeat(config.heartbeat_path(), config.health.max_age_secs);
+        tracing::info!("Dispatcher initialized, bot is ready");
+
+        let error_handler = bot::handlers::admin_error_reporter(bot.clone(), state.clone());
+        let probe_bot = bot.clone();
+        let mut dispatcher = Dispatcher::builder(bot, bot::handlers::schema())
+            .dependencies(dptree::deps![state])
+            .error_handler(error_handler)
+            .enable_ctrlc_handler()
+            .build();
+
+        let (reload_tx, mut reload_rx) = tokio::sync::oneshot::channel();
+        let reload_watcher = tokio::spawn(watch_token_reload(
+            config.clone(),
+            http_client.clone(),
+            token.clone(),
+            dispatcher.shutdown_token(),
+            reload_tx,
+            token_reload.clone(),
+            admin_alerts.clone(),
+        ));
+        let (failover_tx, mut failover_rx) = tokio::sync::oneshot::channel();
+        let failover_watcher = backup_token.map(|backup| {
+            tokio::spawn(watch_token_failover(
+                probe_bot,
+                backup,
+                dispatcher.shutdown_token(),
+                failover_tx,
+                admin_alerts.clone(),
+            ))
+        });
+        dispatcher.dispatch().await;
+        reload_watcher.abort();
+        if let Some(failover_watcher) = failover_watcher {
+            failover_watcher.abort();
+        }
+        job_worker.abort();
+        alerts_worker.abort();
+        ephemeral_sweeper.abort();
+        group_scheduler.abort();
+        expiry_worker.abort();
+        broadcast_worker.abort();
+        heartbeat.abort();
+        if let Some(reminders_worker) = reminders_worker {
+            reminders_worker.abort();
+        }
+        if let Some(onboarding_worker) = onboarding_worker {
+            onboarding_worker.abort();
+        }
+        if let Some(survey_worker) = survey_worker {
+            survey_worker.abort();
+        }
+        if let Some(weekly_report) = weekly_report {
+            weekly_report.abort();
+        }
+        if let Some(ticket_closer) = ticket_closer {
+            ticket_closer.abort();
+        }
+        if let Some(blocklist_sync) = blocklist_sync {
+            blocklist_sync.abort();
+        }
+        if let Some(proxy_monitor) = proxy_monitor {
+            proxy_monitor.abort();
+        }
+        if let Some(web_server) = web_server {
+            web_server.abort();
+        }
+
+        match reload_rx.try_recv().or_else(|_| failover_rx.try_recv()) {
+            Ok(new_token) => {
+                tracing::info!("Bot token changed, restarting dispatcher");
+                token = new_token;
+                token_rotated = true;
+            }
+            Err(_) => break,
+        }
+    }
 
-    let bot = Bot::new(token);
-    let bot_username = match bot.get_me().await {
-        Ok(me) => me.user.username.clone(),
+    Ok(())
+}
+
+/// По SIGHUP или `/reloadcfg` перечитывает токен бота и, если он изменился и
+/// принят Telegram (`getMe`), останавливает диспетчер, чтобы `main` пересоздал
+/// бота с новым токеном. Недействительный токен не применяется: бот продолжает
+/// работать со старым. Итог `/reloadcfg` сообщается админам.
+async fn watch_token_reload(
+    config: Arc<config::Config>,
+    http_client: reqwest::Client,
+    current_token: String,
+    shutdown: teloxide::dispatching::ShutdownToken,
+    reload_tx: tokio::sync::oneshot::Sender<String>,
+    trigger: Arc<tokio::sync::Notify>,
+    alerts: alerts::AdminAlerts,
+) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(stream) => Some(stream),
         Err(error) => {
-            tracing::warn!(
-                error = %error,
-                "Не удалось получить username бота через getMe"
-            );
+            tracing::warn!(error = %error, "Не удалось подписаться на SIGHUP");
             None
         }
     };
+    loop {
+        let from_command = match hangup.as_mut() {
+            Some(hangup) => tokio::select! {
+                signal = hangup.recv() => {
+                    if signal.is_none() {
+                        return;
+                    }
+                    false
+                }
+                _ = trigger.notified() => true,
+            },
+            None => {
+                trigger.notified().await;
+                true
+            }
+        };
+        let report = |text: &str| {
+            if from_command {
+                alerts.send(text);
+            }
+        };
+        if !config.bot_token_from_file() {
+            tracing::info!(
+                "Token reload requested, bot token is not file-based, nothing to reload"
+            );
+            report("🔑 Токен бота задан не файлом — перечитывать нечего.");
+            continue;
+        }
+        let token = match config.bot_token() {
+            Ok(token) => token,
+            Err(error) => {
+                tracing::error!(error = %error, "Не удалось перечитать токен бота");
+                report(&format!("🔑 Не удалось перечитать токен бота: {}", error));
+                continue;
+            }
+        };
+        if token == current_token {
+            tracing::info!("Token reload requested, bot token unchanged");
+            report("🔑 Токен бота не изменился.");
+            continue;
+        }
+        let candidate =
+            match bot::client::build_bot(&config.telegram, http_client.clone(), token.clone()) {
+                Ok(candidate) => candidate,
+                Err(error) => {
+                    tracing::error!(error = %error, "Не удалось создать бота с новым токеном");
+                    report(&format!(
+                        "🔑 Не удалось создать бота с новым токеном: {}",
+                        error
+                    ));
+                    continue;
+                }
+            };
+        if let Err(error) = candidate.get_me().await {
+            tracing::error!(error = %error, "Новый токен бота не принят Telegram, остаюсь на старом");
+            alerts.send(format!(
+                "🔑 Новый токен бота не принят Telegram ({}). Бот продолжает работать со старым токеном.",
+                error
+            ));
+            continue;
+        }
+        let _ = reload_tx.send(token);
+        match shutdown.shutdown() {
+            Ok(wait) => wait.await,
+            Err(error) => {
+                tracing::warn!(error = %error, "Диспетчер не запущен, перезапуск отложен")
+            }
+        }
+        return;
+    }
+}
 
-    let state = bot::handlers::BotState {
-        config,
-        db,
-        telemt_cfg,
-        service,
-        bot_username,
-        awaiting_invite_users: Arc::new(Mutex::new(std::collections::HashSet::new())),
-    };
-    tracing::info!("Dispatcher initialized, bot is ready");
+/// Сколько проверок подряд основной токен должен быть отвергнут, прежде чем
+/// бот переключится на резервный.
+const FAILOVER_AFTER_CHECKS: u32 = 3;
+const FAILOVER_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 
-    Dispatcher::builder(bot, bot::handlers::schema())
-        .dependencies(dptree::deps![state])
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+fn is_invalid_token(error: &teloxide::RequestError) -> bool {
+    matches!(
+        error,
+        teloxide::RequestError::Api(teloxide::ApiError::InvalidToken)
+    )
+}
 
-    Ok(())
+/// Раз в минуту проверяет текущий токен через `getMe`. Если Telegram несколько
+/// раз подряд отвечает, что токен недействителен (отозван или бот заблокирован),
+/// останавливает диспетчер, чтобы `main` переключился на резервный токен.
+/// Сетевые ошибки не считаются: от них резервный токен не поможет.
+async fn watch_token_failover(
+    bot: Bot,
+    backup_token: String,
+    shutdown: teloxide::dispatching::ShutdownToken,
+    failover_tx: tokio::sync::oneshot::Sender<String>,
+    alerts: alerts::AdminAlerts,
+) {
+    let mut failures = 0;
+    let mut interval = tokio::time::interval(FAILOVER_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match bot.get_me().await {
+            Err(error) if is_invalid_token(&error) => {
+                failures += 1;
+                tracing::warn!(failures = failures, "Telegram отверг токен бота");
+            }
+            _ => failures = 0,
+        }
+        if failures < FAILOVER_AFTER_CHECKS {
+            continue;
+        }
+        tracing::error!("Bot token is invalid, failing over to backup token");
+        alerts.send("⚠️ Основной токен бота недействителен — переключаюсь на резервный токен.");
+        let _ = failover_tx.send(backup_token);
+        match shutdown.shutdown() {
+            Ok(wait) => wait.await,
+            Err(error) => {
+                tracing::warn!(error = %error, "Диспетчер не запущен, переключение отложено")
+            }
+        }
+        return;
+    }
 }
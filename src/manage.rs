@@ -0,0 +1,236 @@
+//! Управление без Telegram: подкоманды `user`, `token` и `sync` работают с теми же
+//! БД и конфигом telemt, что и бот, и пишут действия в журнал аудита как системные.
+//! Изменения конфига идут через собственный `ConfigWriter` процесса с немедленным
+//! рестартом (без окна `debounce_secs`) и той же защитой от частых рестартов, что
+//! у бота. Подкоманда дожидается рестарта, применившего изменения, в том числе
+//! отложенного защитой: иначе процесс завершился бы раньше него.
+
+use crate::alerts;
+use crate::audit::AuditLog;
+use crate::cli::ManageCommand;
+use crate::config::Config;
+use crate::db::{Db, NewInviteToken};
+use crate::error::AppError;
+use crate::link::{build_proxy_link, generate_user_secret};
+use crate::service::ServiceController;
+use crate::telemt_cfg::{TelemtConfig, UserMutation};
+use crate::telemt_writer::ConfigWriter;
+use anyhow::anyhow;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Размер пакета при выводе списка пользователей.
+const LIST_BATCH: i64 = 500;
+
+struct Manager {
+    config: Arc<Config>,
+    db: Arc<Db>,
+    telemt_cfg: Arc<TelemtConfig>,
+    cfg_writer: ConfigWriter,
+    audit: AuditLog,
+}
+
+pub async fn run(
+    command: ManageCommand,
+    config: Arc<Config>,
+    db: Arc<Db>,
+    dry_run: bool,
+) -> Result<(), AppError> {
+    let telemt_cfg = Arc::new(
+        TelemtConfig::new(&config.telemt_config_path)
+            .with_dry_run(dry_run)
+            .with_backups(config.telemt_backup_dir(), config.telemt_backups.keep),
+    );
+    // Уведомления писателя конфига некому доставить: ошибки рестарта выводятся в лог.
+    let (admin_alerts, _admin_alerts_rx) = alerts::channel();
+    let service = ServiceController::new(&config.service_name)
+        .with_timeout(Duration::from_secs(config.systemctl_timeout_secs))
+        .with_config_check(config.config_check_command())
+        .with_restart_limit(
+            config.restart.max_restarts,
+            Duration::from_secs(config.restart.window_minutes * 60),
+            admin_alerts.clone(),
+        );
+    let cfg_writer = ConfigWriter::spawn(telemt_cfg.clone(), service, admin_alerts, Duration::ZERO);
+    let manager = Manager {
+        audit: AuditLog::new(db.clone(), config.audit.clone()),
+        config,
+        db,
+        telemt_cfg,
+        cfg_writer,
+    };
+    match command {
+        ManageCommand::UserList => manager.user_list().await,
+        ManageCommand::UserCreate(target) => manager.user_create(&target).await,
+        ManageCommand::UserDelete(tg_user_id) => manager.user_delete(tg_user_id).await,
+        ManageCommand::TokenCreate {
+            days,
+            max_uses,
+            auto_approve,
+        } => manager.token_create(days, max_uses, auto_approve).await,
+        ManageCommand::Sync { fix } => manager.sync(fix).await,
+    }
+}
+
+impl Manager {
+    async fn user_list(&self) -> Result<(), AppError> {
+        let mut after = i64::MIN;
+        loop {
+            let users = self.db.list_users_after(after, LIST_BATCH).await?;
+            for user in &users {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    user.tg_user_id,
+                    user.status,
+                    user.telemt_username.as_deref().unwrap_or("-"),
+                    user.tg_username
+                        .as_deref()
+                        .map(|username| format!("@{}", username))
+                        .unwrap_or_else(|| "-".to_string()),
+                    user.tg_display_name.as_deref().unwrap_or("-"),
+                );
+            }
+            match users.last() {
+                Some(last) if users.len() as i64 == LIST_BATCH => after = last.tg_user_id,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    async fn user_create(&self, target: &str) -> Result<(), AppError> {
+        let tg_user_id = match target.parse::<i64>() {
+            Ok(tg_user_id) if tg_user_id > 0 => tg_user_id,
+            Ok(_) => return Err(anyhow!("tg_user_id должен быть положительным").into()),
+            Err(_) => self
+                .db
+                .find_tg_user_id_by_username(target)
+                .await?
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Пользователь {} не найден в базе: он должен хотя бы раз написать боту",
+                        target
+                    )
+                })?,
+        };
+        let telemt_user = format!("tg_{}", tg_user_id);
+        let secret = generate_user_secret();
+        self.cfg_writer
+            .apply_and_wait(vec![UserMutation::Upsert {
+                username: telemt_user.clone(),
+                secret: secret.clone(),
+            }])
+            .await?;
+        self.db
+            .set_approved(tg_user_id, None, None, &telemt_user, &secret)
+            .await?;
+        self.audit
+            .record_system("create", &format!("tg_user:{}", tg_user_id), "cli")
+            .await;
+        tracing::info!(tg_user_id = tg_user_id, "User created from CLI");
+
+        let params = self.telemt_cfg.read_link_params().await?;
+        let link = build_proxy_link(&params, &secret)?;
+        println!("{}\t{}", telemt_user, link);
+        Ok(())
+    }
+
+    async fn user_delete(&self, tg_user_id: i64) -> Result<(), AppError> {
+        // Импортированные пользователи могут жить в конфиге под прежним именем.
+        let telemt_user = match self.db.get_approved(tg_user_id).await? {
+            Some((telemt_user, _)) => telemt_user,
+            None => format!("tg_{}", tg_user_id),
+        };
+        let removed_from_cfg = self
+            .cfg_writer
+            .apply_and_wait(vec![UserMutation::Remove {
+                username: telemt_user.clone(),
+            }])
+            .await?
+            .first()
+            .copied()
+            .unwrap_or(false);
+        let removed_from_db = self.db.deactivate_user(tg_user_id).await?;
+        if !removed_from_cfg && !removed_from_db {
+            return Err(anyhow!("Пользователь {} не найден", telemt_user).into());
+        }
+        self.audit
+            .record_system("delete", &format!("tg_user:{}", tg_user_id), "cli")
+            .await;
+        tracing::info!(tg_user_id = tg_user_id, "User deleted from CLI");
+        println!("Пользователь {} удалён", telemt_user);
+        Ok(())
+    }
+
+    async fn token_create(
+        &self,
+        days: Option<i64>,
+        max_uses: Option<i64>,
+        auto_approve: bool,
+    ) -> Result<(), AppError> {
+        let security = &self.config.security;
+        let days = days.unwrap_or(security.default_token_days);
+        if days < 1 || days > security.max_token_days {
+            return Err(anyhow!(
+                "Срок действия токена — от 1 до {} дней",
+                security.max_token_days
+            )
+            .into());
+        }
+        if auto_approve && !security.allow_auto_approve_tokens {
+            return Err(anyhow!("Автоподтверждение токенов запрещено в конфигурации").into());
+        }
+        if max_uses.is_some_and(|max_uses| max_uses < 1) {
+            return Err(anyhow!("Лимит использований должен быть не меньше 1").into());
+        }
+        let token = self
+            .db
+            .create_invite_token(NewInviteToken {
+                days,
+                auto_approve,
+                max_usage: max_uses,
+                created_by: None,
+                for_tg_user_id: None,
+                plans: &[],
+                label: None,
+                valid_from: None,
+                group_name: None,
+                referrer_tg_user_id: None,
+            })
+            .await?;
+        self.audit
+            .record_system(
+                "token_create",
+                &format!("token:{}", token.token),
+                &format!("days={} auto={} cli", days, auto_approve),
+            )
+            .await;
+        println!("{}", token.token);
+        if let Some(url) = crate::web::invite_page_url(&self.config.web, &token.token) {
+            println!("{}", url);
+        }
+        Ok(())
+    }
+
+    async fn sync(&self, fix: bool) -> Result<(), AppError> {
+        let drift = crate::bot::handlers::detect_drift(&self.db, &self.telemt_cfg).await?;
+        println!("{}", crate::bot::handlers::render_drift(&drift));
+        if !fix || drift.is_empty() {
+            return Ok(());
+        }
+        let mut mutations = drift.readd_mutations();
+        mutations.extend(drift.remove_mutations());
+        let count = mutations.len();
+        // Одна запись и один рестарт на все исправления.
+        self.cfg_writer.apply_and_wait(mutations).await?;
+        self.audit
+            .record_system(
+                "sync_fix",
+                "telemt:access.users",
+                &format!("записей: {}, cli", count),
+            )
+            .await;
+        tracing::info!(count = count, "Config drift fixed from CLI");
+        println!("\nИсправлено записей: {}.", count);
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Метрики Prometheus. По умолчанию бот не открывает HTTP-порт: метрики
+//! периодически отправляются в Pushgateway (`[metrics] pushgateway_url`), что
+//! удобно за NAT и без ingress. Для прямого сбора можно включить `[metrics] listen`
+//! — тогда бот отдаёт те же метрики на `GET /metrics`.
+
+use crate::config::MetricsConfig;
+use crate::db::{Db, DbError};
+use crate::service::ServiceController;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+/// Метка серии счётчика: имя и значение.
+type Label = Option<(&'static str, &'static str)>;
+
+/// Счётчики событий с момента запуска: (метрика, метка) → значение.
+static COUNTERS: Mutex<BTreeMap<(&'static str, Label), u64>> = Mutex::new(BTreeMap::new());
+
+/// Счётчики в порядке вывода: имя и описание.
+const COUNTER_METRICS: &[(&str, &str)] = &[
+    (
+        "telemt_admin_approvals_total",
+        "Users approved since start.",
+    ),
+    (
+        "telemt_admin_rejections_total",
+        "Registration requests rejected since start.",
+    ),
+    (
+        "telemt_admin_deletions_total",
+        "Users deleted since start, by reason.",
+    ),
+    (
+        "telemt_admin_token_consumptions_total",
+        "Invite tokens consumed since start.",
+    ),
+    (
+        "telemt_admin_restarts_total",
+        "telemt restarts since start, by result.",
+    ),
+    (
+        "telemt_admin_handler_errors_total",
+        "Failed bot handlers since start, by error class.",
+    ),
+    (
+        "telemt_admin_admin_delivery_failures_total",
+        "Admin notifications not delivered since start.",
+    ),
+];
+
+fn increment(name: &'static str, label: Label) {
+    let mut counters = COUNTERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *counters.entry((name, label)).or_default() += 1;
+}
+
+pub fn record_approval() {
+    increment("telemt_admin_approvals_total", None);
+}
+
+pub fn record_rejection() {
+    increment("telemt_admin_rejections_total", None);
+}
+
+/// `reason`: `admin`, `cleanup`, `purge`, `expiry`, `blocklist`.
+pub fn record_deletion(reason: &'static str) {
+    increment("telemt_admin_deletions_total", Some(("reason", reason)));
+}
+
+pub fn record_token_consumed() {
+    increment("telemt_admin_token_consumptions_total", None);
+}
+
+/// `result`: `ok`, `failed`, `denied`.
+pub fn record_restart(result: &'static str) {
+    increment("telemt_admin_restarts_total", Some(("result", result)));
+}
+
+pub fn record_handler_error(class: &'static str) {
+    increment("telemt_admin_handler_errors_total", Some(("class", class)));
+}
+
+pub fn record_admin_delivery_failure() {
+    increment("telemt_admin_admin_delivery_failures_total", None);
+}
+
+fn render_counters(out: &mut String) {
+    let counters = COUNTERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (name, help) in COUNTER_METRICS {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let mut series = counters.iter().filter(|(key, _)| key.0 == *name).peekable();
+        if series.peek().is_none() {
+            let _ = writeln!(out, "{} 0", name);
+        }
+        for ((_, label), value) in series {
+            match label {
+                Some((key, label_value)) => {
+                    let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, key, label_value, value);
+                }
+                None => {
+                    let _ = writeln!(out, "{} {}", name, value);
+                }
+            }
+        }
+    }
+}
+
+/// HTTP-эндпоинт `GET /metrics` (`[metrics] listen`) для прямого сбора Prometheus.
+pub fn spawn_server(
+    db: Arc<Db>,
+    service: ServiceController,
+    config: &MetricsConfig,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let listen = config.listen.clone()?;
+    let allow_cidrs = config.allow_cidrs.clone();
+    Some(tokio::spawn(async move {
+        let listener = match TcpListener::bind(&listen).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                tracing::warn!(listen = %listen, error = %error, "Не удалось открыть порт метрик");
+                return;
+            }
+        };
+        tracing::info!(listen = %listen, "Metrics endpoint started");
+        loop {
+            let (mut stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    tracing::warn!(error = %error, "Не удалось принять соединение к метрикам");
+                    continue;
+                }
+            };
+            if !crate::web::peer_allowed(&allow_cidrs, peer) {
+                tracing::debug!(peer = %peer, "Metrics connection rejected by allow_cidrs");
+                continue;
+            }
+            let db = db.clone();
+            let service = service.clone();
+            tokio::spawn(async move {
+                if let Err(error) = serve_metrics(&mut stream, &db, &service).await {
+                    tracing::debug!(peer = %peer, error = %error, "Metrics request failed");
+                }
+            });
+        }
+    }))
+}
+
+async fn serve_metrics(
+    stream: &mut tokio::net::TcpStream,
+    db: &Db,
+    service: &ServiceController,
+) -> std::io::Result<()> {
+    let request = crate::web::read_request(stream).await?;
+    let (status, body) = match request {
+        Some(request) if request.method == "GET" && request.path == "/metrics" => {
+            match render(db, service).await {
+                Ok(body) => ("200 OK", body),
+                Err(error) => {
+                    tracing::warn!(error = %error, "Не удалось собрать метрики");
+                    ("500 Internal Server Error", String::new())
+                }
+            }
+        }
+        Some(_) => ("404 Not Found", String::new()),
+        None => ("400 Bad Request", String::new()),
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+pub fn spawn_pusher(
+    db: Arc<Db>,
+    service: ServiceController,
+    config: MetricsConfig,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let Some(base_url) = config.pushgateway_url.clone() else {
+        tracing::info!("Metrics push disabled");
+        return None;
+    };
+    let url = push_url(&base_url, &config);
+    let interval = Duration::from_secs(config.push_interval_secs.max(1));
+    Some(tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            if let Err(error) = push_once(&client, &url, &db, &service).await {
+                tracing::warn!(url = %url, error = %error, "Не удалось отправить метрики в Pushgateway");
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }))
+}
+
+fn push_url(base_url: &str, config: &MetricsConfig) -> String {
+    let mut url = format!(
+        "{}/metrics/job/{}",
+        base_url.trim_end_matches('/'),
+        urlencoding::encode(&config.job)
+    );
+    if let Some(instance) = &config.instance {
+        url.push_str(&format!("/instance/{}", urlencoding::encode(instance)));
+    }
+    url
+}
+
+async fn push_once(
+    client: &reqwest::Client,
+    url: &str,
+    db: &Db,
+    service: &ServiceController,
+) -> Result<(), String> {
+    let body = render(db, service).await.map_err(|e| e.to_string())?;
+    // PUT заменяет все метрики группы, чтобы исчезнувшие серии не залипали.
+    let response = client
+        .put(url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("{} вернул {}", url, status));
+    }
+    tracing::debug!(url = %url, "Metrics pushed");
+    Ok(())
+}
+
+/// Текстовый формат экспозиции Prometheus.
+pub async fn render(db: &Db, service: &ServiceController) -> Result<String, DbError> {
+    let stats = db.admin_stats().await?;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP telemt_admin_requests Registration records by status."
+    );
+    let _ = writeln!(out, "# TYPE telemt_admin_requests gauge");
+    for (status, value) in [
+        ("pending", stats.pending),
+        ("approved", stats.approved),
+        ("rejected", stats.rejected),
+        ("deleted", stats.deleted),
+    ] {
+        let _ = writeln!(
+            out,
+            "telemt_admin_requests{{status=\"{}\"}} {}",
+            status, value
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP telemt_admin_telemt_up Whether telemt.service is active."
+    );
+    let _ = writeln!(out, "# TYPE telemt_admin_telemt_up gauge");
+    let _ = writeln!(
+        out,
+        "telemt_admin_telemt_up {}",
+        u8::from(service.is_active().await)
+    );
+    render_counters(&mut out);
+    Ok(out)
+}
@@ -0,0 +1,33 @@
+//! Декларативная предварительная выдача доступа из секции `[[provision]]`:
+//! перечисленные пользователи одобряются автоматически при первом /start,
+//! без invite-токена и без ручного одобрения, с заданными группой и сроком доступа.
+
+use crate::config::ProvisionEntry;
+use crate::db::{Db, DbError};
+
+/// Переносит список из конфига в БД при старте. Записи, уже получившие доступ,
+/// повторно не выдаются: удалённый админом пользователь не вернётся сам.
+pub async fn sync(db: &Db, entries: &[ProvisionEntry]) -> Result<(), DbError> {
+    let mut added = 0;
+    for entry in entries {
+        if db
+            .add_provisioned_user(
+                entry.tg_user_id,
+                entry.note.as_deref(),
+                entry.group.as_deref(),
+                entry.expires_in_days.filter(|days| *days > 0),
+            )
+            .await?
+        {
+            added += 1;
+        }
+    }
+    if !entries.is_empty() {
+        tracing::info!(
+            total = entries.len(),
+            added = added,
+            "Provision list synchronized"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! Периодическая очистка: старые отклонённые/удалённые заявки и истёкшие токены
+//! переносятся в архивные таблицы, чтобы основные таблицы не росли бесконечно.
+
+use crate::config::{RetentionConfig, RetentionMode};
+use crate::db::Db;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+pub fn spawn(db: Arc<Db>, config: RetentionConfig) -> Option<tokio::task::JoinHandle<()>> {
+    if !config.enabled {
+        tracing::info!("Retention policy disabled");
+        return None;
+    }
+    let interval = Duration::from_secs(config.interval_hours.max(1) * 60 * 60);
+    Some(tokio::spawn(async move {
+        loop {
+            run_once(&db, &config).await;
+            tokio::time::sleep(interval).await;
+        }
+    }))
+}
+
+async fn run_once(db: &Db, config: &RetentionConfig) {
+    let now = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(error) => {
+            tracing::warn!(error = %error, "Retention skipped: system clock before UNIX epoch");
+            return;
+        }
+    };
+    let requests_before = now - config.requests_days.max(0) * SECONDS_PER_DAY;
+    let tokens_before = now - config.tokens_days.max(0) * SECONDS_PER_DAY;
+    match db
+        .apply_retention(
+            requests_before,
+            tokens_before,
+            config.mode == RetentionMode::Archive,
+        )
+        .await
+    {
+        Ok(report) if report.requests > 0 || report.tokens > 0 => tracing::info!(
+            requests = report.requests,
+            tokens = report.tokens,
+            mode = %config.mode,
+            "Old records moved to archive"
+        ),
+        Ok(_) => tracing::debug!("Retention: nothing to archive"),
+        Err(error) => tracing::warn!(error = %error, "Retention pass failed"),
+    }
+}
@@ -0,0 +1,222 @@
+//! Внешние источники чувствительных значений (HashiCorp Vault, SOPS).
+//!
+//! Секреты загружаются один раз при старте и дальше доступны через
+//! [`crate::config::Config::secret`]. Имена ключей совпадают с параметрами
+//! конфига, которые они заменяют (например, `bot_token`).
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Ошибка получения секретов из внешнего провайдера.
+#[derive(Debug, Error)]
+pub enum SecretsError {
+    #[error("Vault: {0}")]
+    Vault(String),
+    #[error("SOPS: {0}")]
+    Sops(String),
+    #[error("Не задан параметр [secrets]: {0}")]
+    Missing(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsProviderKind {
+    #[default]
+    None,
+    Vault,
+    Sops,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SecretsConfig {
+    /// Провайдер секретов: `none`, `vault` или `sops`
+    #[serde(default)]
+    pub provider: SecretsProviderKind,
+    #[serde(default)]
+    pub vault: VaultConfig,
+    #[serde(default)]
+    pub sops: SopsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultConfig {
+    /// Адрес Vault (или через VAULT_ADDR)
+    pub addr: Option<String>,
+    /// Файл с токеном Vault (иначе VAULT_TOKEN)
+    pub token_file: Option<PathBuf>,
+    /// Точка монтирования KV v2
+    #[serde(default = "default_vault_mount")]
+    pub mount: String,
+    /// Путь секрета внутри mount
+    #[serde(default = "default_vault_path")]
+    pub path: String,
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        Self {
+            addr: None,
+            token_file: None,
+            mount: default_vault_mount(),
+            path: default_vault_path(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SopsConfig {
+    /// Зашифрованный SOPS-файл (yaml/json/env)
+    pub file: Option<PathBuf>,
+    /// Путь к бинарнику sops
+    #[serde(default = "default_sops_binary")]
+    pub binary: String,
+}
+
+impl Default for SopsConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            binary: default_sops_binary(),
+        }
+    }
+}
+
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_vault_path() -> String {
+    "telemt-admin".to_string()
+}
+
+fn default_sops_binary() -> String {
+    "sops".to_string()
+}
+
+/// Загруженные значения секретов.
+#[derive(Clone, Default)]
+pub struct SecretValues(HashMap<String, String>);
+
+impl SecretValues {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+}
+
+impl std::fmt::Debug for SecretValues {
+    // Значения никогда не попадают в логи.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Загружает секреты из настроенного провайдера.
+pub async fn load(config: &SecretsConfig) -> Result<SecretValues, SecretsError> {
+    let values = match config.provider {
+        SecretsProviderKind::None => return Ok(SecretValues::default()),
+        SecretsProviderKind::Vault => load_vault(&config.vault).await?,
+        SecretsProviderKind::Sops => load_sops(&config.sops).await?,
+    };
+    let mut keys: Vec<&str> = values.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    tracing::info!(
+        provider = ?config.provider,
+        keys = ?keys,
+        "Secrets loaded from external provider"
+    );
+    Ok(SecretValues(values))
+}
+
+async fn load_vault(config: &VaultConfig) -> Result<HashMap<String, String>, SecretsError> {
+    let addr = config
+        .addr
+        .clone()
+        .or_else(|| std::env::var("VAULT_ADDR").ok())
+        .ok_or(SecretsError::Missing("vault.addr или VAULT_ADDR"))?;
+    let token = match &config.token_file {
+        Some(path) => tokio::fs::read_to_string(path)
+            .await
+            .map(|content| content.trim().to_string())
+            .map_err(|e| {
+                SecretsError::Vault(format!("не удалось прочитать {}: {}", path.display(), e))
+            })?,
+        None => std::env::var("VAULT_TOKEN")
+            .map_err(|_| SecretsError::Missing("vault.token_file или VAULT_TOKEN"))?,
+    };
+
+    // KV v2: GET /v1/<mount>/data/<path> → { "data": { "data": { ... } } }
+    let url = format!(
+        "{}/v1/{}/data/{}",
+        addr.trim_end_matches('/'),
+        config.mount.trim_matches('/'),
+        config.path.trim_matches('/')
+    );
+    tracing::debug!(url = %url, "Fetching secrets from Vault");
+    let response = reqwest::Client::new()
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .map_err(|e| SecretsError::Vault(e.to_string()))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(SecretsError::Vault(format!("{} вернул {}", url, status)));
+    }
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| SecretsError::Vault(format!("некорректный ответ: {}", e)))?;
+    let data = body
+        .pointer("/data/data")
+        .ok_or_else(|| SecretsError::Vault("в ответе нет data.data (ожидается KV v2)".into()))?;
+    flatten_strings(data).map_err(SecretsError::Vault)
+}
+
+async fn load_sops(config: &SopsConfig) -> Result<HashMap<String, String>, SecretsError> {
+    let file = config
+        .file
+        .as_ref()
+        .ok_or(SecretsError::Missing("sops.file"))?;
+    tracing::debug!(file = %file.display(), "Decrypting secrets with sops");
+    let output = tokio::process::Command::new(&config.binary)
+        .arg("--decrypt")
+        .arg("--output-type")
+        .arg("json")
+        .arg(file)
+        .output()
+        .await
+        .map_err(|e| {
+            SecretsError::Sops(format!("не удалось запустить {}: {}", config.binary, e))
+        })?;
+    if !output.status.success() {
+        return Err(SecretsError::Sops(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| SecretsError::Sops(format!("некорректный вывод sops: {}", e)))?;
+    flatten_strings(&value).map_err(SecretsError::Sops)
+}
+
+/// Берёт строковые и числовые значения верхнего уровня JSON-объекта.
+fn flatten_strings(value: &serde_json::Value) -> Result<HashMap<String, String>, String> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| "ожидается объект ключ-значение".to_string())?;
+    let mut values = HashMap::new();
+    for (key, value) in object {
+        let text = match value {
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Number(number) => number.to_string(),
+            serde_json::Value::Bool(flag) => flag.to_string(),
+            _ => {
+                tracing::warn!(key = %key, "Secret value is not a scalar, skipped");
+                continue;
+            }
+        };
+        values.insert(key.clone(), text);
+    }
+    Ok(values)
+}
@@ -1,41 +1,179 @@
-//! Управление systemd-сервисом telemt.
+//! Управление systemd-сервисом telemt. systemctl и journalctl запускаются через
+//! `tokio::process` с таймаутом: зависший вызов не блокирует рантайм, а по
+//! таймауту или при отмене future процесс убивается.
 
-use std::process::Command;
+use crate::alerts::AdminAlerts;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::process::Command;
 
-#[derive(Debug, Clone)]
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
 pub struct ServiceController {
     service_name: String,
+    timeout: Duration,
+    limiter: Option<Arc<RestartLimiter>>,
+    config_check: Arc<Vec<String>>,
+}
+
+/// Защита от частых рестартов: не больше `max_restarts` за `window`.
+struct RestartLimiter {
+    max_restarts: usize,
+    window: Duration,
+    alerts: AdminAlerts,
+    history: Mutex<RestartHistory>,
+}
+
+#[derive(Default)]
+struct RestartHistory {
+    /// Выполненные рестарты в пределах окна: время и причина.
+    recent: VecDeque<(Instant, String)>,
+    /// Причины отклонённых рестартов с момента последнего выполненного.
+    suppressed: Vec<String>,
+}
+
+/// Рестарт отклонён ограничителем частоты.
+#[derive(Debug, Clone, Error)]
+#[error(
+    "Слишком частые рестарты: {count} за {window_minutes} мин. Следующий возможен через {} с",
+    .retry_after.as_secs()
+)]
+pub struct RestartDenied {
+    pub count: usize,
+    pub window_minutes: u64,
+    pub retry_after: Duration,
+}
+
+/// Ошибка рестарта через ограничитель.
+#[derive(Debug, Clone, Error)]
+pub enum RestartError {
+    #[error(transparent)]
+    Denied(#[from] RestartDenied),
+    #[error(transparent)]
+    Failed(#[from] ServiceError),
 }
 
 #[derive(Debug)]
 pub struct ServiceResult {
     pub success: bool,
+    /// Код завершения systemctl; `None`, если процесс не запустился, убит сигналом
+    /// или прерван по таймауту.
+    pub exit_code: Option<i32>,
+    /// systemctl не уложился в таймаут и был остановлен.
+    pub timed_out: bool,
     pub stdout: String,
     pub stderr: String,
 }
 
+impl ServiceResult {
+    /// Краткий итог: `OK`, `Ошибка (код N)`; для отчётов админу и журнала аудита.
+    pub fn status_label(&self) -> String {
+        match (self.success, self.exit_code) {
+            (true, _) => "OK".to_string(),
+            (false, Some(code)) => format!("Ошибка (код {})", code),
+            (false, None) if self.timed_out => "Ошибка (таймаут)".to_string(),
+            (false, None) => "Ошибка".to_string(),
+        }
+    }
+}
+
+/// systemctl завершился неуспешно там, где это считается ошибкой.
+#[derive(Debug, Clone, Error)]
+#[error("systemctl {action} {service} завершился с ошибкой: {stderr}")]
+pub struct ServiceError {
+    pub action: &'static str,
+    pub service: String,
+    pub stderr: String,
+}
+
 impl ServiceController {
     pub fn new(service_name: impl Into<String>) -> Self {
         Self {
             service_name: service_name.into(),
+            timeout: DEFAULT_TIMEOUT,
+            limiter: None,
+            config_check: Arc::new(Vec::new()),
         }
     }
 
-    fn run_systemctl(&self, action: &str) -> ServiceResult {
+    /// Команда проверки конфига telemt перед рестартом после изменений (`[restart] check_command`).
+    pub fn with_config_check(mut self, command: Vec<String>) -> Self {
+        self.config_check = Arc::new(command);
+        self
+    }
+
+    /// Сколько ждать завершения одного вызова systemctl.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout.max(Duration::from_secs(1));
+        self
+    }
+
+    /// Включает защиту от частых рестартов; о срабатываниях сообщается админам.
+    pub fn with_restart_limit(
+        mut self,
+        max_restarts: usize,
+        window: Duration,
+        alerts: AdminAlerts,
+    ) -> Self {
+        self.limiter = Some(Arc::new(RestartLimiter {
+            max_restarts: max_restarts.max(1),
+            window,
+            alerts,
+            history: Mutex::new(RestartHistory::default()),
+        }));
+        self
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    async fn run_systemctl(&self, action: &str) -> ServiceResult {
+        self.run_command("systemctl", action, &[action, self.service_name.as_str()])
+            .await
+    }
+
+    /// Последние `lines` строк журнала сервиса (`journalctl -u`).
+    pub async fn logs(&self, lines: usize) -> ServiceResult {
+        let lines = lines.to_string();
+        let args = [
+            "-u",
+            self.service_name.as_str(),
+            "-n",
+            lines.as_str(),
+            "--no-pager",
+            "-o",
+            "short-iso",
+        ];
+        self.run_command("journalctl", "logs", &args).await
+    }
+
+    /// Запускает `[restart] check_command`; `None`, если проверка не настроена.
+    pub async fn check_config(&self) -> Option<ServiceResult> {
+        let (program, args) = self.config_check.split_first()?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        Some(self.run_command(program, "check-config", &args).await)
+    }
+
+    async fn run_command(&self, program: &str, action: &str, args: &[&str]) -> ServiceResult {
         tracing::info!(
             action = action,
             service = %self.service_name,
-            "Running systemctl command"
+            "Running {} command",
+            program
         );
-        let output = Command::new("systemctl")
-            .arg(action)
-            .arg(&self.service_name)
-            .output();
+        // kill_on_drop: при таймауте или отмене вызывающей задачи процесс не остаётся висеть.
+        let output = Command::new(program).args(args).kill_on_drop(true).output();
 
-        match output {
-            Ok(o) => {
+        match tokio::time::timeout(self.timeout, output).await {
+            Ok(Ok(o)) => {
                 let result = ServiceResult {
                     success: o.status.success(),
+                    exit_code: o.status.code(),
+                    timed_out: false,
                     stdout: String::from_utf8_lossy(&o.stdout).trim().to_string(),
                     stderr: String::from_utf8_lossy(&o.stderr).trim().to_string(),
                 };
@@ -43,57 +181,154 @@ impl ServiceController {
                     tracing::info!(
                         action = action,
                         service = %self.service_name,
-                        "systemctl finished successfully"
+                        "{} finished successfully",
+                        program
                     );
                 } else {
                     tracing::warn!(
                         action = action,
                         service = %self.service_name,
+                        exit_code = ?result.exit_code,
                         stderr = %result.stderr,
-                        "systemctl returned non-zero status"
+                        "{} returned non-zero status",
+                        program
                     );
                 }
                 result
             }
-            Err(e) => ServiceResult {
-                success: false,
-                stdout: String::new(),
-                stderr: {
-                    tracing::error!(
-                        action = action,
-                        service = %self.service_name,
-                        error = %e,
-                        "Failed to execute systemctl"
-                    );
-                    format!("Ошибка запуска systemctl: {}", e)
-                },
-            },
+            Ok(Err(e)) => {
+                tracing::error!(
+                    action = action,
+                    service = %self.service_name,
+                    error = %e,
+                    "Failed to execute {}",
+                    program
+                );
+                ServiceResult {
+                    success: false,
+                    exit_code: None,
+                    timed_out: false,
+                    stdout: String::new(),
+                    stderr: format!("Ошибка запуска {}: {}", program, e),
+                }
+            }
+            Err(_) => {
+                tracing::error!(
+                    action = action,
+                    service = %self.service_name,
+                    timeout_secs = self.timeout.as_secs(),
+                    "{} timed out",
+                    program
+                );
+                ServiceResult {
+                    success: false,
+                    exit_code: None,
+                    timed_out: true,
+                    stdout: String::new(),
+                    stderr: format!(
+                        "{} {} не завершился за {} с и был остановлен",
+                        program,
+                        action,
+                        self.timeout.as_secs()
+                    ),
+                }
+            }
         }
     }
 
-    pub fn start(&self) -> ServiceResult {
-        self.run_systemctl("start")
+    pub async fn start(&self) -> ServiceResult {
+        self.run_systemctl("start").await
     }
 
-    pub fn stop(&self) -> ServiceResult {
-        self.run_systemctl("stop")
+    pub async fn stop(&self) -> ServiceResult {
+        self.run_systemctl("stop").await
     }
 
-    pub fn restart(&self) -> ServiceResult {
-        self.run_systemctl("restart")
+    /// Рестарт через ограничитель частоты. `reason` попадает в уведомления
+    /// админам; `force` пропускает проверку лимита (рестарт всё равно учитывается).
+    pub async fn restart(&self, reason: &str, force: bool) -> Result<ServiceResult, RestartDenied> {
+        if let Some(limiter) = &self.limiter
+            && let Err(denied) = limiter.acquire(&self.service_name, reason, force)
+        {
+            crate::metrics::record_restart("denied");
+            return Err(denied);
+        }
+        let result = self.run_systemctl("restart").await;
+        crate::metrics::record_restart(if result.success { "ok" } else { "failed" });
+        Ok(result)
     }
 
-    pub fn reload(&self) -> ServiceResult {
-        self.run_systemctl("reload")
+    /// Рестарт, неуспешный результат которого превращается в [`ServiceError`].
+    pub async fn restart_checked(
+        &self,
+        reason: &str,
+        force: bool,
+    ) -> Result<ServiceResult, RestartError> {
+        let result = self.restart(reason, force).await?;
+        if result.success {
+            Ok(result)
+        } else {
+            Err(ServiceError {
+                action: "restart",
+                service: self.service_name.clone(),
+                stderr: result.stderr,
+            }
+            .into())
+        }
+    }
+
+    pub async fn reload(&self) -> ServiceResult {
+        self.run_systemctl("reload").await
     }
 
-    pub fn status(&self) -> ServiceResult {
-        self.run_systemctl("status")
+    pub async fn status(&self) -> ServiceResult {
+        self.run_systemctl("status").await
+    }
+
+    /// `systemctl is-active`: сервис запущен и работает.
+    pub async fn is_active(&self) -> bool {
+        self.run_systemctl("is-active").await.success
+    }
+
+    pub async fn enable(&self) -> ServiceResult {
+        self.run_systemctl("enable").await
+    }
+
+    pub async fn disable(&self) -> ServiceResult {
+        self.run_systemctl("disable").await
+    }
+
+    /// Состояние автозапуска (`systemctl is-enabled`): `enabled`, `disabled`,
+    /// `static`, `masked` и т.д. `None`, если systemctl ничего не вернул.
+    pub async fn enabled_state(&self) -> Option<String> {
+        let result = self.run_systemctl("is-enabled").await;
+        let state = result
+            .stdout
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        if state.is_empty() { None } else { Some(state) }
+    }
+
+    /// Строка «Автозапуск при загрузке: да/нет» для вывода статуса.
+    pub async fn format_enabled_line(&self) -> String {
+        match self.enabled_state().await {
+            Some(state) => {
+                let enabled = matches!(state.as_str(), "enabled" | "enabled-runtime" | "alias");
+                format!(
+                    "Автозапуск при загрузке: {} ({})",
+                    if enabled { "да" } else { "нет" },
+                    state
+                )
+            }
+            None => "Автозапуск при загрузке: неизвестно".to_string(),
+        }
     }
 
     pub fn format_result(&self, action: &str, r: &ServiceResult) -> String {
-        let status = if r.success { "OK" } else { "Ошибка" };
-        let mut out = format!("{} telemt: {}\n", action, status);
+        let mut out = format!("{} telemt: {}\n", action, r.status_label());
         if !r.stdout.is_empty() {
             out.push_str(&r.stdout);
             out.push('\n');
@@ -104,3 +339,75 @@ impl ServiceController {
         out.trim().to_string()
     }
 }
+
+impl RestartLimiter {
+    fn acquire(&self, service: &str, reason: &str, force: bool) -> Result<(), RestartDenied> {
+        let mut history = self
+            .history
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        while history
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= self.window)
+        {
+            history.recent.pop_front();
+        }
+
+        if !force && history.recent.len() >= self.max_restarts {
+            let oldest = history.recent[history.recent.len() - self.max_restarts].0;
+            let denied = RestartDenied {
+                count: history.recent.len(),
+                window_minutes: self.window.as_secs() / 60,
+                retry_after: (oldest + self.window).saturating_duration_since(now),
+            };
+            let first_denial = history.suppressed.is_empty();
+            history.suppressed.push(reason.to_string());
+            tracing::warn!(
+                service = %service,
+                reason = %reason,
+                retry_after_secs = denied.retry_after.as_secs(),
+                "Restart denied by storm protection"
+            );
+            // Одно уведомление на серию отказов; остальные причины попадут
+            // в сводку при следующем выполненном рестарте.
+            if first_denial {
+                let mut text = format!(
+                    "🚦 Защита от частых рестартов {}: {}\nОтклонён рестарт: {}\n\nПоследние рестарты:",
+                    service, denied, reason
+                );
+                for (at, recent_reason) in &history.recent {
+                    text.push_str(&format!(
+                        "\n• {} мин назад — {}",
+                        now.duration_since(*at).as_secs() / 60,
+                        recent_reason
+                    ));
+                }
+                text.push_str("\n\nПринудительно: /service restart --force");
+                self.alerts.send(text);
+            }
+            return Err(denied);
+        }
+
+        let suppressed = std::mem::take(&mut history.suppressed);
+        history.recent.push_back((now, reason.to_string()));
+        if !suppressed.is_empty() {
+            let mut text = format!(
+                "♻️ Рестарт {} ({}{}). Накопленные причины отклонённых рестартов:",
+                service,
+                reason,
+                if force {
+                    ", принудительно"
+                } else {
+                    ""
+                }
+            );
+            for suppressed_reason in &suppressed {
+                text.push_str(&format!("\n• {}", suppressed_reason));
+            }
+            self.alerts.send(text);
+        }
+        Ok(())
+    }
+}
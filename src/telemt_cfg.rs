@@ -1,11 +1,42 @@
 //! Чтение и обновление конфига telemt (/etc/telemt.toml).
+//!
+//! Запись идёт через временный файл, `fsync` и `rename`; перед каждой записью прежнее
+//! содержимое сохраняется в каталог резервных копий (`[telemt_backups]`), откуда его
+//! можно вернуть `/config rollback`.
 
 use serde::Deserialize;
 use std::io::ErrorKind;
 use std::path::Path;
-use std::sync::Mutex;
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, RwLock};
 use toml_edit::{DocumentMut, Item};
 
+/// Ошибка чтения или изменения конфига telemt.
+#[derive(Debug, Clone, Error)]
+pub enum TelemtCfgError {
+    #[error("Не удалось прочитать {}: {source}", .path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: Arc<std::io::Error>,
+    },
+    #[error("Не удалось записать {}: {source}", .path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: Arc<std::io::Error>,
+    },
+    #[error("Ошибка парсинга telemt конфига: {0}")]
+    Parse(String),
+    #[error("{0}")]
+    Missing(&'static str),
+    #[error("Конфиг telemt не прошёл проверку: {0}")]
+    Invalid(String),
+}
+
 /// Параметры для генерации ссылки (host, port, tls_domain).
 #[derive(Debug, Clone)]
 pub struct TelemtLinkParams {
@@ -14,6 +45,43 @@ pub struct TelemtLinkParams {
     pub tls_domain: String,
 }
 
+/// Изменение списка пользователей в [access.users].
+#[derive(Debug, Clone)]
+pub enum UserMutation {
+    Upsert { username: String, secret: String },
+    Remove { username: String },
+}
+
+/// Результат применения пакета мутаций.
+#[derive(Debug)]
+pub struct AppliedMutations {
+    /// Содержимое файла до записи; `None`, если файл не менялся.
+    pub previous: Option<String>,
+    /// Для каждой мутации — изменила ли она конфиг.
+    pub changed: Vec<bool>,
+}
+
+/// Резервная копия конфига в каталоге `[telemt_backups] dir`.
+#[derive(Debug, Clone)]
+pub struct ConfigBackup {
+    /// Имя файла: `<имя конфига>.<ГГГГММДД-ЧЧММСС.мс>`.
+    pub name: String,
+    pub size: u64,
+}
+
+/// Разобранные параметры ссылки и версия файла, из которой они получены.
+struct CachedLinkParams {
+    version: FileVersion,
+    params: TelemtLinkParams,
+}
+
+/// Время изменения и размер файла: по ним видно правки конфига в обход бота.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileVersion {
+    modified: std::time::SystemTime,
+    len: u64,
+}
+
 /// Минимальная структура для чтения нужных полей telemt.
 #[derive(Debug, Deserialize)]
 struct TelemtConfigRaw {
@@ -39,9 +107,19 @@ struct CensorshipSection {
 }
 
 /// Сервис для работы с конфигом telemt.
+///
+/// Все циклы чтение-изменение-запись выполняются под асинхронным мьютексом и
+/// межпроцессной блокировкой (`flock` каталога конфига): бот и CLI-подкоманды
+/// могут менять файл одновременно. Изменения пользователей вызываются только из
+/// [`crate::telemt_writer`].
 pub struct TelemtConfig {
-    path: std::path::PathBuf,
+    path: PathBuf,
     write_lock: Mutex<()>,
+    dry_run: bool,
+    backup_dir: Option<PathBuf>,
+    backup_keep: usize,
+    /// Кэш `read_link_params`: сбрасывается при записи и при изменении файла на диске.
+    link_params: RwLock<Option<CachedLinkParams>>,
 }
 
 impl TelemtConfig {
@@ -49,17 +127,63 @@ impl TelemtConfig {
         Self {
             path: path.as_ref().to_path_buf(),
             write_lock: Mutex::new(()),
+            dry_run: false,
+            backup_dir: None,
+            backup_keep: 0,
+            link_params: RwLock::new(None),
+        }
+    }
+
+    /// Перед каждой записью сохранять прежний конфиг в `dir`, храня `keep` последних копий.
+    pub fn with_backups(mut self, dir: impl AsRef<Path>, keep: usize) -> Self {
+        self.backup_dir = Some(dir.as_ref().to_path_buf());
+        self.backup_keep = keep;
+        self
+    }
+
+    /// В режиме dry-run изменения вычисляются и логируются, но файл не перезаписывается.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Параметры для генерации ссылки. Вызывается на каждый `/link`, `/start` и
+    /// одобрение, поэтому файл разбирается заново, только если он изменился.
+    pub async fn read_link_params(&self) -> Result<TelemtLinkParams, TelemtCfgError> {
+        let version = self.file_version().await;
+        if let Some(version) = version
+            && let Some(cached) = self.link_params.read().await.as_ref()
+            && cached.version == version
+        {
+            return Ok(cached.params.clone());
         }
+        let params = self.parse_link_params().await?;
+        // Без метаданных файла кэшировать нечем проверять — читаем каждый раз.
+        *self.link_params.write().await = version.map(|version| CachedLinkParams {
+            version,
+            params: params.clone(),
+        });
+        Ok(params)
     }
 
-    /// Читает параметры для генерации ссылки.
-    pub fn read_link_params(&self) -> Result<TelemtLinkParams, anyhow::Error> {
+    async fn file_version(&self) -> Option<FileVersion> {
+        let metadata = tokio::fs::metadata(&self.path).await.ok()?;
+        Some(FileVersion {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+
+    async fn parse_link_params(&self) -> Result<TelemtLinkParams, TelemtCfgError> {
         tracing::debug!("Reading link params from {}", self.path.display());
-        let content = std::fs::read_to_string(&self.path)
-            .map_err(|e| anyhow::anyhow!("Не удалось прочитать {}: {}", self.path.display(), e))?;
+        let content = self.read_content().await?;
 
-        let parsed: TelemtConfigRaw = toml::from_str(&content)
-            .map_err(|e| anyhow::anyhow!("Ошибка парсинга telemt конфига: {}", e))?;
+        let parsed: TelemtConfigRaw =
+            toml::from_str(&content).map_err(|e| TelemtCfgError::Parse(e.to_string()))?;
 
         let port = parsed.server.as_ref().and_then(|s| s.port).unwrap_or(443);
 
@@ -71,13 +195,15 @@ impl TelemtConfig {
                 list.iter()
                     .find_map(|l| l.announce.clone().or(l.announce_ip.clone()))
             })
-            .ok_or_else(|| anyhow::anyhow!("Не найден announce/announce_ip в server.listeners"))?;
+            .ok_or(TelemtCfgError::Missing(
+                "Не найден announce/announce_ip в server.listeners",
+            ))?;
 
         let tls_domain = parsed
             .censorship
             .as_ref()
             .and_then(|c| c.tls_domain.clone())
-            .ok_or_else(|| anyhow::anyhow!("Не задан censorship.tls_domain"))?;
+            .ok_or(TelemtCfgError::Missing("Не задан censorship.tls_domain"))?;
 
         let params = TelemtLinkParams {
             host,
@@ -92,81 +218,336 @@ impl TelemtConfig {
         Ok(params)
     }
 
-    /// Добавляет или обновляет пользователя в [access.users].
-    pub fn upsert_user(&self, username: &str, secret: &str) -> Result<(), anyhow::Error> {
-        tracing::info!(username = username, "Upserting user in telemt config");
-        let _lock = self
-            .write_lock
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Mutex poisoned: {}", e))?;
+    /// Читает пары «имя — секрет» из [access.users] в порядке файла.
+    /// Нестроковые значения возвращаются в виде TOML-текста.
+    pub async fn read_users(&self) -> Result<Vec<(String, String)>, TelemtCfgError> {
+        let content = self.read_content().await?;
+        parse_users(&content)
+    }
+
+    /// Применяет пакет изменений [access.users] одной записью файла.
+    ///
+    /// Файл перезаписывается, только если хотя бы одна мутация что-то изменила;
+    /// прежнее содержимое возвращается вызывающему для возможного отката.
+    pub async fn apply_mutations(
+        &self,
+        mutations: &[UserMutation],
+    ) -> Result<AppliedMutations, TelemtCfgError> {
+        let _lock = self.write_lock.lock().await;
+        let _file_lock = self.lock_file().await?;
 
-        let content = std::fs::read_to_string(&self.path)
-            .map_err(|e| anyhow::anyhow!("Не удалось прочитать {}: {}", self.path.display(), e))?;
+        let content = self.read_content().await?;
 
         let mut doc: DocumentMut = content
             .parse()
-            .map_err(|e| anyhow::anyhow!("Ошибка парсинга TOML: {}", e))?;
+            .map_err(|e: toml_edit::TomlError| TelemtCfgError::Parse(e.to_string()))?;
+        let users = users_table_mut(&mut doc)?;
 
-        let access = doc
-            .get_mut("access")
-            .and_then(|a| a.as_table_mut())
-            .ok_or_else(|| anyhow::anyhow!("Секция [access] не найдена"))?;
+        let mut changed = Vec::with_capacity(mutations.len());
+        for mutation in mutations {
+            let mutation_changed = apply_mutation(users, mutation);
+            match (mutation, mutation_changed) {
+                (UserMutation::Upsert { username, .. }, true) => {
+                    tracing::info!(username = %username, "User upserted in telemt config");
+                }
+                (UserMutation::Remove { username }, true) => {
+                    tracing::info!(username = %username, "User removed from telemt config");
+                }
+                (UserMutation::Remove { username }, false) => {
+                    tracing::warn!(username = %username, "User was not found in telemt config");
+                }
+                (UserMutation::Upsert { .. }, false) => {}
+            }
+            changed.push(mutation_changed);
+        }
 
-        let users = access
-            .get_mut("users")
-            .and_then(|u| u.as_table_mut())
-            .ok_or_else(|| anyhow::anyhow!("Секция [access.users] не найдена"))?;
+        if !changed.iter().any(|value| *value) {
+            return Ok(AppliedMutations {
+                previous: None,
+                changed,
+            });
+        }
 
-        users[username] = Item::Value(toml_edit::Value::from(secret));
+        self.write_atomic(&doc.to_string()).await?;
+        Ok(AppliedMutations {
+            previous: Some(content),
+            changed,
+        })
+    }
 
-        let new_content = doc.to_string();
-        self.write_atomic(&new_content)?;
-        tracing::info!(username = username, "User upserted in telemt config");
+    /// Проверяет конфиг на диске перед рестартом: TOML разбирается, есть `[access.users]`
+    /// и поля, без которых бот не построит ссылку (`server.listeners` с announce,
+    /// `censorship.tls_domain`).
+    pub async fn validate(&self) -> Result<(), TelemtCfgError> {
+        self.read_link_params().await?;
+        self.read_users().await?;
         Ok(())
     }
 
-    /// Удаляет пользователя из [access.users].
-    pub fn remove_user(&self, username: &str) -> Result<bool, anyhow::Error> {
-        tracing::info!(username = username, "Removing user from telemt config");
-        let _lock = self
-            .write_lock
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Mutex poisoned: {}", e))?;
+    /// Текст конфига до и после мутаций без записи в файл — для предпросмотра.
+    /// Значения `[access.users]` в обоих вариантах проходят через `mask`.
+    pub async fn preview_mutations(
+        &self,
+        mutations: &[UserMutation],
+        mask: impl Fn(&str) -> String,
+    ) -> Result<(String, String), TelemtCfgError> {
+        let content = self.read_content().await?;
+        let mut before: DocumentMut = content
+            .parse()
+            .map_err(|e: toml_edit::TomlError| TelemtCfgError::Parse(e.to_string()))?;
+        let mut after = before.clone();
+        let users = users_table_mut(&mut after)?;
+        for mutation in mutations {
+            apply_mutation(users, mutation);
+        }
+        for doc in [&mut before, &mut after] {
+            let users = users_table_mut(doc)?;
+            for (_, item) in users.iter_mut() {
+                if let Some(masked) = item.as_str().map(&mask) {
+                    *item = Item::Value(toml_edit::Value::from(masked));
+                }
+            }
+        }
+        Ok((before.to_string(), after.to_string()))
+    }
 
-        let content = std::fs::read_to_string(&self.path)
-            .map_err(|e| anyhow::anyhow!("Не удалось прочитать {}: {}", self.path.display(), e))?;
+    /// Возвращает конфиг к ранее сохранённому содержимому (откат).
+    pub async fn restore(&self, content: &str) -> Result<(), TelemtCfgError> {
+        let _lock = self.write_lock.lock().await;
+        let _file_lock = self.lock_file().await?;
+        tracing::warn!(path = %self.path.display(), "Restoring previous telemt config");
+        self.write_atomic(content).await
+    }
 
-        let mut doc: DocumentMut = content
-            .parse()
-            .map_err(|e| anyhow::anyhow!("Ошибка парсинга TOML: {}", e))?;
+    /// Резервные копии, от новых к старым.
+    pub async fn list_backups(&self) -> Result<Vec<ConfigBackup>, TelemtCfgError> {
+        let Some(dir) = &self.backup_dir else {
+            return Ok(Vec::new());
+        };
+        let read_error = |source| TelemtCfgError::Read {
+            path: dir.clone(),
+            source: Arc::new(source),
+        };
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(read_error(err)),
+        };
+        let prefix = self.backup_prefix();
+        let mut backups = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(read_error)? {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !name.starts_with(&prefix) {
+                continue;
+            }
+            let size = entry.metadata().await.map(|meta| meta.len()).unwrap_or(0);
+            backups.push(ConfigBackup { name, size });
+        }
+        // Метка времени в имени сортируется как строка.
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(backups)
+    }
 
-        let access = doc
-            .get_mut("access")
-            .and_then(|a| a.as_table_mut())
-            .ok_or_else(|| anyhow::anyhow!("Секция [access] не найдена"))?;
+    /// Содержимое резервной копии `name`. Сам откат идёт через
+    /// [`crate::telemt_writer::ConfigWriter::restore`] с проверкой и рестартом.
+    pub async fn read_backup(&self, name: &str) -> Result<String, TelemtCfgError> {
+        let backups = self.list_backups().await?;
+        let backup =
+            backups
+                .iter()
+                .find(|backup| backup.name == name)
+                .ok_or(TelemtCfgError::Missing(
+                    "Резервная копия конфига не найдена",
+                ))?;
+        let Some(dir) = &self.backup_dir else {
+            return Err(TelemtCfgError::Missing(
+                "Резервные копии конфига не настроены",
+            ));
+        };
+        let path = dir.join(&backup.name);
+        tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|source| TelemtCfgError::Read {
+                path,
+                source: Arc::new(source),
+            })
+    }
+
+    /// Удаляет пользователя `username` из всех резервных копий (стирание по `/purge`:
+    /// иначе его секрет остался бы в копиях и вернулся бы откатом). Копию, которую не
+    /// удаётся разобрать, но где встречается имя, приходится удалить целиком.
+    /// Возвращает число изменённых или удалённых копий.
+    pub async fn scrub_backups(&self, username: &str) -> Result<usize, TelemtCfgError> {
+        let Some(dir) = &self.backup_dir else {
+            return Ok(0);
+        };
+        let mut scrubbed = 0;
+        for backup in self.list_backups().await? {
+            let path = dir.join(&backup.name);
+            let content =
+                tokio::fs::read_to_string(&path)
+                    .await
+                    .map_err(|source| TelemtCfgError::Read {
+                        path: path.clone(),
+                        source: Arc::new(source),
+                    })?;
+            if !content.contains(username) {
+                continue;
+            }
+            let write_error = |source| TelemtCfgError::Write {
+                path: path.clone(),
+                source: Arc::new(source),
+            };
+            match retain_users(&content, |name| name != username) {
+                Ok((_, dropped)) if dropped.is_empty() => continue,
+                Ok((cleaned, _)) => write_synced(&path, cleaned.as_bytes())
+                    .await
+                    .map_err(write_error)?,
+                Err(error) => {
+                    tracing::warn!(
+                        backup = %backup.name,
+                        error = %error,
+                        "Резервная копия не разбирается, удаляю её целиком"
+                    );
+                    tokio::fs::remove_file(&path).await.map_err(write_error)?;
+                }
+            }
+            scrubbed += 1;
+        }
+        Ok(scrubbed)
+    }
+
+    /// Заменяет конфиг целиком (откат к копии). Текущее содержимое при этом тоже
+    /// сохраняется в копию и возвращается для отката; `None` — файл не изменился.
+    pub async fn replace(&self, content: &str) -> Result<Option<String>, TelemtCfgError> {
+        let _lock = self.write_lock.lock().await;
+        let _file_lock = self.lock_file().await?;
+        let previous = self.read_content().await?;
+        if previous == content {
+            return Ok(None);
+        }
+        tracing::warn!(path = %self.path.display(), "Replacing telemt config content");
+        self.write_atomic(content).await?;
+        Ok(Some(previous))
+    }
 
-        let users = access
-            .get_mut("users")
-            .and_then(|u| u.as_table_mut())
-            .ok_or_else(|| anyhow::anyhow!("Секция [access.users] не найдена"))?;
+    fn backup_prefix(&self) -> String {
+        let file_name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "telemt.toml".to_string());
+        format!("{}.", file_name)
+    }
 
-        let existed = users.contains_key(username);
-        users.remove(username);
+    /// Сохраняет текущий файл в каталог копий и удаляет копии сверх `backup_keep`.
+    /// Ошибки только логируются: без копии запись всё равно выполняется.
+    async fn backup_current(&self) {
+        let Some(dir) = &self.backup_dir else {
+            return;
+        };
+        if self.backup_keep == 0 {
+            return;
+        }
+        let content = match tokio::fs::read(&self.path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return,
+            Err(err) => {
+                tracing::warn!(error = %err, "Не удалось прочитать конфиг telemt для резервной копии");
+                return;
+            }
+        };
+        let name = format!(
+            "{}{}",
+            self.backup_prefix(),
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+        );
+        let path = dir.join(&name);
+        let result = async {
+            tokio::fs::create_dir_all(dir).await?;
+            write_synced(&path, &content).await
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!(
+                path = %path.display(),
+                error = %err,
+                "Не удалось сохранить резервную копию конфига telemt"
+            );
+            return;
+        }
+        tracing::debug!(backup = %name, "telemt config backed up");
 
-        if existed {
-            let new_content = doc.to_string();
-            self.write_atomic(&new_content)?;
-            tracing::info!(username = username, "User removed from telemt config");
-        } else {
-            tracing::warn!(username = username, "User was not found in telemt config");
+        match self.list_backups().await {
+            Ok(backups) => {
+                for backup in backups.iter().skip(self.backup_keep) {
+                    if let Err(err) = tokio::fs::remove_file(dir.join(&backup.name)).await {
+                        tracing::warn!(
+                            backup = %backup.name,
+                            error = %err,
+                            "Не удалось удалить старую резервную копию конфига telemt"
+                        );
+                    }
+                }
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, "Не удалось прочитать каталог резервных копий");
+            }
         }
-        Ok(existed)
     }
 
-    fn write_atomic(&self, content: &str) -> Result<(), anyhow::Error> {
+    /// Эксклюзивная advisory-блокировка (`flock`) каталога конфига; снимается при
+    /// закрытии возвращённого файла. Блокируется каталог, а не сам файл: запись
+    /// через `rename` подменяет файл, и блокировка старого inode не защитила бы
+    /// следующего писателя.
+    async fn lock_file(&self) -> Result<std::fs::File, TelemtCfgError> {
+        let dir = self
+            .path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+        let lock_error = |source| TelemtCfgError::Write {
+            path: dir.clone(),
+            source: Arc::new(source),
+        };
+        let target = dir.clone();
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&target)?;
+            file.lock()?;
+            Ok(file)
+        })
+        .await
+        .map_err(|join_error| lock_error(std::io::Error::other(join_error)))?
+        .map_err(lock_error)
+    }
+
+    async fn read_content(&self) -> Result<String, TelemtCfgError> {
+        tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|source| TelemtCfgError::Read {
+                path: self.path.clone(),
+                source: Arc::new(source),
+            })
+    }
+
+    async fn write_atomic(&self, content: &str) -> Result<(), TelemtCfgError> {
         // Дополнительная валидация финального текста перед заменой файла.
         let _: toml::Value = toml::from_str(content)
-            .map_err(|e| anyhow::anyhow!("Невалидный TOML перед записью: {}", e))?;
+            .map_err(|e| TelemtCfgError::Parse(format!("Невалидный TOML перед записью: {}", e)))?;
+
+        if self.dry_run {
+            tracing::info!(
+                target_path = %self.path.display(),
+                bytes = content.len(),
+                "Dry-run: telemt config write skipped"
+            );
+            return Ok(());
+        }
+
+        self.backup_current().await;
 
         let parent = self.path.parent().unwrap_or(std::path::Path::new("."));
         let nonce = std::time::SystemTime::now()
@@ -174,7 +555,7 @@ impl TelemtConfig {
             .map(|value| value.as_nanos())
             .unwrap_or(0);
         let tmp = parent.join(format!(".telemt.toml.{}.{}", std::process::id(), nonce));
-        if let Err(err) = std::fs::write(&tmp, content) {
+        if let Err(err) = write_synced(&tmp, content.as_bytes()).await {
             if err.kind() == ErrorKind::PermissionDenied {
                 // В некоторых окружениях есть права на изменение файла, но нет прав
                 // на создание новых файлов в директории (например, /etc).
@@ -182,22 +563,33 @@ impl TelemtConfig {
                     target_path = %self.path.display(),
                     "No permission to create temporary file; falling back to direct write"
                 );
-                std::fs::write(&self.path, content).map_err(|e| {
-                    anyhow::anyhow!(
-                        "Не удалось записать {} после fallback: {}",
-                        self.path.display(),
-                        e
-                    )
-                })?;
+                write_synced(&self.path, content.as_bytes())
+                    .await
+                    .map_err(|source| TelemtCfgError::Write {
+                        path: self.path.clone(),
+                        source: Arc::new(source),
+                    })?;
+                *self.link_params.write().await = None;
                 return Ok(());
             }
-            return Err(anyhow::anyhow!(
-                "Не удалось записать временный файл: {}",
-                err
-            ));
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(TelemtCfgError::Write {
+                path: tmp,
+                source: Arc::new(err),
+            });
         }
-        std::fs::rename(&tmp, &self.path)
-            .map_err(|e| anyhow::anyhow!("Не удалось переименовать временный файл: {}", e))?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .map_err(|source| TelemtCfgError::Write {
+                path: self.path.clone(),
+                source: Arc::new(source),
+            })?;
+        // fsync каталога: переименование переживёт сбой питания.
+        if let Ok(dir) = tokio::fs::File::open(parent).await {
+            let _ = dir.sync_all().await;
+        }
+        // Время изменения может не смениться при записи в ту же секунду — сбрасываем явно.
+        *self.link_params.write().await = None;
         tracing::debug!(
             tmp_path = %tmp.display(),
             target_path = %self.path.display(),
@@ -206,3 +598,85 @@ impl TelemtConfig {
         Ok(())
     }
 }
+
+/// Пары «имя — секрет» из `[access.users]` текста конфига, в порядке файла.
+fn parse_users(content: &str) -> Result<Vec<(String, String)>, TelemtCfgError> {
+    let doc: DocumentMut = content
+        .parse()
+        .map_err(|e: toml_edit::TomlError| TelemtCfgError::Parse(e.to_string()))?;
+
+    let users = doc
+        .get("access")
+        .and_then(|a| a.as_table_like())
+        .ok_or(TelemtCfgError::Missing("Секция [access] не найдена"))?
+        .get("users")
+        .and_then(|u| u.as_table_like())
+        .ok_or(TelemtCfgError::Missing("Секция [access.users] не найдена"))?;
+
+    Ok(users
+        .iter()
+        .map(|(name, item)| {
+            let value = item
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| item.to_string().trim().to_string());
+            (name.to_string(), value)
+        })
+        .collect())
+}
+
+/// Оставляет в `[access.users]` текста конфига только пользователей, для которых
+/// `keep` вернул true; остальной текст не меняется. Возвращает новый текст и имена
+/// удалённых.
+pub fn retain_users(
+    content: &str,
+    keep: impl Fn(&str) -> bool,
+) -> Result<(String, Vec<String>), TelemtCfgError> {
+    let mut doc: DocumentMut = content
+        .parse()
+        .map_err(|e: toml_edit::TomlError| TelemtCfgError::Parse(e.to_string()))?;
+    let users = users_table_mut(&mut doc)?;
+    let dropped: Vec<String> = users
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .filter(|name| !keep(name))
+        .collect();
+    if dropped.is_empty() {
+        return Ok((content.to_string(), dropped));
+    }
+    for name in &dropped {
+        users.remove(name);
+    }
+    Ok((doc.to_string(), dropped))
+}
+
+fn users_table_mut(doc: &mut DocumentMut) -> Result<&mut toml_edit::Table, TelemtCfgError> {
+    doc.get_mut("access")
+        .and_then(|a| a.as_table_mut())
+        .ok_or(TelemtCfgError::Missing("Секция [access] не найдена"))?
+        .get_mut("users")
+        .and_then(|u| u.as_table_mut())
+        .ok_or(TelemtCfgError::Missing("Секция [access.users] не найдена"))
+}
+
+/// Применяет мутацию к `[access.users]`; `true`, если таблица изменилась.
+fn apply_mutation(users: &mut toml_edit::Table, mutation: &UserMutation) -> bool {
+    match mutation {
+        UserMutation::Upsert { username, secret } => {
+            let current = users.get(username).and_then(|item| item.as_str());
+            let differs = current != Some(secret.as_str());
+            if differs {
+                users[username.as_str()] = Item::Value(toml_edit::Value::from(secret.as_str()));
+            }
+            differs
+        }
+        UserMutation::Remove { username } => users.remove(username).is_some(),
+    }
+}
+
+/// Записывает файл и дожидается сброса данных на диск.
+async fn write_synced(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::File::create(path).await?;
+    file.write_all(content).await?;
+    file.sync_all().await
+}
@@ -0,0 +1,506 @@
+//! Единственный писатель конфига telemt.
+//!
+//! Все изменения `[access.users]`, откат конфига к резервной копии и решение о
+//! рестарте сервиса проходят через
+//! одну задачу с очередью команд: это гарантирует порядок применения, позволяет
+//! объединять накопившиеся команды в одну запись и один рестарт, а логика отката
+//! живёт только здесь. Если рестарт отклонён защитой от частых рестартов,
+//! изменения остаются в конфиге и применяются одним отложенным рестартом.
+//! С `[restart] debounce_secs` рестарт после изменений всегда откладывается на
+//! это окно, чтобы массовые операции обрывали соединения пользователей один раз.
+//! Перед рестартом записанный конфиг проверяется (`TelemtConfig::validate` и
+//! `[restart] check_command`): непрошедший проверку конфиг откатывается без рестарта.
+
+use crate::alerts::AdminAlerts;
+use crate::service::{RestartError, ServiceController, ServiceError};
+use crate::telemt_cfg::{TelemtCfgError, TelemtConfig, UserMutation};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{Notify, mpsc, oneshot};
+
+/// Ошибка применения изменений через писатель конфига.
+#[derive(Debug, Clone, Error)]
+pub enum ConfigWriteError {
+    #[error(transparent)]
+    Cfg(#[from] TelemtCfgError),
+    #[error(transparent)]
+    Service(#[from] ServiceError),
+    #[error("Писатель конфига telemt остановлен")]
+    Closed,
+}
+
+type WriteReply = oneshot::Sender<Result<Vec<bool>, ConfigWriteError>>;
+
+/// Что записать в конфиг.
+enum WriteRequest {
+    /// Изменения `[access.users]`; соседние команды объединяются в одну запись.
+    Users(Vec<UserMutation>),
+    /// Замена конфига целиком (откат к резервной копии) — всегда отдельной записью.
+    Replace { content: String, reason: String },
+}
+
+struct WriteCommand {
+    request: WriteRequest,
+    /// Ответить только после рестарта, применившего изменения (в том числе отложенного).
+    wait_restart: bool,
+    reply: WriteReply,
+}
+
+impl WriteCommand {
+    fn mutations(&self) -> &[UserMutation] {
+        match &self.request {
+            WriteRequest::Users(mutations) => mutations,
+            WriteRequest::Replace { .. } => &[],
+        }
+    }
+}
+
+/// Команда, ждущая отложенного рестарта: ответ отправляется по его итогу.
+struct RestartWaiter {
+    changed: Vec<bool>,
+    reply: WriteReply,
+}
+
+/// Ожидающие отложенного рестарта. `restarting` — рестарт уже начался: флаг
+/// отложенного рестарта сброшен, но записанные до этого изменения применит он же.
+#[derive(Default)]
+struct WaitQueue {
+    restarting: bool,
+    waiters: Vec<RestartWaiter>,
+}
+
+type RestartWaiters = Arc<Mutex<WaitQueue>>;
+
+/// Handle для отправки команд писателю конфига.
+#[derive(Clone)]
+pub struct ConfigWriter {
+    tx: mpsc::UnboundedSender<WriteCommand>,
+    deferred_restart: Arc<AtomicBool>,
+    restart_now: Arc<Notify>,
+}
+
+impl ConfigWriter {
+    /// Запускает задачу-писателя и возвращает handle к ней.
+    pub fn spawn(
+        telemt_cfg: Arc<TelemtConfig>,
+        service: ServiceController,
+        alerts: AdminAlerts,
+        debounce: Duration,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let deferred_restart = Arc::new(AtomicBool::new(false));
+        let restart_now = Arc::new(Notify::new());
+        let writer = Writer {
+            telemt_cfg,
+            service,
+            alerts,
+            debounce,
+            deferred_restart: deferred_restart.clone(),
+            restart_now: restart_now.clone(),
+            restart_waiters: Arc::default(),
+        };
+        tokio::spawn(run_writer(writer, rx));
+        Self {
+            tx,
+            deferred_restart,
+            restart_now,
+        }
+    }
+
+    /// Есть ли записанные изменения, ждущие отложенного рестарта.
+    pub fn restart_deferred(&self) -> bool {
+        self.deferred_restart.load(Ordering::SeqCst)
+    }
+
+    /// Выполняет отложенный рестарт немедленно, не дожидаясь конца окна.
+    /// Возвращает false, если отложенного рестарта нет.
+    pub fn restart_pending_now(&self) -> bool {
+        if !self.deferred_restart.load(Ordering::SeqCst) {
+            return false;
+        }
+        self.restart_now.notify_one();
+        true
+    }
+
+    /// Добавляет или обновляет пользователя.
+    pub async fn upsert_user(&self, username: &str, secret: &str) -> Result<(), ConfigWriteError> {
+        self.apply(vec![UserMutation::Upsert {
+            username: username.to_string(),
+            secret: secret.to_string(),
+        }])
+        .await?;
+        Ok(())
+    }
+
+    /// Удаляет пользователя. Возвращает false, если его не было в конфиге.
+    pub async fn remove_user(&self, username: &str) -> Result<bool, ConfigWriteError> {
+        let changed = self
+            .apply(vec![UserMutation::Remove {
+                username: username.to_string(),
+            }])
+            .await?;
+        Ok(changed.first().copied().unwrap_or(false))
+    }
+
+    /// Применяет пакет изменений с одним рестартом. Для каждой мутации возвращает,
+    /// изменила ли она конфиг.
+    pub async fn apply(&self, mutations: Vec<UserMutation>) -> Result<Vec<bool>, ConfigWriteError> {
+        self.send(WriteRequest::Users(mutations), false).await
+    }
+
+    /// Заменяет конфиг целиком (откат к резервной копии) с той же проверкой,
+    /// защитой от частых рестартов и откатом при неудачном рестарте, что и изменения
+    /// пользователей.
+    pub async fn replace(&self, content: String, reason: String) -> Result<(), ConfigWriteError> {
+        self.send(WriteRequest::Replace { content, reason }, false)
+            .await
+            .map(|_| ())
+    }
+
+    /// Как [`ConfigWriter::apply`], но возвращается только после рестарта telemt,
+    /// применившего изменения: отложенный рестарт тоже дожидается. Нужен, когда
+    /// пользователю нельзя отдавать ссылку раньше, чем секрет заработает.
+    pub async fn apply_and_wait(
+        &self,
+        mutations: Vec<UserMutation>,
+    ) -> Result<Vec<bool>, ConfigWriteError> {
+        self.send(WriteRequest::Users(mutations), true).await
+    }
+
+    async fn send(
+        &self,
+        request: WriteRequest,
+        wait_restart: bool,
+    ) -> Result<Vec<bool>, ConfigWriteError> {
+        let (reply, rx) = oneshot::channel();
+        self.tx
+            .send(WriteCommand {
+                request,
+                wait_restart,
+                reply,
+            })
+            .map_err(|_| ConfigWriteError::Closed)?;
+        rx.await.map_err(|_| ConfigWriteError::Closed)?
+    }
+}
+
+struct Writer {
+    telemt_cfg: Arc<TelemtConfig>,
+    service: ServiceController,
+    alerts: AdminAlerts,
+    /// Окно объединения изменений в один рестарт; ноль — рестарт сразу.
+    debounce: Duration,
+    /// Рестарт отложен (окном объединения или защитой от частых рестартов) и ещё не выполнен.
+    deferred_restart: Arc<AtomicBool>,
+    /// Сигнал выполнить отложенный рестарт досрочно.
+    restart_now: Arc<Notify>,
+    /// Команды `apply_and_wait`, ответ которым отправит отложенный рестарт.
+    restart_waiters: RestartWaiters,
+}
+
+async fn run_writer(writer: Writer, mut rx: mpsc::UnboundedReceiver<WriteCommand>) {
+    tracing::info!("telemt config writer started");
+    while let Some(first) = rx.recv().await {
+        // Забираем всё, что успело накопиться, чтобы записать и перезапустить один раз.
+        let mut batch = vec![first];
+        while let Ok(next) = rx.try_recv() {
+            batch.push(next);
+        }
+
+        // Замена конфига целиком не смешивается с изменениями пользователей: порядок
+        // команд сохраняется, пользователи до и после замены пишутся своими пакетами.
+        let mut users = Vec::new();
+        for command in batch {
+            if let WriteRequest::Replace { content, reason } = &command.request {
+                apply_users_batch(&writer, std::mem::take(&mut users)).await;
+                tracing::info!(reason = %reason, "Replacing telemt config");
+                let result = match writer.telemt_cfg.replace(content).await {
+                    Ok(previous) => finish_write(&writer, previous.as_deref(), reason).await,
+                    Err(error) => Err(error.into()),
+                };
+                match result {
+                    Ok(restart_deferred) => {
+                        send_reply(&writer, command, Vec::new(), restart_deferred)
+                    }
+                    Err(error) => {
+                        let _ = command.reply.send(Err(error));
+                    }
+                }
+            } else {
+                users.push(command);
+            }
+        }
+        apply_users_batch(&writer, users).await;
+    }
+    tracing::warn!("telemt config writer stopped: all handles dropped");
+}
+
+/// Пишет изменения пользователей из нескольких команд одной записью и одним рестартом.
+async fn apply_users_batch(writer: &Writer, batch: Vec<WriteCommand>) {
+    if batch.is_empty() {
+        return;
+    }
+    let mutations: Vec<UserMutation> = batch
+        .iter()
+        .flat_map(|command| command.mutations().iter().cloned())
+        .collect();
+    tracing::info!(
+        commands = batch.len(),
+        mutations = mutations.len(),
+        "Applying telemt config batch"
+    );
+
+    let result = match writer.telemt_cfg.apply_mutations(&mutations).await {
+        Ok(applied) => {
+            let reason = describe_batch(&mutations, &applied.changed);
+            finish_write(writer, applied.previous.as_deref(), &reason)
+                .await
+                .map(|restart_deferred| (applied.changed, restart_deferred))
+        }
+        Err(error) => Err(error.into()),
+    };
+    match result {
+        Ok((changed, restart_deferred)) => {
+            let mut offset = 0;
+            for command in batch {
+                let count = command.mutations().len();
+                let slice = changed[offset..offset + count].to_vec();
+                offset += count;
+                send_reply(writer, command, slice, restart_deferred);
+            }
+        }
+        Err(error) => {
+            for command in batch {
+                let _ = command.reply.send(Err(error.clone()));
+            }
+        }
+    }
+}
+
+/// Отвечает команде сразу или, для `apply_and_wait` при отложенном рестарте, ставит
+/// её в очередь ожидающих этого рестарта.
+fn send_reply(writer: &Writer, command: WriteCommand, changed: Vec<bool>, restart_deferred: bool) {
+    if command.wait_restart && restart_deferred {
+        // Флаг проверяется под блокировкой очереди: иначе рестарт мог
+        // завершиться раньше, чем команда встала в очередь.
+        let mut queue = lock_waiters(&writer.restart_waiters);
+        if queue.restarting || writer.deferred_restart.load(Ordering::SeqCst) {
+            queue.waiters.push(RestartWaiter {
+                changed,
+                reply: command.reply,
+            });
+            return;
+        }
+    }
+    let _ = command.reply.send(Ok(changed));
+}
+
+/// Проверка, рестарт и откат после записи конфига. `previous` — содержимое до
+/// записи (`None` — файл не менялся), `reason` — причина рестарта. Возвращает true,
+/// если изменения ждут отложенного рестарта.
+async fn finish_write(
+    writer: &Writer,
+    previous: Option<&str>,
+    reason: &str,
+) -> Result<bool, ConfigWriteError> {
+    let telemt_cfg = &writer.telemt_cfg;
+    let Some(previous) = previous else {
+        tracing::debug!("telemt config unchanged, restart skipped");
+        // Изменения могли прийти раньше и ещё ждать отложенного рестарта.
+        return Ok(writer.deferred_restart.load(Ordering::SeqCst));
+    };
+
+    if telemt_cfg.is_dry_run() {
+        tracing::info!("Dry-run: telemt restart skipped");
+        return Ok(false);
+    }
+
+    if let Err(reason) = validate_config(telemt_cfg, &writer.service).await {
+        tracing::error!(reason = %reason, "telemt config failed validation, rolling back");
+        telemt_cfg.restore(previous).await?;
+        writer.alerts.send(format!(
+            "❌ Изменённый конфиг telemt не прошёл проверку — изменения отменены, \
+             рестарт не выполнялся.\n{}",
+            reason
+        ));
+        return Err(TelemtCfgError::Invalid(reason).into());
+    }
+
+    if writer.deferred_restart.load(Ordering::SeqCst) {
+        // Уже запланированный отложенный рестарт применит и эти изменения.
+        tracing::info!("Deferred restart pending, batch will be applied with it");
+        return Ok(true);
+    }
+
+    if !writer.debounce.is_zero() {
+        // Откат при неудачном рестарте здесь невозможен: о сбое узнают админы.
+        schedule_deferred_restart(writer, writer.debounce, false);
+        return Ok(true);
+    }
+
+    // telemt не перечитывает конфиг на лету — после записи нужен рестарт.
+    match restart(&writer.service, reason, false).await {
+        Ok(()) => Ok(false),
+        Err(RestartError::Denied(denied)) => {
+            schedule_deferred_restart(writer, denied.retry_after, true);
+            Ok(true)
+        }
+        Err(RestartError::Failed(restart_error)) => {
+            tracing::error!(
+                error = %restart_error,
+                "telemt restart failed after config change, rolling back"
+            );
+            if let Err(restore_error) = telemt_cfg.restore(previous).await {
+                tracing::error!(error = %restore_error, "Не удалось откатить конфиг telemt");
+                return Err(restore_error.into());
+            }
+            if let Err(second_error) = restart(&writer.service, "откат конфига", true).await
+            {
+                tracing::error!(
+                    error = %second_error,
+                    "telemt restart failed after rollback"
+                );
+            }
+            Err(restart_error.into())
+        }
+    }
+}
+
+/// Проверка записанного конфига перед рестартом: без неё telemt с битым конфигом
+/// уйдёт в цикл перезапусков. `Err` — причина для админов.
+async fn validate_config(
+    telemt_cfg: &TelemtConfig,
+    service: &ServiceController,
+) -> Result<(), String> {
+    telemt_cfg
+        .validate()
+        .await
+        .map_err(|error| error.to_string())?;
+    if let Some(result) = service.check_config().await
+        && !result.success
+    {
+        let status = result.status_label();
+        let output = if result.stderr.is_empty() {
+            result.stdout
+        } else {
+            result.stderr
+        };
+        return Err(format!("check_command: {}\n{}", status, output));
+    }
+    Ok(())
+}
+
+/// Краткое описание пакета для причины рестарта.
+fn describe_batch(mutations: &[UserMutation], changed: &[bool]) -> String {
+    let (mut upserts, mut removals) = (0, 0);
+    for (mutation, changed) in mutations.iter().zip(changed) {
+        if !changed {
+            continue;
+        }
+        match mutation {
+            UserMutation::Upsert { .. } => upserts += 1,
+            UserMutation::Remove { .. } => removals += 1,
+        }
+    }
+    format!("изменение пользователей: +{} / −{}", upserts, removals)
+}
+
+/// Планирует один рестарт через `delay` (окно объединения или окно ограничителя).
+/// Изменения, записанные до него, применятся этим же рестартом. Рестарт без `force`,
+/// отклонённый ограничителем, повторяется принудительно после окончания его окна.
+fn schedule_deferred_restart(writer: &Writer, delay: Duration, force: bool) {
+    if writer.deferred_restart.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    if force {
+        tracing::warn!(
+            delay_secs = delay.as_secs(),
+            "telemt restart deferred by storm protection"
+        );
+    } else {
+        tracing::info!(delay_secs = delay.as_secs(), "telemt restart debounced");
+    }
+    let telemt_cfg = writer.telemt_cfg.clone();
+    let service = writer.service.clone();
+    let alerts = writer.alerts.clone();
+    let flag = writer.deferred_restart.clone();
+    let restart_now = writer.restart_now.clone();
+    let waiters = writer.restart_waiters.clone();
+    tokio::spawn(async move {
+        let (mut delay, mut force) = (delay, force);
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = restart_now.notified() => force = true,
+            }
+            {
+                let mut queue = lock_waiters(&waiters);
+                queue.restarting = true;
+                flag.store(false, Ordering::SeqCst);
+            }
+            // Конфиг могли поменять вручную за время ожидания.
+            if let Err(reason) = validate_config(&telemt_cfg, &service).await {
+                tracing::error!(reason = %reason, "Deferred telemt restart cancelled: invalid config");
+                alerts.send(format!(
+                    "❌ Отложенный рестарт telemt отменён: конфиг не прошёл проверку.\n{}\n\
+                     Исправьте конфиг или верните копию: /config rollback",
+                    reason
+                ));
+                notify_waiters(&waiters, Err(TelemtCfgError::Invalid(reason).into()));
+                break;
+            }
+            match restart(
+                &service,
+                "отложенный рестарт после изменений конфига",
+                force,
+            )
+            .await
+            {
+                Ok(()) => {
+                    tracing::info!("Deferred telemt restart completed");
+                    notify_waiters(&waiters, Ok(()));
+                }
+                Err(RestartError::Denied(denied)) => {
+                    flag.store(true, Ordering::SeqCst);
+                    (delay, force) = (denied.retry_after, true);
+                    continue;
+                }
+                Err(RestartError::Failed(error)) => {
+                    tracing::error!(error = %error, "Deferred telemt restart failed");
+                    alerts.send(format!("❌ Отложенный рестарт telemt не удался: {}", error));
+                    notify_waiters(&waiters, Err(error.into()));
+                }
+            }
+            break;
+        }
+    });
+}
+
+fn lock_waiters(waiters: &RestartWaiters) -> std::sync::MutexGuard<'_, WaitQueue> {
+    waiters
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Отвечает командам, ждавшим отложенного рестарта, его итогом.
+fn notify_waiters(waiters: &RestartWaiters, result: Result<(), ConfigWriteError>) {
+    let pending = {
+        let mut queue = lock_waiters(waiters);
+        queue.restarting = false;
+        std::mem::take(&mut queue.waiters)
+    };
+    for waiter in pending {
+        let reply = result.clone().map(|()| waiter.changed);
+        let _ = waiter.reply.send(reply);
+    }
+}
+
+async fn restart(
+    service: &ServiceController,
+    reason: &str,
+    force: bool,
+) -> Result<(), RestartError> {
+    service.restart_checked(reason, force).await.map(|_| ())
+}
@@ -0,0 +1,110 @@
+//! Трафик пользователей: бот периодически читает метрики telemt в формате Prometheus
+//! (`[traffic] stats_url`), суммирует счётчики байт по метке пользователя и копит их
+//! в БД. Счётчики telemt обнуляются при рестарте, накопленные значения — нет.
+
+use crate::config::TrafficConfig;
+use crate::db::Db;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn spawn(db: Arc<Db>, config: TrafficConfig) -> Option<tokio::task::JoinHandle<()>> {
+    let Some(url) = config.stats_url.clone() else {
+        tracing::info!("Traffic stats disabled");
+        return None;
+    };
+    let interval = Duration::from_secs(config.poll_interval_secs.max(1));
+    Some(tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            if let Err(error) = poll_once(&client, &url, &db, &config).await {
+                tracing::warn!(url = %url, error = %error, "Не удалось собрать трафик из метрик telemt");
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }))
+}
+
+async fn poll_once(
+    client: &reqwest::Client,
+    url: &str,
+    db: &Db,
+    config: &TrafficConfig,
+) -> Result<(), String> {
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let body = response.text().await.map_err(|e| e.to_string())?;
+    let from_client = sum_by_label(&body, &config.from_client_metric, &config.user_label);
+    let to_client = sum_by_label(&body, &config.to_client_metric, &config.user_label);
+
+    let users = db
+        .list_users_expected_in_config()
+        .await
+        .map_err(|e| e.to_string())?;
+    let samples = users
+        .iter()
+        .filter_map(|user| {
+            let username = user.telemt_username.as_deref()?;
+            let from_client = from_client.get(username).copied();
+            let to_client = to_client.get(username).copied();
+            if from_client.is_none() && to_client.is_none() {
+                return None;
+            }
+            Some((
+                user.tg_user_id,
+                from_client.unwrap_or_default(),
+                to_client.unwrap_or_default(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    db.record_traffic_samples(&samples)
+        .await
+        .map_err(|e| e.to_string())?;
+    tracing::debug!(users = samples.len(), "Traffic stats updated");
+    Ok(())
+}
+
+/// Сумма значений метрики `metric` по значению метки `label`. Серии без метки и
+/// нечисловые значения пропускаются.
+fn sum_by_label(body: &str, metric: &str, label: &str) -> HashMap<String, i64> {
+    let mut totals = HashMap::new();
+    for line in body.lines() {
+        let Some(rest) = line.trim().strip_prefix(metric) else {
+            continue;
+        };
+        let Some(rest) = rest.strip_prefix('{') else {
+            continue;
+        };
+        let Some((labels, value)) = rest.split_once('}') else {
+            continue;
+        };
+        let Some(user) = label_value(labels, label) else {
+            continue;
+        };
+        // После значения может идти timestamp.
+        let Some(value) = value
+            .split_whitespace()
+            .next()
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value >= 0.0)
+        else {
+            continue;
+        };
+        *totals.entry(user.to_string()).or_insert(0_i64) += value as i64;
+    }
+    totals
+}
+
+/// Значение метки `name` из списка `a="1",b="2"` (без экранированных кавычек —
+/// имена пользователей telemt их не содержат).
+fn label_value<'a>(labels: &'a str, name: &str) -> Option<&'a str> {
+    labels.split(',').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if key.trim() != name {
+            return None;
+        }
+        value.trim().strip_prefix('"')?.strip_suffix('"')
+    })
+}
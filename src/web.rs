@@ -0,0 +1,385 @@
+//! Встроенный HTTP-сервер (`[web]`): страница приглашения `/i/<токен>` с QR-кодом
+//! deep-link на бота и короткой инструкцией (посещения учитываются в воронке
+//! токена) и короткие ссылки `/p/<slug>` с редиректом на актуальную ссылку
+//! прокси пользователя, а при `[web] admin_app` — Mini App админов `/admin` с API
+//! `/admin/api/...` (см. `bot::handlers::webapp`). Сервер рассчитан на работу за reverse
+//! proxy (TLS, домен), понимает только `GET` (и `POST` для действий Mini App) и
+//! закрывает соединение после каждого ответа.
+
+use crate::bot::handlers::AdminApp;
+use crate::config::WebConfig;
+use crate::db::Db;
+use crate::i18n::{Lang, t};
+use crate::link::{build_bot_start_link, build_proxy_web_link};
+use crate::telemt_cfg::TelemtConfig;
+use ipnet::IpNet;
+use qrcode::QrCode;
+use qrcode::render::svg;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Ссылка на страницу приглашения, если задан `[web] public_url`.
+pub fn invite_page_url(config: &WebConfig, token: &str) -> Option<String> {
+    config
+        .public_url
+        .as_deref()
+        .map(|base| format!("{}/i/{}", base.trim_end_matches('/'), token))
+}
+
+/// Короткая ссылка пользователя, если включены `[web] short_links` и задан `public_url`.
+pub async fn short_link_url(
+    config: &WebConfig,
+    db: &Db,
+    tg_user_id: i64,
+) -> Result<Option<String>, crate::db::DbError> {
+    let Some(base) = config.public_url.as_deref().filter(|_| config.short_links) else {
+        return Ok(None);
+    };
+    let slug = db.ensure_short_link(tg_user_id).await?;
+    Ok(Some(format!("{}/p/{}", base.trim_end_matches('/'), slug)))
+}
+
+/// Разрешено ли подключение с адреса `peer` по списку `allow_cidrs` (пустой — всем).
+pub(crate) fn peer_allowed(allow_cidrs: &[IpNet], peer: SocketAddr) -> bool {
+    let ip = peer.ip().to_canonical();
+    allow_cidrs.is_empty() || allow_cidrs.iter().any(|net| net.contains(&ip))
+}
+
+struct WebContext {
+    db: Arc<Db>,
+    telemt_cfg: Arc<TelemtConfig>,
+    bot_username: Option<String>,
+    short_links: bool,
+    admin_app: Option<AdminApp>,
+}
+
+pub fn spawn(
+    db: Arc<Db>,
+    telemt_cfg: Arc<TelemtConfig>,
+    config: WebConfig,
+    bot_username: Option<String>,
+    admin_app: Option<AdminApp>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let listen = config.listen?;
+    let allow_cidrs = config.allow_cidrs;
+    if bot_username.is_none() {
+        tracing::warn!("У бота не задан username в Telegram: страницы приглашений недоступны");
+    }
+    let context = Arc::new(WebContext {
+        db,
+        telemt_cfg,
+        bot_username,
+        short_links: config.short_links,
+        admin_app,
+    });
+    Some(tokio::spawn(async move {
+        let listener = match TcpListener::bind(&listen).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                tracing::warn!(listen = %listen, error = %error, "Не удалось запустить веб-сервер");
+                return;
+            }
+        };
+        tracing::info!(listen = %listen, "Web server started");
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    tracing::warn!(error = %error, "Не удалось принять HTTP-соединение");
+                    continue;
+                }
+            };
+            if !peer_allowed(&allow_cidrs, peer) {
+                tracing::debug!(peer = %peer, "Web connection rejected by allow_cidrs");
+                continue;
+            }
+            let context = context.clone();
+            tokio::spawn(async move {
+                if let Err(error) = handle_connection(stream, &context).await {
+                    tracing::debug!(peer = %peer, error = %error, "Web request failed");
+                }
+            });
+        }
+    }))
+}
+
+/// Русский, если браузер его принимает или язык не указан.
+fn lang_from_accept_language(value: Option<&str>) -> Lang {
+    match value {
+        Some(value) if !value.to_ascii_lowercase().contains("ru") => Lang::En,
+        _ => Lang::Ru,
+    }
+}
+
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    query: String,
+    accept_language: Option<String>,
+    /// initData Telegram WebApp из заголовка `X-Telegram-Init-Data`.
+    init_data: Option<String>,
+}
+
+/// Заголовок HTTP-запроса; `None` — запрос некорректен или слишком велик.
+pub(crate) async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Request>> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+        if buffer.len() > MAX_REQUEST_HEAD {
+            return Ok(None);
+        }
+        let read = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut chunk))
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "таймаут чтения"))??;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    let head = String::from_utf8_lossy(&buffer);
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or("").split(' ');
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Ok(None);
+    };
+    let headers: Vec<(&str, &str)> = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .collect();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim().to_string())
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Ok(Some(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        accept_language: header("accept-language"),
+        init_data: header("x-telegram-init-data"),
+    }))
+}
+
+async fn handle_connection(mut stream: TcpStream, context: &WebContext) -> std::io::Result<()> {
+    let Some(request) = read_request(&mut stream).await? else {
+        return write_response(&mut stream, "400 Bad Request", "").await;
+    };
+    if request.path == "/admin" || request.path.starts_with("/admin/") {
+        return handle_admin_app(&mut stream, context, &request).await;
+    }
+    if request.method != "GET" {
+        return write_response(&mut stream, "405 Method Not Allowed", "").await;
+    }
+    let lang = lang_from_accept_language(request.accept_language.as_deref());
+
+    if let Some(slug) = request.path.strip_prefix("/p/")
+        && context.short_links
+        && is_token_like(slug)
+    {
+        return handle_short_link(&mut stream, context, slug, lang).await;
+    }
+    let token = request
+        .path
+        .strip_prefix("/i/")
+        .filter(|token| is_token_like(token));
+    let (Some(token), Some(bot_username)) = (token, context.bot_username.as_deref()) else {
+        return write_response(&mut stream, "404 Not Found", &render_not_found(lang)).await;
+    };
+
+    let valid = match context.db.record_token_web_visit(token).await {
+        Ok(valid) => valid,
+        Err(error) => {
+            tracing::warn!(error = %error, "Не удалось учесть посещение страницы приглашения");
+            return write_response(&mut stream, "500 Internal Server Error", "").await;
+        }
+    };
+    if !valid {
+        return write_response(&mut stream, "404 Not Found", &render_not_found(lang)).await;
+    }
+    let page = render_invite_page(lang, &build_bot_start_link(bot_username, token));
+    write_response(&mut stream, "200 OK", &page).await
+}
+
+/// Mini App админов: страница без авторизации (данные она получает только через API),
+/// API — с проверкой initData.
+async fn handle_admin_app(
+    stream: &mut TcpStream,
+    context: &WebContext,
+    request: &Request,
+) -> std::io::Result<()> {
+    let Some(app) = context.admin_app.as_ref() else {
+        return write_response(stream, "404 Not Found", &render_not_found(Lang::Ru)).await;
+    };
+    if request.path == "/admin" || request.path == "/admin/" {
+        if request.method != "GET" {
+            return write_response(stream, "405 Method Not Allowed", "").await;
+        }
+        return write_response(stream, "200 OK", crate::bot::handlers::ADMIN_APP_PAGE).await;
+    }
+    let Some(route) = request.path.strip_prefix("/admin/api/") else {
+        return write_response(stream, "404 Not Found", &render_not_found(Lang::Ru)).await;
+    };
+    let admin_id = request
+        .init_data
+        .as_deref()
+        .and_then(|init_data| app.authorize(init_data));
+    let response = match admin_id {
+        Some(admin_id) => {
+            app.handle(admin_id, &request.method, route, &request.query)
+                .await
+        }
+        None => crate::bot::handlers::ApiResponse::unauthorized(),
+    };
+    write_typed_response(stream, response.status, "application/json", &response.body).await
+}
+
+/// Редирект на актуальную ссылку: секрет берётся из БД в момент запроса,
+/// поэтому короткая ссылка переживает ротацию.
+async fn handle_short_link(
+    stream: &mut TcpStream,
+    context: &WebContext,
+    slug: &str,
+    lang: Lang,
+) -> std::io::Result<()> {
+    let secret = match context.db.resolve_short_link(slug).await {
+        Ok(Some(secret)) => secret,
+        Ok(None) => return write_response(stream, "404 Not Found", &render_not_found(lang)).await,
+        Err(error) => {
+            tracing::warn!(error = %error, "Не удалось найти короткую ссылку");
+            return write_response(stream, "500 Internal Server Error", "").await;
+        }
+    };
+    let link = match context.telemt_cfg.read_link_params().await {
+        Ok(params) => build_proxy_web_link(&params, &secret).ok(),
+        Err(error) => {
+            tracing::warn!(error = %error, "Не удалось прочитать параметры ссылки telemt");
+            None
+        }
+    };
+    let Some(link) = link else {
+        return write_response(stream, "500 Internal Server Error", "").await;
+    };
+    let head = format!(
+        "HTTP/1.1 302 Found\r\n\
+         Location: {}\r\n\
+         Content-Length: 0\r\n\
+         Cache-Control: no-store\r\n\
+         Referrer-Policy: no-referrer\r\n\
+         Connection: close\r\n\r\n",
+        link
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    write_typed_response(stream, status, "text/html", body).await
+}
+
+async fn write_typed_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: {}; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: no-store\r\n\
+         X-Content-Type-Options: nosniff\r\n\
+         Referrer-Policy: no-referrer\r\n\
+         Connection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Токены и slug состоят из букв и цифр; всё остальное сразу отдаёт 404 без обращения к БД.
+fn is_token_like(value: &str) -> bool {
+    !value.is_empty() && value.len() <= 64 && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn render_qr_svg(payload: &str) -> String {
+    let Ok(code) = QrCode::new(payload.as_bytes()) else {
+        return String::new();
+    };
+    let image = code
+        .render::<svg::Color>()
+        .min_dimensions(240, 240)
+        .quiet_zone(true)
+        .build();
+    // Без XML-пролога: SVG встраивается прямо в HTML.
+    match image.find("<svg") {
+        Some(start) => image[start..].to_string(),
+        None => image,
+    }
+}
+
+fn render_page(lang: Lang, title: &str, content: &str) -> String {
+    let html_lang = lang.code();
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{html_lang}">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{title}</title>
+<style>
+body {{ font-family: -apple-system, "Segoe UI", Roboto, sans-serif; max-width: 28rem; margin: 2rem auto; padding: 0 1rem; color: #222; text-align: center; }}
+.button {{ display: inline-block; margin: 1rem 0; padding: 0.8rem 1.6rem; border-radius: 0.6rem; background: #2aabee; color: #fff; text-decoration: none; font-weight: 600; }}
+ol {{ text-align: left; line-height: 1.5; }}
+svg {{ width: 240px; height: 240px; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{content}
+</body>
+</html>
+"#
+    )
+}
+
+fn render_invite_page(lang: Lang, deep_link: &str) -> String {
+    let steps: String = [
+        "web_invite_step_open",
+        "web_invite_step_start",
+        "web_invite_step_connect",
+    ]
+    .into_iter()
+    .map(|key| format!("<li>{}</li>", t(lang, key)))
+    .collect();
+    let content = format!(
+        r#"<a class="button" href="{link}">{button}</a>
+<div>{qr}</div>
+<ol>{steps}</ol>"#,
+        link = deep_link,
+        button = t(lang, "web_invite_button"),
+        qr = render_qr_svg(deep_link),
+        steps = steps
+    );
+    render_page(lang, t(lang, "web_invite_title"), &content)
+}
+
+fn render_not_found(lang: Lang) -> String {
+    render_page(
+        lang,
+        t(lang, "web_not_found_title"),
+        &format!("<p>{}</p>", t(lang, "web_not_found_text")),
+    )
+}